@@ -326,7 +326,7 @@ async fn main() -> anyhow::Result<()> {
 
                             writeln!(stdout, "> {username} blocked you")?;
                         },
-                        warp::multipass::MultiPassEventKind::IdentityUpdate { did } => {
+                        warp::multipass::MultiPassEventKind::IdentityUpdate { did, .. } => {
                             let username = account
                                 .get_identity(Identifier::did_key(did.clone())).await
                                 .map(|ident| ident.username())
@@ -131,6 +131,9 @@ async fn main() -> anyhow::Result<()> {
                             error
                         );
                     }
+                    Progression::MetadataRemoved { name, removed } => {
+                        println!("Removed {} from {name}", removed.join(", "));
+                    }
                 }
             }
         }
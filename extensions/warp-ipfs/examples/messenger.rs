@@ -760,6 +760,9 @@ async fn main() -> anyhow::Result<()> {
                                     while let Some(event) = stream.next().await {
                                         match event {
                                             AttachmentKind::AttachedProgress(_location, Progression::CurrentProgress { .. }) => {},
+                                            AttachmentKind::AttachedProgress(_location, Progression::MetadataRemoved { name, removed }) => {
+                                                writeln!(stdout, "> Removed {} from {name}", removed.join(", "))?;
+                                            },
                                             AttachmentKind::AttachedProgress(_location, Progression::ProgressComplete { name, .. }) => {
                                                 writeln!(stdout, "> {name} is uploaded")?;
                                             },
@@ -826,6 +829,7 @@ async fn main() -> anyhow::Result<()> {
                                                     error
                                                 )?;
                                             }
+                                            Progression::MetadataRemoved { .. } => {}
                                         }
                                     }
                                     Ok::<_, anyhow::Error>(())
@@ -71,14 +71,14 @@ pub async fn run() -> Result<(), JsError> {
             Some(ev) = subscribe_a.next() => {
                 match ev {
                     MultiPassEventKind::FriendRequestSent { .. } => sent = true,
-                    MultiPassEventKind::IdentityUpdate { did } if did == ident_b.did_key() => seen_b = true,
+                    MultiPassEventKind::IdentityUpdate { did, .. } if did == ident_b.did_key() => seen_b = true,
                     _ => {}
                 }
             }
             Some(ev) = subscribe_b.next() => {
                 match ev {
                     MultiPassEventKind::FriendRequestReceived { .. } => received = true,
-                    MultiPassEventKind::IdentityUpdate { did } if did == ident_a.did_key() => seen_a = true,
+                    MultiPassEventKind::IdentityUpdate { did, .. } if did == ident_a.did_key() => seen_a = true,
                     _ => {}
                 }
             }
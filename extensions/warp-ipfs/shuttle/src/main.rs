@@ -54,6 +54,10 @@ struct Opt {
 
     #[clap(long)]
     enable_relay_server: bool,
+
+    /// Enable the registry where identities are able to claim a unique username
+    #[clap(long)]
+    enable_username_registry: bool,
 }
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -119,6 +123,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         false,
         &opts.listen_addr,
         true,
+        opts.enable_username_registry,
     )
     .await?;
 
@@ -3,7 +3,9 @@ use std::{path::PathBuf, time::Duration};
 use ipfs::{Multiaddr, Protocol};
 use rust_ipfs as ipfs;
 
-use warp::{constellation::file::FileType, multipass::identity::Identity};
+use warp::{
+    constellation::file::FileType, crypto::cipher::CipherSuite, multipass::identity::Identity,
+};
 
 #[derive(Default, Debug, Clone)]
 pub enum Bootstrap {
@@ -113,6 +115,47 @@ pub type DefaultPfpFn = std::sync::Arc<
     dyn Fn(&Identity) -> Result<(Vec<u8>, FileType), std::io::Error> + Send + Sync + 'static,
 >;
 
+/// Which uploaded images have their metadata (eg the EXIF location and camera details) removed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StripImageMetadata {
+    Never,
+    /// Only images that are sent as attachments
+    #[default]
+    Attachments,
+    Always,
+}
+
+/// Who is able to see a field of the identity
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    #[default]
+    Everyone,
+    Friends,
+    Nobody,
+}
+
+impl Visibility {
+    pub fn allows(&self, friend: bool) -> bool {
+        match self {
+            Visibility::Everyone => true,
+            Visibility::Friends => friend,
+            Visibility::Nobody => false,
+        }
+    }
+}
+
+/// Visibility of the fields of the identity when it is sent to another peer or announced to the mesh
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileVisibility {
+    pub picture: Visibility,
+    pub banner: Visibility,
+    pub status_message: Visibility,
+    /// Status along with the custom presence
+    pub presence: Visibility,
+    /// Only applies when [`StoreSetting::share_platform`] is enabled
+    pub platform: Visibility,
+}
+
 #[derive(Clone)]
 pub struct StoreSetting {
     /// Allow only interactions with friends
@@ -133,12 +176,33 @@ pub struct StoreSetting {
     pub share_platform: bool,
     /// Waits for a response from peer for a specific duration
     pub friend_request_response_duration: Option<Duration>,
+    /// Duration after which a friend request is closed if it has not been answered, unless
+    /// an expiry is given when sending the request
+    /// Note: If `None`, requests do not expire by default
+    pub friend_request_expiry: Option<Duration>,
     /// Disable providing images for identities
     pub disable_images: bool,
     /// Announce to mesh network
     pub announce_to_mesh: bool,
     /// Function to call to provide data for a default profile picture if one is not apart of the identity
     pub default_profile_picture: Option<DefaultPfpFn>,
+    /// Remove metadata from JPEG, PNG and WebP images before they are stored
+    pub strip_image_metadata: StripImageMetadata,
+    /// Run the node with a key specific to this device, which is authorized within the identity document
+    /// so the same identity could be used across multiple devices
+    pub device_key: bool,
+    /// Duration to wait for friends to approve the requests for their recovery shares when recovering an identity
+    pub recovery_response_duration: Duration,
+    /// Duration without any use of the api after which the status is switched to away until it is used again
+    /// Note: If `None`, this will be disabled
+    pub idle_timeout: Option<Duration>,
+    /// Who is able to see each field of the identity
+    pub visibility: ProfileVisibility,
+    /// Cipher suite used to encrypt messages and the keys exchanged within conversations.
+    /// The suite is recorded along with the data, so messages of either suite can be read.
+    /// Note: Peers on releases prior to the suite being recorded can only read messages and keys
+    ///       encrypted with the default of AES-256-GCM
+    pub cipher_suite: CipherSuite,
 }
 
 impl std::fmt::Debug for StoreSetting {
@@ -158,10 +222,17 @@ impl Default for StoreSetting {
             fetch_over_bitswap: false,
             share_platform: false,
             friend_request_response_duration: None,
+            friend_request_expiry: None,
             disable_images: false,
             with_friends: false,
             default_profile_picture: None,
             announce_to_mesh: false,
+            strip_image_metadata: StripImageMetadata::default(),
+            device_key: false,
+            recovery_response_duration: Duration::from_secs(60 * 10),
+            idle_timeout: None,
+            visibility: ProfileVisibility::default(),
+            cipher_suite: CipherSuite::default(),
         }
     }
 }
@@ -180,6 +251,7 @@ pub struct Config {
     max_file_size: Option<usize>,
     thumbnail_size: (u32, u32),
     thumbnail_exact_format: bool,
+    image_variant_sizes: Vec<u32>,
 }
 
 impl Config {
@@ -230,6 +302,11 @@ impl Config {
     pub fn thumbnail_exact_format(&self) -> bool {
         self.thumbnail_exact_format
     }
+
+    /// Sizes, along the longest side, of the resized copies stored alongside uploaded images
+    pub fn image_variant_sizes(&self) -> &[u32] {
+        &self.image_variant_sizes
+    }
 }
 
 impl Config {
@@ -280,6 +357,10 @@ impl Config {
     pub fn thumbnail_exact_format_mut(&mut self) -> &mut bool {
         &mut self.thumbnail_exact_format
     }
+
+    pub fn image_variant_sizes_mut(&mut self) -> &mut Vec<u32> {
+        &mut self.image_variant_sizes
+    }
 }
 
 impl Config {
@@ -314,6 +395,11 @@ impl Config {
     pub fn with_thumbnail_exact_format(&mut self, exact: bool) {
         self.thumbnail_exact_format = exact
     }
+
+    /// Set the sizes of the resized copies of uploaded images. An empty list disables them
+    pub fn set_image_variant_sizes(&mut self, sizes: Vec<u32>) {
+        self.image_variant_sizes = sizes
+    }
 }
 
 impl Default for Config {
@@ -345,6 +431,7 @@ impl Default for Config {
             max_file_size: Some(100 * 1024 * 1024),
             thumbnail_size: (128, 128),
             thumbnail_exact_format: true,
+            image_variant_sizes: vec![64, 256, 1024],
         }
     }
 }
@@ -26,12 +26,13 @@ use uuid::Uuid;
 use crate::config::{Bootstrap, DiscoveryType};
 use crate::rt::{Executor, LocalExecutor};
 use crate::store::discovery::Discovery;
+use crate::store::ds_key::DataStoreKey;
 use crate::store::phonebook::PhoneBook;
 use crate::store::{ecdh_decrypt, PeerIdExt};
 use crate::store::{MAX_IMAGE_SIZE, MAX_USERNAME_LENGTH, MIN_USERNAME_LENGTH};
 use crate::utils::{ByteCollection, ReaderStream};
 use config::Config;
-use store::document::ResolvedRootDocument;
+use store::document::{bundle::ExportBundle, ResolvedRootDocument};
 use store::event_subscription::EventSubscription;
 use store::files::FileStore;
 use store::identity::IdentityStore;
@@ -39,6 +40,7 @@ use store::message::MessageStore;
 use utils::ExtensionType;
 use warp::constellation::directory::Directory;
 use warp::constellation::file::FileType;
+use warp::constellation::search::{ConstellationSearchStream, SearchOptions};
 use warp::constellation::{
     Constellation, ConstellationEvent, ConstellationEventKind, ConstellationEventStream,
     ConstellationProgressStream,
@@ -49,13 +51,13 @@ use warp::crypto::{KeyMaterial, DID};
 use warp::error::Error;
 use warp::module::Module;
 use warp::multipass::identity::{
-    FriendRequest, Identifier, Identity, IdentityImage, IdentityProfile, IdentityUpdate,
-    Relationship,
+    Contact, ContactUpdate, Device, FriendRequest, Identifier, Identity, IdentityImage,
+    IdentityProfile, IdentityUpdate, RecoveryRequest, Relationship, RevocationCertificate,
 };
 use warp::multipass::{
-    identity, Friends, GetIdentity, IdentityImportOption, IdentityInformation, ImportLocation,
-    LocalIdentity, MultiPass, MultiPassEvent, MultiPassEventKind, MultiPassEventStream,
-    MultiPassImportExport,
+    identity, BundleImportMode, ExportManifest, ExportSection, Friends, GetIdentity,
+    IdentityImportOption, IdentityInformation, ImportLocation, LocalIdentity, MultiPass,
+    MultiPassEvent, MultiPassEventKind, MultiPassEventStream, MultiPassImportExport,
 };
 use warp::raygun::{
     AttachmentEventStream, Conversation, ConversationImage, EmbedState, GroupPermissionOpt,
@@ -71,6 +73,7 @@ use warp::{Extension, SingleHandle};
 mod behaviour;
 pub mod config;
 pub(crate) mod rt;
+mod sanitize;
 pub mod shuttle;
 pub mod store;
 mod thumbnail;
@@ -78,6 +81,9 @@ mod utils;
 
 const PUBSUB_MAX_BUF: usize = 8_388_608;
 
+// Interval for synchronizing the root document between devices of the identity
+const DEVICE_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
 #[derive(Clone)]
 pub struct WarpIpfs {
     tesseract: Tesseract,
@@ -243,6 +249,30 @@ impl WarpIpfs {
         Ok(())
     }
 
+    /// Load the key specific to this device, generating it if it does not exist
+    fn device_keypair(&self) -> Result<Keypair, Error> {
+        let tesseract = &self.tesseract;
+
+        if !tesseract.exist("device_keypair") {
+            tracing::info!("Device keypair doesnt exist. Generating keypair....");
+            let kp = Keypair::generate_ed25519()
+                .try_into_ed25519()
+                .map_err(|e| {
+                    tracing::error!(error = %e, "Unreachable. Report this as a bug");
+                    Error::Other
+                })?;
+            let encoded_kp = bs58::encode(&kp.to_bytes()).into_string();
+            tesseract.set("device_keypair", &encoded_kp)?;
+            let bytes = Zeroizing::new(kp.secret().as_ref().to_vec());
+            return Keypair::ed25519_from_bytes(bytes).map_err(|_| Error::PrivateKeyInvalid);
+        }
+
+        let keypair = tesseract.retrieve("device_keypair")?;
+        let kp = Zeroizing::new(bs58::decode(keypair).into_vec()?);
+        let id_kp = warp::crypto::ed25519_dalek::Keypair::from_bytes(&kp)?;
+        Keypair::ed25519_from_bytes(id_kp.secret.to_bytes()).map_err(|_| Error::PrivateKeyInvalid)
+    }
+
     pub(crate) async fn init_ipfs(&self, keypair: Keypair) -> Result<(), Error> {
         // Since some trait functions are not async (this may change in the future), we cannot hold a lock that is not async-aware
         // through this function without suffering dead locks in the process through await points with the lock held.
@@ -259,6 +289,13 @@ impl WarpIpfs {
             return Err(Error::IdentityExist);
         }
 
+        let device_key = self.inner.config.store_setting().device_key;
+
+        let node_keypair = match device_key {
+            true => self.device_keypair()?,
+            false => keypair.clone(),
+        };
+
         let peer_id = keypair.public().to_peer_id();
 
         let did = peer_id.to_did().expect("Valid conversion");
@@ -295,12 +332,22 @@ impl WarpIpfs {
         let behaviour = behaviour::Behaviour {
             shuttle_identity: enable
                 .then(|| {
-                    shuttle::identity::client::Behaviour::new(&keypair, None, id_sh_rx, &nodes)
+                    shuttle::identity::client::Behaviour::new(
+                        &node_keypair,
+                        device_key.then_some(&keypair),
+                        id_sh_rx,
+                        &nodes,
+                    )
                 })
                 .into(),
             shuttle_message: enable
                 .then(|| {
-                    shuttle::message::client::Behaviour::new(&keypair, None, msg_sh_rx, &nodes)
+                    shuttle::message::client::Behaviour::new(
+                        &node_keypair,
+                        device_key.then_some(&keypair),
+                        msg_sh_rx,
+                        &nodes,
+                    )
                 })
                 .into(),
             phonebook: behaviour::phonebook::Behaviour::new(self.multipass_tx.clone(), pb_rx),
@@ -327,7 +374,7 @@ impl WarpIpfs {
             .with_relay(true)
             .set_listening_addrs(self.inner.config.listen_on().to_vec())
             .with_custom_behaviour(behaviour)
-            .set_keypair(&keypair)
+            .set_keypair(&node_keypair)
             .set_span(span.clone())
             .set_transport_configuration(TransportConfig {
                 enable_memory_transport: self.inner.config.ipfs_setting().memory_transport,
@@ -344,7 +391,7 @@ impl WarpIpfs {
             if self.inner.config.persist() {
                 // Namespace will used the public key to prevent conflicts between multiple instances during testing.
                 uninitialized = uninitialized.set_storage_type(rust_ipfs::StorageType::IndexedDb {
-                    namespace: Some(keypair.public().to_peer_id().to_string()),
+                    namespace: Some(node_keypair.public().to_peer_id().to_string()),
                 });
             }
         }
@@ -604,6 +651,7 @@ impl WarpIpfs {
         tracing::info!("Initializing identity profile");
         let identity_store = IdentityStore::new(
             &ipfs,
+            &keypair,
             &self.inner.config,
             self.multipass_tx.clone(),
             &phonebook,
@@ -633,11 +681,39 @@ impl WarpIpfs {
             self.raygun_tx.clone(),
             &identity_store,
             msg_sh_tx,
+            self.inner.config.store_setting().cipher_suite,
         )
         .await;
 
         tracing::info!("Messaging store initialized");
 
+        if device_key && enable {
+            self.executor.dispatch({
+                let mut identity_store = identity_store.clone();
+                let mut file_store = filestore.clone();
+                let message_store = message_store.clone();
+                async move {
+                    loop {
+                        futures_timer::Delay::new(DEVICE_SYNC_INTERVAL).await;
+
+                        match identity_store.synchronize().await {
+                            Ok(true) => {
+                                if let Err(e) = file_store.merge_index().await {
+                                    tracing::warn!(error = %e, "unable to merge file index");
+                                }
+                                message_store.sync_conversations().await;
+                            }
+                            Ok(false) => {}
+                            Err(e) => {
+                                tracing::debug!(error = %e, "unable to synchronize root document")
+                            }
+                        }
+                    }
+                }
+                .instrument(span.clone())
+            });
+        }
+
         *self.inner.components.write() = Some(Components {
             ipfs,
             identity_store,
@@ -674,6 +750,8 @@ impl WarpIpfs {
         if created && !store.local_id_created().await {
             return Err(Error::IdentityNotCreated);
         }
+
+        store.record_activity();
         Ok(store)
     }
 
@@ -682,7 +760,10 @@ impl WarpIpfs {
             .components
             .read()
             .as_ref()
-            .map(|com| com.message_store.clone())
+            .map(|com| {
+                com.identity_store.record_activity();
+                com.message_store.clone()
+            })
             .ok_or(Error::RayGunExtensionUnavailable)
     }
 
@@ -691,7 +772,10 @@ impl WarpIpfs {
             .components
             .read()
             .as_ref()
-            .map(|com| com.file_store.clone())
+            .map(|com| {
+                com.identity_store.record_activity();
+                com.file_store.clone()
+            })
             .ok_or(Error::ConstellationExtensionUnavailable)
     }
 
@@ -715,6 +799,48 @@ impl WarpIpfs {
             .map(|com| com.ipfs.clone())
             .ok_or(Error::MultiPassExtensionUnavailable)
     }
+
+    /// Store the key derived from the phrase in place of the current key, restoring the previous entries if it
+    /// could not be stored
+    fn store_rotated_phrase(&self, phrase: &str) -> Result<(), Error> {
+        let save_phrase = self.inner.config.save_phrase();
+        let previous = ["keypair", "mnemonic", "chain"]
+            .map(|key| (key, self.tesseract.retrieve(key).ok().map(Zeroizing::new)));
+
+        let result = warp::crypto::keypair::mnemonic_into_tesseract(
+            &self.tesseract,
+            phrase,
+            None,
+            save_phrase,
+            true,
+        )
+        .and_then(|_| {
+            // The phrase of the previous key would otherwise be left behind
+            if !save_phrase {
+                for key in ["mnemonic", "chain"] {
+                    if self.tesseract.exist(key) {
+                        self.tesseract.delete(key)?;
+                    }
+                }
+            }
+            Ok(())
+        });
+
+        if result.is_err() {
+            for (key, value) in previous {
+                let restored = match value {
+                    Some(value) => self.tesseract.set(key, &value),
+                    None if self.tesseract.exist(key) => self.tesseract.delete(key),
+                    None => Ok(()),
+                };
+                if let Err(e) = restored {
+                    tracing::error!(error = %e, key, "unable to restore tesseract entry");
+                }
+            }
+        }
+
+        result
+    }
 }
 
 impl Extension for WarpIpfs {
@@ -853,6 +979,15 @@ impl LocalIdentity for WarpIpfs {
                     });
                 }
 
+                // The claim only verifies the username it was made for
+                if identity
+                    .username_claim
+                    .as_ref()
+                    .is_some_and(|claim| claim.key() != username.to_lowercase())
+                {
+                    identity.username_claim = None;
+                }
+
                 identity.username = username;
                 return store.identity_update(identity).await;
             }
@@ -1141,6 +1276,119 @@ impl LocalIdentity for WarpIpfs {
     fn tesseract(&self) -> Tesseract {
         self.tesseract.clone()
     }
+
+    async fn authorize_device(&mut self, did: &DID, name: Option<&str>) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.authorize_device(did, name).await
+    }
+
+    async fn revoke_device(&mut self, did: &DID) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.revoke_device(did).await
+    }
+
+    async fn list_devices(&self) -> Result<Vec<Device>, Error> {
+        let store = self.identity_store(true).await?;
+        store.list_devices().await
+    }
+
+    async fn rotate_identity_key(&mut self) -> Result<IdentityProfile, Error> {
+        let _g = self.inner.identity_guard.lock().await;
+
+        let device_key = self.inner.config.store_setting().device_key;
+
+        // The node is restarted with the new key, so the repo has to outlive the current instance.
+        // On wasm, the storage is namespaced by the node key unless a device key is used
+        if !self.inner.config.persist() || (cfg!(target_arch = "wasm32") && !device_key) {
+            return Err(Error::OtherWithContext(
+                "Identity key rotation requires a persistent store".into(),
+            ));
+        }
+
+        let mut store = self.identity_store(true).await?;
+        let ipfs = self.ipfs()?;
+        let old_did = store.did_key();
+
+        // The new key is derived from a new phrase so the identity can still be restored from a phrase
+        let (phrase, new_did) =
+            warp::crypto::keypair::generate_keypair(PhraseType::Standard, None)?;
+        let keypair = keypair_from_passphrase(&phrase)?;
+
+        // The new key is staged until the root document for it is stored and is committed last, so a failure along the
+        // way leaves the current identity as is
+        let root_cid = store.rotate_identity_key(&keypair).await?;
+
+        let key = match device_key {
+            true => ipfs.root(),
+            false => keypair.root(),
+        };
+
+        let previous_root = ipfs
+            .repo()
+            .data_store()
+            .get(key.as_bytes())
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        ipfs.repo()
+            .data_store()
+            .put(key.as_bytes(), root_cid.to_string().as_bytes())
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        if let Err(e) = self.store_rotated_phrase(&phrase) {
+            let restored = match previous_root {
+                Some(cid) => ipfs.repo().data_store().put(key.as_bytes(), &cid).await,
+                None => ipfs.repo().data_store().remove(key.as_bytes()).await,
+            };
+            if let Err(e) = restored {
+                tracing::error!(error = %e, "unable to restore the previous root document");
+            }
+            return Err(e);
+        }
+
+        tracing::info!(%old_did, %new_did, "identity key rotated. Restarting node");
+
+        drop(store);
+        let components = self.inner.components.write().take();
+        if let Some(components) = components {
+            components.ipfs.exit_daemon().await;
+        }
+
+        self.init_ipfs(keypair).await?;
+
+        self.multipass_tx
+            .emit(MultiPassEventKind::IdentityKeyRotated {
+                old: old_did,
+                new: new_did,
+            })
+            .await;
+
+        let identity = self.identity_store(true).await?.own_identity().await?;
+        Ok(IdentityProfile::new(identity, Some(phrase)))
+    }
+
+    async fn create_revocation_certificate(
+        &self,
+        reason: Option<&str>,
+    ) -> Result<RevocationCertificate, Error> {
+        let store = self.identity_store(true).await?;
+        store.create_revocation_certificate(reason)
+    }
+
+    async fn publish_revocation_certificate(
+        &mut self,
+        certificate: &RevocationCertificate,
+    ) -> Result<(), Error> {
+        // The identity does not need to be created since the certificate could be published after the key is lost
+        let store = self.identity_store(false).await?;
+        store.publish_revocation_certificate(certificate).await
+    }
+
+    async fn claim_username(&mut self) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.claim_username().await
+    }
 }
 
 #[async_trait::async_trait]
@@ -1243,7 +1491,76 @@ impl MultiPassImportExport for WarpIpfs {
 
                 let mut store = self.identity_store(false).await?;
 
-                store.import_identity_remote_resolve().await
+                let identity = store.import_identity_remote_resolve().await?;
+
+                // The index was loaded before the root document was imported
+                if let Err(e) = self.file_store()?.import_index().await {
+                    tracing::warn!(error = %e, "Unable to import index");
+                }
+
+                Ok(identity)
+            }
+            IdentityImportOption::Recover { did, friends } => {
+                // Friends are reached with a temporary key until the key of the identity is recovered
+                self.init_ipfs(Keypair::generate_ed25519()).await?;
+
+                let mut store = self
+                    .inner
+                    .components
+                    .read()
+                    .as_ref()
+                    .map(|com| com.identity_store.clone())
+                    .ok_or(Error::MultiPassExtensionUnavailable)?;
+
+                let duration = self.inner.config.store_setting().recovery_response_duration;
+
+                let result = async {
+                    let mut stream = store.subscribe().await?;
+                    store.request_recovery(&did, &friends).await?;
+                    loop {
+                        if let Some(recovered) = store.try_recover().await? {
+                            return Ok(recovered);
+                        }
+                        // Shares are checked again after each event, such as a share being approved
+                        if stream.next().await.is_none() {
+                            return Err(Error::InsufficientShares);
+                        }
+                    }
+                }
+                .timeout(duration)
+                .await
+                .map_err(|_| Error::InsufficientShares)
+                .and_then(|result| result);
+
+                drop(store);
+                let components = self.inner.components.write().take();
+                if let Some(components) = components {
+                    components.ipfs.exit_daemon().await;
+                }
+
+                let recovered = result?;
+
+                let kp = recovered
+                    .keypair
+                    .clone()
+                    .try_into_ed25519()
+                    .map_err(|_| Error::PrivateKeyInvalid)?;
+                let encoded_kp = Zeroizing::new(bs58::encode(&kp.to_bytes()).into_string());
+                self.tesseract.set("keypair", &encoded_kp)?;
+
+                self.init_ipfs(recovered.keypair).await?;
+
+                let mut store = self.identity_store(false).await?;
+
+                let identity = store
+                    .recover_identity(recovered.identity, &recovered.friends)
+                    .await?;
+
+                if let Err(e) = self.file_store()?.import_index().await {
+                    tracing::warn!(error = %e, "Unable to import index");
+                }
+
+                Ok(identity)
             }
         }
     }
@@ -1267,13 +1584,124 @@ impl MultiPassImportExport for WarpIpfs {
             }
         }
     }
+
+    async fn export_bundle<'a>(
+        &mut self,
+        location: ImportLocation<'a>,
+        sections: &[ExportSection],
+    ) -> Result<ExportManifest, Error> {
+        let store = self.identity_store(true).await?;
+
+        let bundle = store.root_document().export_bundle(sections).await?;
+        let bytes = bundle.to_bytes(store.root_document().keypair())?;
+
+        match location {
+            ImportLocation::Local { path } => fs::write(path, bytes).await?,
+            ImportLocation::Memory { buffer } => *buffer = bytes,
+            // Bundles are only written locally since the root document is what gets exported remotely
+            ImportLocation::Remote => return Err(Error::Unimplemented),
+        }
+
+        Ok(bundle.manifest)
+    }
+
+    async fn verify_bundle<'a>(
+        &self,
+        location: ImportLocation<'a>,
+        passphrase: &str,
+    ) -> Result<ExportManifest, Error> {
+        let bytes = match location {
+            ImportLocation::Local { path } => fs::read(path).await?,
+            ImportLocation::Memory { buffer } => buffer.clone(),
+            ImportLocation::Remote => return Err(Error::Unimplemented),
+        };
+
+        let keypair = keypair_from_passphrase(passphrase)?;
+        let bundle = ExportBundle::from_bytes(&keypair, bytes)?;
+
+        Ok(bundle.manifest)
+    }
+
+    async fn import_bundle<'a>(
+        &mut self,
+        location: ImportLocation<'a>,
+        passphrase: &str,
+        mode: BundleImportMode,
+    ) -> Result<Identity, Error> {
+        let bytes = match location {
+            ImportLocation::Local { path } => fs::read(path).await?,
+            ImportLocation::Memory { buffer } => std::mem::take(buffer),
+            ImportLocation::Remote => return Err(Error::Unimplemented),
+        };
+
+        let internal_keypair = keypair_from_passphrase(passphrase)?;
+        let bundle = ExportBundle::from_bytes(&internal_keypair, bytes)?;
+        let has_file_index = bundle.root.file_index.is_some();
+
+        let identity = match mode {
+            BundleImportMode::Merge => {
+                let mut store = self.identity_store(true).await?;
+                store.merge_bundle(bundle).await?;
+                store.own_identity().await?
+            }
+            BundleImportMode::Replace => {
+                if self.inner.components.read().is_some() {
+                    return Err(Error::IdentityExist);
+                }
+                let _g = self.inner.identity_guard.lock().await;
+                if !self.tesseract.is_unlock() {
+                    return Err(Error::TesseractLocked);
+                }
+
+                warp::crypto::keypair::mnemonic_into_tesseract(
+                    &self.tesseract,
+                    passphrase,
+                    None,
+                    self.inner.config.save_phrase(),
+                    false,
+                )?;
+
+                self.init_ipfs(internal_keypair).await?;
+
+                let mut store = self.identity_store(false).await?;
+
+                store.import_bundle(bundle).await?
+            }
+        };
+
+        // The stores were loaded before the sections of the bundle were imported
+        if has_file_index {
+            let mut file_store = self.file_store()?;
+            let result = match mode {
+                BundleImportMode::Merge => file_store.merge_index().await,
+                BundleImportMode::Replace => file_store.import_index().await,
+            };
+            if let Err(e) = result {
+                tracing::warn!(error = %e, "Unable to import index");
+            }
+        }
+
+        self.messaging_store()?.sync_conversations().await;
+
+        Ok(identity)
+    }
 }
 
 #[async_trait::async_trait]
 impl Friends for WarpIpfs {
     async fn send_request(&mut self, pubkey: &DID) -> Result<(), Error> {
         let mut store = self.identity_store(true).await?;
-        store.send_request(pubkey).await
+        store.send_request(pubkey, None, None).await
+    }
+
+    async fn send_request_with(
+        &mut self,
+        pubkey: &DID,
+        message: Option<String>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.send_request(pubkey, message, expires).await
     }
 
     async fn accept_request(&mut self, pubkey: &DID) -> Result<(), Error> {
@@ -1345,6 +1773,51 @@ impl Friends for WarpIpfs {
         let store = self.identity_store(true).await?;
         store.is_friend(pubkey).await
     }
+
+    async fn setup_social_recovery(&mut self, friends: &[DID], threshold: u8) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.setup_social_recovery(friends, threshold).await
+    }
+
+    async fn list_recovery_shares(&self) -> Result<Vec<DID>, Error> {
+        let store = self.identity_store(true).await?;
+        store.list_recovery_shares().await
+    }
+
+    async fn list_recovery_requests(&self) -> Result<Vec<RecoveryRequest>, Error> {
+        let store = self.identity_store(true).await?;
+        store.list_recovery_requests().await
+    }
+
+    async fn approve_recovery_request(&mut self, did: &DID, device: &DID) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.approve_recovery_request(did, device).await
+    }
+
+    async fn deny_recovery_request(&mut self, did: &DID, device: &DID) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.deny_recovery_request(did, device).await
+    }
+
+    async fn list_contacts(&self) -> Result<Vec<Contact>, Error> {
+        let store = self.identity_store(true).await?;
+        store.list_contacts().await
+    }
+
+    async fn get_contact(&self, did: &DID) -> Result<Contact, Error> {
+        let store = self.identity_store(true).await?;
+        store.get_contact(did).await
+    }
+
+    async fn update_contact(&mut self, did: &DID, option: ContactUpdate) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.update_contact(did, option).await
+    }
+
+    async fn list_contact_groups(&self) -> Result<Vec<String>, Error> {
+        let store = self.identity_store(true).await?;
+        store.list_contact_groups().await
+    }
 }
 
 #[async_trait::async_trait]
@@ -1377,6 +1850,16 @@ impl IdentityInformation for WarpIpfs {
         store.set_identity_status(status).await
     }
 
+    async fn identity_presence(&self, did: &DID) -> Result<identity::Presence, Error> {
+        let store = self.identity_store(true).await?;
+        store.identity_presence(did).await
+    }
+
+    async fn set_identity_presence(&mut self, presence: identity::Presence) -> Result<(), Error> {
+        let mut store = self.identity_store(true).await?;
+        store.set_identity_presence(presence).await
+    }
+
     async fn identity_platform(&self, did: &DID) -> Result<identity::Platform, Error> {
         let store = self.identity_store(true).await?;
         store.identity_platform(did).await
@@ -1658,7 +2141,19 @@ impl RayGunAttachment for WarpIpfs {
         file: &str,
     ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
         self.messaging_store()?
-            .download_stream(conversation_id, message_id, file)
+            .download_stream(conversation_id, message_id, file, None)
+            .await
+    }
+
+    async fn download_variant_stream(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        file: &str,
+        size: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        self.messaging_store()?
+            .download_stream(conversation_id, message_id, file, Some(size))
             .await
     }
 }
@@ -1803,6 +2298,14 @@ impl Constellation for WarpIpfs {
         self.file_store()?.get_stream(name).await
     }
 
+    async fn get_variant_stream(
+        &self,
+        name: &str,
+        size: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        self.file_store()?.get_variant_stream(name, size).await
+    }
+
     /// Used to remove data from the filesystem
     async fn remove(&mut self, name: &str, recursive: bool) -> Result<(), Error> {
         self.file_store()?.remove(name, recursive).await
@@ -1812,6 +2315,10 @@ impl Constellation for WarpIpfs {
         self.file_store()?.rename(current, new).await
     }
 
+    async fn move_item(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        self.file_store()?.move_item(from, to).await
+    }
+
     async fn create_directory(&mut self, name: &str, recursive: bool) -> Result<(), Error> {
         self.file_store()?.create_directory(name, recursive).await
     }
@@ -1820,6 +2327,33 @@ impl Constellation for WarpIpfs {
         self.file_store()?.sync_ref(path).await
     }
 
+    async fn search(&self, options: SearchOptions) -> Result<ConstellationSearchStream, Error> {
+        self.file_store()?.search(options).await
+    }
+
+    async fn set_item_metadata(
+        &mut self,
+        path: &str,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<(), Error> {
+        self.file_store()?
+            .set_item_metadata(path, key, value.map(str::to_string))
+            .await
+    }
+
+    async fn add_tag(&mut self, path: &str, tag: &str) -> Result<(), Error> {
+        self.file_store()?.add_tag(path, tag).await
+    }
+
+    async fn remove_tag(&mut self, path: &str, tag: &str) -> Result<(), Error> {
+        self.file_store()?.remove_tag(path, tag).await
+    }
+
+    async fn set_quota(&mut self, path: &str, quota: Option<usize>) -> Result<(), Error> {
+        self.file_store()?.set_quota(path, quota).await
+    }
+
     fn set_path(&mut self, path: PathBuf) {
         if let Ok(mut store) = self.file_store() {
             store.set_path(path)
@@ -1841,6 +2375,13 @@ impl ConstellationEvent for WarpIpfs {
     }
 }
 
+/// Keypair of the identity derived from its passphrase
+fn keypair_from_passphrase(passphrase: &str) -> Result<Keypair, Error> {
+    let keypair = warp::crypto::keypair::did_from_mnemonic(passphrase, None)?;
+    let bytes = Zeroizing::new(keypair.private_key_bytes());
+    Keypair::ed25519_from_bytes(bytes).map_err(|_| Error::PrivateKeyInvalid)
+}
+
 pub(crate) fn to_file_type(name: &str) -> FileType {
     let name = PathBuf::from(name.trim());
     let extension = name
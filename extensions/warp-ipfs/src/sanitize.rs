@@ -0,0 +1,355 @@
+//! Removal of metadata, such as the EXIF location and camera details, from images before they are stored.
+//! Only the containers are rewritten so the image data itself is left untouched
+use std::{ffi::OsStr, path::Path};
+
+use warp::error::Error;
+
+use crate::utils::ExtensionType;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Check if metadata could be removed from the file based on its extension
+pub fn is_supported(name: &str) -> bool {
+    let extension = Path::new(name)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map(ExtensionType::from)
+        .unwrap_or(ExtensionType::Other);
+
+    matches!(
+        extension,
+        ExtensionType::JPG | ExtensionType::PNG | ExtensionType::WEBP
+    )
+}
+
+/// Remove metadata from a JPEG, PNG or WebP image, returning the image along with the kind of metadata
+/// that was removed (eg `EXIF` or `XMP`). The format is detected from the data itself, so `None` is returned
+/// if the data is not one of those formats or if there was no metadata to remove.
+///
+/// Note: The orientation of a JPEG is kept since the image would otherwise be displayed incorrectly
+pub fn strip_metadata(data: &[u8]) -> Result<Option<(Vec<u8>, Vec<String>)>, Error> {
+    let (data, removed) = if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        strip_jpeg(data)?
+    } else if data.starts_with(PNG_SIGNATURE) {
+        strip_png(data)?
+    } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        strip_webp(data)?
+    } else {
+        return Ok(None);
+    };
+
+    if removed.is_empty() {
+        return Ok(None);
+    }
+
+    Ok(Some((data, removed)))
+}
+
+fn malformed(format: &str) -> Error {
+    Error::OtherWithContext(format!("unable to remove metadata from malformed {format}"))
+}
+
+fn record(removed: &mut Vec<String>, kind: &str) {
+    if !removed.iter().any(|item| item == kind) {
+        removed.push(kind.to_string());
+    }
+}
+
+fn strip_jpeg(data: &[u8]) -> Result<(Vec<u8>, Vec<String>), Error> {
+    let mut removed = vec![];
+    let mut leading = vec![];
+    let mut segments = vec![];
+    let mut orientation = None;
+    let mut pos = 2;
+
+    let rest = loop {
+        if data.get(pos) != Some(&0xFF) {
+            return Err(malformed("jpeg"));
+        }
+
+        // Markers may be preceded by any number of fill bytes
+        while data.get(pos + 1) == Some(&0xFF) {
+            pos += 1;
+        }
+
+        let marker = *data.get(pos + 1).ok_or_else(|| malformed("jpeg"))?;
+
+        match marker {
+            // Markers without a length
+            0x01 | 0xD0..=0xD8 => {
+                segments.push(&data[pos..pos + 2]);
+                pos += 2;
+                continue;
+            }
+            // Start of scan and end of image, after which there is only image data
+            0xDA | 0xD9 => break &data[pos..],
+            _ => {}
+        }
+
+        let length = data
+            .get(pos + 2..pos + 4)
+            .map(|length| u16::from_be_bytes([length[0], length[1]]) as usize)
+            .filter(|length| *length >= 2)
+            .ok_or_else(|| malformed("jpeg"))?;
+
+        let end = pos + 2 + length;
+        let segment = data.get(pos..end).ok_or_else(|| malformed("jpeg"))?;
+        let payload = &segment[4..];
+
+        let kind = match marker {
+            0xE1 if payload.starts_with(b"Exif\0\0") => {
+                orientation = orientation.or_else(|| exif_orientation(&payload[6..]));
+                Some("EXIF")
+            }
+            0xE1 if payload.starts_with(b"http://ns.adobe.com/") => Some("XMP"),
+            0xE1 => Some("APP1"),
+            // ICC profiles are needed to display the colors correctly
+            0xE2 if payload.starts_with(b"ICC_PROFILE\0") => None,
+            0xE2 => Some("APP2"),
+            0xED => Some("IPTC"),
+            0xFE => Some("comment"),
+            // APP0 (JFIF) and APP14 (Adobe) describe how the image data is encoded
+            0xE0 | 0xEE => None,
+            0xE3..=0xEF => Some("vendor"),
+            _ => None,
+        };
+
+        match kind {
+            Some(kind) => record(&mut removed, kind),
+            None if marker == 0xE0 && segments.is_empty() => leading.push(segment),
+            None => segments.push(segment),
+        }
+
+        pos = end;
+    };
+
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(&[0xFF, 0xD8]);
+    for segment in leading {
+        output.extend_from_slice(segment);
+    }
+    if let Some(orientation) = orientation {
+        output.extend_from_slice(&orientation_segment(orientation));
+    }
+    for segment in segments {
+        output.extend_from_slice(segment);
+    }
+    output.extend_from_slice(rest);
+
+    Ok((output, removed))
+}
+
+/// Read the orientation from the first IFD of the EXIF data, if it is anything other than the default
+fn exif_orientation(tiff: &[u8]) -> Option<u16> {
+    let big_endian = match tiff.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+
+    let read_u16 = |offset: usize| {
+        tiff.get(offset..offset + 2).map(|bytes| match big_endian {
+            true => u16::from_be_bytes([bytes[0], bytes[1]]),
+            false => u16::from_le_bytes([bytes[0], bytes[1]]),
+        })
+    };
+
+    let read_u32 = |offset: usize| {
+        tiff.get(offset..offset + 4).map(|bytes| {
+            let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+            match big_endian {
+                true => u32::from_be_bytes(bytes),
+                false => u32::from_le_bytes(bytes),
+            }
+        })
+    };
+
+    let ifd = read_u32(4)? as usize;
+    let count = read_u16(ifd)? as usize;
+
+    (0..count)
+        .map(|index| ifd + 2 + index * 12)
+        .find(|entry| read_u16(*entry) == Some(0x0112))
+        .and_then(|entry| read_u16(entry + 8))
+        .filter(|orientation| (2..=8).contains(orientation))
+}
+
+/// APP1 segment containing EXIF data with only the orientation
+fn orientation_segment(orientation: u16) -> Vec<u8> {
+    let mut payload = Vec::with_capacity(32);
+    payload.extend_from_slice(b"Exif\0\0");
+    // Big endian TIFF header, with the first IFD directly after it
+    payload.extend_from_slice(b"MM\0\x2a\0\0\0\x08");
+    payload.extend_from_slice(&1u16.to_be_bytes());
+    // Orientation tag stored as a single SHORT
+    payload.extend_from_slice(&[0x01, 0x12, 0x00, 0x03, 0x00, 0x00, 0x00, 0x01]);
+    payload.extend_from_slice(&orientation.to_be_bytes());
+    payload.extend_from_slice(&[0x00, 0x00]);
+    // No further IFD
+    payload.extend_from_slice(&[0x00, 0x00, 0x00, 0x00]);
+
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&(payload.len() as u16 + 2).to_be_bytes());
+    segment.extend_from_slice(&payload);
+    segment
+}
+
+fn strip_png(data: &[u8]) -> Result<(Vec<u8>, Vec<String>), Error> {
+    let mut removed = vec![];
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(PNG_SIGNATURE);
+
+    let mut pos = PNG_SIGNATURE.len();
+
+    loop {
+        let length = data
+            .get(pos..pos + 4)
+            .map(|length| u32::from_be_bytes([length[0], length[1], length[2], length[3]]) as usize)
+            .ok_or_else(|| malformed("png"))?;
+
+        // Length, type, data and crc
+        let end = pos + 12 + length;
+        let chunk = data.get(pos..end).ok_or_else(|| malformed("png"))?;
+        let (ty, body) = (&chunk[4..8], &chunk[8..8 + length]);
+
+        let kind = match ty {
+            b"eXIf" => Some("EXIF"),
+            b"iTXt" if body.starts_with(b"XML:com.adobe.xmp\0") => Some("XMP"),
+            b"tEXt" | b"zTXt" | b"iTXt" => Some("text"),
+            b"tIME" => Some("timestamp"),
+            _ => None,
+        };
+
+        match kind {
+            Some(kind) => record(&mut removed, kind),
+            None => output.extend_from_slice(chunk),
+        }
+
+        // Anything after the end of the image is discarded
+        if ty == b"IEND" {
+            if end < data.len() {
+                record(&mut removed, "trailing data");
+            }
+            break;
+        }
+
+        pos = end;
+    }
+
+    Ok((output, removed))
+}
+
+fn strip_webp(data: &[u8]) -> Result<(Vec<u8>, Vec<String>), Error> {
+    let mut removed = vec![];
+    let mut output = Vec::with_capacity(data.len());
+    output.extend_from_slice(b"RIFF\0\0\0\0WEBP");
+
+    let riff_end = data
+        .get(4..8)
+        .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize + 8)
+        .filter(|end| *end <= data.len())
+        .ok_or_else(|| malformed("webp"))?;
+
+    let mut pos = 12;
+    let mut extended = None;
+
+    while pos < riff_end {
+        let size = data
+            .get(pos + 4..pos + 8)
+            .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+            .ok_or_else(|| malformed("webp"))?;
+
+        // Chunks are padded to an even size
+        let end = (pos + 8 + size + (size & 1)).min(riff_end);
+        let chunk = data.get(pos..end).ok_or_else(|| malformed("webp"))?;
+
+        match &chunk[..4] {
+            b"EXIF" => record(&mut removed, "EXIF"),
+            b"XMP " => record(&mut removed, "XMP"),
+            ty => {
+                if ty == b"VP8X" {
+                    extended = Some(output.len());
+                }
+                output.extend_from_slice(chunk);
+            }
+        }
+
+        pos = end;
+    }
+
+    // Clear the flags that announce the presence of the removed chunks
+    if let Some(flags) = extended.and_then(|offset| output.get_mut(offset + 8)) {
+        *flags &= !(0x08 | 0x04);
+    }
+
+    let size = (output.len() - 8) as u32;
+    output[4..8].copy_from_slice(&size.to_le_bytes());
+
+    Ok((output, removed))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{exif_orientation, orientation_segment, strip_metadata, PNG_SIGNATURE};
+
+    fn png_chunk(ty: &[u8], body: &[u8]) -> Vec<u8> {
+        let mut chunk = (body.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(ty);
+        chunk.extend_from_slice(body);
+        // The crc is not validated
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    #[test]
+    fn strip_jpeg_keeps_orientation() -> anyhow::Result<()> {
+        let exif = orientation_segment(6);
+        let mut jpeg = vec![0xFF, 0xD8];
+        jpeg.extend_from_slice(&[0xFF, 0xE0, 0x00, 0x04, 0x4A, 0x46]);
+        jpeg.extend_from_slice(&exif);
+        jpeg.extend_from_slice(&[0xFF, 0xFE, 0x00, 0x05, b'h', b'i', b'!']);
+        jpeg.extend_from_slice(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]);
+
+        let (stripped, removed) = strip_metadata(&jpeg)?.expect("metadata removed");
+        assert_eq!(removed, vec!["EXIF", "comment"]);
+        assert!(!stripped.windows(3).any(|window| window == b"hi!"));
+        // The orientation is written back as a minimal EXIF segment after the JFIF segment
+        assert_eq!(&stripped[8..8 + exif.len()], exif.as_slice());
+        assert_eq!(exif_orientation(&exif[10..]), Some(6));
+        assert!(stripped.ends_with(&[0xFF, 0xDA, 0x00, 0x02, 0x12, 0x34, 0xFF, 0xD9]));
+        Ok(())
+    }
+
+    #[test]
+    fn strip_png_text_chunks() -> anyhow::Result<()> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(png_chunk(b"IHDR", &[0; 13]));
+        png.extend(png_chunk(b"tEXt", b"Author\0someone"));
+        png.extend(png_chunk(b"eXIf", b"MM\0\x2a"));
+        png.extend(png_chunk(b"IDAT", &[1, 2, 3]));
+        png.extend(png_chunk(b"IEND", &[]));
+
+        let (stripped, removed) = strip_metadata(&png)?.expect("metadata removed");
+        assert_eq!(removed, vec!["text", "EXIF"]);
+
+        let mut expected = PNG_SIGNATURE.to_vec();
+        expected.extend(png_chunk(b"IHDR", &[0; 13]));
+        expected.extend(png_chunk(b"IDAT", &[1, 2, 3]));
+        expected.extend(png_chunk(b"IEND", &[]));
+        assert_eq!(stripped, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn unsupported_or_clean_images_are_untouched() -> anyhow::Result<()> {
+        assert!(strip_metadata(b"plain text")?.is_none());
+
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend(png_chunk(b"IHDR", &[0; 13]));
+        png.extend(png_chunk(b"IEND", &[]));
+        assert!(strip_metadata(&png)?.is_none());
+
+        assert!(strip_metadata(&[0xFF, 0xD8, 0xFF, 0xE1, 0xFF]).is_err());
+        Ok(())
+    }
+}
@@ -45,6 +45,10 @@ pub struct RequestPayload {
     pub sender: DID,
     pub event: RequestEvent,
     pub created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub original_signature: Vec<u8>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
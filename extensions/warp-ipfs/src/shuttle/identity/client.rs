@@ -21,8 +21,13 @@ use rust_ipfs::{
     Keypair, Multiaddr, NetworkBehaviour, PeerId,
 };
 use warp::crypto::DID;
+use warp::multipass::identity::RevocationCertificate;
 
-use crate::store::{document::identity::IdentityDocument, payload::PayloadMessage, PeerIdExt};
+use crate::store::{
+    document::identity::{IdentityDocument, UsernameClaim},
+    payload::PayloadMessage,
+    PeerIdExt,
+};
 
 use super::protocol::payload_message_construct;
 
@@ -62,6 +67,7 @@ pub enum IdentityCommand {
     Register {
         peer_id: PeerId,
         root_cid: Cid,
+        identity: IdentityDocument,
         response: futures::channel::oneshot::Sender<Result<(), warp::error::Error>>,
     },
     Lookup {
@@ -73,6 +79,7 @@ pub enum IdentityCommand {
     UpdateRootDocument {
         peer_id: PeerId,
         package: Cid,
+        identity: IdentityDocument,
     },
     SendRequest {
         peer_id: PeerId,
@@ -88,6 +95,16 @@ pub enum IdentityCommand {
         peer_id: PeerId,
         response: futures::channel::oneshot::Sender<Result<Cid, warp::error::Error>>,
     },
+    Revoke {
+        peer_id: PeerId,
+        certificate: RevocationCertificate,
+        response: futures::channel::oneshot::Sender<Result<(), warp::error::Error>>,
+    },
+    ClaimUsername {
+        peer_id: PeerId,
+        claim: UsernameClaim,
+        response: futures::channel::oneshot::Sender<Result<UsernameClaim, warp::error::Error>>,
+    },
 }
 
 #[allow(dead_code)]
@@ -118,6 +135,9 @@ enum IdentityResponse {
     Fetch {
         response: futures::channel::oneshot::Sender<Result<Cid, warp::error::Error>>,
     },
+    UsernameClaim {
+        response: futures::channel::oneshot::Sender<Result<UsernameClaim, warp::error::Error>>,
+    },
 }
 
 impl Behaviour {
@@ -202,6 +222,29 @@ impl Behaviour {
                     Response::RegisterResponse(response) => {
                         let res = match self.waiting_on_response.remove(&id) {
                             Some(IdentityResponse::Register { response }) => response,
+                            Some(IdentityResponse::UsernameClaim { response: res }) => {
+                                let result = match response {
+                                    RegisterResponse::UsernameClaimed { claim } => Ok(claim),
+                                    RegisterResponse::Error(
+                                        super::protocol::RegisterError::UsernameTaken,
+                                    ) => Err(warp::error::Error::UsernameTaken),
+                                    RegisterResponse::Error(
+                                        super::protocol::RegisterError::UsernameRegistryDisabled,
+                                    ) => Err(warp::error::Error::UsernameRegistryUnavailable),
+                                    RegisterResponse::Error(
+                                        super::protocol::RegisterError::NotRegistered,
+                                    ) => Err(warp::error::Error::IdentityDoesntExist),
+                                    RegisterResponse::Error(
+                                        super::protocol::RegisterError::IdentityRevoked,
+                                    ) => Err(warp::error::Error::IdentityRevoked),
+                                    RegisterResponse::Error(
+                                        super::protocol::RegisterError::IdentityVerificationFailed,
+                                    ) => Err(warp::error::Error::IdentityInvalid),
+                                    _ => Err(warp::error::Error::Other),
+                                };
+                                let _ = res.send(result);
+                                return;
+                            }
                             _ => return,
                         };
 
@@ -222,6 +265,11 @@ impl Behaviour {
                             ) => {
                                 let _ = res.send(Err(warp::error::Error::IdentityInvalid));
                             }
+                            RegisterResponse::Error(
+                                super::protocol::RegisterError::IdentityRevoked,
+                            ) => {
+                                let _ = res.send(Err(warp::error::Error::IdentityRevoked));
+                            }
                             RegisterResponse::Error(
                                 super::protocol::RegisterError::NotRegistered,
                             ) => {
@@ -236,6 +284,13 @@ impl Behaviour {
                                 //TODO?
                                 let _ = res.send(Ok(()));
                             }
+                            RegisterResponse::Error(
+                                super::protocol::RegisterError::UsernameTaken
+                                | super::protocol::RegisterError::UsernameRegistryDisabled,
+                            )
+                            | RegisterResponse::UsernameClaimed { .. } => {
+                                let _ = res.send(Err(warp::error::Error::Other));
+                            }
                         }
                     }
                     Response::LookupResponse(response) => {
@@ -495,13 +550,37 @@ impl NetworkBehaviour for Behaviour {
                     IdentityCommand::Register {
                         peer_id,
                         root_cid,
+                        identity,
                         response,
                     } => {
                         tracing::info!("Registering to {peer_id}");
                         let payload = payload_message_construct(
                             &self.keypair,
                             self.primary_keypair.as_ref(),
-                            Request::Register(Register::RegisterIdentity { root_cid }),
+                            Request::Register(Register::RegisterIdentity {
+                                root_cid,
+                                identity: Some(identity),
+                            }),
+                        )
+                        .expect("Valid construction of payload");
+
+                        let id = self.inner.send_request(&peer_id, payload);
+
+                        tracing::debug!(?id, "Request sent");
+
+                        self.waiting_on_response
+                            .insert(id, IdentityResponse::Register { response });
+                    }
+                    IdentityCommand::Revoke {
+                        peer_id,
+                        certificate,
+                        response,
+                    } => {
+                        tracing::info!("Publishing revocation certificate to {peer_id}");
+                        let payload = payload_message_construct(
+                            &self.keypair,
+                            self.primary_keypair.as_ref(),
+                            Request::Register(Register::Revoke { certificate }),
                         )
                         .expect("Valid construction of payload");
 
@@ -512,6 +591,26 @@ impl NetworkBehaviour for Behaviour {
                         self.waiting_on_response
                             .insert(id, IdentityResponse::Register { response });
                     }
+                    IdentityCommand::ClaimUsername {
+                        peer_id,
+                        claim,
+                        response,
+                    } => {
+                        tracing::info!("Claiming username with {peer_id}");
+                        let payload = payload_message_construct(
+                            &self.keypair,
+                            self.primary_keypair.as_ref(),
+                            Request::Register(Register::ClaimUsername { claim }),
+                        )
+                        .expect("Valid construction of payload");
+
+                        let id = self.inner.send_request(&peer_id, payload);
+
+                        tracing::debug!(?id, "Request sent");
+
+                        self.waiting_on_response
+                            .insert(id, IdentityResponse::UsernameClaim { response });
+                    }
                     IdentityCommand::IsRegistered { peer_id, response } => {
                         tracing::info!("Registering to {peer_id}");
                         let payload = payload_message_construct(
@@ -547,7 +646,11 @@ impl NetworkBehaviour for Behaviour {
                         self.waiting_on_response
                             .insert(id, IdentityResponse::Lookup { response });
                     }
-                    IdentityCommand::UpdateRootDocument { peer_id, package } => {
+                    IdentityCommand::UpdateRootDocument {
+                        peer_id,
+                        package,
+                        identity,
+                    } => {
                         tracing::info!(
                             package = %package,
                             "Sending package to {peer_id}"
@@ -555,7 +658,10 @@ impl NetworkBehaviour for Behaviour {
                         let payload = payload_message_construct(
                             &self.keypair,
                             self.primary_keypair.as_ref(),
-                            Request::Synchronized(super::protocol::Synchronized::Store { package }),
+                            Request::Synchronized(super::protocol::Synchronized::Store {
+                                package,
+                                identity: Some(identity),
+                            }),
                         )
                         .expect("Valid construction of payload");
 
@@ -698,6 +804,9 @@ impl NetworkBehaviour for Behaviour {
                             IdentityResponse::RequestsReceived { response } => {
                                 _ = response.send(Err(warp::error::Error::Boxed(Box::new(error))))
                             }
+                            IdentityResponse::UsernameClaim { response } => {
+                                _ = response.send(Err(warp::error::Error::Boxed(Box::new(error))))
+                            }
                         }
                     }
                     continue;
@@ -1,10 +1,13 @@
 use ipld_core::cid::Cid;
 use rust_ipfs::{libp2p::StreamProtocol, Keypair};
 use serde::{Deserialize, Serialize};
-use warp::{crypto::DID, multipass::identity::ShortId};
+use warp::{
+    crypto::DID,
+    multipass::identity::{RevocationCertificate, ShortId},
+};
 
 use crate::store::{
-    document::identity::IdentityDocument,
+    document::identity::{IdentityDocument, UsernameClaim},
     payload::{PayloadBuilder, PayloadMessage},
 };
 
@@ -184,13 +187,25 @@ pub enum LookupResponse {
 #[serde(rename_all = "snake_case")]
 pub enum Register {
     IsRegistered,
-    RegisterIdentity { root_cid: Cid },
+    RegisterIdentity {
+        root_cid: Cid,
+        /// Redacted copy of the identity that is served on lookup
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        identity: Option<IdentityDocument>,
+    },
+    Revoke {
+        certificate: RevocationCertificate,
+    },
+    ClaimUsername {
+        claim: UsernameClaim,
+    },
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum RegisterResponse {
     Ok,
+    UsernameClaimed { claim: UsernameClaim },
     Error(RegisterError),
 }
 
@@ -200,15 +215,25 @@ pub enum RegisterError {
     InternalError,
     IdentityExist,
     IdentityVerificationFailed,
+    IdentityRevoked,
     NotRegistered,
+    UsernameTaken,
+    UsernameRegistryDisabled,
     None,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum Synchronized {
-    PeerRecord { record: Vec<u8> },
-    Store { package: Cid },
+    PeerRecord {
+        record: Vec<u8>,
+    },
+    Store {
+        package: Cid,
+        /// Redacted copy of the identity that is served on lookup
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        identity: Option<IdentityDocument>,
+    },
     Fetch,
 }
 
@@ -87,6 +87,7 @@ struct ShuttleTask {
     message_rx: mpsc::Receiver<MessageReceiver>,
     precord_tx: mpsc::Sender<PeerRecord>,
     requests: FuturesUnordered<BoxFuture<'static, ()>>,
+    username_registry: bool,
 }
 
 impl ShuttleServer {
@@ -97,6 +98,7 @@ impl ShuttleServer {
         memory_transport: bool,
         listen_addrs: &[Multiaddr],
         ext: bool,
+        enable_username_registry: bool,
     ) -> anyhow::Result<Self> {
         let path = path.map(|p| p.as_ref().to_path_buf());
 
@@ -192,6 +194,7 @@ impl ShuttleServer {
             message_rx: msg_event_rx,
             precord_tx,
             requests: Default::default(),
+            username_registry: enable_username_registry,
         };
 
         let task = tokio::spawn(async move {
@@ -239,11 +242,45 @@ impl ShuttleTask {
         let ipfs = self.ipfs.clone();
         let identity_storage = self.identity_storage.clone();
         let mut subscriptions = self.subscriptions.clone();
+        let username_registry = self.username_registry;
 
         let fut = async move {
             let keypair = ipfs.keypair();
             tracing::info!(request_id = ?id, "Processing Incoming Request");
             let sender = payload.sender();
+
+            // Requests from a device on behalf of a registered identity are only accepted if the device is authorized.
+            // Note: Storing or registering the root document is checked against the document provided so the authorization
+            //       could be stored, and checking the registration does not act on behalf of the identity
+            if payload.cosigner().is_some()
+                && !matches!(
+                    payload.message(),
+                    Message::Request(
+                        identity::protocol::Request::Synchronized(Synchronized::Store { .. })
+                            | identity::protocol::Request::Register(
+                                Register::IsRegistered | Register::RegisterIdentity { .. }
+                            )
+                    )
+                )
+            {
+                let authorized = match (sender.to_did(), payload.original_sender().to_did()) {
+                    (Ok(did), Ok(device)) => match identity_storage
+                        .lookup(Lookup::PublicKey { did })
+                        .await
+                        .map(|list| list.first().cloned())
+                    {
+                        Ok(Some(document)) => document.is_authorized(&device),
+                        _ => false,
+                    },
+                    _ => false,
+                };
+
+                if !authorized {
+                    tracing::warn!(%sender, device = %payload.original_sender(), "device is not authorized");
+                    return;
+                }
+            }
+
             match payload.message() {
                 Message::Request(req) => match req {
                     identity::protocol::Request::Register(Register::IsRegistered) => {
@@ -295,10 +332,93 @@ impl ShuttleTask {
                             let _ = resp.send((ch, payload));
                         }
                     }
+                    identity::protocol::Request::Register(Register::Revoke { certificate }) => {
+                        let keypair = ipfs.keypair();
+                        let did = certificate.did();
+
+                        let response = match identity_storage.revoke(certificate).await {
+                            Ok(_) => {
+                                tracing::info!(%did, %sender, "identity has been revoked");
+                                // Clients are notified so they no longer accept the revoked key
+                                let bytes =
+                                    serde_json::to_vec(certificate).expect("Valid serialization");
+                                _ = ipfs.pubsub_publish("/identity/revoke/v0", bytes).await;
+                                RegisterResponse::Ok
+                            }
+                            Err(e) => {
+                                tracing::warn!(%did, %sender, error = %e, "unable to revoke identity");
+                                RegisterResponse::Error(
+                                    identity::protocol::RegisterError::IdentityVerificationFailed,
+                                )
+                            }
+                        };
+
+                        let payload = payload_message_construct(
+                            keypair,
+                            None,
+                            Response::RegisterResponse(response),
+                        )
+                        .expect("Valid payload construction");
+
+                        if let (Some(ch), Some(resp)) = (ch, resp) {
+                            let _ = resp.send((ch, payload));
+                        }
+                    }
+                    identity::protocol::Request::Register(Register::ClaimUsername { claim }) => {
+                        let keypair = ipfs.keypair();
+
+                        let response = match sender.to_did() {
+                            _ if !username_registry => RegisterResponse::Error(
+                                identity::protocol::RegisterError::UsernameRegistryDisabled,
+                            ),
+                            Ok(did) if did == claim.did => {
+                                match identity_storage.claim_username(claim, keypair).await {
+                                    Ok(claim) => {
+                                        tracing::info!(%did, username = %claim.username, "username has been claimed");
+                                        RegisterResponse::UsernameClaimed { claim }
+                                    }
+                                    Err(e) => {
+                                        tracing::warn!(%did, username = %claim.username, error = %e, "unable to claim username");
+                                        RegisterResponse::Error(match e {
+                                            WarpError::UsernameTaken => {
+                                                identity::protocol::RegisterError::UsernameTaken
+                                            }
+                                            WarpError::IdentityDoesntExist => {
+                                                identity::protocol::RegisterError::NotRegistered
+                                            }
+                                            WarpError::IdentityRevoked => {
+                                                identity::protocol::RegisterError::IdentityRevoked
+                                            }
+                                            _ => identity::protocol::RegisterError::IdentityVerificationFailed,
+                                        })
+                                    }
+                                }
+                            }
+                            _ => {
+                                tracing::warn!(%sender, did = %claim.did, "username claim does not belong to sender");
+                                RegisterResponse::Error(
+                                    identity::protocol::RegisterError::IdentityVerificationFailed,
+                                )
+                            }
+                        };
+
+                        let payload = payload_message_construct(
+                            keypair,
+                            None,
+                            Response::RegisterResponse(response),
+                        )
+                        .expect("Valid payload construction");
+
+                        if let (Some(ch), Some(resp)) = (ch, resp) {
+                            let _ = resp.send((ch, payload));
+                        }
+                    }
                     identity::protocol::Request::Register(Register::RegisterIdentity {
                         root_cid,
+                        identity,
                     }) => {
                         let root_cid = *root_cid;
+                        let public = identity.as_ref();
 
                         tracing::debug!(%sender, %root_cid, "preloading root document");
                         if let Err(e) = ipfs.fetch(&root_cid).recursive().await {
@@ -344,7 +464,23 @@ impl ShuttleTask {
                             return;
                         }
 
-                        if document.verify().is_err() {
+                        let authorized = match payload.cosigner().is_some() {
+                            true => payload
+                                .original_sender()
+                                .to_did()
+                                .map(|device| {
+                                    sender.to_did().is_ok_and(|did| did == document.did)
+                                        && document.is_authorized(&device)
+                                })
+                                .unwrap_or_default(),
+                            false => true,
+                        };
+
+                        let public_invalid = public.is_some_and(|public| {
+                            public.did != document.did || public.verify().is_err()
+                        });
+
+                        if document.verify().is_err() || !authorized || public_invalid {
                             tracing::warn!(%document.did, "Identity cannot be verified");
                             let payload = payload_message_construct(
                                 keypair,
@@ -362,7 +498,8 @@ impl ShuttleTask {
                             return;
                         }
 
-                        if let Err(e) = identity_storage.register(&document, root_cid).await {
+                        if let Err(e) = identity_storage.register(&document, root_cid, public).await
+                        {
                             tracing::warn!(%document.did, error = %e, "Unable to register identity");
                             let res_error = match e {
                                 WarpError::IdentityExist => {
@@ -370,6 +507,11 @@ impl ShuttleTask {
                                         identity::protocol::RegisterError::IdentityExist,
                                     ))
                                 }
+                                WarpError::IdentityRevoked => {
+                                    Response::RegisterResponse(RegisterResponse::Error(
+                                        identity::protocol::RegisterError::IdentityRevoked,
+                                    ))
+                                }
                                 _ => Response::RegisterResponse(RegisterResponse::Error(
                                     identity::protocol::RegisterError::None,
                                 )),
@@ -395,7 +537,10 @@ impl ShuttleTask {
                             // Although we arent able to subscribe, we can still process the request while leaving this as a warning
                         }
 
-                        let payload = PayloadBuilder::new(keypair, document.clone())
+                        // Note: Older clients do not send a public copy, in which case the identity
+                        //       from the root document is announced as before
+                        let announced = public.unwrap_or(&document).clone();
+                        let payload = PayloadBuilder::new(keypair, announced)
                             .build()
                             .expect("Valid payload construction");
 
@@ -598,7 +743,10 @@ impl ShuttleTask {
                             }
                         }
                     }
-                    identity::protocol::Request::Synchronized(Synchronized::Store { package }) => {
+                    identity::protocol::Request::Synchronized(Synchronized::Store {
+                        package,
+                        identity,
+                    }) => {
                         let peer_id = payload.sender();
                         let Ok(did) = peer_id.to_did() else {
                             tracing::warn!(%peer_id, "Could not convert to did key");
@@ -610,6 +758,11 @@ impl ShuttleTask {
                             return;
                         }
 
+                        if identity_storage.is_revoked(&did).await {
+                            tracing::warn!(%did, "Identity has been revoked");
+                            return;
+                        }
+
                         let keypair = ipfs.keypair();
                         tracing::debug!(%did, %package, "preloading root document");
                         if let Err(e) = ipfs.fetch(package).recursive().await {
@@ -646,8 +799,37 @@ impl ShuttleTask {
                             }
                         };
 
+                        if document.verify().is_err() || document.did != did {
+                            tracing::warn!(%did, %package, "identity in root document is invalid");
+                            return;
+                        }
+
+                        let public = identity.as_ref();
+
+                        if public
+                            .is_some_and(|public| public.did != did || public.verify().is_err())
+                        {
+                            tracing::warn!(%did, %package, "public copy of the identity is invalid");
+                            return;
+                        }
+
+                        if payload.cosigner().is_some() {
+                            let authorized = payload
+                                .original_sender()
+                                .to_did()
+                                .map(|device| document.is_authorized(&device))
+                                .unwrap_or_default();
+
+                            if !authorized {
+                                tracing::warn!(%did, device = %payload.original_sender(), "device is not authorized");
+                                return;
+                            }
+                        }
+
                         tracing::debug!(%did, %package, "root document preloaded");
-                        if let Err(e) = identity_storage.update_user_document(&did, *package).await
+                        if let Err(e) = identity_storage
+                            .update_user_document(&did, *package, public)
+                            .await
                         {
                             tracing::warn!(%did, %package, error = %e, "unable to store document");
                             return;
@@ -655,12 +837,16 @@ impl ShuttleTask {
 
                         tracing::info!(%did, %package, "root document is stored");
 
-                        if document.modified > current_document.modified {
+                        // Note: Older clients do not send a public copy, in which case the identity
+                        //       from the root document is announced as before
+                        let announced = public.unwrap_or(&document);
+
+                        if announced.modified > current_document.modified {
                             tracing::info!(%did, "Identity updated");
 
                             tracing::info!(%did, "Announcing to mesh");
 
-                            let payload = PayloadBuilder::new(keypair, document.clone())
+                            let payload = PayloadBuilder::new(keypair, announced.clone())
                                 .build()
                                 .expect("Valid payload construction");
 
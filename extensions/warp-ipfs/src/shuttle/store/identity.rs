@@ -5,20 +5,26 @@ use futures::{
     StreamExt,
 };
 use ipld_core::cid::Cid;
-use rust_ipfs::{Ipfs, IpfsPath};
+use rust_ipfs::{Ipfs, IpfsPath, Keypair};
 use tokio::sync::RwLock;
-use warp::{crypto::DID, error::Error};
+use warp::{crypto::DID, error::Error, multipass::identity::RevocationCertificate};
 
 use crate::{
     shuttle::identity::{protocol::Lookup, RequestPayload},
     store::{
-        document::{identity::IdentityDocument, RootDocument},
+        document::{
+            identity::{verify_revocation_certificate, IdentityDocument, UsernameClaim},
+            RootDocument,
+        },
         DidExt,
     },
 };
 
 use super::root::RootStorage;
 
+/// Number of successive key rotations followed when handing a username over to a successor
+const MAX_SUCCESSIONS: usize = 16;
+
 #[derive(Debug, Clone)]
 pub struct IdentityStorage {
     inner: Arc<RwLock<IdentityStorageInner>>,
@@ -30,20 +36,31 @@ impl IdentityStorage {
 
         let users = root_dag.users;
         let mailbox = root_dag.mailbox;
+        let revoked = root_dag.revoked;
+        let usernames = root_dag.usernames;
+        let public_identities = root_dag.public_identities;
 
         let inner = Arc::new(RwLock::new(IdentityStorageInner {
             ipfs: ipfs.clone(),
             root: root.clone(),
             mailbox,
             users,
+            revoked,
+            usernames,
+            public_identities,
         }));
 
         Self { inner }
     }
 
-    pub async fn register(&self, document: &IdentityDocument, root_cid: Cid) -> Result<(), Error> {
+    pub async fn register(
+        &self,
+        document: &IdentityDocument,
+        root_cid: Cid,
+        public: Option<&IdentityDocument>,
+    ) -> Result<(), Error> {
         let inner = &mut *self.inner.write().await;
-        inner.register(document, root_cid).await
+        inner.register(document, root_cid, public).await
     }
 
     pub async fn lookup(&self, kind: Lookup) -> Result<Vec<IdentityDocument>, Error> {
@@ -66,9 +83,14 @@ impl IdentityStorage {
         inner.deliver_request(to, request).await
     }
 
-    pub async fn update_user_document(&self, did: &DID, data: Cid) -> Result<(), Error> {
+    pub async fn update_user_document(
+        &self,
+        did: &DID,
+        data: Cid,
+        public: Option<&IdentityDocument>,
+    ) -> Result<(), Error> {
         let inner = &mut *self.inner.write().await;
-        inner.update_user_document(did, data).await
+        inner.update_user_document(did, data, public).await
     }
 
     pub async fn get_user_document(&self, did: &DID) -> Result<Cid, Error> {
@@ -81,6 +103,25 @@ impl IdentityStorage {
         inner.list().await
     }
 
+    pub async fn revoke(&self, certificate: &RevocationCertificate) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.revoke(certificate).await
+    }
+
+    pub async fn is_revoked(&self, did: &DID) -> bool {
+        let inner = &*self.inner.read().await;
+        inner.is_revoked(did).await
+    }
+
+    pub async fn claim_username(
+        &self,
+        claim: &UsernameClaim,
+        keypair: &Keypair,
+    ) -> Result<UsernameClaim, Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.claim_username(claim, keypair).await
+    }
+
     // pub async fn remove(&self, did: &DID) -> Result<(), Error> {
     //     let (tx, rx) = futures::channel::oneshot::channel();
 
@@ -103,6 +144,10 @@ struct IdentityStorageInner {
     ipfs: Ipfs,
     users: Option<Cid>,
     mailbox: Option<Cid>,
+    revoked: Option<Cid>,
+    usernames: Option<Cid>,
+    /// Redacted copies of the identities that are served in place of the identity within the root document
+    public_identities: Option<Cid>,
     root: RootStorage,
 }
 
@@ -120,9 +165,243 @@ impl IdentityStorageInner {
         self.ipfs.get_dag(path).local().await.is_ok()
     }
 
-    async fn register(&mut self, document: &IdentityDocument, root_cid: Cid) -> Result<(), Error> {
+    async fn is_revoked(&self, did: &DID) -> bool {
+        let cid = match self.revoked {
+            Some(cid) => cid,
+            None => return false,
+        };
+
+        let path = IpfsPath::from(cid)
+            .sub_path(&did.to_string())
+            .expect("Valid path");
+
+        self.ipfs.get_dag(path).local().await.is_ok()
+    }
+
+    async fn revoke(&mut self, certificate: &RevocationCertificate) -> Result<(), Error> {
+        verify_revocation_certificate(certificate)?;
+
+        let mut list: BTreeMap<String, RevocationCertificate> = match self.revoked {
+            Some(cid) => self
+                .ipfs
+                .get_dag(cid)
+                .local()
+                .deserialized()
+                .await
+                .unwrap_or_default(),
+            None => BTreeMap::new(),
+        };
+
+        let did_str = certificate.did().to_string();
+
+        if list.contains_key(&did_str) {
+            return Ok(());
+        }
+
+        list.insert(did_str, certificate.clone());
+
+        let cid = self.ipfs.put_dag(list).pin(true).await?;
+
+        let old_cid = self.revoked.replace(cid);
+
+        if let Some(old_cid) = old_cid {
+            if old_cid != cid && self.ipfs.is_pinned(&old_cid).await.unwrap_or_default() {
+                _ = self.ipfs.remove_pin(old_cid).await;
+            }
+        }
+
+        self.root.set_revoked(cid).await?;
+
+        // Usernames held by the identity are released so they could be claimed again
+        let mut usernames = self.username_list().await;
+        let count = usernames.len();
+        usernames.retain(|_, claim| claim.did != *certificate.did());
+        if usernames.len() != count {
+            self.set_username_list(usernames).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn username_list(&self) -> BTreeMap<String, UsernameClaim> {
+        match self.usernames {
+            Some(cid) => self
+                .ipfs
+                .get_dag(cid)
+                .local()
+                .deserialized()
+                .await
+                .unwrap_or_default(),
+            None => BTreeMap::new(),
+        }
+    }
+
+    async fn set_username_list(
+        &mut self,
+        list: BTreeMap<String, UsernameClaim>,
+    ) -> Result<(), Error> {
+        let cid = self.ipfs.put_dag(list).pin(true).await?;
+
+        let old_cid = self.usernames.replace(cid);
+
+        if let Some(old_cid) = old_cid {
+            if old_cid != cid && self.ipfs.is_pinned(&old_cid).await.unwrap_or_default() {
+                _ = self.ipfs.remove_pin(old_cid).await;
+            }
+        }
+
+        self.root.set_usernames(cid).await?;
+
+        Ok(())
+    }
+
+    async fn claim_username(
+        &mut self,
+        claim: &UsernameClaim,
+        keypair: &Keypair,
+    ) -> Result<UsernameClaim, Error> {
+        claim.verify_claim()?;
+
+        if !self.contains(&claim.did).await {
+            return Err(Error::IdentityDoesntExist);
+        }
+
+        if self.is_revoked(&claim.did).await {
+            return Err(Error::IdentityRevoked);
+        }
+
+        let mut list = self.username_list().await;
+
+        let key = claim.key();
+
+        if let Some(existing) = list.get(&key) {
+            // The username is handed over to the successor of the key that claimed it
+            if existing.did != claim.did && !self.is_successor(&claim.did, &existing.did).await {
+                return Err(Error::UsernameTaken);
+            }
+        }
+
+        // An identity can only hold a single username at a time
+        list.retain(|_, existing| existing.did != claim.did);
+
+        let claim = claim.clone().countersign(keypair)?;
+
+        list.insert(key, claim.clone());
+
+        self.set_username_list(list).await?;
+
+        Ok(claim)
+    }
+
+    /// Check that the key succeeds the previous key, following the verified successions of the
+    /// keys registered in between
+    async fn is_successor(&self, did: &DID, previous: &DID) -> bool {
+        let mut current = did.clone();
+
+        for _ in 0..MAX_SUCCESSIONS {
+            let Ok(document) = self.identity_document(&current).await else {
+                return false;
+            };
+
+            let Some(succession) = document.predecessor else {
+                return false;
+            };
+
+            // A revoked key could otherwise hand the username over to a key of the attacker
+            if self.is_revoked(&succession.previous).await {
+                return false;
+            }
+
+            if succession.previous == *previous {
+                return true;
+            }
+
+            current = succession.previous;
+        }
+
+        false
+    }
+
+    async fn identity_document(&self, did: &DID) -> Result<IdentityDocument, Error> {
+        let path = match self.public_identity_list().await.get(&did.to_string()) {
+            Some(cid) => IpfsPath::from(*cid),
+            None => IpfsPath::from(self.get_user_document(did).await?)
+                .sub_path("identity")
+                .map_err(anyhow::Error::from)?,
+        };
+
+        let document = self
+            .ipfs
+            .get_dag(path)
+            .local()
+            .deserialized::<IdentityDocument>()
+            .await
+            .map_err(anyhow::Error::from)?;
+
+        document.verify()?;
+
+        if document.did != *did {
+            return Err(Error::IdentityInvalid);
+        }
+
+        Ok(document)
+    }
+
+    async fn public_identity_list(&self) -> BTreeMap<String, Cid> {
+        match self.public_identities {
+            Some(cid) => self
+                .ipfs
+                .get_dag(cid)
+                .local()
+                .deserialized()
+                .await
+                .unwrap_or_default(),
+            None => BTreeMap::new(),
+        }
+    }
+
+    async fn set_public_identity(&mut self, document: &IdentityDocument) -> Result<(), Error> {
         document.verify()?;
 
+        let mut list = self.public_identity_list().await;
+
+        let document_cid = self.ipfs.put_dag(document).await?;
+
+        list.insert(document.did.to_string(), document_cid);
+
+        let cid = self.ipfs.put_dag(list).await?;
+
+        self.ipfs.insert_pin(cid).recursive().await?;
+
+        let old_cid = self.public_identities.replace(cid);
+
+        if let Some(old_cid) = old_cid {
+            if old_cid != cid && self.ipfs.is_pinned(&old_cid).await.unwrap_or_default() {
+                _ = self.ipfs.remove_pin(old_cid).recursive().await;
+            }
+        }
+
+        self.root.set_public_identities(cid).await?;
+
+        Ok(())
+    }
+
+    async fn register(
+        &mut self,
+        document: &IdentityDocument,
+        root_cid: Cid,
+        public: Option<&IdentityDocument>,
+    ) -> Result<(), Error> {
+        document.verify()?;
+
+        if public.is_some_and(|public| public.did != document.did) {
+            return Err(Error::IdentityInvalid);
+        }
+
+        if self.is_revoked(&document.did).await {
+            return Err(Error::IdentityRevoked);
+        }
+
         let mut list: BTreeMap<String, Cid> = match self.users {
             Some(cid) => self
                 .ipfs
@@ -154,14 +433,27 @@ impl IdentityStorageInner {
 
         self.root.set_user_documents(cid).await?;
 
+        if let Some(public) = public {
+            self.set_public_identity(public).await?;
+        }
+
         Ok(())
     }
 
-    async fn update_user_document(&mut self, did: &DID, document: Cid) -> Result<(), Error> {
+    async fn update_user_document(
+        &mut self,
+        did: &DID,
+        document: Cid,
+        public: Option<&IdentityDocument>,
+    ) -> Result<(), Error> {
         if !self.contains(did).await {
             return Err(Error::IdentityDoesntExist);
         }
 
+        if public.is_some_and(|public| &public.did != did) {
+            return Err(Error::IdentityInvalid);
+        }
+
         let identity_peer_id = did.to_peer_id()?;
 
         let mut list: BTreeMap<String, Cid> = match self.users {
@@ -218,6 +510,10 @@ impl IdentityStorageInner {
         }
         self.root.set_user_documents(cid).await?;
 
+        if let Some(public) = public {
+            self.set_public_identity(public).await?;
+        }
+
         Ok(())
     }
 
@@ -255,13 +551,20 @@ impl IdentityStorageInner {
             None => BTreeMap::new(),
         };
 
+        let public_list = self.public_identity_list().await;
+
         let ipfs = self.ipfs.clone();
 
-        FuturesUnordered::from_iter(list.values().copied().map(|cid| {
+        // The public copy is served when one was provided, since the identity within the
+        // root document may hold fields that are not visible to everyone
+        FuturesUnordered::from_iter(list.into_iter().map(|(did, cid)| {
             let ipfs = ipfs.clone();
+            let path = match public_list.get(&did) {
+                Some(public_cid) => Ok(IpfsPath::from(*public_cid)),
+                None => IpfsPath::from(cid).sub_path("identity"),
+            };
             async move {
-                let path = IpfsPath::from(cid).sub_path("identity")?;
-                ipfs.get_dag(path)
+                ipfs.get_dag(path?)
                     .local()
                     .deserialized::<IdentityDocument>()
                     .await
@@ -276,7 +579,27 @@ impl IdentityStorageInner {
     //      and resolve within a stream while matching conditions
     //TODO: Filter stream instead
     async fn lookup(&self, kind: Lookup) -> Result<Vec<IdentityDocument>, Error> {
-        let list_stream = self.list().await;
+        let revoked: BTreeMap<String, RevocationCertificate> = match self.revoked {
+            Some(cid) => self
+                .ipfs
+                .get_dag(cid)
+                .local()
+                .deserialized()
+                .await
+                .unwrap_or_default(),
+            None => BTreeMap::new(),
+        };
+
+        // Identities that have been revoked are no longer discoverable
+        let list_stream = self
+            .list()
+            .await
+            .filter(move |document| {
+                futures::future::ready(!revoked.contains_key(&document.did.to_string()))
+            })
+            .boxed();
+
+        let usernames = self.username_list().await;
 
         let list = match kind {
             Lookup::PublicKey { did } => {
@@ -305,7 +628,7 @@ impl IdentityStorageInner {
                 //TODO: Score against invalid username scheme
                 let split_data = username.split('#').collect::<Vec<&str>>();
 
-                let list = if split_data.len() != 2 {
+                let mut list = if split_data.len() != 2 {
                     list_stream
                         .filter(|document| {
                             futures::future::ready(
@@ -339,13 +662,21 @@ impl IdentityStorageInner {
                     }
                 };
 
+                let name = split_data.first().map(|s| s.to_lowercase());
+                let holder = name
+                    .and_then(|name| usernames.get(&name))
+                    .map(|claim| &claim.did);
+
+                // The verified holder of the username is returned first
+                list.sort_by_key(|document| Some(&document.did) != holder);
+
                 tracing::info!(list_size = list.len(), "Found identities");
 
                 list
             }
             Lookup::Username { username, .. } => {
                 //TODO: Score against invalid username scheme
-                let list = list_stream
+                let mut list = list_stream
                     .filter(|document| {
                         futures::future::ready(
                             document
@@ -357,6 +688,13 @@ impl IdentityStorageInner {
                     .collect::<Vec<_>>()
                     .await;
 
+                let holder = usernames
+                    .get(&username.to_lowercase())
+                    .map(|claim| &claim.did);
+
+                // The verified holder of the username is returned first
+                list.sort_by_key(|document| Some(&document.did) != holder);
+
                 tracing::info!(list_size = list.len(), "Found identities");
 
                 list
@@ -532,3 +870,241 @@ impl IdentityStorageInner {
     //     Ok(())
     // }
 }
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use chrono::Utc;
+    use rust_ipfs::{Ipfs, Keypair, UninitializedIpfsDefault};
+    use warp::{crypto::Fingerprint, error::Error, multipass::identity::SHORT_ID_SIZE};
+
+    use super::IdentityStorage;
+    use crate::{
+        shuttle::{identity::protocol::Lookup, store::root::RootStorage},
+        store::{
+            document::identity::{IdentityDocument, KeySuccession, UsernameClaim},
+            PeerIdExt,
+        },
+    };
+
+    async fn register(
+        ipfs: &Ipfs,
+        storage: &IdentityStorage,
+        keypair: &Keypair,
+        predecessor: Option<KeySuccession>,
+    ) {
+        let did = keypair.to_did().expect("valid keypair");
+        let fingerprint = did.fingerprint();
+        let bytes = fingerprint.as_bytes();
+        let time = Utc::now();
+
+        let document = IdentityDocument {
+            username: warp::multipass::generator::generate_name(),
+            short_id: bytes[bytes.len() - SHORT_ID_SIZE..]
+                .try_into()
+                .expect("Valid conversion"),
+            did,
+            created: time,
+            modified: time,
+            status_message: None,
+            metadata: Default::default(),
+            version: Default::default(),
+            devices: vec![],
+            predecessor,
+            username_claim: None,
+            presence: None,
+            redacted: false,
+            signature: None,
+        }
+        .sign(keypair)
+        .expect("valid");
+
+        let identity_cid = ipfs.put_dag(&document).await.expect("stored identity");
+        let cid = ipfs
+            .put_dag(BTreeMap::from([("identity".to_string(), identity_cid)]))
+            .await
+            .expect("stored root document");
+        storage
+            .register(&document, cid, None)
+            .await
+            .expect("registered identity");
+    }
+
+    #[tokio::test]
+    async fn unique_username() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let root = RootStorage::new(&ipfs, None).await;
+        let storage = IdentityStorage::new(&ipfs, &root).await;
+
+        let registry = Keypair::generate_ed25519();
+        let first = Keypair::generate_ed25519();
+        let second = Keypair::generate_ed25519();
+
+        register(&ipfs, &storage, &first, None).await;
+        register(&ipfs, &storage, &second, None).await;
+
+        let claim = UsernameClaim::new(&first, "Satellite", registry.to_did()?)?;
+        storage.claim_username(&claim, &registry).await?.verify()?;
+
+        // Usernames are unique regardless of case
+        let claim = UsernameClaim::new(&second, "satellite", registry.to_did()?)?;
+        assert!(matches!(
+            storage.claim_username(&claim, &registry).await,
+            Err(Error::UsernameTaken)
+        ));
+
+        // The username is released once its holder claims another
+        let other = UsernameClaim::new(&first, "Satellite2", registry.to_did()?)?;
+        storage.claim_username(&other, &registry).await?;
+        storage.claim_username(&claim, &registry).await?.verify()?;
+
+        // An identity that is not registered cannot claim a username
+        let unknown = Keypair::generate_ed25519();
+        let claim = UsernameClaim::new(&unknown, "Unknown", registry.to_did()?)?;
+        assert!(matches!(
+            storage.claim_username(&claim, &registry).await,
+            Err(Error::IdentityDoesntExist)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn username_follows_key_succession() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let root = RootStorage::new(&ipfs, None).await;
+        let storage = IdentityStorage::new(&ipfs, &root).await;
+
+        let registry = Keypair::generate_ed25519();
+        let first = Keypair::generate_ed25519();
+        let second = Keypair::generate_ed25519();
+        let third = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+
+        register(&ipfs, &storage, &first, None).await;
+        register(
+            &ipfs,
+            &storage,
+            &second,
+            Some(KeySuccession::new(&first, &second)?),
+        )
+        .await;
+        register(
+            &ipfs,
+            &storage,
+            &third,
+            Some(KeySuccession::new(&second, &third)?),
+        )
+        .await;
+        register(&ipfs, &storage, &other, None).await;
+
+        let claim = UsernameClaim::new(&first, "Satellite", registry.to_did()?)?;
+        storage.claim_username(&claim, &registry).await?;
+
+        // A key that does not succeed the holder cannot take the username
+        let claim = UsernameClaim::new(&other, "Satellite", registry.to_did()?)?;
+        assert!(matches!(
+            storage.claim_username(&claim, &registry).await,
+            Err(Error::UsernameTaken)
+        ));
+
+        // The successor of a successor takes the username over
+        let claim = UsernameClaim::new(&third, "Satellite", registry.to_did()?)?;
+        storage.claim_username(&claim, &registry).await?.verify()?;
+
+        // The previous key no longer holds it
+        let claim = UsernameClaim::new(&first, "Satellite", registry.to_did()?)?;
+        assert!(matches!(
+            storage.claim_username(&claim, &registry).await,
+            Err(Error::UsernameTaken)
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn lookup_serves_public_copy() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let root = RootStorage::new(&ipfs, None).await;
+        let storage = IdentityStorage::new(&ipfs, &root).await;
+
+        let keypair = Keypair::generate_ed25519();
+        let did = keypair.to_did()?;
+        let fingerprint = did.fingerprint();
+        let bytes = fingerprint.as_bytes();
+        let time = Utc::now();
+
+        let document = IdentityDocument {
+            username: warp::multipass::generator::generate_name(),
+            short_id: bytes[bytes.len() - SHORT_ID_SIZE..]
+                .try_into()
+                .expect("Valid conversion"),
+            did: did.clone(),
+            created: time,
+            modified: time,
+            status_message: Some("Only for friends".into()),
+            metadata: Default::default(),
+            version: Default::default(),
+            devices: vec![],
+            predecessor: None,
+            username_claim: None,
+            presence: None,
+            redacted: false,
+            signature: None,
+        }
+        .sign(&keypair)?;
+
+        let mut public = document.clone();
+        public.status_message = None;
+        public.redacted = true;
+        let public = public.sign(&keypair)?;
+
+        let identity_cid = ipfs.put_dag(&document).await?;
+        let root_cid = ipfs
+            .put_dag(BTreeMap::from([("identity".to_string(), identity_cid)]))
+            .await?;
+
+        // A copy that belongs to another identity is rejected
+        let other = Keypair::generate_ed25519();
+        let mut forged = public.clone();
+        forged.did = other.to_did()?;
+        let forged = forged.sign(&other)?;
+        assert!(matches!(
+            storage.register(&document, root_cid, Some(&forged)).await,
+            Err(Error::IdentityInvalid)
+        ));
+
+        storage.register(&document, root_cid, Some(&public)).await?;
+
+        let found = storage
+            .lookup(Lookup::PublicKey { did: did.clone() })
+            .await?;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].status_message, None);
+        assert!(found[0].redacted);
+
+        let found = storage
+            .lookup(Lookup::Username {
+                username: document.username.clone(),
+                count: 0,
+            })
+            .await?;
+        assert!(found.iter().all(|found| found.status_message.is_none()));
+        assert_eq!(found.len(), 1);
+
+        Ok(())
+    }
+}
@@ -15,6 +15,12 @@ pub struct Root {
     pub mailbox: Option<Cid>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub conversation_mailbox: Option<Cid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked: Option<Cid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usernames: Option<Cid>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_identities: Option<Cid>,
 }
 
 #[derive(Debug)]
@@ -81,6 +87,21 @@ impl RootStorage {
         inner.set_conversation_mailbox(&self.ipfs, cid).await
     }
 
+    pub async fn set_revoked(&self, cid: Cid) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.set_revoked(&self.ipfs, cid).await
+    }
+
+    pub async fn set_usernames(&self, cid: Cid) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.set_usernames(&self.ipfs, cid).await
+    }
+
+    pub async fn set_public_identities(&self, cid: Cid) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.set_public_identities(&self.ipfs, cid).await
+    }
+
     pub async fn get_root(&self) -> Root {
         let inner = &*self.inner.read().await;
         inner.root
@@ -111,6 +132,27 @@ impl RootInner {
         Ok(())
     }
 
+    async fn set_revoked(&mut self, ipfs: &Ipfs, cid: Cid) -> Result<(), Error> {
+        self.root.revoked.replace(cid);
+        tracing::debug!(%cid, "revocation list set");
+        self.save(ipfs).await?;
+        Ok(())
+    }
+
+    async fn set_usernames(&mut self, ipfs: &Ipfs, cid: Cid) -> Result<(), Error> {
+        self.root.usernames.replace(cid);
+        tracing::debug!(%cid, "username registry set");
+        self.save(ipfs).await?;
+        Ok(())
+    }
+
+    async fn set_public_identities(&mut self, ipfs: &Ipfs, cid: Cid) -> Result<(), Error> {
+        self.root.public_identities.replace(cid);
+        tracing::debug!(%cid, "public identities set");
+        self.save(ipfs).await?;
+        Ok(())
+    }
+
     async fn save(&mut self, ipfs: &Ipfs) -> std::io::Result<()> {
         //TODO: Reenable ipns
         // self.ipfs
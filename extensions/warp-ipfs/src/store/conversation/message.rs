@@ -1,7 +1,7 @@
 use crate::store::document::FileAttachmentDocument;
 use crate::store::keystore::Keystore;
 use crate::store::{
-    ecdh_decrypt, ecdh_encrypt, ecdh_encrypt_with_nonce, extract_data_slice, DidExt, PeerIdExt,
+    ecdh_decrypt, ecdh_encrypt_with_nonce, ecdh_encrypt_with_suite, DidExt, PeerIdExt,
     MAX_ATTACHMENT, MAX_MESSAGE_SIZE, MAX_REACTIONS, MIN_MESSAGE_SIZE,
 };
 use bytes::Bytes;
@@ -13,7 +13,7 @@ use rust_ipfs::{Ipfs, Keypair};
 use serde::{Deserialize, Deserializer, Serialize};
 use std::future::IntoFuture;
 use uuid::Uuid;
-use warp::crypto::cipher::Cipher;
+use warp::crypto::cipher::{Cipher, CipherSuite};
 use warp::crypto::hash::sha256_iter;
 use warp::crypto::{DIDKey, Ed25519KeyPair, KeyMaterial, DID};
 use warp::error::Error;
@@ -91,6 +91,7 @@ impl MessageDocument {
         keypair: &Keypair,
         message: Message,
         key: Either<&DID, &Keystore>,
+        suite: CipherSuite,
     ) -> Result<Self, Error> {
         let id = message.id();
         let message_type = message.message_type();
@@ -154,9 +155,12 @@ impl MessageDocument {
         let data = match key {
             Either::Right(keystore) => {
                 let key = keystore.get_latest(keypair, &sender)?;
-                Cipher::direct_encrypt(&bytes, &key)?.into()
+                Cipher::from(&key)
+                    .with_suite(suite)
+                    .encrypt(&bytes, None)?
+                    .into()
             }
-            Either::Left(key) => ecdh_encrypt(keypair, Some(key), &bytes)?.into(),
+            Either::Left(key) => ecdh_encrypt_with_suite(keypair, Some(key), &bytes, suite)?.into(),
         };
 
         let message = Some(data);
@@ -229,8 +233,7 @@ impl MessageDocument {
 
     pub fn nonce_from_message(&self) -> Result<&[u8], Error> {
         let raw_encrypted_message = self.raw_encrypted_message()?;
-        let (nonce, _) = extract_data_slice::<12>(raw_encrypted_message);
-        debug_assert_eq!(nonce.len(), 12);
+        let (_, nonce) = Cipher::extract_nonce(raw_encrypted_message)?;
         Ok(nonce)
     }
 
@@ -246,6 +249,7 @@ impl MessageDocument {
         signature: Option<Vec<u8>>,
         key: Either<&DID, &Keystore>,
         nonce: Option<&[u8]>,
+        suite: CipherSuite,
     ) -> Result<(), Error> {
         let did = &keypair.to_did()?;
         tracing::info!(id = %self.conversation_id, message_id = %self.id, "Updating message");
@@ -309,6 +313,8 @@ impl MessageDocument {
 
             let bytes = serde_json::to_vec(&lines)?;
 
+            // Note: The suite of a given nonce is determined by its size, so the message is encrypted the same
+            //       way as it was by the sender
             let data = match (key, nonce) {
                 (Either::Right(keystore), Some(nonce)) => {
                     let key = keystore.get_latest(keypair, &sender)?;
@@ -319,9 +325,11 @@ impl MessageDocument {
                 }
                 (Either::Right(keystore), None) => {
                     let key = keystore.get_latest(keypair, &sender)?;
-                    Cipher::direct_encrypt(&bytes, &key)?
+                    Cipher::from(&key).with_suite(suite).encrypt(&bytes, None)?
+                }
+                (Either::Left(key), None) => {
+                    ecdh_encrypt_with_suite(keypair, Some(key), &bytes, suite)?
                 }
-                (Either::Left(key), None) => ecdh_encrypt(keypair, Some(key), &bytes)?,
             };
 
             self.message = (!data.is_empty()).then_some(data.into());
@@ -1,7 +1,7 @@
 use crate::store::conversation::MessageDocument;
 use futures::stream::BoxStream;
 use futures::{stream, StreamExt};
-use indexmap::IndexMap;
+use indexmap::{map::Entry, IndexMap};
 use ipld_core::cid::Cid;
 use rust_ipfs::{Ipfs, IpfsPath};
 use serde::{Deserialize, Serialize};
@@ -296,6 +296,102 @@ impl MessageReferenceList {
         Ok(())
     }
 
+    /// Combine the references of both lists. A message removed from either list is left out, and a message
+    /// found in both is kept as it was last modified
+    pub async fn merge(
+        &self,
+        ipfs: &Ipfs,
+        other: &MessageReferenceList,
+    ) -> Result<MessageReferenceList, Error> {
+        let mut entries = self.entries(ipfs).await?;
+
+        for (id, cid) in other.entries(ipfs).await? {
+            let mut entry = match entries.entry(id) {
+                Entry::Vacant(entry) => {
+                    entry.insert(cid);
+                    continue;
+                }
+                Entry::Occupied(entry) => entry,
+            };
+
+            match (*entry.get(), cid) {
+                (_, None) => {
+                    entry.insert(None);
+                }
+                (Some(current), Some(cid)) if current != cid => {
+                    let current = ipfs
+                        .get_dag(current)
+                        .timeout(Duration::from_secs(10))
+                        .deserialized::<MessageDocument>()
+                        .await?;
+                    let message = ipfs
+                        .get_dag(cid)
+                        .timeout(Duration::from_secs(10))
+                        .deserialized::<MessageDocument>()
+                        .await?;
+                    if message.modified > current.modified {
+                        entry.insert(Some(cid));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Self::from_entries(ipfs, entries).await
+    }
+
+    /// References of every message along the list, including those marked as removed
+    async fn entries(&self, ipfs: &Ipfs) -> Result<IndexMap<String, Option<Cid>>, Error> {
+        let mut entries = IndexMap::new();
+        let mut current = Some(*self);
+
+        while let Some(list) = current.take() {
+            if let Some(cid) = list.messages {
+                entries.extend(
+                    ipfs.get_dag(cid)
+                        .timeout(Duration::from_secs(10))
+                        .deserialized::<IndexMap<String, Option<Cid>>>()
+                        .await?,
+                );
+            }
+
+            if let Some(next) = list.next {
+                let list = ipfs
+                    .get_dag(next)
+                    .timeout(Duration::from_secs(10))
+                    .deserialized::<MessageReferenceList>()
+                    .await?;
+                current = Some(list);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Build a list from the references, split into pages of the same length `insert` fills them to
+    async fn from_entries(
+        ipfs: &Ipfs,
+        entries: IndexMap<String, Option<Cid>>,
+    ) -> Result<MessageReferenceList, Error> {
+        let entries = Vec::from_iter(entries);
+        let mut list = MessageReferenceList::default();
+
+        // Note: Pages are stored from the end since each page refers to the one after it
+        for page in entries.chunks(REFERENCE_LENGTH + 1).rev() {
+            let next = match list.messages {
+                Some(_) => Some(ipfs.put_dag(list).await?),
+                None => None,
+            };
+            let page = IndexMap::<String, Option<Cid>>::from_iter(page.iter().cloned());
+            list = MessageReferenceList {
+                messages: Some(ipfs.put_dag(page).await?),
+                next,
+            };
+        }
+
+        Ok(list)
+    }
+
     // Since we have `IndexMap<String, Option<Cid>>` where the value is an `Option`, it is possible that
     // that there could be some fragmentation when it comes to removing messages. This function would consume
     // the current `MessageReferenceList` and walk down the reference list via `MessageReferenceList::list`
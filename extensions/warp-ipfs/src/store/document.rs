@@ -14,25 +14,27 @@ use uuid::Uuid;
 use warp::{
     constellation::{
         directory::Directory,
-        file::{File, FileType},
+        file::{File, FileType, ImageVariant},
         Progression,
     },
     error::Error,
     multipass::identity::{Identity, IdentityStatus},
 };
 
-use super::{keystore::Keystore, DidExt};
+use super::{canonical_json, keystore::Keystore, DidExt};
 
 use self::{
-    files::{DirectoryDocument, FileDocument},
+    files::{DirectoryDocument, FileDocument, VariantDocument},
     identity::IdentityDocument,
     image_dag::ImageDag,
 };
 
+pub mod bundle;
 pub mod cache;
 pub mod files;
 pub mod identity;
 pub mod image_dag;
+pub mod recovery;
 pub mod root;
 
 #[derive(Debug, serde::Serialize, serde::Deserialize, Clone)]
@@ -46,18 +48,33 @@ pub struct ResolvedRootDocument {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_index: Option<Directory>,
     pub request: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recovery_shares: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contacts: Vec<u8>,
     pub conversation_keystore: BTreeMap<Uuid, Keystore>,
     pub signature: Option<Vec<u8>>,
 }
 
 impl ResolvedRootDocument {
+    pub fn sign(mut self, keypair: &Keypair) -> Result<Self, Error> {
+        self.signature = None;
+        let bytes = canonical_json(&self)?;
+        let signature = keypair.sign(&bytes).expect("not RSA key");
+        self.signature = Some(signature);
+        Ok(self)
+    }
+
     pub fn verify(&self) -> Result<(), Error> {
         let mut doc = self.clone();
         let signature = doc.signature.take().ok_or(Error::InvalidSignature)?;
-        let bytes = serde_json::to_vec(&doc)?;
         let identity_public_key = self.identity.did_key().to_public_key()?;
 
-        if !identity_public_key.verify(&bytes, &signature) {
+        // Note: Documents signed prior to the canonical encoding are checked against their plain encoding
+        let signed = identity_public_key.verify(&canonical_json(&doc)?, &signature)
+            || identity_public_key.verify(&serde_json::to_vec(&doc)?, &signature);
+
+        if !signed {
             return Err(Error::InvalidSignature);
         }
 
@@ -87,6 +104,12 @@ pub struct RootDocument {
     /// array of request (Request)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub request: Option<Cid>,
+    /// array of recovery shares held on behalf of friends (RecoveryShareDocument)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery_shares: Option<Cid>,
+    /// array of contact records for friends (ContactDocument)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub contacts: Option<Cid>,
     /// map of conversations
     #[serde(skip_serializing_if = "Option::is_none")]
     pub conversations: Option<Cid>,
@@ -96,6 +119,9 @@ pub struct RootDocument {
     /// index to constellation
     #[serde(skip_serializing_if = "Option::is_none")]
     pub file_index: Option<Cid>,
+    /// map of the last time entries were added or removed (EntryChange)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub changes: Option<Cid>,
     /// Online/Away/Busy/Offline status
     #[serde(skip_serializing_if = "Option::is_none")]
     pub status: Option<IdentityStatus>,
@@ -104,6 +130,14 @@ pub struct RootDocument {
     pub signature: Option<String>,
 }
 
+/// Last change made to an entry of the root document, allowing a removal to be kept when merging the document
+/// of another device that still holds the entry
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryChange {
+    pub removed: bool,
+    pub modified: DateTime<Utc>,
+}
+
 impl RootDocument {
     #[tracing::instrument(skip(self, keypair))]
     pub fn sign(mut self, keypair: &Keypair) -> Result<Self, Error> {
@@ -118,6 +152,22 @@ impl RootDocument {
         Ok(self)
     }
 
+    /// Check if both documents point to the same state, regardless of when they were signed
+    pub fn same_state(&self, other: &RootDocument) -> bool {
+        self.identity == other.identity
+            && self.friends == other.friends
+            && self.blocks == other.blocks
+            && self.block_by == other.block_by
+            && self.request == other.request
+            && self.recovery_shares == other.recovery_shares
+            && self.contacts == other.contacts
+            && self.conversations == other.conversations
+            && self.conversations_keystore == other.conversations_keystore
+            && self.file_index == other.file_index
+            && self.changes == other.changes
+            && self.status == other.status
+    }
+
     #[tracing::instrument(skip(self, ipfs))]
     pub async fn verify(&self, ipfs: &Ipfs) -> Result<(), Error> {
         let identity: IdentityDocument = ipfs
@@ -201,6 +251,28 @@ impl RootDocument {
             .await
             .unwrap_or_default();
 
+        let recovery_shares = futures::future::ready(self.recovery_shares.ok_or(Error::Other))
+            .and_then(|document| async move {
+                ipfs.get_dag(document)
+                    .local()
+                    .deserialized()
+                    .await
+                    .map_err(Error::from)
+            })
+            .await
+            .unwrap_or_default();
+
+        let contacts = futures::future::ready(self.contacts.ok_or(Error::Other))
+            .and_then(|document| async move {
+                ipfs.get_dag(document)
+                    .local()
+                    .deserialized()
+                    .await
+                    .map_err(Error::from)
+            })
+            .await
+            .unwrap_or_default();
+
         let conversation_keystore =
             futures::future::ready(self.conversations_keystore.ok_or(Error::Other))
                 .and_then(|document| async move {
@@ -235,7 +307,7 @@ impl RootDocument {
 
         let file_index = None;
 
-        let mut exported = ResolvedRootDocument {
+        let exported = ResolvedRootDocument {
             identity,
             created: self.created,
             modified: self.modified,
@@ -243,17 +315,14 @@ impl RootDocument {
             block_list,
             block_by_list,
             request,
+            recovery_shares,
+            contacts,
             file_index,
             conversation_keystore,
             signature: None,
         };
 
-        let bytes = serde_json::to_vec(&exported)?;
-        let kp = keypair.unwrap_or_else(|| ipfs.keypair());
-        let signature = kp.sign(&bytes).expect("not RSA key");
-
-        exported.signature = Some(signature);
-        Ok(exported)
+        exported.sign(keypair.unwrap_or_else(|| ipfs.keypair()))
     }
 
     #[tracing::instrument(skip(self, ipfs))]
@@ -302,6 +371,32 @@ impl RootDocument {
             })
             .await;
 
+        let _ = futures::future::ready(self.recovery_shares.ok_or(Error::Other))
+            .and_then(|document| async move {
+                ipfs.get_dag(document)
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .map_err(Error::from)
+            })
+            .await;
+
+        let _ = futures::future::ready(self.contacts.ok_or(Error::Other))
+            .and_then(|document| async move {
+                ipfs.get_dag(document)
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .map_err(Error::from)
+            })
+            .await;
+        let _ = futures::future::ready(self.changes.ok_or(Error::Other))
+            .and_then(|document| async move {
+                ipfs.get_dag(document)
+                    .await
+                    .map_err(anyhow::Error::from)
+                    .map_err(Error::from)
+            })
+            .await;
+
         let _ = futures::future::ready(self.conversations_keystore.ok_or(Error::Other))
             .and_then(|document| async move {
                 let map: BTreeMap<String, Cid> = ipfs.get_dag(document).deserialized().await?;
@@ -321,12 +416,13 @@ impl RootDocument {
         self.verify(ipfs).await
     }
 
-    // TODO: Include optional keypair to represent the actual keypair
-    pub async fn import(ipfs: &Ipfs, data: ResolvedRootDocument) -> Result<Self, Error> {
+    pub async fn import(
+        ipfs: &Ipfs,
+        keypair: &Keypair,
+        data: ResolvedRootDocument,
+    ) -> Result<Self, Error> {
         data.verify()?;
 
-        let keypair = ipfs.keypair();
-
         let metadata = data.identity.metadata().clone();
 
         let mut document: IdentityDocument = data.identity.into();
@@ -348,9 +444,12 @@ impl RootDocument {
             blocks: None,
             block_by: None,
             request: None,
+            recovery_shares: None,
+            contacts: None,
             conversations: None,
             conversations_keystore: None,
             file_index: None,
+            changes: None,
             status: None,
             signature: None,
         };
@@ -359,6 +458,8 @@ impl RootDocument {
         let has_blocks = !data.block_list.is_empty();
         let has_block_by_list = !data.block_by_list.is_empty();
         let has_requests = !data.request.is_empty();
+        let has_recovery_shares = !data.recovery_shares.is_empty();
+        let has_contacts = !data.contacts.is_empty();
         let has_keystore = !data.conversation_keystore.is_empty();
 
         if has_friends {
@@ -377,6 +478,14 @@ impl RootDocument {
             root_document.request = ipfs.put_dag(data.request).await.ok();
         }
 
+        if has_recovery_shares {
+            root_document.recovery_shares = ipfs.put_dag(data.recovery_shares).await.ok();
+        }
+
+        if has_contacts {
+            root_document.contacts = ipfs.put_dag(data.contacts).await.ok();
+        }
+
         if has_keystore {
             let mut pointer_map: BTreeMap<String, Cid> = BTreeMap::new();
             for (k, v) in data.conversation_keystore {
@@ -413,6 +522,8 @@ pub struct FileAttachmentDocument {
     pub creation: DateTime<Utc>,
     pub thumbnail: Option<Cid>,
     pub file_type: FileType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<VariantDocument>,
     // Note: We use `String` instead of `Cid` to create a stop point when it comes to walking dag
     //       since we dont want to calculate the depth of the message to prevent the fetching before
     //       it is requested
@@ -430,6 +541,12 @@ impl FileAttachmentDocument {
         file.set_id(self.id);
         file.set_size(self.size);
         file.set_file_type(self.file_type.clone());
+        file.set_variants(
+            self.variants
+                .iter()
+                .map(VariantDocument::to_variant)
+                .collect(),
+        );
 
         if let Some(cid) = self.thumbnail {
             let image: ImageDag = ipfs
@@ -532,15 +649,30 @@ impl FileAttachmentDocument {
         progress_stream.boxed()
     }
 
+    /// Stream the attachment, or the variant best suited to display it at `size` pixels along its longest side
     pub fn download_stream(
         &self,
         ipfs: &Ipfs,
         members: &[PeerId],
+        size: Option<u32>,
         timeout: Option<Duration>,
     ) -> BoxStream<'static, Result<Bytes, std::io::Error>> {
-        let link = match Cid::from_str(&self.data) {
-            Ok(link) => link,
-            Err(e) => return stream::once(async { Err(std::io::Error::other(e)) }).boxed(),
+        let variants = self
+            .variants
+            .iter()
+            .map(VariantDocument::to_variant)
+            .collect::<Vec<_>>();
+
+        let variant = size
+            .and_then(|size| ImageVariant::select(&variants, size))
+            .and_then(VariantDocument::from_variant);
+
+        let link = match variant {
+            Some(variant) => variant.link,
+            None => match Cid::from_str(&self.data) {
+                Ok(link) => link,
+                Err(e) => return stream::once(async { Err(std::io::Error::other(e)) }).boxed(),
+            },
         };
 
         let stream = ipfs
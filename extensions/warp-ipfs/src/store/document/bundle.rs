@@ -0,0 +1,309 @@
+//! Export bundle of an identity.
+//!
+//! A bundle is the JSON encoding of [`ExportBundle`], encrypted to the key of the identity, and consists of
+//! - `manifest`: version of the format, the identity, when it was created and the sections that were included
+//! - `root`: signed root document of the identity, with the lists of any section that was not selected left empty
+//! - `conversations`: conversations along with their messages when [`ExportSection::Messages`] was selected
+//! - `signature`: base58 encoded signature of the bundle without the signature, made by the key of the identity.
+//!   The bundle is signed in its canonical encoding, with the keys of every object sorted, since it holds maps
+//!   whose entries are not kept in order
+//!
+//! Exports made prior to the bundle are a bare root document, which are read as a bundle of version 0
+//! holding the friends and conversation keys.
+use chrono::Utc;
+use rust_ipfs::Keypair;
+use serde::{Deserialize, Serialize};
+
+use warp::{
+    error::Error,
+    multipass::{ExportManifest, ExportSection},
+};
+
+use crate::store::{
+    canonical_json,
+    conversation::{message::MessageDocument, ConversationDocument},
+    ecdh_decrypt, ecdh_encrypt, DidExt,
+};
+
+use super::ResolvedRootDocument;
+
+/// Version of the bundle format
+pub const BUNDLE_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportBundle {
+    pub manifest: ExportManifest,
+    pub root: ResolvedRootDocument,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conversations: Vec<ConversationExport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationExport {
+    pub document: ConversationDocument,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<MessageDocument>,
+}
+
+impl ExportBundle {
+    pub fn new(
+        keypair: &Keypair,
+        mut root: ResolvedRootDocument,
+        mut conversations: Vec<ConversationExport>,
+        sections: &[ExportSection],
+    ) -> Result<Self, Error> {
+        let mut sections = sections.to_vec();
+        sections.sort();
+        sections.dedup();
+
+        if !sections.contains(&ExportSection::Friends) {
+            root.friends.clear();
+            root.block_list.clear();
+            root.block_by_list.clear();
+            root.request.clear();
+            root.recovery_shares.clear();
+            root.contacts.clear();
+        }
+
+        if !sections.contains(&ExportSection::ConversationKeys) {
+            root.conversation_keystore.clear();
+        }
+
+        if !sections.contains(&ExportSection::FileIndex) {
+            root.file_index = None;
+        }
+
+        if !sections.contains(&ExportSection::Messages) {
+            conversations.clear();
+        }
+
+        let root = root.sign(keypair)?;
+
+        let manifest = ExportManifest::new(
+            BUNDLE_VERSION,
+            root.identity.did_key(),
+            Utc::now(),
+            sections,
+        );
+
+        let bundle = ExportBundle {
+            manifest,
+            root,
+            conversations,
+            signature: None,
+        };
+
+        bundle.sign(keypair)
+    }
+
+    fn sign(mut self, keypair: &Keypair) -> Result<Self, Error> {
+        self.signature = None;
+        let bytes = canonical_json(&self)?;
+        let signature = keypair.sign(&bytes).expect("not RSA key");
+        self.signature = Some(bs58::encode(signature).into_string());
+        Ok(self)
+    }
+
+    pub fn verify(&self) -> Result<(), Error> {
+        if self.manifest.version() > BUNDLE_VERSION {
+            return Err(Error::UnsupportedBundleVersion);
+        }
+
+        if self.root.identity.did_key() != *self.manifest.did() {
+            return Err(Error::IdentityInvalid);
+        }
+
+        self.root.verify()?;
+
+        // Exports prior to the bundle are only signed through the root document
+        if self.manifest.version() == 0 {
+            return match self.conversations.is_empty() {
+                true => Ok(()),
+                false => Err(Error::InvalidSignature),
+            };
+        }
+
+        let mut bundle = self.clone();
+        let signature = bundle.signature.take().ok_or(Error::InvalidSignature)?;
+        let signature = bs58::decode(signature).into_vec()?;
+        let bytes = canonical_json(&bundle)?;
+        let public_key = self.manifest.did().to_public_key()?;
+
+        if !public_key.verify(&bytes, &signature) {
+            return Err(Error::InvalidSignature);
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self, keypair: &Keypair) -> Result<Vec<u8>, Error> {
+        let bytes = serde_json::to_vec(self)?;
+        ecdh_encrypt(keypair, None, bytes)
+    }
+
+    /// Decrypt and verify a bundle, or an export made prior to the bundle
+    pub fn from_bytes(keypair: &Keypair, bytes: impl AsRef<[u8]>) -> Result<Self, Error> {
+        let bytes = ecdh_decrypt(keypair, None, bytes)?;
+
+        let bundle = match serde_json::from_slice::<ExportBundle>(&bytes) {
+            Ok(bundle) => bundle,
+            Err(_) => {
+                let root = serde_json::from_slice::<ResolvedRootDocument>(&bytes)?;
+                let mut sections = vec![];
+                if !root.friends.is_empty() {
+                    sections.push(ExportSection::Friends);
+                }
+                if !root.conversation_keystore.is_empty() {
+                    sections.push(ExportSection::ConversationKeys);
+                }
+                let manifest =
+                    ExportManifest::new(0, root.identity.did_key(), root.modified, sections);
+                ExportBundle {
+                    manifest,
+                    root,
+                    conversations: vec![],
+                    signature: None,
+                }
+            }
+        };
+
+        bundle.verify()?;
+
+        Ok(bundle)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use rust_ipfs::Keypair;
+    use warp::{
+        error::Error,
+        multipass::{identity::Identity, ExportSection},
+    };
+
+    use super::{ConversationExport, ExportBundle, BUNDLE_VERSION};
+    use crate::store::{
+        conversation::ConversationDocument, document::ResolvedRootDocument, ecdh_encrypt,
+        keystore::Keystore, PeerIdExt,
+    };
+
+    fn root_document(keypair: &Keypair) -> anyhow::Result<ResolvedRootDocument> {
+        let mut identity = Identity::default();
+        identity.set_did_key(keypair.to_did()?);
+        let friends = ecdh_encrypt(keypair, None, b"[]")?;
+        let document = ResolvedRootDocument {
+            identity,
+            created: Utc::now(),
+            modified: Utc::now(),
+            friends,
+            block_list: vec![],
+            block_by_list: vec![],
+            file_index: None,
+            request: vec![],
+            recovery_shares: vec![],
+            contacts: vec![],
+            conversation_keystore: Default::default(),
+            signature: None,
+        };
+        Ok(document.sign(keypair)?)
+    }
+
+    #[test]
+    fn bundle_roundtrip() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let root = root_document(&keypair)?;
+
+        let bundle = ExportBundle::new(&keypair, root.clone(), vec![], &[ExportSection::Friends])?;
+        assert_eq!(bundle.manifest.version(), BUNDLE_VERSION);
+        assert_eq!(bundle.manifest.sections(), &[ExportSection::Friends]);
+        assert_eq!(bundle.root.friends, root.friends);
+
+        let bytes = bundle.to_bytes(&keypair)?;
+        let imported = ExportBundle::from_bytes(&keypair, &bytes)?;
+        assert_eq!(imported.manifest, bundle.manifest);
+
+        // Sections that were not selected are left out
+        let bundle = ExportBundle::new(&keypair, root, vec![], &[])?;
+        assert!(bundle.root.friends.is_empty());
+        bundle.verify()?;
+
+        assert!(ExportBundle::from_bytes(&Keypair::generate_ed25519(), &bytes).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_rejects_tampering() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let root = root_document(&keypair)?;
+        let bundle = ExportBundle::new(&keypair, root, vec![], &ExportSection::all())?;
+
+        let mut tampered = bundle.clone();
+        tampered.manifest = warp::multipass::ExportManifest::new(
+            BUNDLE_VERSION,
+            bundle.manifest.did().clone(),
+            bundle.manifest.created(),
+            vec![],
+        );
+        assert!(matches!(tampered.verify(), Err(Error::InvalidSignature)));
+
+        let mut unsupported = bundle.clone();
+        unsupported.manifest = warp::multipass::ExportManifest::new(
+            BUNDLE_VERSION + 1,
+            bundle.manifest.did().clone(),
+            bundle.manifest.created(),
+            bundle.manifest.sections().to_vec(),
+        );
+        assert!(matches!(
+            unsupported.verify(),
+            Err(Error::UnsupportedBundleVersion)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_signature_is_independent_of_map_order() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let mut root = root_document(&keypair)?;
+
+        let own = keypair.to_did()?;
+        let friend = Keypair::generate_ed25519().to_did()?;
+        let mut conversation = ConversationDocument::new_direct(&keypair, [own, friend])?;
+
+        let mut keystore = Keystore::new();
+        for _ in 0..16 {
+            let did = Keypair::generate_ed25519().to_did()?;
+            keystore.insert(&keypair, &did, warp::crypto::generate::<32>())?;
+            conversation.excluded.insert(did, "signature".into());
+        }
+        root.conversation_keystore.insert(conversation.id, keystore);
+
+        let conversations = vec![ConversationExport {
+            document: conversation,
+            messages: vec![],
+        }];
+
+        let bundle = ExportBundle::new(&keypair, root, conversations, &ExportSection::all())?;
+
+        // Every decoding builds the maps anew, with their entries in a different order
+        let bytes = serde_json::to_vec(&bundle)?;
+        for _ in 0..16 {
+            serde_json::from_slice::<ExportBundle>(&bytes)?.verify()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn bundle_reads_legacy_export() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let root = root_document(&keypair)?;
+
+        let bytes = ecdh_encrypt(&keypair, None, serde_json::to_vec(&root)?)?;
+        let bundle = ExportBundle::from_bytes(&keypair, bytes)?;
+        assert_eq!(bundle.manifest.version(), 0);
+        assert_eq!(bundle.manifest.sections(), &[ExportSection::Friends]);
+        Ok(())
+    }
+}
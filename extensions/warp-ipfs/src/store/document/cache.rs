@@ -1,4 +1,7 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
 
 use futures::{
     stream::{BoxStream, FuturesUnordered},
@@ -7,11 +10,11 @@ use futures::{
 use ipld_core::cid::Cid;
 use rust_ipfs::{Ipfs, IpfsPath};
 use tokio::sync::RwLock;
-use warp::{crypto::DID, error::Error};
+use warp::{crypto::DID, error::Error, multipass::identity::RevocationCertificate};
 
 use crate::store::ds_key::DataStoreKey;
 
-use super::identity::IdentityDocument;
+use super::identity::{verify_revocation_certificate, IdentityDocument};
 
 #[derive(Debug, Clone)]
 pub struct IdentityCache {
@@ -30,11 +33,28 @@ impl IdentityCache {
             .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
             .and_then(|cid_str| cid_str.parse().ok());
 
-        let inner = IdentityCacheInner {
+        let key = ipfs.revoked();
+        let revoked = ipfs
+            .repo()
+            .data_store()
+            .get(key.as_bytes())
+            .await
+            .unwrap_or_default()
+            .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+            .and_then(|cid_str| cid_str.parse().ok());
+
+        let mut inner = IdentityCacheInner {
             ipfs: ipfs.clone(),
             list,
+            revoked,
+            devices: HashMap::new(),
         };
 
+        let mut documents = inner.list().await;
+        while let Some(document) = documents.next().await {
+            inner.index_devices(&document);
+        }
+
         Self {
             inner: Arc::new(RwLock::new(inner)),
         }
@@ -62,21 +82,63 @@ impl IdentityCache {
         let inner = &*self.inner.read().await;
         inner.list().await
     }
+
+    /// Store the revocation certificate, removing the identity from the cache.
+    /// Returns false if the key was already revoked
+    pub async fn revoke(&self, certificate: &RevocationCertificate) -> Result<bool, Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.revoke(certificate).await
+    }
+
+    pub async fn is_revoked(&self, did: &DID) -> bool {
+        let inner = &*self.inner.read().await;
+        inner.is_revoked(did).await
+    }
+
+    /// Find the identity that authorized the device
+    pub async fn get_by_device(&self, device: &DID) -> Result<IdentityDocument, Error> {
+        let inner = &*self.inner.read().await;
+        let did = inner
+            .devices
+            .get(device)
+            .cloned()
+            .ok_or(Error::IdentityDoesntExist)?;
+        let document = inner.get(&did).await?;
+        if !document.devices.iter().any(|entry| entry.device.eq(device)) {
+            return Err(Error::IdentityDoesntExist);
+        }
+        Ok(document)
+    }
 }
 
 #[derive(Debug)]
 struct IdentityCacheInner {
     pub ipfs: Ipfs,
     pub list: Option<Cid>,
+    pub revoked: Option<Cid>,
+    /// Maps an authorized device to the identity that authorized it
+    pub devices: HashMap<DID, DID>,
 }
 
 impl IdentityCacheInner {
+    fn index_devices(&mut self, document: &IdentityDocument) {
+        self.devices.retain(|_, did| did.ne(&document.did));
+        for entry in &document.devices {
+            self.devices
+                .insert(entry.device.clone(), document.did.clone());
+        }
+    }
+
     async fn insert(
         &mut self,
         document: &IdentityDocument,
     ) -> Result<Option<IdentityDocument>, Error> {
         document.verify()?;
 
+        if self.is_revoked(&document.did).await {
+            return Err(Error::IdentityRevoked);
+        }
+
         let mut list: HashMap<String, Cid> = match self.list {
             Some(cid) => self
                 .ipfs
@@ -122,6 +184,8 @@ impl IdentityCacheInner {
 
                 self.save(cid).await?;
 
+                self.index_devices(document);
+
                 Ok(Some(old_document))
             }
             None => {
@@ -133,6 +197,8 @@ impl IdentityCacheInner {
 
                 self.save(cid).await?;
 
+                self.index_devices(document);
+
                 Ok(None)
             }
         }
@@ -168,6 +234,75 @@ impl IdentityCacheInner {
         Ok(())
     }
 
+    async fn is_revoked(&self, did: &DID) -> bool {
+        let cid = match self.revoked {
+            Some(cid) => cid,
+            None => return false,
+        };
+
+        let Ok(path) = IpfsPath::from(cid).sub_path(&did.to_string()) else {
+            return false;
+        };
+
+        self.ipfs.get_dag(path).local().await.is_ok()
+    }
+
+    async fn revoke(&mut self, certificate: &RevocationCertificate) -> Result<bool, Error> {
+        verify_revocation_certificate(certificate)?;
+
+        let mut list: BTreeMap<String, RevocationCertificate> = match self.revoked {
+            Some(cid) => self
+                .ipfs
+                .get_dag(cid)
+                .local()
+                .deserialized()
+                .await
+                .unwrap_or_default(),
+            None => BTreeMap::new(),
+        };
+
+        let did = certificate.did();
+
+        if list.contains_key(&did.to_string()) {
+            return Ok(false);
+        }
+
+        list.insert(did.to_string(), certificate.clone());
+
+        let cid = self.ipfs.put_dag(list).await?;
+
+        if !self.ipfs.is_pinned(cid).await? {
+            self.ipfs.insert_pin(cid).recursive().local().await?;
+        }
+
+        let old_cid = self.revoked.replace(cid);
+
+        let key = self.ipfs.revoked();
+
+        if let Err(e) = self
+            .ipfs
+            .repo()
+            .data_store()
+            .put(key.as_bytes(), cid.to_string().as_bytes())
+            .await
+        {
+            tracing::error!(error = %e, "unable to store revoked list cid");
+        }
+
+        if let Some(old_cid) = old_cid {
+            if old_cid != cid && self.ipfs.is_pinned(&old_cid).await? {
+                self.ipfs.remove_pin(old_cid).recursive().await?;
+            }
+        }
+
+        match self.remove(did).await {
+            Ok(_) | Err(Error::IdentityDoesntExist) => {}
+            Err(e) => return Err(e),
+        }
+
+        Ok(true)
+    }
+
     async fn get(&self, did: &DID) -> Result<IdentityDocument, Error> {
         let cid = match self.list {
             Some(cid) => cid,
@@ -213,6 +348,8 @@ impl IdentityCacheInner {
 
         self.save(cid).await?;
 
+        self.devices.retain(|_, id| id.ne(did));
+
         Ok(())
     }
 
@@ -260,7 +397,10 @@ mod test {
     };
 
     use crate::store::{
-        document::{cache::IdentityCache, identity::IdentityDocument},
+        document::{
+            cache::IdentityCache,
+            identity::{create_revocation_certificate, DeviceAuthorization, IdentityDocument},
+        },
         PeerIdExt,
     };
 
@@ -282,6 +422,11 @@ mod test {
             status_message: None,
             metadata: Default::default(),
             version: Default::default(),
+            devices: vec![],
+            predecessor: None,
+            username_claim: None,
+            presence: None,
+            redacted: false,
             signature: None,
         };
 
@@ -364,4 +509,47 @@ mod test {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn identity_by_device() -> anyhow::Result<()> {
+        let cache = pregenerated_cache::<5>().await;
+
+        let (keypair, _, mut document) = random_document();
+        let device = Keypair::generate_ed25519().to_did()?;
+
+        document
+            .devices
+            .push(DeviceAuthorization::new(&keypair, device.clone(), None)?);
+        let document = document.sign(&keypair)?;
+
+        cache.insert(&document).await?;
+
+        let found = cache.get_by_device(&device).await?;
+        assert_eq!(found.did, document.did);
+
+        cache.remove(&document.did).await?;
+
+        assert!(cache.get_by_device(&device).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn revoked_identity() -> anyhow::Result<()> {
+        let cache = pregenerated_cache::<5>().await;
+
+        let (keypair, did, document) = random_document();
+
+        cache.insert(&document).await?;
+
+        let certificate = create_revocation_certificate(&keypair, None)?;
+
+        assert!(cache.revoke(&certificate).await?);
+        assert!(!cache.revoke(&certificate).await?);
+        assert!(cache.is_revoked(&did).await);
+        assert!(cache.get(&did).await.is_err());
+        assert!(cache.insert(&document).await.is_err());
+
+        Ok(())
+    }
 }
@@ -1,9 +1,11 @@
 use std::str::FromStr;
 use std::time::Duration;
 
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use futures::stream::FuturesUnordered;
 use futures::{StreamExt, TryFutureExt};
+use indexmap::{IndexMap, IndexSet};
 use ipld_core::cid::Cid;
 use rust_ipfs::{Ipfs, IpfsPath};
 use serde::{Deserialize, Serialize};
@@ -13,7 +15,7 @@ use warp::error::Error;
 
 use warp::constellation::{
     directory::Directory,
-    file::{File, FileType, Hash},
+    file::{File, FileType, Hash, ImageVariant},
 };
 
 use crate::store::document::image_dag::ImageDag;
@@ -30,6 +32,12 @@ pub struct DirectoryDocument {
     pub modified: DateTime<Utc>,
     pub thumbnail: Option<Cid>,
     pub items: Option<Cid>,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub metadata: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "IndexSet::is_empty")]
+    pub tags: IndexSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<usize>,
 }
 
 impl DirectoryDocument {
@@ -43,6 +51,9 @@ impl DirectoryDocument {
             modified: root.modified(),
             thumbnail: None,
             items: None,
+            metadata: root.metadata(),
+            tags: IndexSet::from_iter(root.tags()),
+            quota: root.quota(),
         };
 
         let items = FuturesUnordered::from_iter(
@@ -79,6 +90,9 @@ impl DirectoryDocument {
         let mut directory = Directory::new(&self.name);
         directory.set_description(&self.description);
         directory.set_favorite(self.favorite);
+        directory.set_metadata(self.metadata.clone());
+        directory.set_tags(Vec::from_iter(self.tags.clone()));
+        directory.set_quota(self.quota);
         directory.set_creation(self.creation);
         directory.set_modified(Some(self.modified));
 
@@ -130,6 +144,36 @@ impl DirectoryDocument {
     }
 }
 
+/// Add the items that only exist in `other` into `target`, replacing files in `target` that were modified
+/// less recently. Directories existing in both are merged, while an item whose type differs is left as is.
+/// Returns true if `target` was changed
+pub fn merge_directory(target: &Directory, other: &Directory) -> bool {
+    let mut changed = false;
+
+    for item in other.get_items() {
+        let Ok(index) = target.get_item_index(&item.name()) else {
+            changed |= target.add_item(item).is_ok();
+            continue;
+        };
+
+        let mut items = target.get_items();
+
+        match (&items[index], item) {
+            (Item::Directory(current), Item::Directory(directory)) => {
+                changed |= merge_directory(current, &directory);
+            }
+            (Item::File(current), Item::File(file)) if file.modified() > current.modified() => {
+                items[index] = Item::File(file);
+                target.set_items(items);
+                changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    changed
+}
+
 #[derive(Clone, Debug, Copy, Deserialize, Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ItemDocument {
@@ -194,7 +238,54 @@ pub struct FileDocument {
     pub modified: DateTime<Utc>,
     pub file_type: FileType,
     pub reference: Option<String>,
+    /// Key used to decrypt the contents of `reference`, wrapped by the owner of the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<Bytes>,
     pub hash: Hash,
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub metadata: IndexMap<String, String>,
+    #[serde(default, skip_serializing_if = "IndexSet::is_empty")]
+    pub tags: IndexSet<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variants: Vec<VariantDocument>,
+}
+
+/// Resized copy of an image, encrypted with the same key as the file it belongs to
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct VariantDocument {
+    pub width: u32,
+    pub height: u32,
+    pub size: usize,
+    pub mime: FileType,
+    pub link: Cid,
+}
+
+impl VariantDocument {
+    pub fn from_variant(variant: &ImageVariant) -> Option<Self> {
+        let link = variant
+            .reference()
+            .parse::<IpfsPath>()
+            .ok()
+            .and_then(|path| path.root().cid().copied())?;
+
+        Some(Self {
+            width: variant.width(),
+            height: variant.height(),
+            size: variant.size(),
+            mime: variant.format().into(),
+            link,
+        })
+    }
+
+    pub fn to_variant(&self) -> ImageVariant {
+        ImageVariant::new(
+            self.width,
+            self.height,
+            self.size,
+            self.mime.clone().into(),
+            &IpfsPath::from(self.link).to_string(),
+        )
+    }
 }
 
 impl FileDocument {
@@ -209,9 +300,27 @@ impl FileDocument {
             file_type: file.file_type(),
             hash: file.hash(),
             reference: None,
+            key: file.encryption_key().map(Bytes::from),
             thumbnail: None,
+            metadata: file.metadata(),
+            tags: IndexSet::from_iter(file.tags()),
+            variants: vec![],
         };
 
+        for variant in file.variants() {
+            let Some(variant) = VariantDocument::from_variant(&variant) else {
+                continue;
+            };
+            if ipfs
+                .repo()
+                .contains(&variant.link)
+                .await
+                .unwrap_or_default()
+            {
+                document.variants.push(variant);
+            }
+        }
+
         if let Some(cid) = file
             .reference()
             .and_then(|refs| refs.parse::<IpfsPath>().ok())
@@ -250,6 +359,7 @@ impl FileDocument {
             creation: Utc::now(),
             thumbnail: self.thumbnail,
             file_type: self.file_type.clone(),
+            variants: self.variants.clone(),
             data,
         })
     }
@@ -259,10 +369,18 @@ impl FileDocument {
         file.set_description(&self.description);
         file.set_size(self.size);
         file.set_favorite(self.favorite);
+        file.set_metadata(self.metadata.clone());
+        file.set_tags(Vec::from_iter(self.tags.clone()));
         file.set_creation(self.creation);
         file.set_modified(Some(self.modified));
         file.set_hash(self.hash.clone());
         file.set_file_type(self.file_type.clone());
+        file.set_variants(
+            self.variants
+                .iter()
+                .map(VariantDocument::to_variant)
+                .collect(),
+        );
 
         if let Some(cid) = self.thumbnail {
             file.set_thumbnail_reference(&IpfsPath::from(cid).to_string());
@@ -274,7 +392,8 @@ impl FileDocument {
 
             file.set_thumbnail_format(image.mime.into());
 
-            if resolve_thumbnail {
+            // Encrypted thumbnails are decrypted by the file store, which holds the keypair to unwrap the file key
+            if resolve_thumbnail && !image.encrypted {
                 let data = ipfs
                     .cat_unixfs(image.link)
                     .timeout(Duration::from_secs(10))
@@ -294,6 +413,7 @@ impl FileDocument {
             // Since the cid is valid, we will convert it to a ipfs path to store as a reference in `File::reference`
             let path = IpfsPath::from(cid);
             file.set_reference(&path.to_string());
+            file.set_encryption_key(self.key.as_ref().map(|key| key.to_vec()));
         }
 
         Ok(file)
@@ -303,12 +423,14 @@ impl FileDocument {
 #[cfg(test)]
 mod test {
 
+    use chrono::{Duration, Utc};
     use rust_ipfs::{Ipfs, UninitializedIpfsDefault};
     use tracing::Span;
     use warp::constellation::directory::Directory;
+    use warp::constellation::file::File;
     use warp::constellation::ConstellationEventKind;
 
-    use super::DirectoryDocument;
+    use super::{merge_directory, DirectoryDocument};
     use crate::config::Config;
     use crate::store::document::root::RootDocumentMap;
     use crate::store::{event_subscription::EventSubscription, files::FileStore};
@@ -368,6 +490,37 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn merge_directories() -> anyhow::Result<()> {
+        let target = Directory::new("root");
+        let shared = Directory::new("shared");
+        shared.add_item(File::new("a.txt"))?;
+        target.add_item(shared)?;
+        target.add_item(File::new("local.txt"))?;
+        let stale = File::new("stale.txt");
+        stale.set_modified(Some(Utc::now() - Duration::minutes(1)));
+        target.add_item(stale)?;
+
+        let other = Directory::new("root");
+        let shared = Directory::new("shared");
+        shared.add_item(File::new("b.txt"))?;
+        other.add_item(shared)?;
+        other.add_item(Directory::new("local.txt"))?;
+        let updated = File::new("stale.txt");
+        updated.set_size(10);
+        other.add_item(updated)?;
+
+        assert!(merge_directory(&target, &other));
+
+        assert!(target.get_item_by_path("/shared/a.txt").is_ok());
+        assert!(target.get_item_by_path("/shared/b.txt").is_ok());
+        assert!(target.get_item("local.txt")?.is_file());
+        assert_eq!(target.get_item("stale.txt")?.size(), 10);
+
+        assert!(!merge_directory(&target, &other));
+        Ok(())
+    }
+
     #[allow(dead_code)]
     pub const PROFILE_IMAGE: &[u8] = &[
         137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 1, 144, 0, 0, 1, 144,
@@ -6,10 +6,16 @@ use std::hash::Hash;
 use warp::{
     crypto::{Fingerprint, DID},
     error::Error,
-    multipass::identity::{Identity, IdentityStatus, Platform, SHORT_ID_SIZE},
+    multipass::identity::{
+        Device, Identity, IdentityField, IdentityStatus, Platform, Presence, RevocationCertificate,
+        SHORT_ID_SIZE,
+    },
 };
 
-use crate::store::{DidExt, MAX_STATUS_LENGTH, MAX_USERNAME_LENGTH, MIN_USERNAME_LENGTH};
+use crate::store::{
+    DidExt, PeerIdExt, MAX_DEVICES, MAX_DEVICE_NAME_LENGTH, MAX_PRESENCE_EMOJI_LENGTH,
+    MAX_STATUS_LENGTH, MAX_USERNAME_LENGTH, MIN_USERNAME_LENGTH,
+};
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -38,10 +44,353 @@ pub struct IdentityDocument {
     #[serde(default)]
     pub version: IdentityDocumentVersion,
 
+    /// Devices with their own key that are authorized to act on behalf of the identity
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub devices: Vec<DeviceAuthorization>,
+
+    /// Statement linking the previous key of the identity to this one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub predecessor: Option<KeySuccession>,
+
+    /// Claim of the username, countersigned by the registry that holds it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username_claim: Option<UsernameClaim>,
+
+    /// Custom status shown along side the status in the metadata
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence: Option<PresenceDocument>,
+
+    /// Fields only visible to friends were left out of this copy of the document
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub redacted: bool,
+
     #[serde(skip_serializing_if = "Option::is_none")]
     pub signature: Option<String>,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct PresenceDocument {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl PresenceDocument {
+    /// Custom status of the presence, if there is any
+    pub fn new(presence: &Presence) -> Option<Self> {
+        if presence.emoji().is_none() && presence.text().is_none() && presence.until().is_none() {
+            return None;
+        }
+
+        Some(PresenceDocument {
+            emoji: presence.emoji().map(str::to_string),
+            text: presence.text().map(str::to_string),
+            until: presence.until(),
+        })
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.until.is_some_and(|until| until <= Utc::now())
+    }
+
+    pub fn resolve(&self, status: IdentityStatus) -> Presence {
+        let mut presence = Presence::new(status);
+        presence.set_emoji(self.emoji.clone());
+        presence.set_text(self.text.clone());
+        presence.set_until(self.until);
+        presence
+    }
+
+    pub fn verify(&self) -> Result<(), Error> {
+        if let Some(emoji) = &self.emoji {
+            if emoji.is_empty() || emoji.len() > MAX_PRESENCE_EMOJI_LENGTH {
+                return Err(Error::InvalidLength {
+                    context: "presence emoji".into(),
+                    current: emoji.len(),
+                    minimum: Some(1),
+                    maximum: Some(MAX_PRESENCE_EMOJI_LENGTH),
+                });
+            }
+        }
+
+        if let Some(text) = &self.text {
+            if text.is_empty() || text.len() > MAX_STATUS_LENGTH {
+                return Err(Error::InvalidLength {
+                    context: "presence text".into(),
+                    current: text.len(),
+                    minimum: Some(1),
+                    maximum: Some(MAX_STATUS_LENGTH),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Authorization of a device, signed by the key of the identity
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct DeviceAuthorization {
+    pub device: DID,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    pub created: DateTime<Utc>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl DeviceAuthorization {
+    pub fn new(keypair: &Keypair, device: DID, name: Option<String>) -> Result<Self, Error> {
+        let authorization = DeviceAuthorization {
+            device,
+            name,
+            created: Utc::now(),
+            signature: None,
+        };
+
+        let bytes = serde_json::to_vec(&authorization)?;
+        let signature = bs58::encode(keypair.sign(&bytes).expect("not RSA")).into_string();
+
+        Ok(DeviceAuthorization {
+            signature: Some(signature),
+            ..authorization
+        })
+    }
+
+    /// Verify that the device was authorized by the given identity
+    pub fn verify(&self, identity: &DID) -> Result<(), Error> {
+        if let Some(name) = &self.name {
+            if name.len() > MAX_DEVICE_NAME_LENGTH {
+                return Err(Error::InvalidLength {
+                    context: "device name".into(),
+                    current: name.len(),
+                    minimum: None,
+                    maximum: Some(MAX_DEVICE_NAME_LENGTH),
+                });
+            }
+        }
+
+        let mut payload = self.clone();
+        let signature = std::mem::take(&mut payload.signature).ok_or(Error::InvalidSignature)?;
+        let signature_bytes = bs58::decode(signature).into_vec()?;
+        let bytes = serde_json::to_vec(&payload)?;
+        let pk = identity.to_public_key()?;
+        if !pk.verify(&bytes, &signature_bytes) {
+            return Err(Error::InvalidSignature);
+        }
+
+        Ok(())
+    }
+}
+
+/// Statement from a previous key of an identity naming its successor, signed by both keys
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct KeySuccession {
+    pub previous: DID,
+
+    pub successor: DID,
+
+    pub created: DateTime<Utc>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub successor_signature: Option<String>,
+}
+
+impl KeySuccession {
+    pub fn new(previous: &Keypair, successor: &Keypair) -> Result<Self, Error> {
+        let mut succession = KeySuccession {
+            previous: previous.to_did()?,
+            successor: successor.to_did()?,
+            created: Utc::now(),
+            signature: None,
+            successor_signature: None,
+        };
+
+        let bytes = serde_json::to_vec(&succession)?;
+        let signature = bs58::encode(previous.sign(&bytes).expect("not RSA")).into_string();
+        let successor_signature =
+            bs58::encode(successor.sign(&bytes).expect("not RSA")).into_string();
+
+        succession.signature = Some(signature);
+        succession.successor_signature = Some(successor_signature);
+        Ok(succession)
+    }
+
+    pub fn verify(&self) -> Result<(), Error> {
+        if self.previous == self.successor {
+            return Err(Error::IdentityInvalid);
+        }
+
+        let mut payload = self.clone();
+        let signature = std::mem::take(&mut payload.signature).ok_or(Error::InvalidSignature)?;
+        let successor_signature =
+            std::mem::take(&mut payload.successor_signature).ok_or(Error::InvalidSignature)?;
+        let bytes = serde_json::to_vec(&payload)?;
+
+        for (did, signature) in [
+            (&self.previous, signature),
+            (&self.successor, successor_signature),
+        ] {
+            let signature_bytes = bs58::decode(signature).into_vec()?;
+            if !did.to_public_key()?.verify(&bytes, &signature_bytes) {
+                return Err(Error::InvalidSignature);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Claim of a unique username by an identity, signed by the identity and countersigned by the registry
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct UsernameClaim {
+    pub username: String,
+
+    pub did: DID,
+
+    pub registry: DID,
+
+    pub created: DateTime<Utc>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registry_signature: Option<String>,
+}
+
+impl UsernameClaim {
+    pub fn new(keypair: &Keypair, username: &str, registry: DID) -> Result<Self, Error> {
+        let mut claim = UsernameClaim {
+            username: username.to_string(),
+            did: keypair.to_did()?,
+            registry,
+            created: Utc::now(),
+            signature: None,
+            registry_signature: None,
+        };
+
+        let bytes = serde_json::to_vec(&claim)?;
+        let signature = bs58::encode(keypair.sign(&bytes).expect("not RSA")).into_string();
+        claim.signature = Some(signature);
+        Ok(claim)
+    }
+
+    /// Username in the form used to check for uniqueness
+    pub fn key(&self) -> String {
+        self.username.to_lowercase()
+    }
+
+    /// Countersign the claim by the registry after it was accepted
+    pub fn countersign(mut self, keypair: &Keypair) -> Result<Self, Error> {
+        if keypair.to_did()? != self.registry {
+            return Err(Error::PublicKeyInvalid);
+        }
+
+        let signature = std::mem::take(&mut self.signature);
+        self.registry_signature = None;
+        let bytes = serde_json::to_vec(&self)?;
+        let registry_signature = bs58::encode(keypair.sign(&bytes).expect("not RSA")).into_string();
+        self.signature = signature;
+        self.registry_signature = Some(registry_signature);
+        Ok(self)
+    }
+
+    /// Verify the signature of the identity on the claim
+    pub fn verify_claim(&self) -> Result<(), Error> {
+        if !(MIN_USERNAME_LENGTH..=MAX_USERNAME_LENGTH).contains(&self.username.len()) {
+            return Err(Error::InvalidLength {
+                context: "username".into(),
+                current: self.username.len(),
+                minimum: Some(MIN_USERNAME_LENGTH),
+                maximum: Some(MAX_USERNAME_LENGTH),
+            });
+        }
+
+        let mut payload = self.clone();
+        let signature = std::mem::take(&mut payload.signature).ok_or(Error::InvalidSignature)?;
+        payload.registry_signature = None;
+        let bytes = serde_json::to_vec(&payload)?;
+        let signature_bytes = bs58::decode(signature).into_vec()?;
+        if !self.did.to_public_key()?.verify(&bytes, &signature_bytes) {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+
+    /// Verify both the signature of the identity and the countersignature of the registry
+    pub fn verify(&self) -> Result<(), Error> {
+        self.verify_claim()?;
+
+        let mut payload = self.clone();
+        let registry_signature =
+            std::mem::take(&mut payload.registry_signature).ok_or(Error::InvalidSignature)?;
+        payload.signature = None;
+        let bytes = serde_json::to_vec(&payload)?;
+        let signature_bytes = bs58::decode(registry_signature).into_vec()?;
+        if !self
+            .registry
+            .to_public_key()?
+            .verify(&bytes, &signature_bytes)
+        {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+}
+
+pub fn create_revocation_certificate(
+    keypair: &Keypair,
+    reason: Option<String>,
+) -> Result<RevocationCertificate, Error> {
+    if let Some(reason) = &reason {
+        if reason.len() > MAX_STATUS_LENGTH {
+            return Err(Error::InvalidLength {
+                context: "reason".into(),
+                current: reason.len(),
+                minimum: None,
+                maximum: Some(MAX_STATUS_LENGTH),
+            });
+        }
+    }
+
+    let certificate = RevocationCertificate::new(keypair.to_did()?, reason);
+    let bytes = serde_json::to_vec(&certificate)?;
+    let signature = keypair.sign(&bytes).expect("not RSA");
+    Ok(certificate.with_signature(signature))
+}
+
+pub fn verify_revocation_certificate(certificate: &RevocationCertificate) -> Result<(), Error> {
+    let payload = certificate.clone().with_signature(Vec::new());
+    let bytes = serde_json::to_vec(&payload)?;
+    let pk = certificate.did().to_public_key()?;
+    if !pk.verify(&bytes, certificate.signature()) {
+        return Err(Error::InvalidSignature);
+    }
+    Ok(())
+}
+
+impl From<&DeviceAuthorization> for Device {
+    fn from(authorization: &DeviceAuthorization) -> Self {
+        Device::new(
+            authorization.device.clone(),
+            authorization.name.clone(),
+            authorization.created,
+        )
+    }
+}
+
 #[derive(Default, Debug, Clone, Copy, Deserialize, Serialize, Eq, PartialEq)]
 pub struct IdentityMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -78,6 +427,11 @@ impl From<Identity> for IdentityDocument {
             modified,
             metadata: Default::default(),
             version: IdentityDocumentVersion::V0,
+            devices: vec![],
+            predecessor: None,
+            username_claim: None,
+            presence: None,
+            redacted: false,
             signature: None,
         }
     }
@@ -118,8 +472,13 @@ impl Hash for IdentityDocument {
 impl IdentityDocument {
     // Used to tell if another identity document is different but also valid
     pub fn different(&self, other: &Self) -> bool {
+        !self.changed_fields(other).is_empty()
+    }
+
+    /// Fields that differ in another valid identity document of the same identity
+    pub fn changed_fields(&self, other: &Self) -> Vec<IdentityField> {
         if self.ne(other) {
-            return false;
+            return vec![];
         }
 
         if other.verify().is_err() {
@@ -127,12 +486,66 @@ impl IdentityDocument {
                 "identity for {} is not valid, corrupted or been tampered with.",
                 self.did
             );
-            return false;
+            return vec![];
         }
 
-        self.username != other.username
-            || self.status_message != other.status_message
-            || self.metadata != other.metadata
+        let mut fields = vec![];
+
+        if self.username != other.username {
+            fields.push(IdentityField::Username);
+        }
+        if self.status_message != other.status_message {
+            fields.push(IdentityField::StatusMessage);
+        }
+        if self.metadata.profile_picture != other.metadata.profile_picture {
+            fields.push(IdentityField::Picture);
+        }
+        if self.metadata.profile_banner != other.metadata.profile_banner {
+            fields.push(IdentityField::Banner);
+        }
+        if self.metadata.platform != other.metadata.platform {
+            fields.push(IdentityField::Platform);
+        }
+        if self.metadata.status != other.metadata.status {
+            fields.push(IdentityField::Status);
+        }
+        if self.metadata.arb_data != other.metadata.arb_data {
+            fields.push(IdentityField::Metadata);
+        }
+        if self.presence != other.presence {
+            fields.push(IdentityField::Presence);
+        }
+        if self.devices != other.devices {
+            fields.push(IdentityField::Devices);
+        }
+        if self.username_claim != other.username_claim {
+            fields.push(IdentityField::Verified);
+        }
+
+        fields
+    }
+
+    /// Presence of the identity, leaving out the custom status once it has expired
+    pub fn presence(&self) -> Presence {
+        let status = self.metadata.status.unwrap_or(IdentityStatus::Online);
+        match &self.presence {
+            Some(presence) if !presence.is_expired() => presence.resolve(status),
+            _ => Presence::new(status),
+        }
+    }
+
+    /// Check if the username is backed by a valid claim countersigned by one of the registries provided
+    pub fn has_verified_username(&self, registries: &[DID]) -> bool {
+        self.username_claim.as_ref().is_some_and(|claim| {
+            registries.contains(&claim.registry)
+                && claim.username.to_lowercase() == self.username.to_lowercase()
+                && claim.verify().is_ok()
+        })
+    }
+
+    /// Check if the key belongs to the identity or one of its authorized devices
+    pub fn is_authorized(&self, did: &DID) -> bool {
+        self.did.eq(did) || self.devices.iter().any(|device| device.device.eq(did))
     }
 }
 
@@ -202,6 +615,37 @@ impl IdentityDocument {
             }
         }
 
+        if let Some(presence) = &payload.presence {
+            presence.verify()?;
+        }
+
+        if payload.devices.len() > MAX_DEVICES {
+            return Err(Error::InvalidLength {
+                context: "devices".into(),
+                current: payload.devices.len(),
+                minimum: None,
+                maximum: Some(MAX_DEVICES),
+            });
+        }
+
+        for device in &payload.devices {
+            device.verify(&payload.did)?;
+        }
+
+        if let Some(succession) = &payload.predecessor {
+            if succession.successor != payload.did {
+                return Err(Error::IdentityInvalid);
+            }
+            succession.verify()?;
+        }
+
+        if let Some(claim) = &payload.username_claim {
+            if claim.did != payload.did {
+                return Err(Error::IdentityInvalid);
+            }
+            claim.verify()?;
+        }
+
         let _ = std::mem::take(&mut payload.metadata);
 
         let signature = std::mem::take(&mut payload.signature).ok_or(Error::InvalidSignature)?;
@@ -215,3 +659,73 @@ impl IdentityDocument {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rust_ipfs::Keypair;
+    use warp::error::Error;
+
+    use super::UsernameClaim;
+    use crate::store::PeerIdExt;
+
+    #[test]
+    fn username_claim() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let registry = Keypair::generate_ed25519();
+
+        let claim = UsernameClaim::new(&keypair, "Satellite", registry.to_did()?)?;
+        claim.verify_claim()?;
+        assert!(matches!(claim.verify(), Err(Error::InvalidSignature)));
+        assert_eq!(claim.key(), "satellite");
+
+        let claim = claim.countersign(&registry)?;
+        claim.verify()?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn username_claim_countersigned_by_other_registry() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let registry = Keypair::generate_ed25519();
+        let other = Keypair::generate_ed25519();
+
+        let claim = UsernameClaim::new(&keypair, "Satellite", registry.to_did()?)?;
+        assert!(matches!(
+            claim.countersign(&other),
+            Err(Error::PublicKeyInvalid)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tampered_username_claim() -> anyhow::Result<()> {
+        let keypair = Keypair::generate_ed25519();
+        let registry = Keypair::generate_ed25519();
+
+        let claim = UsernameClaim::new(&keypair, "Satellite", registry.to_did()?)?
+            .countersign(&registry)?;
+
+        let mut tampered = claim.clone();
+        tampered.username = "Satellite2".into();
+        assert!(matches!(
+            tampered.verify_claim(),
+            Err(Error::InvalidSignature)
+        ));
+        assert!(matches!(tampered.verify(), Err(Error::InvalidSignature)));
+
+        let mut tampered = claim.clone();
+        tampered.did = Keypair::generate_ed25519().to_did()?;
+        assert!(matches!(
+            tampered.verify_claim(),
+            Err(Error::InvalidSignature)
+        ));
+
+        let mut tampered = claim;
+        tampered.registry = Keypair::generate_ed25519().to_did()?;
+        assert!(tampered.verify().is_err());
+
+        Ok(())
+    }
+}
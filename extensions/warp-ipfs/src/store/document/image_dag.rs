@@ -9,6 +9,9 @@ pub struct ImageDag {
     pub link: Cid,
     pub size: u64,
     pub mime: FileType,
+    /// Contents behind `link` are encrypted with the key of the file the image belongs to
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub encrypted: bool,
 }
 
 #[tracing::instrument(skip(ipfs, opt))]
@@ -56,6 +59,7 @@ pub async fn store_photo(
         link: cid,
         size: size as _,
         mime: file_type,
+        encrypted: false,
     };
 
     let cid = ipfs.put_dag(dag).pin(true).await?;
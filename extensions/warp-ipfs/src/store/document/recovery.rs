@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use rust_ipfs::Keypair;
+use serde::{Deserialize, Serialize};
+use warp::{
+    crypto::{
+        cipher::Cipher,
+        shamir::{self, Share},
+        zeroize::Zeroizing,
+        DID,
+    },
+    error::Error,
+};
+
+use crate::store::{DidExt, PeerIdExt};
+
+/// Share of the recovery secret of an identity, held by one of its friends
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RecoveryShareDocument {
+    pub did: DID,
+
+    pub share: Share,
+
+    /// Key of the identity encrypted with the key that was split into shares
+    pub secret: Vec<u8>,
+
+    pub created: DateTime<Utc>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl RecoveryShareDocument {
+    /// Split the key of the identity into `count` shares, of which `threshold` are needed to recover it
+    pub fn split(keypair: &Keypair, threshold: u8, count: u8) -> Result<Vec<Self>, Error> {
+        let did = keypair.to_did()?;
+
+        let secret_key = Zeroizing::new(
+            keypair
+                .clone()
+                .try_into_ed25519()
+                .map_err(|_| Error::PrivateKeyInvalid)?
+                .secret()
+                .as_ref()
+                .to_vec(),
+        );
+
+        let key = Zeroizing::new(warp::crypto::generate::<32>());
+        let secret = Cipher::direct_encrypt(&secret_key, key.as_ref())?;
+        let shares = shamir::split(key.as_ref(), threshold, count)?;
+        let created = Utc::now();
+
+        shares
+            .into_iter()
+            .map(|share| {
+                RecoveryShareDocument {
+                    did: did.clone(),
+                    share,
+                    secret: secret.clone(),
+                    created,
+                    signature: None,
+                }
+                .sign(keypair)
+            })
+            .collect()
+    }
+
+    /// Recover the key of the identity, using the shares from the most recent split
+    pub fn combine(shares: &[RecoveryShareDocument]) -> Result<Keypair, Error> {
+        let latest = shares
+            .iter()
+            .max_by_key(|document| document.created)
+            .ok_or(Error::InsufficientShares)?;
+
+        let mut list = vec![];
+        for document in shares
+            .iter()
+            .filter(|document| document.secret == latest.secret)
+        {
+            if document.did != latest.did {
+                return Err(Error::InvalidShare);
+            }
+            document.verify()?;
+            list.push(document.share.clone());
+        }
+
+        let key = Zeroizing::new(shamir::combine(&list)?);
+        let secret_key = Zeroizing::new(Cipher::direct_decrypt(&latest.secret, &key)?);
+        let keypair =
+            Keypair::ed25519_from_bytes(secret_key).map_err(|_| Error::PrivateKeyInvalid)?;
+
+        if keypair.to_did()? != latest.did {
+            return Err(Error::PrivateKeyInvalid);
+        }
+
+        Ok(keypair)
+    }
+
+    pub fn sign(mut self, keypair: &Keypair) -> Result<Self, Error> {
+        self.signature = None;
+        let bytes = serde_json::to_vec(&self)?;
+        let signature = bs58::encode(keypair.sign(&bytes).expect("not RSA")).into_string();
+        self.signature = Some(signature);
+        Ok(self)
+    }
+
+    pub fn verify(&self) -> Result<(), Error> {
+        let mut payload = self.clone();
+        let signature = std::mem::take(&mut payload.signature).ok_or(Error::InvalidSignature)?;
+        let signature_bytes = bs58::decode(signature).into_vec()?;
+        let bytes = serde_json::to_vec(&payload)?;
+        if !self.did.to_public_key()?.verify(&bytes, &signature_bytes) {
+            return Err(Error::InvalidSignature);
+        }
+        Ok(())
+    }
+}
@@ -6,24 +6,40 @@ use futures::{
 use indexmap::IndexMap;
 use ipld_core::cid::Cid;
 use rust_ipfs::{Ipfs, IpfsPath, Keypair};
+use serde::{de::DeserializeOwned, Serialize};
 use std::borrow::Borrow;
-use std::{collections::BTreeMap, future::IntoFuture, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Display,
+    future::IntoFuture,
+    sync::Arc,
+};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
 use warp::{
-    constellation::directory::Directory, crypto::DID, error::Error,
-    multipass::identity::IdentityStatus,
+    constellation::{directory::Directory, item::Item},
+    crypto::{zeroize::Zeroizing, Fingerprint, DID},
+    error::Error,
+    multipass::{
+        identity::{Contact, IdentityStatus, SHORT_ID_SIZE},
+        ExportSection,
+    },
+    raygun::ConversationType,
 };
 
 use crate::store::{
     conversation::ConversationDocument, ds_key::DataStoreKey, ecdh_decrypt, ecdh_encrypt,
-    identity::Request, keystore::Keystore, VecExt, MAX_METADATA_ENTRIES, MAX_METADATA_KEY_LENGTH,
-    MAX_METADATA_VALUE_LENGTH,
+    ecdh_shared_key, identity::Request, keystore::Keystore, PeerIdExt, VecExt,
+    MAX_METADATA_ENTRIES, MAX_METADATA_KEY_LENGTH, MAX_METADATA_VALUE_LENGTH,
 };
 
 use super::{
-    files::DirectoryDocument, identity::IdentityDocument, ResolvedRootDocument, RootDocument,
+    bundle::{ConversationExport, ExportBundle},
+    files::{merge_directory, DirectoryDocument},
+    identity::{IdentityDocument, KeySuccession},
+    recovery::RecoveryShareDocument,
+    EntryChange, ResolvedRootDocument, RootDocument,
 };
 
 #[derive(Debug, Clone)]
@@ -151,6 +167,31 @@ impl RootDocumentMap {
         inner.is_blocked_by(did).await
     }
 
+    pub async fn get_recovery_shares(&self) -> Result<Vec<RecoveryShareDocument>, Error> {
+        let inner = &*self.inner.read().await;
+        inner.recovery_share_list().await
+    }
+
+    pub async fn add_recovery_share(&self, share: RecoveryShareDocument) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.add_recovery_share(share).await
+    }
+
+    pub async fn remove_recovery_share(&self, did: &DID) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.remove_recovery_share(did).await
+    }
+
+    pub async fn get_contacts(&self) -> Result<Vec<Contact>, Error> {
+        let inner = &*self.inner.read().await;
+        inner.contact_list().await
+    }
+
+    pub async fn set_contact(&self, contact: Contact) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.set_contact(contact).await
+    }
+
     pub async fn export_root_cid(&self) -> Result<Cid, Error> {
         let inner = &*self.inner.read().await;
         inner.cid.ok_or(Error::IdentityNotCreated)
@@ -166,11 +207,53 @@ impl RootDocumentMap {
         inner.export().await
     }
 
+    /// Create a root document for the successor key, returning its cid without replacing the current document
+    pub async fn rotate(
+        &self,
+        new_keypair: &Keypair,
+        succession: KeySuccession,
+    ) -> Result<Cid, Error> {
+        let inner = &*self.inner.read().await;
+        inner.rotate(new_keypair, succession).await
+    }
+
+    /// Merge the root document of another device of the identity, returning true if the local document changed
+    pub async fn merge(&self, cid: Cid) -> Result<bool, Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.merge(cid).await
+    }
+
     pub async fn export_bytes(&self) -> Result<Vec<u8>, Error> {
         let inner = &*self.inner.read().await;
         inner.export_bytes().await
     }
 
+    /// Cids of the contents referenced by the attachments of every conversation
+    pub async fn attachment_references(&self) -> HashSet<Cid> {
+        let inner = &*self.inner.read().await;
+        inner.attachment_references().await
+    }
+
+    pub async fn export_bundle(&self, sections: &[ExportSection]) -> Result<ExportBundle, Error> {
+        let inner = &*self.inner.read().await;
+        inner.export_bundle(sections).await
+    }
+
+    /// Merge the sections of a bundle of the same identity, keeping any entry that already exist
+    pub async fn merge_bundle(&self, bundle: ExportBundle) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.merge_bundle(bundle).await
+    }
+
+    /// Add the conversations and messages that do not already exist
+    pub async fn import_conversations(
+        &self,
+        conversations: Vec<ConversationExport>,
+    ) -> Result<(), Error> {
+        let inner = &mut *self.inner.write().await;
+        inner.import_conversations(conversations).await
+    }
+
     pub async fn get_conversation_keystore_map(&self) -> Result<BTreeMap<String, Cid>, Error> {
         let inner = &*self.inner.read().await;
         inner.get_conversation_keystore_map().await
@@ -278,14 +361,8 @@ impl RootDocumentInner {
         let list = list
             .iter()
             .map(|item| match item {
-                OldRequest::In(did) => Request::In {
-                    did: did.clone(),
-                    date: Utc::now(),
-                },
-                OldRequest::Out(did) => Request::Out {
-                    did: did.clone(),
-                    date: Utc::now(),
-                },
+                OldRequest::In(did) => Request::request_in(did.clone()),
+                OldRequest::Out(did) => Request::request_out(did.clone()),
             })
             .collect::<Vec<_>>();
 
@@ -511,6 +588,8 @@ impl RootDocumentInner {
             None => vec![],
         };
 
+        let key = change_key("request", request.did());
+
         if !list.insert_item(request) {
             return Err(Error::FriendRequestExist);
         }
@@ -523,6 +602,8 @@ impl RootDocumentInner {
             false => None,
         };
 
+        self.record_changes(&mut document, [(key, false)]).await?;
+
         self.set_root_document(document).await?;
         Ok(())
     }
@@ -549,6 +630,8 @@ impl RootDocumentInner {
             return Err(Error::FriendRequestExist);
         }
 
+        let key = change_key("request", request.did());
+
         document.request = match !list.is_empty() {
             true => {
                 let bytes = ecdh_encrypt(self.keypair(), None, serde_json::to_vec(&list)?)?;
@@ -557,6 +640,8 @@ impl RootDocumentInner {
             false => None,
         };
 
+        self.record_changes(&mut document, [(key, true)]).await?;
+
         self.set_root_document(document).await?;
         Ok(())
     }
@@ -599,7 +684,7 @@ impl RootDocumentInner {
             None => vec![],
         };
 
-        if !list.insert_item(did) {
+        if !list.insert_item(did.clone()) {
             return Err::<_, Error>(Error::FriendExist);
         }
 
@@ -611,6 +696,9 @@ impl RootDocumentInner {
             false => None,
         };
 
+        self.record_changes(&mut document, [(change_key("friends", did), false)])
+            .await?;
+
         self.set_root_document(document).await?;
         Ok(())
     }
@@ -635,6 +723,31 @@ impl RootDocumentInner {
     async fn set_root_index(&mut self, root: Directory) -> Result<(), Error> {
         let mut document = self.get_root_document().await?;
 
+        let current = match document.file_index {
+            Some(cid) => {
+                let index = self
+                    .ipfs
+                    .get_dag(cid)
+                    .local()
+                    .deserialized::<DirectoryDocument>()
+                    .await?
+                    .resolve(&self.ipfs, false)
+                    .await?;
+                index_paths(&index)
+            }
+            None => HashSet::new(),
+        };
+
+        let paths = index_paths(&root);
+        let removed = current
+            .difference(&paths)
+            .map(|path| (change_key("files", path), true));
+        let added = paths
+            .difference(&current)
+            .map(|path| (change_key("files", path), false));
+        let changes = removed.chain(added).collect::<Vec<_>>();
+        self.record_changes(&mut document, changes).await?;
+
         let index_document = DirectoryDocument::new(&self.ipfs, &root).await?;
 
         let cid = self.ipfs.put_dag(index_document).await?;
@@ -676,11 +789,131 @@ impl RootDocumentInner {
             false => None,
         };
 
+        let mut changes = vec![(change_key("friends", &did), true)];
+
+        // The contact record is only kept for as long as the identity is a friend
+        let mut contacts = self.contact_list().await?;
+        let len = contacts.len();
+        contacts.retain(|contact| contact.did().ne(&did));
+        if contacts.len() != len {
+            document.contacts = self.put_contact_list(&contacts).await?;
+            changes.push((change_key("contacts", did), true));
+        }
+
+        self.record_changes(&mut document, changes).await?;
+
         self.set_root_document(document).await?;
 
         Ok(())
     }
 
+    async fn contact_list(&self) -> Result<Vec<Contact>, Error> {
+        let cid = match self.cid {
+            Some(cid) => cid,
+            None => return Ok(vec![]),
+        };
+        let path = IpfsPath::from(cid).sub_path("contacts")?;
+        let list: Vec<Contact> = self
+            .ipfs
+            .get_dag(path)
+            .local()
+            .deserialized::<Vec<u8>>()
+            .await
+            .and_then(|bytes| {
+                let bytes = ecdh_decrypt(self.keypair(), None, bytes)?;
+                serde_json::from_slice(&bytes).map_err(anyhow::Error::from)
+            })
+            .unwrap_or_default();
+        Ok(list)
+    }
+
+    async fn put_contact_list(&self, list: &[Contact]) -> Result<Option<Cid>, Error> {
+        if list.is_empty() {
+            return Ok(None);
+        }
+        let bytes = ecdh_encrypt(self.keypair(), None, serde_json::to_vec(list)?)?;
+        let cid = self.ipfs.put_dag(bytes).await?;
+        Ok(Some(cid))
+    }
+
+    // Note: Records without any information are removed rather than stored
+    async fn set_contact(&mut self, contact: Contact) -> Result<(), Error> {
+        let mut document = self.get_root_document().await?;
+        let mut list = self.contact_list().await?;
+        list.retain(|item| item.did() != contact.did());
+        let change = (change_key("contacts", contact.did()), contact.is_empty());
+        if !contact.is_empty() {
+            list.push(contact);
+        }
+        document.contacts = self.put_contact_list(&list).await?;
+        self.record_changes(&mut document, [change]).await?;
+        self.set_root_document(document).await
+    }
+
+    async fn recovery_share_list(&self) -> Result<Vec<RecoveryShareDocument>, Error> {
+        let cid = match self.cid {
+            Some(cid) => cid,
+            None => return Ok(vec![]),
+        };
+        let path = IpfsPath::from(cid).sub_path("recovery_shares")?;
+        let list: Vec<RecoveryShareDocument> = self
+            .ipfs
+            .get_dag(path)
+            .local()
+            .deserialized::<Vec<u8>>()
+            .await
+            .and_then(|bytes| {
+                let bytes = ecdh_decrypt(self.keypair(), None, bytes)?;
+                serde_json::from_slice(&bytes).map_err(anyhow::Error::from)
+            })
+            .unwrap_or_default();
+        Ok(list)
+    }
+
+    async fn set_recovery_share_list(
+        &mut self,
+        list: Vec<RecoveryShareDocument>,
+        did: &DID,
+        removed: bool,
+    ) -> Result<(), Error> {
+        let mut document = self.get_root_document().await?;
+
+        document.recovery_shares = match !list.is_empty() {
+            true => {
+                let bytes = ecdh_encrypt(self.keypair(), None, serde_json::to_vec(&list)?)?;
+                Some(self.ipfs.put_dag(bytes).await?)
+            }
+            false => None,
+        };
+
+        self.record_changes(
+            &mut document,
+            [(change_key("recovery_shares", did), removed)],
+        )
+        .await?;
+
+        self.set_root_document(document).await
+    }
+
+    // Note: Only a single share is held per identity, so a share from a newer split replaces the previous one
+    async fn add_recovery_share(&mut self, share: RecoveryShareDocument) -> Result<(), Error> {
+        let mut list = self.recovery_share_list().await?;
+        let did = share.did.clone();
+        list.retain(|item| item.did != did);
+        list.push(share);
+        self.set_recovery_share_list(list, &did, false).await
+    }
+
+    async fn remove_recovery_share(&mut self, did: &DID) -> Result<(), Error> {
+        let mut list = self.recovery_share_list().await?;
+        let len = list.len();
+        list.retain(|item| item.did.ne(did));
+        if list.len() == len {
+            return Err(Error::RecoveryNotSetup);
+        }
+        self.set_recovery_share_list(list, did, true).await
+    }
+
     async fn block_list(&self) -> Result<Vec<DID>, Error> {
         let cid = match self.cid {
             Some(cid) => cid,
@@ -731,7 +964,7 @@ impl RootDocumentInner {
             None => vec![],
         };
 
-        if !list.insert_item(did) {
+        if !list.insert_item(did.clone()) {
             return Err::<_, Error>(Error::PublicKeyIsBlocked);
         }
 
@@ -743,6 +976,9 @@ impl RootDocumentInner {
             false => None,
         };
 
+        self.record_changes(&mut document, [(change_key("blocks", did), false)])
+            .await?;
+
         self.set_root_document(document).await?;
 
         Ok(())
@@ -778,6 +1014,9 @@ impl RootDocumentInner {
             false => None,
         };
 
+        self.record_changes(&mut document, [(change_key("blocks", did), true)])
+            .await?;
+
         self.set_root_document(document).await?;
 
         Ok(())
@@ -821,7 +1060,7 @@ impl RootDocumentInner {
             None => vec![],
         };
 
-        if !list.insert_item(did) {
+        if !list.insert_item(did.clone()) {
             return Err::<_, Error>(Error::PublicKeyIsntBlocked);
         }
 
@@ -833,6 +1072,9 @@ impl RootDocumentInner {
             false => None,
         };
 
+        self.record_changes(&mut document, [(change_key("block_by", did), false)])
+            .await?;
+
         self.set_root_document(document).await?;
 
         Ok(())
@@ -868,12 +1110,28 @@ impl RootDocumentInner {
             false => None,
         };
 
+        self.record_changes(&mut document, [(change_key("block_by", did), true)])
+            .await?;
+
         self.set_root_document(document).await?;
         Ok(())
     }
 
     async fn set_conversation_keystore(&mut self, map: BTreeMap<String, Cid>) -> Result<(), Error> {
         let mut document = self.get_root_document().await?;
+        let current = self.get_conversation_keystore_map().await?;
+
+        let removed = current
+            .keys()
+            .filter(|id| !map.contains_key(*id))
+            .map(|id| (change_key("conversations_keystore", id), true));
+        let added = map
+            .keys()
+            .filter(|id| !current.contains_key(*id))
+            .map(|id| (change_key("conversations_keystore", id), false));
+        let changes = removed.chain(added).collect::<Vec<_>>();
+        self.record_changes(&mut document, changes).await?;
+
         document.conversations_keystore = Some(self.ipfs.put_dag(map).await?);
         self.set_root_document(document).await
     }
@@ -957,10 +1215,46 @@ impl RootDocumentInner {
         };
 
         let id = conversation_document.id().to_string();
+        let mut changes = vec![(
+            change_key("conversations", &id),
+            conversation_document.deleted,
+        )];
+
+        // Members added or removed are recorded so a removal is not undone when merging with another device
+        let previous = match list.get(&id) {
+            Some(cid) => self
+                .ipfs
+                .get_dag(*cid)
+                .local()
+                .deserialized::<ConversationDocument>()
+                .await
+                .ok(),
+            None => None,
+        };
+        if let Some(previous) = previous {
+            let section = change_key("conversation_recipients", &id);
+            let recipients = &conversation_document.recipients;
+            changes.extend(
+                previous
+                    .recipients
+                    .iter()
+                    .filter(|did| !recipients.contains(*did))
+                    .map(|did| (change_key(&section, did), true)),
+            );
+            changes.extend(
+                recipients
+                    .iter()
+                    .filter(|did| !previous.recipients.contains(*did))
+                    .map(|did| (change_key(&section, did), false)),
+            );
+        }
+
         let cid = self.ipfs.put_dag(conversation_document).await?;
 
         list.insert(id, cid);
 
+        self.record_changes(&mut document, changes).await?;
+
         let cid = self.ipfs.put_dag(list).await?;
 
         document.conversations.replace(cid);
@@ -1023,6 +1317,747 @@ impl RootDocumentInner {
         ecdh_encrypt(self.keypair(), None, bytes)
     }
 
+    async fn attachment_references(&self) -> HashSet<Cid> {
+        let mut references = HashSet::new();
+        let mut stream = self.list_conversation_stream().await;
+
+        while let Some(document) = stream.next().await {
+            let messages = document
+                .get_message_list(&self.ipfs)
+                .await
+                .unwrap_or_default();
+
+            references.extend(messages.iter().flat_map(|message| {
+                message.attachments().iter().filter_map(|attachment| {
+                    attachment
+                        .data
+                        .parse::<IpfsPath>()
+                        .ok()
+                        .and_then(|path| path.root().cid().copied())
+                })
+            }));
+        }
+
+        references
+    }
+
+    async fn export_bundle(&self, sections: &[ExportSection]) -> Result<ExportBundle, Error> {
+        let mut root = self.export().await?;
+
+        if sections.contains(&ExportSection::FileIndex) {
+            root.file_index = self.get_root_index().await.ok();
+        }
+
+        let mut conversations = vec![];
+        if sections.contains(&ExportSection::Messages) {
+            let mut stream = self.list_conversation_stream().await;
+            while let Some(document) = stream.next().await {
+                let messages = document
+                    .get_message_list(&self.ipfs)
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .collect();
+                conversations.push(ConversationExport { document, messages });
+            }
+        }
+
+        ExportBundle::new(self.keypair(), root, conversations, sections)
+    }
+
+    // Note: Lists are combined by their key, with the local entry being kept when both contain one. Entries
+    //       removed locally after being added are not restored from the bundle
+    async fn merge_bundle(&mut self, bundle: ExportBundle) -> Result<(), Error> {
+        let mut document = self.get_root_document().await?;
+        let root = bundle.root;
+        let changes = self.change_map(document.changes).await?;
+
+        document.friends = self
+            .merge_list(
+                document.friends,
+                root.friends,
+                "friends",
+                &changes,
+                |did: &DID| did.clone(),
+            )
+            .await?;
+        document.blocks = self
+            .merge_list(
+                document.blocks,
+                root.block_list,
+                "blocks",
+                &changes,
+                |did: &DID| did.clone(),
+            )
+            .await?;
+        document.block_by = self
+            .merge_list(
+                document.block_by,
+                root.block_by_list,
+                "block_by",
+                &changes,
+                |did: &DID| did.clone(),
+            )
+            .await?;
+        document.request = self
+            .merge_list(
+                document.request,
+                root.request,
+                "request",
+                &changes,
+                |request: &Request| request.did().clone(),
+            )
+            .await?;
+        document.recovery_shares = self
+            .merge_list(
+                document.recovery_shares,
+                root.recovery_shares,
+                "recovery_shares",
+                &changes,
+                |share: &RecoveryShareDocument| share.did.clone(),
+            )
+            .await?;
+        document.contacts = self
+            .merge_list(
+                document.contacts,
+                root.contacts,
+                "contacts",
+                &changes,
+                |contact: &Contact| contact.did().clone(),
+            )
+            .await?;
+
+        if !root.conversation_keystore.is_empty() {
+            let mut map: BTreeMap<String, Cid> = match document.conversations_keystore {
+                Some(cid) => self.ipfs.get_dag(cid).local().deserialized().await?,
+                None => BTreeMap::new(),
+            };
+            for (id, keystore) in root.conversation_keystore {
+                let id = id.to_string();
+                if is_removed(&changes, "conversations_keystore", &id) {
+                    continue;
+                }
+                if let std::collections::btree_map::Entry::Vacant(entry) = map.entry(id) {
+                    entry.insert(self.ipfs.put_dag(keystore).await?);
+                }
+            }
+            document.conversations_keystore = Some(self.ipfs.put_dag(map).await?);
+        }
+
+        if let Some(index) = root.file_index {
+            let exported = self
+                .ipfs
+                .put_dag(DirectoryDocument::new(&self.ipfs, &index).await?)
+                .await?;
+            document.file_index = self
+                .merge_index(document.file_index, Some(exported), &changes)
+                .await?;
+        }
+
+        self.set_root_document(document).await?;
+
+        self.import_conversations(bundle.conversations).await
+    }
+
+    /// Add the items of an exported list that do not exist in the stored list, leaving out the items of either list
+    /// that were last removed within `section`
+    async fn merge_list<T, K, F>(
+        &self,
+        current: Option<Cid>,
+        exported: Vec<u8>,
+        section: &str,
+        changes: &BTreeMap<String, EntryChange>,
+        key: F,
+    ) -> Result<Option<Cid>, Error>
+    where
+        T: Serialize + DeserializeOwned,
+        K: PartialEq + Display,
+        F: Fn(&T) -> K,
+    {
+        let mut list: Vec<T> = match current {
+            Some(cid) => {
+                let bytes: Vec<u8> = self.ipfs.get_dag(cid).local().deserialized().await?;
+                serde_json::from_slice(&ecdh_decrypt(self.keypair(), None, bytes)?)?
+            }
+            None => vec![],
+        };
+
+        let exported: Vec<T> = match exported.is_empty() {
+            true => vec![],
+            false => serde_json::from_slice(&ecdh_decrypt(self.keypair(), None, exported)?)?,
+        };
+
+        let len = list.len();
+        list.retain(|item| !is_removed(changes, section, key(item)));
+        let mut changed = list.len() != len;
+
+        for item in exported {
+            if is_removed(changes, section, key(&item))
+                || list.iter().any(|existing| key(existing) == key(&item))
+            {
+                continue;
+            }
+            list.push(item);
+            changed = true;
+        }
+
+        if !changed {
+            return Ok(current);
+        }
+
+        if list.is_empty() {
+            return Ok(None);
+        }
+
+        let bytes = ecdh_encrypt(self.keypair(), None, serde_json::to_vec(&list)?)?;
+        Ok(Some(self.ipfs.put_dag(bytes).await?))
+    }
+
+    async fn import_conversations(
+        &mut self,
+        conversations: Vec<ConversationExport>,
+    ) -> Result<(), Error> {
+        for ConversationExport { document, messages } in conversations {
+            let mut conversation = match self.get_conversation_document(document.id()).await {
+                Ok(conversation) => conversation,
+                Err(_) => ConversationDocument {
+                    messages: None,
+                    ..document
+                },
+            };
+
+            for message in messages {
+                if conversation.contains(&self.ipfs, message.id).await? {
+                    continue;
+                }
+                conversation
+                    .insert_message_document(&self.ipfs, &message)
+                    .await?;
+            }
+
+            self.set_conversation_document(&conversation).await?;
+        }
+        Ok(())
+    }
+
+    // Note: Lists, keystores and the keys of files are encrypted to the key of the identity and are re-encrypted to
+    //       the successor key. Conversations refer to the successor key, with the keys used in direct conversations
+    //       being kept in their keystore so messages exchanged with the previous key can still be read
+    async fn rotate(&self, new_keypair: &Keypair, succession: KeySuccession) -> Result<Cid, Error> {
+        let mut document = self.get_root_document().await?;
+        let mut identity = self.identity().await?;
+
+        if identity.did != succession.previous {
+            return Err(Error::IdentityInvalid);
+        }
+
+        let did = new_keypair.to_did()?;
+        let fingerprint = did.fingerprint();
+        let bytes = fingerprint.as_bytes();
+
+        identity.short_id = bytes[bytes.len() - SHORT_ID_SIZE..]
+            .try_into()
+            .map_err(anyhow::Error::from)?;
+        identity.did = did;
+        // Devices were authorized by the previous key and would need to be authorized again
+        identity.devices.clear();
+        // The username was claimed by the previous key. Registries hand it over to the successor once claimed again
+        identity.username_claim = None;
+        identity.predecessor = Some(succession.clone());
+
+        let identity = identity.sign(new_keypair)?;
+        document.identity = self.ipfs.put_dag(identity).await?;
+
+        for field in [
+            &mut document.friends,
+            &mut document.blocks,
+            &mut document.block_by,
+            &mut document.request,
+            &mut document.recovery_shares,
+            &mut document.contacts,
+            &mut document.changes,
+        ] {
+            let Some(cid) = *field else {
+                continue;
+            };
+
+            let bytes: Vec<u8> = self.ipfs.get_dag(cid).local().deserialized().await?;
+            let bytes = ecdh_decrypt(self.keypair(), None, bytes)?;
+            let bytes = ecdh_encrypt(new_keypair, None, bytes)?;
+            *field = Some(self.ipfs.put_dag(bytes).await?);
+        }
+
+        let mut keystores = BTreeMap::new();
+        if let Some(cid) = document.conversations_keystore {
+            let map: BTreeMap<String, Cid> = self.ipfs.get_dag(cid).local().deserialized().await?;
+            for (id, cid) in map {
+                let keystore: Keystore = self.ipfs.get_dag(cid).local().deserialized().await?;
+                keystores.insert(id, keystore.rekey(self.keypair(), new_keypair)?);
+            }
+        }
+
+        if let Some(cid) = document.conversations {
+            let map: BTreeMap<String, Cid> = self.ipfs.get_dag(cid).local().deserialized().await?;
+            let mut new_map = BTreeMap::new();
+            for (id, cid) in map {
+                let mut conversation: ConversationDocument =
+                    self.ipfs.get_dag(cid).local().deserialized().await?;
+                let keystore = keystores.entry(id.clone()).or_default();
+                self.rekey_conversation(&mut conversation, keystore, new_keypair, &succession)?;
+                new_map.insert(id, self.ipfs.put_dag(conversation).await?);
+            }
+            document.conversations = Some(self.ipfs.put_dag(new_map).await?);
+        }
+
+        if !keystores.is_empty() {
+            let mut new_map = BTreeMap::new();
+            for (id, keystore) in keystores.into_iter().filter(|(_, ks)| !ks.is_empty()) {
+                new_map.insert(id, self.ipfs.put_dag(keystore).await?);
+            }
+            document.conversations_keystore = Some(self.ipfs.put_dag(new_map).await?);
+        }
+
+        if let Some(cid) = document.file_index {
+            let index = self
+                .ipfs
+                .get_dag(cid)
+                .local()
+                .deserialized::<DirectoryDocument>()
+                .await?
+                .resolve(&self.ipfs, false)
+                .await?;
+
+            let mut pending = vec![index.clone()];
+            while let Some(directory) = pending.pop() {
+                for item in directory.get_items() {
+                    let file = match item {
+                        Item::Directory(directory) => {
+                            pending.push(directory);
+                            continue;
+                        }
+                        Item::File(file) => file,
+                    };
+
+                    let Some(key) = file.encryption_key() else {
+                        continue;
+                    };
+
+                    let key = Zeroizing::new(ecdh_decrypt(self.keypair(), None, key)?);
+                    // Note: Setting the key would otherwise mark the file as modified
+                    let modified = file.modified();
+                    file.set_encryption_key(Some(ecdh_encrypt(new_keypair, None, key.as_slice())?));
+                    file.set_modified(Some(modified));
+                }
+            }
+
+            let index = DirectoryDocument::new(&self.ipfs, &index).await?;
+            document.file_index = Some(self.ipfs.put_dag(index).await?);
+        }
+
+        let document = document.sign(new_keypair)?;
+        document.verify(&self.ipfs).await?;
+
+        let cid = self.ipfs.put_dag(document).await?;
+        self.ipfs.insert_pin(cid).recursive().await?;
+
+        Ok(cid)
+    }
+
+    // Note: The most recently modified document is used as a base, with the lists, conversations, keystores and
+    //       file index of both documents being combined so changes made on either device are not lost. An entry is
+    //       left out of the result when the most recent change recorded for it by either device is its removal
+    async fn merge(&mut self, cid: Cid) -> Result<bool, Error> {
+        let local = self.get_root_document().await?;
+
+        if let Err(e) = self.ipfs.fetch(&cid).recursive().await {
+            tracing::warn!(%cid, error = %e, "unable to fetch root document");
+        }
+
+        let remote = self
+            .ipfs
+            .get_dag(cid)
+            .deserialized::<RootDocument>()
+            .await?;
+
+        remote.resolve2(&self.ipfs).await?;
+        remote.verify(&self.ipfs).await?;
+
+        let local_identity = self.identity().await?;
+        let remote_identity: IdentityDocument = self
+            .ipfs
+            .get_dag(remote.identity)
+            .local()
+            .deserialized()
+            .await?;
+
+        if remote_identity.did != local_identity.did {
+            return Err(Error::IdentityInvalid);
+        }
+
+        let (mut document, older) = match remote.modified > local.modified {
+            true => (remote, local.clone()),
+            false => (local.clone(), remote),
+        };
+
+        let mut changes = self.change_map(document.changes).await?;
+        let older_changes = self.change_map(older.changes).await?;
+        if merge_changes(&mut changes, older_changes) {
+            let bytes = ecdh_encrypt(self.keypair(), None, serde_json::to_vec(&changes)?)?;
+            document.changes = Some(self.ipfs.put_dag(bytes).await?);
+        }
+
+        document.friends = self
+            .merge_list(
+                document.friends,
+                self.list_bytes(older.friends).await?,
+                "friends",
+                &changes,
+                |did: &DID| did.clone(),
+            )
+            .await?;
+        document.blocks = self
+            .merge_list(
+                document.blocks,
+                self.list_bytes(older.blocks).await?,
+                "blocks",
+                &changes,
+                |did: &DID| did.clone(),
+            )
+            .await?;
+        document.block_by = self
+            .merge_list(
+                document.block_by,
+                self.list_bytes(older.block_by).await?,
+                "block_by",
+                &changes,
+                |did: &DID| did.clone(),
+            )
+            .await?;
+        document.request = self
+            .merge_list(
+                document.request,
+                self.list_bytes(older.request).await?,
+                "request",
+                &changes,
+                |request: &Request| request.did().clone(),
+            )
+            .await?;
+        document.recovery_shares = self
+            .merge_list(
+                document.recovery_shares,
+                self.list_bytes(older.recovery_shares).await?,
+                "recovery_shares",
+                &changes,
+                |share: &RecoveryShareDocument| share.did.clone(),
+            )
+            .await?;
+        document.contacts = self
+            .merge_list(
+                document.contacts,
+                self.list_bytes(older.contacts).await?,
+                "contacts",
+                &changes,
+                |contact: &Contact| contact.did().clone(),
+            )
+            .await?;
+        document.conversations = self
+            .merge_conversations(document.conversations, older.conversations, &changes)
+            .await?;
+        document.conversations_keystore = self
+            .merge_map(
+                document.conversations_keystore,
+                older.conversations_keystore,
+                "conversations_keystore",
+                &changes,
+            )
+            .await?;
+
+        document.file_index = self
+            .merge_index(document.file_index, older.file_index, &changes)
+            .await?;
+
+        if document.same_state(&local) {
+            return Ok(false);
+        }
+
+        self.set_root_document(document).await?;
+        Ok(true)
+    }
+
+    /// Refer to the successor key within the conversation, keeping the keys of a direct conversation in its keystore
+    /// so messages exchanged with the previous key can be read along with those exchanged with the successor key
+    fn rekey_conversation(
+        &self,
+        conversation: &mut ConversationDocument,
+        keystore: &mut Keystore,
+        new_keypair: &Keypair,
+        succession: &KeySuccession,
+    ) -> Result<(), Error> {
+        let previous = &succession.previous;
+        let successor = &succession.successor;
+
+        for recipient in conversation.recipients.iter_mut() {
+            if recipient == previous {
+                *recipient = successor.clone();
+            }
+        }
+
+        conversation.excluded.remove(previous);
+
+        match conversation.conversation_type() {
+            ConversationType::Direct => {
+                let Some(member) = conversation
+                    .recipients
+                    .iter()
+                    .find(|recipient| recipient.ne(&successor))
+                else {
+                    return Ok(());
+                };
+
+                let previous_key = Zeroizing::new(ecdh_shared_key(self.keypair(), Some(member))?);
+                let key = Zeroizing::new(ecdh_shared_key(new_keypair, Some(member))?);
+
+                // Note: Entries are ordered, so the key of the successor is inserted last to be used from now on
+                for (recipient, key) in [
+                    (previous, &previous_key),
+                    (member, &previous_key),
+                    (successor, &key),
+                    (member, &key),
+                ] {
+                    _ = keystore.insert(new_keypair, recipient, key.as_slice());
+                }
+            }
+            ConversationType::Group => {
+                if conversation.creator.as_ref() == Some(previous) {
+                    for did in conversation.restrict.iter_mut() {
+                        if did == previous {
+                            *did = successor.clone();
+                        }
+                    }
+                    conversation.creator = Some(successor.clone());
+                    conversation.sign(new_keypair)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Encrypted bytes of a list stored in the root document
+    async fn list_bytes(&self, cid: Option<Cid>) -> Result<Vec<u8>, Error> {
+        match cid {
+            Some(cid) => Ok(self.ipfs.get_dag(cid).local().deserialized().await?),
+            None => Ok(vec![]),
+        }
+    }
+
+    /// Changes recorded for the entries of the root document
+    async fn change_map(&self, cid: Option<Cid>) -> Result<BTreeMap<String, EntryChange>, Error> {
+        let bytes = self.list_bytes(cid).await?;
+        if bytes.is_empty() {
+            return Ok(BTreeMap::new());
+        }
+        let bytes = ecdh_decrypt(self.keypair(), None, bytes)?;
+        serde_json::from_slice(&bytes).map_err(Error::from)
+    }
+
+    // Note: Only an entry being added after being removed, or the reverse, is recorded so the time of a change
+    //       reflects when the state of the entry last changed
+    async fn record_changes<I>(&self, document: &mut RootDocument, changes: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (String, bool)>,
+    {
+        let mut map = self.change_map(document.changes).await?;
+        let modified = Utc::now();
+
+        let mut changed = false;
+        for (key, removed) in changes {
+            if map
+                .get(&key)
+                .is_some_and(|change| change.removed == removed)
+            {
+                continue;
+            }
+            map.insert(key, EntryChange { removed, modified });
+            changed = true;
+        }
+
+        if changed {
+            let bytes = ecdh_encrypt(self.keypair(), None, serde_json::to_vec(&map)?)?;
+            document.changes = Some(self.ipfs.put_dag(bytes).await?);
+        }
+
+        Ok(())
+    }
+
+    /// Add the items that only exist in the older index into the newer index, leaving out the items that were last
+    /// removed
+    async fn merge_index(
+        &self,
+        newer: Option<Cid>,
+        older: Option<Cid>,
+        changes: &BTreeMap<String, EntryChange>,
+    ) -> Result<Option<Cid>, Error> {
+        let Some(newer_cid) = newer.or(older) else {
+            return Ok(None);
+        };
+
+        let index = self
+            .ipfs
+            .get_dag(newer_cid)
+            .deserialized::<DirectoryDocument>()
+            .await?
+            .resolve(&self.ipfs, false)
+            .await?;
+
+        let mut changed = remove_index_items(&index, "", changes);
+
+        if let Some(older_cid) = older.filter(|cid| *cid != newer_cid) {
+            let older_index = self
+                .ipfs
+                .get_dag(older_cid)
+                .deserialized::<DirectoryDocument>()
+                .await?
+                .resolve(&self.ipfs, false)
+                .await?;
+
+            // Note: Removed items are left out beforehand so they are not counted as a change of the index
+            remove_index_items(&older_index, "", changes);
+            changed |= merge_directory(&index, &older_index);
+        }
+
+        if !changed {
+            return Ok(Some(newer_cid));
+        }
+
+        let document = DirectoryDocument::new(&self.ipfs, &index).await?;
+        Ok(Some(self.ipfs.put_dag(document).await?))
+    }
+
+    /// Add the entries that only exist in the older map into the newer map, leaving out the entries that were last
+    /// removed within `section`
+    async fn merge_map(
+        &self,
+        newer: Option<Cid>,
+        older: Option<Cid>,
+        section: &str,
+        changes: &BTreeMap<String, EntryChange>,
+    ) -> Result<Option<Cid>, Error> {
+        let mut map: BTreeMap<String, Cid> = match newer {
+            Some(cid) => self.ipfs.get_dag(cid).local().deserialized().await?,
+            None => BTreeMap::new(),
+        };
+
+        let older_map: BTreeMap<String, Cid> = match older {
+            Some(cid) => self.ipfs.get_dag(cid).local().deserialized().await?,
+            None => BTreeMap::new(),
+        };
+
+        let len = map.len();
+        map.retain(|key, _| !is_removed(changes, section, key));
+        let mut changed = map.len() != len;
+
+        for (key, cid) in older_map {
+            if is_removed(changes, section, &key) {
+                continue;
+            }
+            if let std::collections::btree_map::Entry::Vacant(entry) = map.entry(key) {
+                entry.insert(cid);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            return Ok(newer);
+        }
+
+        Ok(Some(self.ipfs.put_dag(map).await?))
+    }
+
+    /// Merge the conversations of the older map into the newer map, combining the conversations kept by both
+    async fn merge_conversations(
+        &self,
+        newer: Option<Cid>,
+        older: Option<Cid>,
+        changes: &BTreeMap<String, EntryChange>,
+    ) -> Result<Option<Cid>, Error> {
+        let merged = self
+            .merge_map(newer, older, "conversations", changes)
+            .await?;
+
+        let (Some(merged_cid), Some(older_cid)) = (merged, older) else {
+            return Ok(merged);
+        };
+
+        let mut map: BTreeMap<String, Cid> =
+            self.ipfs.get_dag(merged_cid).local().deserialized().await?;
+        let older_map: BTreeMap<String, Cid> =
+            self.ipfs.get_dag(older_cid).local().deserialized().await?;
+
+        let mut changed = false;
+        for (id, cid) in map.iter_mut() {
+            let Some(older_cid) = older_map.get(id).filter(|older_cid| **older_cid != *cid) else {
+                continue;
+            };
+
+            let document: ConversationDocument =
+                self.ipfs.get_dag(*cid).local().deserialized().await?;
+            let older_document: ConversationDocument =
+                self.ipfs.get_dag(*older_cid).local().deserialized().await?;
+
+            let document = self
+                .merge_conversation(document, older_document, changes)
+                .await?;
+            *cid = self.ipfs.put_dag(document).await?;
+            changed = true;
+        }
+
+        if !changed {
+            return Ok(merged);
+        }
+
+        Ok(Some(self.ipfs.put_dag(map).await?))
+    }
+
+    // Note: The most recently modified copy is used as a base, with the messages and members of both copies being
+    //       combined. A member is left out when the most recent change recorded for them is their removal
+    async fn merge_conversation(
+        &self,
+        first: ConversationDocument,
+        second: ConversationDocument,
+        changes: &BTreeMap<String, EntryChange>,
+    ) -> Result<ConversationDocument, Error> {
+        let (mut document, older) = match second.modified > first.modified {
+            true => (second, first),
+            false => (first, second),
+        };
+
+        let section = change_key("conversation_recipients", document.id());
+        for did in older.recipients {
+            if !document.recipients.contains(&did) {
+                document.recipients.push(did);
+            }
+        }
+        document
+            .recipients
+            .retain(|did| !is_removed(changes, &section, did));
+
+        for (did, signature) in older.excluded {
+            document.excluded.entry(did).or_insert(signature);
+        }
+
+        if document.messages != older.messages {
+            let list = document
+                .message_reference_list(&self.ipfs)
+                .await?
+                .merge(&self.ipfs, &older.message_reference_list(&self.ipfs).await?)
+                .await?;
+            document.messages = Some(self.ipfs.put_dag(list).await?);
+        }
+
+        Ok(document)
+    }
+
     async fn set_root_cid(&mut self, cid: Cid) -> Result<(), Error> {
         let root_document = self
             .ipfs
@@ -1035,3 +2070,401 @@ impl RootDocumentInner {
         Ok(())
     }
 }
+
+/// Key under which the change of an entry within a section of the root document is recorded
+fn change_key(section: &str, key: impl Display) -> String {
+    format!("{section}/{key}")
+}
+
+/// Check if the most recent change recorded for an entry is its removal
+fn is_removed(changes: &BTreeMap<String, EntryChange>, section: &str, key: impl Display) -> bool {
+    changes
+        .get(&change_key(section, key))
+        .is_some_and(|change| change.removed)
+}
+
+/// Add the changes of `other` that are more recent than those of `target`, returning true if `target` was changed
+fn merge_changes(
+    target: &mut BTreeMap<String, EntryChange>,
+    other: BTreeMap<String, EntryChange>,
+) -> bool {
+    let mut changed = false;
+    for (key, change) in other {
+        if target
+            .get(&key)
+            .is_some_and(|current| current.modified >= change.modified)
+        {
+            continue;
+        }
+        target.insert(key, change);
+        changed = true;
+    }
+    changed
+}
+
+/// Paths of every item within the index, relative to its root
+fn index_paths(directory: &Directory) -> HashSet<String> {
+    let mut paths = HashSet::new();
+    let mut pending = vec![(String::new(), directory.clone())];
+    while let Some((parent, directory)) = pending.pop() {
+        for item in directory.get_items() {
+            let path = format!("{parent}{}", item.name());
+            if let Item::Directory(directory) = item {
+                pending.push((format!("{path}/"), directory));
+            }
+            paths.insert(path);
+        }
+    }
+    paths
+}
+
+/// Remove the items of the index that were last removed, returning true if any item was removed
+fn remove_index_items(
+    directory: &Directory,
+    parent: &str,
+    changes: &BTreeMap<String, EntryChange>,
+) -> bool {
+    let mut changed = false;
+    for item in directory.get_items() {
+        let path = format!("{parent}{}", item.name());
+        if is_removed(changes, "files", &path) {
+            changed |= directory.remove_item(&item.name()).is_ok();
+            continue;
+        }
+        if let Item::Directory(directory) = item {
+            changed |= remove_index_items(&directory, &format!("{path}/"), changes);
+        }
+    }
+    changed
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+    use either::Either;
+    use futures::StreamExt;
+    use rust_ipfs::{Ipfs, Keypair, UninitializedIpfsDefault};
+    use uuid::Uuid;
+    use warp::{
+        constellation::{directory::Directory, file::File},
+        crypto::{cipher::CipherSuite, Fingerprint, DID},
+        multipass::identity::SHORT_ID_SIZE,
+        raygun::{GroupPermissions, Message},
+    };
+
+    use std::time::Duration;
+
+    use tracing::Span;
+
+    use super::RootDocumentMap;
+    use crate::{
+        config::Config,
+        store::{
+            conversation::{message::MessageDocument, ConversationDocument},
+            document::{
+                identity::{IdentityDocument, KeySuccession},
+                RootDocument,
+            },
+            ecdh_shared_key,
+            event_subscription::EventSubscription,
+            files::FileStore,
+            PeerIdExt,
+        },
+    };
+
+    fn identity_document(keypair: &Keypair) -> IdentityDocument {
+        let did = keypair.to_did().expect("valid keypair");
+        let fingerprint = did.fingerprint();
+        let bytes = fingerprint.as_bytes();
+        let time = Utc::now();
+
+        IdentityDocument {
+            username: warp::multipass::generator::generate_name(),
+            short_id: bytes[bytes.len() - SHORT_ID_SIZE..]
+                .try_into()
+                .expect("Valid conversion"),
+            did,
+            created: time,
+            modified: time,
+            status_message: None,
+            metadata: Default::default(),
+            version: Default::default(),
+            devices: vec![],
+            predecessor: None,
+            username_claim: None,
+            presence: None,
+            redacted: false,
+            signature: None,
+        }
+        .sign(keypair)
+        .expect("valid")
+    }
+
+    // Note: Both devices share a single node so the documents of one are available to the other, as they
+    //       would be once fetched through a shuttle
+    async fn device(
+        ipfs: &Ipfs,
+        keypair: &Keypair,
+        identity: &IdentityDocument,
+    ) -> RootDocumentMap {
+        let mut root = RootDocumentMap::new(ipfs, Some(keypair.clone())).await;
+        let cid = ipfs.put_dag(identity).await.expect("stored identity");
+        root.set(RootDocument {
+            identity: cid,
+            ..Default::default()
+        })
+        .await
+        .expect("stored root document");
+        root
+    }
+
+    async fn make_changes(root: &RootDocumentMap, keypair: &Keypair, file: &str) -> DID {
+        let friend = Keypair::generate_ed25519().to_did().expect("valid keypair");
+        root.add_friend(&friend).await.expect("added friend");
+
+        let own = keypair.to_did().expect("valid keypair");
+        let conversation =
+            ConversationDocument::new_direct(keypair, [own, friend.clone()]).expect("valid");
+        root.set_conversation_document(&conversation)
+            .await
+            .expect("stored conversation");
+
+        let index = Directory::new("root");
+        index.add_item(File::new(file)).expect("added file");
+        root.set_directory_index(index).await.expect("stored index");
+
+        friend
+    }
+
+    #[tokio::test]
+    async fn merge_between_devices() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let keypair = Keypair::generate_ed25519();
+        let identity = identity_document(&keypair);
+
+        let first = device(&ipfs, &keypair, &identity).await;
+        let second = device(&ipfs, &keypair, &identity).await;
+
+        let first_friend = make_changes(&first, &keypair, "first.txt").await;
+        let second_friend = make_changes(&second, &keypair, "second.txt").await;
+
+        assert!(first.merge(second.export_root_cid().await?).await?);
+        assert!(second.merge(first.export_root_cid().await?).await?);
+
+        for root in [&first, &second] {
+            let friends = root.get_friends().await?;
+            assert!(friends.contains(&first_friend));
+            assert!(friends.contains(&second_friend));
+
+            let conversations = root
+                .list_conversation_document()
+                .await
+                .collect::<Vec<_>>()
+                .await;
+            assert_eq!(conversations.len(), 2);
+
+            let index = root.get_directory_index().await?;
+            assert!(index.has_item("first.txt"));
+            assert!(index.has_item("second.txt"));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn removals_survive_merge() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let keypair = Keypair::generate_ed25519();
+        let identity = identity_document(&keypair);
+
+        let first = device(&ipfs, &keypair, &identity).await;
+        let second = device(&ipfs, &keypair, &identity).await;
+
+        let friend = make_changes(&first, &keypair, "first.txt").await;
+        second.merge(first.export_root_cid().await?).await?;
+
+        first.remove_friend(&friend).await?;
+        let index = first.get_directory_index().await?;
+        index.remove_item("first.txt")?;
+        first.set_directory_index(index).await?;
+
+        // The document of the second device is the most recent one, while still holding the removed entries
+        let second_friend = Keypair::generate_ed25519().to_did()?;
+        second.add_friend(&second_friend).await?;
+        let index = second.get_directory_index().await?;
+        index.add_item(File::new("second.txt"))?;
+        second.set_directory_index(index).await?;
+
+        assert!(first.merge(second.export_root_cid().await?).await?);
+        assert!(second.merge(first.export_root_cid().await?).await?);
+
+        for root in [&first, &second] {
+            let friends = root.get_friends().await?;
+            assert!(!friends.contains(&friend));
+            assert!(friends.contains(&second_friend));
+
+            let index = root.get_directory_index().await?;
+            assert!(!index.has_item("first.txt"));
+            assert!(index.has_item("second.txt"));
+        }
+
+        Ok(())
+    }
+
+    async fn message(
+        ipfs: &Ipfs,
+        keypair: &Keypair,
+        recipient: &DID,
+        conversation_id: Uuid,
+        text: &str,
+    ) -> MessageDocument {
+        let mut message = Message::default();
+        message.set_conversation_id(conversation_id);
+        message.set_sender(keypair.to_did().expect("valid keypair"));
+        message.set_lines(vec![text.into()]);
+        MessageDocument::new(
+            ipfs,
+            keypair,
+            message,
+            Either::Left(recipient),
+            CipherSuite::default(),
+        )
+        .await
+        .expect("valid message")
+    }
+
+    #[tokio::test]
+    async fn merge_combines_conversations() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let keypair = Keypair::generate_ed25519();
+        let identity = identity_document(&keypair);
+
+        let first = device(&ipfs, &keypair, &identity).await;
+        let second = device(&ipfs, &keypair, &identity).await;
+
+        let own = keypair.to_did()?;
+        let friend = Keypair::generate_ed25519().to_did()?;
+        let removed = Keypair::generate_ed25519().to_did()?;
+        let added = Keypair::generate_ed25519().to_did()?;
+
+        let conversation = ConversationDocument::new_group(
+            &keypair,
+            None,
+            [own, friend.clone(), removed.clone()],
+            &[],
+            GroupPermissions::new(),
+        )?;
+        let id = conversation.id();
+        first.set_conversation_document(&conversation).await?;
+        second.merge(first.export_root_cid().await?).await?;
+
+        // Each device sends a message, while the first removes a member and the second adds one
+        let mut document = first.get_conversation_document(id).await?;
+        let first_message = message(&ipfs, &keypair, &friend, id, "first").await;
+        document
+            .insert_message_document(&ipfs, &first_message)
+            .await?;
+        document.recipients.retain(|did| did != &removed);
+        first.set_conversation_document(&document).await?;
+
+        let mut document = second.get_conversation_document(id).await?;
+        let second_message = message(&ipfs, &keypair, &friend, id, "second").await;
+        document
+            .insert_message_document(&ipfs, &second_message)
+            .await?;
+        document.recipients.push(added.clone());
+        second.set_conversation_document(&document).await?;
+
+        assert!(first.merge(second.export_root_cid().await?).await?);
+        assert!(second.merge(first.export_root_cid().await?).await?);
+
+        for root in [&first, &second] {
+            let document = root.get_conversation_document(id).await?;
+            assert!(document.contains(&ipfs, first_message.id).await?);
+            assert!(document.contains(&ipfs, second_message.id).await?);
+            assert_eq!(document.messages_length(&ipfs).await?, 2);
+            assert!(document.recipients.contains(&added));
+            assert!(!document.recipients.contains(&removed));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn read_encrypted_file_after_rotation() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let keypair = Keypair::generate_ed25519();
+        let identity = identity_document(&keypair);
+        let root = device(&ipfs, &keypair, &identity).await;
+
+        let friend = Keypair::generate_ed25519().to_did()?;
+        let own = keypair.to_did()?;
+        let conversation = ConversationDocument::new_direct(&keypair, [own, friend.clone()])?;
+        root.set_conversation_document(&conversation).await?;
+
+        let config = Config::development();
+        let mut store = FileStore::new(
+            &ipfs,
+            &root,
+            &config,
+            EventSubscription::new(),
+            &Span::current(),
+        )
+        .await;
+        store.put_buffer("secret.txt", b"secret").await?;
+
+        // The index is exported to the root document by the task
+        while !root.get_directory_index().await?.has_item("secret.txt") {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        let new_keypair = Keypair::generate_ed25519();
+        let new_did = new_keypair.to_did()?;
+        let succession = KeySuccession::new(&keypair, &new_keypair)?;
+        let cid = root.rotate(&new_keypair, succession).await?;
+
+        let rotated = RootDocumentMap::new(&ipfs, Some(new_keypair.clone())).await;
+        rotated.import_root_cid(cid).await?;
+
+        let store = FileStore::new(
+            &ipfs,
+            &rotated,
+            &config,
+            EventSubscription::new(),
+            &Span::current(),
+        )
+        .await;
+        let buffer = store.get_buffer("secret.txt").await?;
+        assert_eq!(buffer.as_ref(), b"secret");
+
+        let conversation = rotated.get_conversation_document(conversation.id).await?;
+        assert!(conversation.recipients.contains(&new_did));
+
+        let keystore = rotated.get_conversation_keystore(conversation.id).await?;
+        assert_eq!(
+            keystore.get_latest(&new_keypair, &new_did)?,
+            ecdh_shared_key(&new_keypair, Some(&friend))?
+        );
+        assert!(keystore
+            .get_all(&new_keypair, &identity.did)?
+            .contains(&ecdh_shared_key(&keypair, Some(&friend))?));
+
+        Ok(())
+    }
+}
@@ -3,7 +3,11 @@ use std::ffi::OsStr;
 
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
-use std::{collections::VecDeque, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use futures::{
     channel::{mpsc, oneshot},
@@ -13,29 +17,47 @@ use futures::{
 };
 use futures_finally::try_stream::FinallyTryStreamExt;
 
-use rust_ipfs::{unixfs::UnixfsStatus, Ipfs, IpfsPath};
+use ipld_core::cid::Cid;
+use rust_ipfs::{unixfs::UnixfsStatus, Ipfs, IpfsPath, Keypair};
 
 use tokio_util::sync::{CancellationToken, DropGuard};
 use tracing::{Instrument, Span};
+use uuid::Uuid;
 use warp::{
     constellation::{
-        directory::Directory, ConstellationEventKind, ConstellationProgressStream, Progression,
+        directory::Directory,
+        file::{File, Hash, ImageVariant},
+        search::{self, ConstellationSearchStream, SearchOptions},
+        ConstellationEventKind, ConstellationProgressStream, Progression,
+    },
+    crypto::{
+        cipher::Cipher,
+        multihash::sha2_256_multihash_digest,
+        sha2::{Digest, Sha256},
+        zeroize::Zeroizing,
     },
     error::Error,
 };
 
-use parking_lot::RwLock;
+use indexmap::IndexMap;
+use parking_lot::{Mutex, RwLock};
 use warp::constellation::item::{Item, ItemType};
 
 use super::{
-    document::root::RootDocumentMap, event_subscription::EventSubscription,
+    document::{files::merge_directory, image_dag::ImageDag, root::RootDocumentMap},
+    ecdh_decrypt, ecdh_encrypt,
+    event_subscription::EventSubscription,
+    message::CHAT_DIRECTORY,
+    MAX_CONTENT_INDEX_CACHE_SIZE, MAX_CONTENT_INDEX_SIZE, MAX_THUMBNAIL_SIZE,
     MAX_THUMBNAIL_STREAM_SIZE,
 };
 use crate::rt::{Executor, LocalExecutor};
 use crate::{
-    config::{self, Config},
-    thumbnail::ThumbnailGenerator,
+    config::{self, Config, StripImageMetadata},
+    sanitize,
+    thumbnail::{ThumbnailGenerator, Variant},
     to_file_type,
+    utils::ByteCollection,
 };
 
 #[derive(Clone)]
@@ -59,7 +81,8 @@ impl FileStore {
 
         let index = Directory::new("root");
 
-        let thumbnail_store = ThumbnailGenerator::new(ipfs);
+        let mut thumbnail_store = ThumbnailGenerator::new(ipfs);
+        thumbnail_store.set_variant_sizes(config.image_variant_sizes());
 
         let (command_sender, command_receiver) = futures::channel::mpsc::channel(1);
         let (export_tx, export_rx) = futures::channel::mpsc::channel(0);
@@ -70,6 +93,7 @@ impl FileStore {
             path: Arc::default(),
             root: root.clone(),
             thumbnail_store,
+            content_index: ContentIndex::default(),
             ipfs: ipfs.clone(),
             constellation_tx,
             config,
@@ -141,7 +165,7 @@ impl FileStore {
 
     /// Current size of the file system
     pub fn current_size(&self) -> usize {
-        self.root_directory().size()
+        self.root_directory().stored_size()
     }
 
     pub fn max_size(&self) -> usize {
@@ -263,6 +287,24 @@ impl FileStore {
         rx.await.map_err(anyhow::Error::from)?
     }
 
+    pub async fn get_variant_stream(
+        &self,
+        name: impl Into<String>,
+        size: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::GetVariantStream {
+                name: name.into(),
+                size,
+                response: tx,
+            })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
     /// Used to remove data from the filesystem
     pub async fn remove(&mut self, name: impl Into<String>, recursive: bool) -> Result<(), Error> {
         let (tx, rx) = oneshot::channel();
@@ -296,6 +338,46 @@ impl FileStore {
         rx.await.map_err(anyhow::Error::from)?
     }
 
+    /// Reload the index from the root document
+    pub async fn import_index(&mut self) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::ImportIndex { response: tx })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
+    /// Merge the index of the root document into the current index
+    pub async fn merge_index(&mut self) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::MergeIndex { response: tx })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
+    pub async fn move_item(
+        &mut self,
+        from: impl Into<String>,
+        to: impl Into<String>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::MoveItem {
+                from: from.into(),
+                to: to.into(),
+                response: tx,
+            })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
     pub async fn create_directory(
         &mut self,
         name: impl Into<String>,
@@ -326,6 +408,94 @@ impl FileStore {
             .await;
         rx.await.map_err(anyhow::Error::from)??.await
     }
+
+    /// Used to search the filesystem for items matching the options
+    pub async fn search(&self, options: SearchOptions) -> Result<ConstellationSearchStream, Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::Search {
+                options,
+                response: tx,
+            })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
+    pub async fn set_item_metadata(
+        &mut self,
+        path: impl Into<String>,
+        key: impl Into<String>,
+        value: Option<String>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::SetItemMetadata {
+                path: path.into(),
+                key: key.into(),
+                value,
+                response: tx,
+            })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
+    pub async fn add_tag(
+        &mut self,
+        path: impl Into<String>,
+        tag: impl Into<String>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::AddTag {
+                path: path.into(),
+                tag: tag.into(),
+                response: tx,
+            })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
+    pub async fn remove_tag(
+        &mut self,
+        path: impl Into<String>,
+        tag: impl Into<String>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::RemoveTag {
+                path: path.into(),
+                tag: tag.into(),
+                response: tx,
+            })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
+
+    pub async fn set_quota(
+        &mut self,
+        path: impl Into<String>,
+        quota: Option<usize>,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .command_sender
+            .clone()
+            .send(FileTaskCommand::SetQuota {
+                path: path.into(),
+                quota,
+                response: tx,
+            })
+            .await;
+        rx.await.map_err(anyhow::Error::from)?
+    }
 }
 
 type GetStream = BoxStream<'static, Result<Bytes, std::io::Error>>;
@@ -358,6 +528,11 @@ enum FileTaskCommand {
         name: String,
         response: oneshot::Sender<Result<GetStream, Error>>,
     },
+    GetVariantStream {
+        name: String,
+        size: u32,
+        response: oneshot::Sender<Result<GetStream, Error>>,
+    },
     GetBuffer {
         name: String,
         response: oneshot::Sender<Result<GetBufferFutResult, Error>>,
@@ -373,6 +548,17 @@ enum FileTaskCommand {
         new: String,
         response: oneshot::Sender<Result<(), Error>>,
     },
+    MoveItem {
+        from: String,
+        to: String,
+        response: oneshot::Sender<Result<(), Error>>,
+    },
+    ImportIndex {
+        response: oneshot::Sender<Result<(), Error>>,
+    },
+    MergeIndex {
+        response: oneshot::Sender<Result<(), Error>>,
+    },
     CreateDirectory {
         name: String,
         recursive: bool,
@@ -382,6 +568,31 @@ enum FileTaskCommand {
         path: String,
         response: oneshot::Sender<Result<BoxFuture<'static, Result<(), Error>>, Error>>,
     },
+    Search {
+        options: SearchOptions,
+        response: oneshot::Sender<Result<ConstellationSearchStream, Error>>,
+    },
+    SetItemMetadata {
+        path: String,
+        key: String,
+        value: Option<String>,
+        response: oneshot::Sender<Result<(), Error>>,
+    },
+    AddTag {
+        path: String,
+        tag: String,
+        response: oneshot::Sender<Result<(), Error>>,
+    },
+    RemoveTag {
+        path: String,
+        tag: String,
+        response: oneshot::Sender<Result<(), Error>>,
+    },
+    SetQuota {
+        path: String,
+        quota: Option<usize>,
+        response: oneshot::Sender<Result<(), Error>>,
+    },
 }
 
 struct FileTask {
@@ -395,14 +606,35 @@ struct FileTask {
     signal_tx: futures::channel::mpsc::UnboundedSender<()>,
     signal_rx: futures::channel::mpsc::UnboundedReceiver<()>,
     thumbnail_store: ThumbnailGenerator,
+    content_index: ContentIndex,
     constellation_tx: EventSubscription<ConstellationEventKind>,
     command_receiver: futures::channel::mpsc::Receiver<FileTaskCommand>,
-    #[allow(dead_code)]
     executor: LocalExecutor,
 }
 
+/// File whose contents were encrypted in the background, waiting to be swapped in by the task
+struct MigratedFile {
+    path: String,
+    file: File,
+    previous: String,
+    reference: String,
+    wrapped_key: Vec<u8>,
+    applied: oneshot::Sender<bool>,
+}
+
 impl FileTask {
     async fn run(&mut self) {
+        let (migrated_tx, mut migrated_rx) = mpsc::channel(0);
+
+        // Encrypting existing files could take a while so it is done in the background while commands are served
+        let _migration = self.executor.spawn_abortable(migrate_plaintext_files(
+            self.ipfs.clone(),
+            self.root.clone(),
+            self.index.clone(),
+            self.plaintext_files(),
+            migrated_tx,
+        ));
+
         loop {
             tokio::select! {
                 biased;
@@ -442,6 +674,9 @@ impl FileTask {
                         FileTaskCommand::GetStream { name, response } => {
                             let _ = response.send(self.get_stream(&name));
                         },
+                        FileTaskCommand::GetVariantStream { name, size, response } => {
+                            let _ = response.send(self.get_variant_stream(&name, size));
+                        },
                         FileTaskCommand::GetBuffer { name, response } => {
                             let _ = response.send(self.get_buffer(name));
                         },
@@ -459,6 +694,15 @@ impl FileTask {
                         } => {
                             let _ = response.send(self.rename(&current, &new).await);
                         },
+                        FileTaskCommand::MoveItem { from, to, response } => {
+                            let _ = response.send(self.move_item(&from, &to).await);
+                        },
+                        FileTaskCommand::ImportIndex { response } => {
+                            let _ = response.send(self.import_index().await);
+                        },
+                        FileTaskCommand::MergeIndex { response } => {
+                            let _ = response.send(self.merge_index().await);
+                        },
                         FileTaskCommand::CreateDirectory {
                             name,
                             recursive,
@@ -469,8 +713,43 @@ impl FileTask {
                         FileTaskCommand::SyncRef { path, response } => {
                             let _ = response.send(self.sync_ref(&path));
                         },
+                        FileTaskCommand::Search { options, response } => {
+                            let _ = response.send(self.search(options));
+                        },
+                        FileTaskCommand::SetItemMetadata {
+                            path,
+                            key,
+                            value,
+                            response,
+                        } => {
+                            let _ = response.send(self.set_item_metadata(&path, key, value).await);
+                        },
+                        FileTaskCommand::AddTag {
+                            path,
+                            tag,
+                            response,
+                        } => {
+                            let _ = response.send(self.add_tag(&path, tag).await);
+                        },
+                        FileTaskCommand::RemoveTag {
+                            path,
+                            tag,
+                            response,
+                        } => {
+                            let _ = response.send(self.remove_tag(&path, &tag).await);
+                        },
+                        FileTaskCommand::SetQuota {
+                            path,
+                            quota,
+                            response,
+                        } => {
+                            let _ = response.send(self.set_quota(&path, quota).await);
+                        },
                     }
                 },
+                Some(migrated) = migrated_rx.next() => {
+                    self.apply_migrated_file(migrated).await;
+                }
                 Some(_) = self.export_rx.next() => {
                     let _ = self.export().await;
                 }
@@ -485,10 +764,202 @@ impl FileTask {
 
     async fn import_v1(&self) -> Result<(), Error> {
         let index = self.root.get_directory_index().await?;
+        self.resolve_encrypted_thumbnails(&index).await;
         self.index.set_items(index.get_items());
         Ok(())
     }
 
+    /// Decrypt the thumbnails of encrypted files, which cannot be resolved along with the index
+    async fn resolve_encrypted_thumbnails(&self, index: &Directory) {
+        let mut pending = vec![index.clone()];
+
+        while let Some(directory) = pending.pop() {
+            for item in directory.get_items() {
+                let file = match item {
+                    Item::Directory(directory) => {
+                        pending.push(directory);
+                        continue;
+                    }
+                    Item::File(file) => file,
+                };
+
+                if !file.thumbnail().is_empty() {
+                    continue;
+                }
+
+                let Some(cid) = file
+                    .thumbnail_reference()
+                    .and_then(|reference| reference.parse::<IpfsPath>().ok())
+                    .and_then(|path| path.root().cid().copied())
+                else {
+                    continue;
+                };
+
+                let Ok(Some(key)) = file_key(self.root.keypair(), &file) else {
+                    continue;
+                };
+
+                let image: ImageDag = match self.ipfs.get_dag(cid).deserialized().await {
+                    Ok(image) => image,
+                    Err(e) => {
+                        tracing::warn!(%cid, error = %e, "Unable to resolve thumbnail");
+                        continue;
+                    }
+                };
+
+                if !image.encrypted {
+                    continue;
+                }
+
+                let Ok(data) = ByteCollection::new(cat_file(
+                    &self.ipfs,
+                    IpfsPath::from(image.link),
+                    Some(key.as_slice()),
+                    Some(MAX_THUMBNAIL_SIZE),
+                ))
+                .await
+                else {
+                    continue;
+                };
+
+                // Note: Setting the thumbnail would otherwise mark the file as modified
+                let modified = file.modified();
+                file.set_thumbnail(data);
+                file.set_modified(Some(modified));
+            }
+        }
+    }
+
+    /// Files that were stored before encryption at rest was introduced, along with their path
+    fn plaintext_files(&self) -> Vec<(String, File)> {
+        let mut files = vec![];
+        let mut pending = vec![(String::new(), self.root_directory())];
+
+        while let Some((prefix, directory)) = pending.pop() {
+            for item in directory.get_items() {
+                let path = format!("{prefix}/{}", item.name());
+                match item {
+                    Item::Directory(directory) => pending.push((path, directory)),
+                    Item::File(file) => {
+                        if file.encryption_key().is_some() || is_shared(&path) {
+                            continue;
+                        }
+                        files.push((path, file));
+                    }
+                }
+            }
+        }
+
+        files
+    }
+
+    /// Swap in the encrypted contents of a file, unless it was changed while its contents were being encrypted
+    async fn apply_migrated_file(&mut self, migrated: MigratedFile) {
+        let MigratedFile {
+            path,
+            file,
+            previous,
+            reference,
+            wrapped_key,
+            applied,
+        } = migrated;
+
+        let unchanged = self
+            .root_directory()
+            .get_item_by_path(&path)
+            .and_then(|item| item.get_file())
+            .is_ok_and(|current| {
+                current.id() == file.id()
+                    && current.encryption_key().is_none()
+                    && current.reference().as_deref() == Some(previous.as_str())
+            });
+
+        if unchanged {
+            // Note: Updating the reference would otherwise mark the file as modified
+            let modified = file.modified();
+            file.set_reference(&reference);
+            file.set_encryption_key(Some(wrapped_key));
+            file.set_modified(Some(modified));
+
+            if let Err(e) = self.export().await {
+                tracing::error!(error = %e, "Error exporting index");
+            }
+        }
+
+        let _ = applied.send(unchanged);
+    }
+
+    /// Reload the index from the root document, reporting what changed.
+    /// Used when the root document has been replaced, such as when importing from another device
+    async fn import_index(&mut self) -> Result<(), Error> {
+        let previous = index_entries(&self.index);
+
+        self.import_v1().await?;
+
+        let mut index = self.index.clone();
+        index.rebuild_paths(&Some(self.signal_tx.clone()));
+
+        self.emit_index_changes(previous).await;
+
+        Ok(())
+    }
+
+    /// Merge the index from the root document into the current index, keeping items that only exist locally.
+    /// Used when the root document was changed by another device
+    async fn merge_index(&mut self) -> Result<(), Error> {
+        let previous = index_entries(&self.index);
+
+        let index = self.root.get_directory_index().await?;
+        self.resolve_encrypted_thumbnails(&index).await;
+
+        if !merge_directory(&self.index, &index) {
+            return Ok(());
+        }
+
+        self.export().await?;
+
+        self.emit_index_changes(previous).await;
+
+        Ok(())
+    }
+
+    /// Emit the entries of the index that changed since the previous entries were taken
+    async fn emit_index_changes(&self, previous: HashMap<String, (Uuid, DateTime<Utc>)>) {
+        let current = index_entries(&self.index);
+
+        let mut added = vec![];
+        let mut modified = vec![];
+        for (path, entry) in &current {
+            match previous.get(path) {
+                None => added.push(path.clone()),
+                Some(previous) if previous != entry => modified.push(path.clone()),
+                _ => {}
+            }
+        }
+
+        let mut removed = previous
+            .keys()
+            .filter(|path| !current.contains_key(*path))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        if added.is_empty() && removed.is_empty() && modified.is_empty() {
+            return;
+        }
+
+        added.sort();
+        removed.sort();
+        modified.sort();
+
+        self.constellation_tx
+            .emit(ConstellationEventKind::IndexSynced {
+                added,
+                removed,
+                modified,
+            })
+            .await;
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn export(&self) -> Result<(), Error> {
         tracing::trace!("Exporting index");
@@ -513,6 +984,19 @@ impl FileTask {
         self.open_directory(&self.get_path().to_string_lossy())
     }
 
+    /// Full path of an item relative to the current directory, as used within events
+    fn full_path(&self, path: &str) -> String {
+        let current = self.get_path();
+        let current = current.to_string_lossy();
+        let path = current
+            .split('/')
+            .chain(path.split('/'))
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("/{path}")
+    }
+
     /// Returns a mutable directory from the filesystem
     fn open_directory(&self, path: &str) -> Result<Directory, Error> {
         match path.trim().is_empty() {
@@ -526,22 +1010,32 @@ impl FileTask {
 
     /// Current size of the file system
     fn current_size(&self) -> usize {
-        self.root_directory().size()
+        self.root_directory().stored_size()
     }
 
     fn max_size(&self) -> usize {
         self.config.max_storage_size().unwrap_or(1024 * 1024 * 1024)
     }
 
+    /// Whether metadata should be removed from the image at the given path before it is stored
+    fn strip_metadata(&self, path: &str) -> bool {
+        if !sanitize::is_supported(path) {
+            return false;
+        }
+        match self.config.store_setting().strip_image_metadata {
+            StripImageMetadata::Never => false,
+            StripImageMetadata::Attachments => is_shared(path),
+            StripImageMetadata::Always => true,
+        }
+    }
+
     fn get_path(&self) -> PathBuf {
         PathBuf::from(self.path.read().to_string_lossy().replace('\\', "/"))
     }
 
     #[cfg(not(target_arch = "wasm32"))]
     async fn put(&mut self, name: &str, path: &str) -> Result<ConstellationProgressStream, Error> {
-        let (name, dest_path) = split_file_from_path(name)?;
-
-        let ipfs = self.ipfs.clone();
+        let item_path = self.full_path(name);
 
         let path = PathBuf::from(path);
         if !path.is_file() {
@@ -550,6 +1044,20 @@ impl FileTask {
 
         let file_size = fs::file_size(&path).await?;
 
+        // The metadata has to be removed before the file is hashed, so the file is passed through the stream
+        // instead of being added directly from its path
+        if file_size > 0 && self.strip_metadata(&item_path) {
+            use crate::utils::ReaderStream;
+            use tokio_util::compat::TokioAsyncReadCompatExt;
+            let file = tokio::fs::File::open(&path).await?;
+            let stream = ReaderStream::from_reader(file.compat()).boxed();
+            return self.put_stream(name, Some(file_size as usize), stream);
+        }
+
+        let (name, dest_path) = split_file_from_path(name)?;
+
+        let ipfs = self.ipfs.clone();
+
         if self.current_size() + (file_size as usize) >= self.max_size() {
             return Err(Error::InvalidLength {
                 context: path
@@ -572,32 +1080,71 @@ impl FileTask {
             return Err(Error::FileExist);
         }
 
-        let ((width, height), exact) = (
-            self.config.thumbnail_size(),
-            self.config.thumbnail_exact_format(),
-        );
+        check_quota(&self.root_directory(), &current_directory, file_size, None)?;
 
-        let thumbnail_store = self.thumbnail_store.clone();
+        let key = match is_shared(&item_path) {
+            true => None,
+            false => Some(generate_file_key(self.root.keypair())?),
+        };
+
+        let encrypted_stream = match &key {
+            Some((key, _)) => {
+                let source = match file_size {
+                    0 => futures::stream::empty().boxed(),
+                    _ => {
+                        use crate::utils::ReaderStream;
+                        use tokio_util::compat::TokioAsyncReadCompatExt;
+                        let file = tokio::fs::File::open(&path).await?;
+                        ReaderStream::from_reader(file.compat()).boxed()
+                    }
+                };
+                Some(encrypt_stream(key, source))
+            }
+            None => None,
+        };
+
+        let ((width, height), exact) = (
+            self.config.thumbnail_size(),
+            self.config.thumbnail_exact_format(),
+        );
+
+        let thumbnail_store = self.thumbnail_store.clone();
 
-        let ticket = thumbnail_store.insert(&path, width, height, exact).await?;
+        let ticket = thumbnail_store
+            .insert(
+                &path,
+                width,
+                height,
+                exact,
+                MAX_THUMBNAIL_STREAM_SIZE,
+                key.as_ref().map(|(key, _)| key.as_slice()),
+            )
+            .await?;
 
         let constellation_tx = self.constellation_tx.clone();
         let mut export_tx = self.export_tx.clone();
 
+        let hash_path = path.clone();
+        let hash = tokio::task::spawn_blocking(move || {
+            let mut hash = Hash::default();
+            hash.hash_from_file(hash_path).map(|_| hash)
+        });
+
         let progress_stream = async_stream::stream! {
             let mut last_written = 0;
 
-            let mut total_written = 0;
             let mut returned_path = None;
 
-            let mut stream = ipfs.add_unixfs(path);
+            let mut stream = match encrypted_stream {
+                Some(stream) => ipfs.add_unixfs(stream),
+                None => ipfs.add_unixfs(path),
+            };
 
             while let Some(status) = stream.next().await {
                 let name = name.clone();
                 match status {
                     UnixfsStatus::CompletedStatus { path, written, total_size } => {
                         returned_path = Some(path);
-                        total_written = written;
                         last_written = written;
                         yield Progression::CurrentProgress {
                             name,
@@ -619,6 +1166,11 @@ impl FileTask {
                     }
                     UnixfsStatus::ProgressStatus { written, total_size } => {
                         last_written = written;
+                        constellation_tx.emit(ConstellationEventKind::Progress {
+                            path: item_path.clone(),
+                            current: written,
+                            total: total_size,
+                        }).await;
                         yield Progression::CurrentProgress {
                             name,
                             current: written,
@@ -640,22 +1192,36 @@ impl FileTask {
                     }
                 };
 
+            // Note: The amount written includes the encryption overhead, so the size of the source is used instead
+            let total_written = file_size;
+
             let file = warp::constellation::file::File::new(&name);
             file.set_size(total_written);
             file.set_reference(&format!("{ipfs_path}"));
+            file.set_encryption_key(key.as_ref().map(|(_, wrapped_key)| wrapped_key.clone()));
             file.set_file_type(to_file_type(&name));
 
+            match hash.await {
+                Ok(Ok(hash)) => file.set_hash(hash),
+                Ok(Err(e)) => tracing::error!(error = %e, "Error hashing file"),
+                Err(e) => tracing::error!(error = %e, "Error hashing file"),
+            }
+
             match thumbnail_store.get(ticket).await {
-                Ok((extension_type, path, thumbnail)) => {
-                    file.set_thumbnail(thumbnail);
-                    file.set_thumbnail_format(extension_type.into());
-                    file.set_thumbnail_reference(&path.to_string());
+                Ok(thumbnail) => {
+                    file.set_thumbnail(thumbnail.data);
+                    file.set_thumbnail_format(thumbnail.format.into());
+                    file.set_thumbnail_reference(&thumbnail.path.to_string());
+                    let key = key.as_ref().map(|(key, _)| key.as_slice());
+                    file.set_variants(store_variants(&ipfs, key, thumbnail.variants).await);
                 }
                 Err(e) => {
                     tracing::error!(error = %e, ticket = %ticket, "Error generating thumbnail");
                 }
             }
 
+            let id = file.id();
+
             if let Err(e) = current_directory.add_item(file) {
                 yield Progression::ProgressFailed {
                     name,
@@ -673,6 +1239,8 @@ impl FileTask {
             };
 
             constellation_tx.emit(ConstellationEventKind::Uploaded {
+                id,
+                path: item_path,
                 filename: name.to_string(),
                 size: Some(total_written)
             }).await;
@@ -689,41 +1257,65 @@ impl FileTask {
         let item = self.current_directory()?.get_item_by_path(name)?;
         let file = item.get_file()?;
         let reference = file.reference().ok_or(Error::Other)?.parse::<IpfsPath>()?; //Reference not found
+        let key = file_key(self.root.keypair(), &file)?;
         let fs_tx = self.constellation_tx.clone();
+        let item_path = self.full_path(name);
         let name = name.to_string();
 
         let stream = async_stream::stream! {
-            let mut stream = ipfs.get_unixfs(reference, &path);
-            while let Some(status) = stream.next().await {
-                match status {
-                    UnixfsStatus::CompletedStatus { total_size, .. } => {
+            match key {
+                Some(key) => match write_file(&path, cat_file(&ipfs, reference, Some(&key), None)).await {
+                    Ok(written) => {
                         yield Progression::ProgressComplete {
                             name: name.to_string(),
-                            total: total_size,
+                            total: Some(written),
                         };
                     }
-                    UnixfsStatus::FailedStatus {
-                        written, error, ..
-                    } => {
+                    Err(error) => {
                         yield Progression::ProgressFailed {
                             name: name.to_string(),
-                            last_size: Some(written),
-                            error: error.into(),
+                            last_size: None,
+                            error,
                         };
                         return;
                     }
-                    UnixfsStatus::ProgressStatus { written, total_size } => {
-                        yield Progression::CurrentProgress {
-                            name: name.to_string(),
-                            current: written,
-                            total: total_size,
-                        };
+                },
+                None => {
+                    let mut stream = ipfs.get_unixfs(reference, &path);
+                    while let Some(status) = stream.next().await {
+                        match status {
+                            UnixfsStatus::CompletedStatus { total_size, .. } => {
+                                yield Progression::ProgressComplete {
+                                    name: name.to_string(),
+                                    total: total_size,
+                                };
+                            }
+                            UnixfsStatus::FailedStatus {
+                                written, error, ..
+                            } => {
+                                yield Progression::ProgressFailed {
+                                    name: name.to_string(),
+                                    last_size: Some(written),
+                                    error: error.into(),
+                                };
+                                return;
+                            }
+                            UnixfsStatus::ProgressStatus { written, total_size } => {
+                                yield Progression::CurrentProgress {
+                                    name: name.to_string(),
+                                    current: written,
+                                    total: total_size,
+                                };
+                            }
+                        }
                     }
                 }
             }
 
             fs_tx
                 .emit(ConstellationEventKind::Downloaded {
+                    id: file.id(),
+                    path: item_path,
                     filename: file.name(),
                     size: Some(file.size()),
                     location: Some(path),
@@ -747,6 +1339,7 @@ impl FileTask {
         let thumbnail_size = self.config.thumbnail_size();
         let thumbnail_format = self.config.thumbnail_exact_format();
 
+        let item_path = self.full_path(&name);
         let (name, dest_path) = split_file_from_path(name)?;
 
         if self.current_size() + buffer.len() >= self.max_size() {
@@ -774,6 +1367,26 @@ impl FileTask {
             None => self.current_directory()?,
         };
 
+        check_quota(
+            &self.root_directory(),
+            &current_directory,
+            buffer.len(),
+            None,
+        )?;
+
+        let key = match is_shared(&item_path) {
+            true => None,
+            false => Some(generate_file_key(self.root.keypair())?),
+        };
+
+        let (buffer, removed) = match self.strip_metadata(&item_path) {
+            true => match sanitize::strip_metadata(&buffer)? {
+                Some((data, removed)) => (Bytes::from(data), removed),
+                None => (buffer, vec![]),
+            },
+            false => (buffer, vec![]),
+        };
+
         Ok(async move {
             if current_directory.get_item_by_path(&name).is_ok() {
                 return Err(Error::FileExist);
@@ -782,19 +1395,34 @@ impl FileTask {
             let ((width, height), exact) = (thumbnail_size, thumbnail_format);
 
             let ticket = thumbnail_store
-                .insert_buffer(&name, &buffer, width, height, exact)
+                .insert_buffer(
+                    &name,
+                    &buffer,
+                    width,
+                    height,
+                    exact,
+                    key.as_ref().map(|(key, _)| key.as_slice()),
+                )
                 .await;
 
-            let mut total_written = 0;
+            let total_written = buffer.len();
             let mut returned_path = None;
 
-            let mut stream = ipfs.add_unixfs(buffer);
+            let mut hash = Hash::default();
+            hash.hash_from_slice(&buffer)?;
+
+            let mut stream = match &key {
+                Some((key, _)) => {
+                    let source = futures::stream::once(async { Ok(buffer) }).boxed();
+                    ipfs.add_unixfs(encrypt_stream(key, source))
+                }
+                None => ipfs.add_unixfs(buffer),
+            };
 
             while let Some(status) = stream.next().await {
                 match status {
-                    UnixfsStatus::CompletedStatus { path, written, .. } => {
+                    UnixfsStatus::CompletedStatus { path, .. } => {
                         returned_path = Some(path);
-                        total_written = written;
                     }
                     UnixfsStatus::FailedStatus { error, .. } => return Err(error.into()),
                     _ => {}
@@ -806,24 +1434,39 @@ impl FileTask {
             let file = warp::constellation::file::File::new(&name);
             file.set_size(total_written);
             file.set_reference(&format!("{ipfs_path}"));
+            file.set_encryption_key(key.as_ref().map(|(_, wrapped_key)| wrapped_key.clone()));
             file.set_file_type(to_file_type(&name));
+            file.set_hash(hash);
 
             match thumbnail_store.get(ticket).await {
-                Ok((extension_type, path, thumbnail)) => {
-                    file.set_thumbnail(thumbnail);
-                    file.set_thumbnail_format(extension_type.into());
-                    file.set_thumbnail_reference(&path.to_string());
+                Ok(thumbnail) => {
+                    file.set_thumbnail(thumbnail.data);
+                    file.set_thumbnail_format(thumbnail.format.into());
+                    file.set_thumbnail_reference(&thumbnail.path.to_string());
+                    let key = key.as_ref().map(|(key, _)| key.as_slice());
+                    file.set_variants(store_variants(&ipfs, key, thumbnail.variants).await);
                 }
                 Err(e) => {
                     tracing::error!(error = %e, ticket = %ticket, "Error generating thumbnail");
                 }
             }
 
+            let id = file.id();
             current_directory.add_item(file)?;
 
             let _ = export_tx.try_send(());
 
+            if !removed.is_empty() {
+                tx.emit(ConstellationEventKind::MetadataRemoved {
+                    path: item_path.clone(),
+                    removed,
+                })
+                .await;
+            }
+
             tx.emit(ConstellationEventKind::Uploaded {
+                id,
+                path: item_path,
                 filename: name.to_string(),
                 size: Some(total_written),
             })
@@ -839,20 +1482,28 @@ impl FileTask {
     ) -> Result<BoxFuture<'static, Result<Bytes, Error>>, Error> {
         let name = name.into();
         let ipfs = self.ipfs.clone();
+        let keypair = self.root.keypair().clone();
         let current_directory = self.current_directory()?;
         let tx = self.constellation_tx.clone();
+        let item_path = self.full_path(&name);
 
         Ok(async move {
             let item = current_directory.get_item_by_path(&name)?;
             let file = item.get_file()?;
             let reference = file.reference().ok_or(Error::Other)?; //Reference not found
+            let key = file_key(&keypair, &file)?;
 
-            let buffer = ipfs
-                .cat_unixfs(reference.parse::<IpfsPath>()?)
-                .await
-                .map_err(anyhow::Error::new)?;
+            let buffer = ByteCollection::new(cat_file(
+                &ipfs,
+                reference.parse::<IpfsPath>()?,
+                key.as_deref().map(Vec::as_slice),
+                None,
+            ))
+            .await?;
 
             tx.emit(ConstellationEventKind::Downloaded {
+                id: file.id(),
+                path: item_path,
                 filename: file.name(),
                 size: Some(file.size()),
                 location: None,
@@ -871,6 +1522,7 @@ impl FileTask {
         total_size: Option<usize>,
         stream: BoxStream<'static, std::io::Result<Bytes>>,
     ) -> Result<ConstellationProgressStream, Error> {
+        let item_path = self.full_path(name);
         let (name, dest_path) = split_file_from_path(name)?;
 
         let ipfs = self.ipfs.clone();
@@ -885,6 +1537,8 @@ impl FileTask {
         }
 
         if let Some(total_size) = total_size {
+            check_quota(&self.root_directory(), &current_directory, total_size, None)?;
+
             if total_size + self.current_size() > self.max_size() {
                 return Err(Error::InvalidLength {
                     context: "stream".into(),
@@ -916,11 +1570,43 @@ impl FileTask {
         let thumbnail_size = self.config.thumbnail_size();
         let thumbnail_format = self.config.thumbnail_exact_format();
 
+        let key = match is_shared(&item_path) {
+            true => None,
+            false => Some(generate_file_key(self.root.keypair())?),
+        };
+
+        let removed = Arc::new(parking_lot::Mutex::new(Vec::new()));
+
+        let stream = match self.strip_metadata(&item_path) {
+            true => strip_stream(stream, max_file_size.unwrap_or(max_size), removed.clone()),
+            false => stream,
+        };
+
+        // Hash the data as it passes through so the file can be compared without being read back.
+        // The size is tracked the same way since the amount written includes the encryption overhead
+        let hasher = Arc::new(parking_lot::Mutex::new((Sha256::new(), 0)));
+        let stream = stream
+            .inspect({
+                let hasher = hasher.clone();
+                move |result| {
+                    if let Ok(bytes) = result {
+                        let (hasher, size) = &mut *hasher.lock();
+                        hasher.update(bytes);
+                        *size += bytes.len();
+                    }
+                }
+            })
+            .boxed();
+
+        let stream = match &key {
+            Some((key, _)) => encrypt_stream(key, stream),
+            None => stream,
+        };
+
         let progress_stream = async_stream::stream! {
 
             let mut last_written = 0;
 
-            let mut total_written = 0;
             let mut returned_path = None;
 
             let mut stream = ipfs.add_unixfs(stream);
@@ -930,7 +1616,6 @@ impl FileTask {
                 match status {
                     UnixfsStatus::CompletedStatus { path, written, .. } => {
                         returned_path = Some(path);
-                        total_written = written;
                         last_written = written;
                         yield Progression::CurrentProgress {
                             name: n,
@@ -952,6 +1637,11 @@ impl FileTask {
                     }
                     UnixfsStatus::ProgressStatus { written, .. } => {
                         last_written = written;
+                        constellation_tx.emit(ConstellationEventKind::Progress {
+                            path: item_path.clone(),
+                            current: written,
+                            total: total_size,
+                        }).await;
                         yield Progression::CurrentProgress {
                             name: n,
                             current: written,
@@ -1006,32 +1696,55 @@ impl FileTask {
 
             // NOTE: To prevent the need of "cloning" the main stream, we will get a stream of bytes from rust-ipfs to pass-through to
             //       the thumbnail store.
-            let st = ipfs
-                .cat_unixfs(ipfs_path.clone())
-                .max_length(MAX_THUMBNAIL_STREAM_SIZE)
-                .map(|result| result.map_err(std::io::Error::other))
-                .boxed();
+            let st = cat_file(
+                &ipfs,
+                ipfs_path.clone(),
+                key.as_ref().map(|(key, _)| key.as_slice()),
+                Some(MAX_THUMBNAIL_STREAM_SIZE),
+            );
 
             let ((width, height), exact) = (thumbnail_size, thumbnail_format);
 
-            let ticket = thumbnail_store.insert_stream(&name, st, width, height, exact, MAX_THUMBNAIL_STREAM_SIZE).await;
+            let ticket = thumbnail_store.insert_stream(&name, st, width, height, exact, MAX_THUMBNAIL_STREAM_SIZE, key.as_ref().map(|(key, _)| key.as_slice())).await;
+
+            let (hasher, total_written) = hasher.lock().clone();
+
+            if let Err(e) = check_quota(&root, &current_directory, total_written, None) {
+                yield Progression::ProgressFailed {
+                    name,
+                    last_size: Some(last_written),
+                    error: e,
+                };
+                return;
+            }
 
             let file = warp::constellation::file::File::new(&name);
             file.set_size(total_written);
             file.set_reference(&format!("{ipfs_path}"));
+            file.set_encryption_key(key.as_ref().map(|(_, wrapped_key)| wrapped_key.clone()));
             file.set_file_type(to_file_type(&name));
 
+            let digest = hasher.finalize();
+            match sha2_256_multihash_digest(&digest) {
+                Ok(hash) => file.hash_mut().set_sha256hash(&hash),
+                Err(e) => tracing::error!(error = %e, "Error hashing file"),
+            }
+
             match thumbnail_store.get(ticket).await {
-                Ok((extension_type, path, thumbnail)) => {
-                    file.set_thumbnail(thumbnail);
-                    file.set_thumbnail_format(extension_type.into());
-                    file.set_thumbnail_reference(&path.to_string());
+                Ok(thumbnail) => {
+                    file.set_thumbnail(thumbnail.data);
+                    file.set_thumbnail_format(thumbnail.format.into());
+                    file.set_thumbnail_reference(&thumbnail.path.to_string());
+                    let key = key.as_ref().map(|(key, _)| key.as_slice());
+                    file.set_variants(store_variants(&ipfs, key, thumbnail.variants).await);
                 }
                 Err(e) => {
                     tracing::error!(error = %e, ticket = %ticket, "Error generating thumbnail");
                 }
             }
 
+            let id = file.id();
+
             if let Err(e) = current_directory.add_item(file) {
                 yield Progression::ProgressFailed {
                     name,
@@ -1043,12 +1756,27 @@ impl FileTask {
 
             let _ = export_tx.try_send(());
 
+            let removed = std::mem::take(&mut *removed.lock());
+            if !removed.is_empty() {
+                constellation_tx.emit(ConstellationEventKind::MetadataRemoved {
+                    path: item_path.clone(),
+                    removed: removed.clone(),
+                }).await;
+
+                yield Progression::MetadataRemoved {
+                    name: name.to_string(),
+                    removed,
+                };
+            }
+
             yield Progression::ProgressComplete {
                 name: name.to_string(),
                 total: Some(total_written),
             };
 
             constellation_tx.emit(ConstellationEventKind::Uploaded {
+                id,
+                path: item_path,
                 filename: name.to_string(),
                 size: Some(total_written)
             }).await;
@@ -1069,25 +1797,53 @@ impl FileTask {
         let size = file.size();
         let reference = file.reference().ok_or(Error::Other)?; //Reference not found
         let path = reference.parse::<IpfsPath>()?;
+        let key = file_key(self.root.keypair(), &file)?;
         let tx = self.constellation_tx.clone();
+        let item_path = self.full_path(name);
 
-        let stream = ipfs
-            .cat_unixfs(path)
-            .map_err(std::io::Error::other)
-            .try_finally(move || async move {
-                let _ = tx
-                    .emit(ConstellationEventKind::Downloaded {
-                        filename: file.name(),
-                        size: Some(size),
-                        location: None,
-                    })
-                    .await;
-            });
+        let stream = cat_file(&ipfs, path, key.as_deref().map(Vec::as_slice), None);
+
+        let stream = stream.try_finally(move || async move {
+            let _ = tx
+                .emit(ConstellationEventKind::Downloaded {
+                    id: file.id(),
+                    path: item_path,
+                    filename: file.name(),
+                    size: Some(size),
+                    location: None,
+                })
+                .await;
+        });
 
         //TODO: Validate file against the hashed reference
         Ok(stream.boxed())
     }
 
+    fn get_variant_stream(
+        &self,
+        name: &str,
+        size: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        let file = self
+            .current_directory()?
+            .get_item_by_path(name)
+            .and_then(|item| item.get_file())?;
+
+        let Some(variant) = file.best_variant(size) else {
+            return self.get_stream(name);
+        };
+
+        let path = variant.reference().parse::<IpfsPath>()?;
+        let key = file_key(self.root.keypair(), &file)?;
+
+        Ok(cat_file(
+            &self.ipfs,
+            path,
+            key.as_deref().map(Vec::as_slice),
+            None,
+        ))
+    }
+
     /// Used to remove data from the filesystem
     async fn remove(&mut self, name: &str, recursive: bool) -> Result<(), Error> {
         let directory = self.current_directory()?;
@@ -1116,7 +1872,9 @@ impl FileTask {
 
         self.constellation_tx
             .emit(ConstellationEventKind::Deleted {
-                item_name: name.to_string(),
+                id: item.id(),
+                path: self.full_path(name),
+                item_name: item.name(),
             })
             .await;
 
@@ -1124,6 +1882,7 @@ impl FileTask {
     }
 
     async fn rename(&mut self, current: &str, new: &str) -> Result<(), Error> {
+        let old_path = self.full_path(current);
         let (current, dest_path) = split_file_from_path(current)?;
 
         let current_directory = match dest_path {
@@ -1136,11 +1895,20 @@ impl FileTask {
         }
 
         current_directory.rename_item(&current, new)?;
+        let id = current_directory.get_item(new)?.id();
 
         self.export().await?;
 
+        let new_path = match old_path.rsplit_once('/') {
+            Some((parent, _)) => format!("{parent}/{new}"),
+            None => self.full_path(new),
+        };
+
         self.constellation_tx
             .emit(ConstellationEventKind::Renamed {
+                id,
+                old_path,
+                new_path,
                 old_item_name: current.to_string(),
                 new_item_name: new.to_string(),
             })
@@ -1160,10 +1928,110 @@ impl FileTask {
             return Err(Error::DirectoryExist);
         }
 
-        directory.add_directory(Directory::new(name))?;
+        let mut components = name
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        let name = components.pop().ok_or(Error::InvalidDirectory)?;
+
+        // Walk through any existing parent directories, creating those that are missing
+        let mut parent = directory;
+        let mut path = self.full_path("");
+        let mut created = vec![];
+        for component in components {
+            path = format!("{}/{component}", path.trim_end_matches('/'));
+            parent = match parent.get_item(component) {
+                Ok(item) => item.get_directory()?,
+                Err(_) => {
+                    let directory = Directory::new(component);
+                    parent.add_directory(directory.clone())?;
+                    created.push((directory.id(), path.clone()));
+                    directory
+                }
+            };
+        }
+
+        let directory = Directory::new(name);
+        parent.add_directory(directory.clone())?;
+        created.push((
+            directory.id(),
+            format!("{}/{name}", path.trim_end_matches('/')),
+        ));
 
         let _ = self.export().await;
 
+        for (id, path) in created {
+            self.constellation_tx
+                .emit(ConstellationEventKind::DirectoryCreated { id, path })
+                .await;
+        }
+
+        Ok(())
+    }
+
+    async fn move_item(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let root = self.current_directory()?;
+
+        let mut components = from
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        let name = components.pop().ok_or(Error::InvalidPath)?;
+        let from = components
+            .iter()
+            .chain(std::iter::once(&name))
+            .copied()
+            .collect::<Vec<_>>()
+            .join("/");
+        let to = to.trim_matches('/');
+
+        // Prevent moving a directory into itself or one of its children
+        if to == from || to.starts_with(&format!("{from}/")) {
+            return Err(Error::DirParadox);
+        }
+
+        let source = match components.is_empty() {
+            true => root.clone(),
+            false => root
+                .get_item_by_path(&components.join("/"))?
+                .get_directory()?,
+        };
+
+        let destination = match to.is_empty() {
+            true => root,
+            false => root.get_item_by_path(to)?.get_directory()?,
+        };
+
+        if destination.has_item(name) {
+            return Err(Error::DuplicateName);
+        }
+
+        let size = source.get_item(name)?.stored_size();
+        check_quota(&self.root_directory(), &destination, size, Some(&source))?;
+
+        let item = source.remove_item(name)?;
+
+        if let Err(e) = destination.add_item(item.clone()) {
+            source.add_item(item)?;
+            return Err(e);
+        }
+
+        self.export().await?;
+
+        let to = match to.is_empty() {
+            true => self.full_path(name),
+            false => self.full_path(&format!("{to}/{name}")),
+        };
+
+        self.constellation_tx
+            .emit(ConstellationEventKind::Moved {
+                id: item.id(),
+                item_name: item.name(),
+                from: self.full_path(&from),
+                to,
+            })
+            .await;
+
         Ok(())
     }
 
@@ -1179,26 +2047,45 @@ impl FileTask {
             .and_then(|item| item.get_file())?;
 
         let reference = file.reference().ok_or(Error::FileNotFound)?;
+        let key = file_key(self.root.keypair(), &file)?;
+
+        // Avoid fetching the file when a thumbnail could not be generated for it
+        if !thumbnail_store.supports(&file.name()) {
+            return Ok(futures::future::ok(()).boxed());
+        }
 
         let mut export_tx = self.export_tx.clone();
 
         Ok(async move {
-            let buffer = ipfs
-                .cat_unixfs(reference.parse::<IpfsPath>()?)
-                .await
-                .map_err(anyhow::Error::from)?;
+            let buffer = ByteCollection::new(cat_file(
+                &ipfs,
+                reference.parse::<IpfsPath>()?,
+                key.as_deref().map(Vec::as_slice),
+                None,
+            ))
+            .await?;
 
             let ((width, height), exact) = (thumbnail_size, thumbnail_format);
 
             // Generate the thumbnail for the file
             let id = thumbnail_store
-                .insert_buffer(file.name(), &buffer, width, height, exact)
+                .insert_buffer(
+                    file.name(),
+                    &buffer,
+                    width,
+                    height,
+                    exact,
+                    key.as_deref().map(Vec::as_slice),
+                )
                 .await;
 
-            if let Ok((extension_type, path, thumbnail)) = thumbnail_store.get(id).await {
-                file.set_thumbnail(thumbnail);
-                file.set_thumbnail_format(extension_type.into());
-                file.set_thumbnail_reference(&path.to_string());
+            if let Ok(thumbnail) = thumbnail_store.get(id).await {
+                file.set_thumbnail(thumbnail.data);
+                file.set_thumbnail_format(thumbnail.format.into());
+                file.set_thumbnail_reference(&thumbnail.path.to_string());
+                remove_variants(&ipfs, &file.variants()).await;
+                let key = key.as_deref().map(Vec::as_slice);
+                file.set_variants(store_variants(&ipfs, key, thumbnail.variants).await);
             }
 
             let _ = export_tx.send(()).await;
@@ -1207,6 +2094,634 @@ impl FileTask {
         }
         .boxed())
     }
+
+    fn search(&self, options: SearchOptions) -> Result<ConstellationSearchStream, Error> {
+        let matcher = options.matcher()?;
+
+        let directory = match options.path() {
+            Some(path) => self.open_directory(path)?,
+            None => self.root_directory(),
+        };
+
+        let items = directory.search(&matcher);
+        let limit = options.limit().unwrap_or(usize::MAX);
+
+        let Some(keyword) = options.content().map(str::to_lowercase) else {
+            return Ok(futures::stream::iter(items).take(limit).boxed());
+        };
+
+        let ipfs = self.ipfs.clone();
+        let keypair = self.root.keypair().clone();
+        let content_index = self.content_index.clone();
+
+        let stream = async_stream::stream! {
+            let mut found = 0;
+            for item in items {
+                if found >= limit {
+                    break;
+                }
+
+                let Item::File(file) = &item else {
+                    continue;
+                };
+
+                if !search::is_text(&file.file_type()) {
+                    continue;
+                }
+
+                let Some(text) = content_index.get(&ipfs, &keypair, file).await else {
+                    continue;
+                };
+
+                if text.contains(&keyword) {
+                    found += 1;
+                    yield item;
+                }
+            }
+        };
+
+        Ok(stream.boxed())
+    }
+
+    async fn set_item_metadata(
+        &mut self,
+        path: &str,
+        key: String,
+        value: Option<String>,
+    ) -> Result<(), Error> {
+        let item = self.current_directory()?.get_item_by_path(path)?;
+
+        if key.is_empty() || key.len() > MAX_METADATA_KEY_LENGTH {
+            return Err(Error::InvalidLength {
+                context: "key".into(),
+                current: key.len(),
+                minimum: Some(1),
+                maximum: Some(MAX_METADATA_KEY_LENGTH),
+            });
+        }
+
+        match value.as_deref() {
+            Some(value) => {
+                if value.len() > MAX_METADATA_VALUE_LENGTH {
+                    return Err(Error::InvalidLength {
+                        context: "value".into(),
+                        current: value.len(),
+                        minimum: None,
+                        maximum: Some(MAX_METADATA_VALUE_LENGTH),
+                    });
+                }
+
+                let metadata = item.metadata();
+                if !metadata.contains_key(&key) && metadata.len() >= MAX_METADATA_ENTRIES {
+                    return Err(Error::InvalidLength {
+                        context: "metadata".into(),
+                        current: metadata.len() + 1,
+                        minimum: None,
+                        maximum: Some(MAX_METADATA_ENTRIES),
+                    });
+                }
+
+                item.insert_metadata(&key, value);
+            }
+            None => {
+                if item.remove_metadata(&key).is_none() {
+                    return Err(Error::ObjectNotFound);
+                }
+            }
+        }
+
+        self.export().await?;
+
+        self.constellation_tx
+            .emit(ConstellationEventKind::MetadataChanged {
+                id: item.id(),
+                path: self.full_path(path),
+                item_name: item.name(),
+                key,
+                value,
+            })
+            .await;
+
+        Ok(())
+    }
+
+    async fn add_tag(&mut self, path: &str, tag: String) -> Result<(), Error> {
+        let item = self.current_directory()?.get_item_by_path(path)?;
+
+        let tag = tag.trim();
+
+        if tag.is_empty() || tag.len() > MAX_TAG_LENGTH {
+            return Err(Error::InvalidLength {
+                context: "tag".into(),
+                current: tag.len(),
+                minimum: Some(1),
+                maximum: Some(MAX_TAG_LENGTH),
+            });
+        }
+
+        if item.has_tag(tag) {
+            return Ok(());
+        }
+
+        let tags = item.tags();
+
+        if tags.len() >= MAX_ITEM_TAGS {
+            return Err(Error::InvalidLength {
+                context: "tags".into(),
+                current: tags.len() + 1,
+                minimum: None,
+                maximum: Some(MAX_ITEM_TAGS),
+            });
+        }
+
+        item.add_tag(tag);
+
+        self.export().await?;
+
+        self.constellation_tx
+            .emit(ConstellationEventKind::TagsChanged {
+                id: item.id(),
+                path: self.full_path(path),
+                item_name: item.name(),
+                tags: item.tags(),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    async fn remove_tag(&mut self, path: &str, tag: &str) -> Result<(), Error> {
+        let item = self.current_directory()?.get_item_by_path(path)?;
+
+        if !item.remove_tag(tag.trim()) {
+            return Err(Error::ObjectNotFound);
+        }
+
+        self.export().await?;
+
+        self.constellation_tx
+            .emit(ConstellationEventKind::TagsChanged {
+                id: item.id(),
+                path: self.full_path(path),
+                item_name: item.name(),
+                tags: item.tags(),
+            })
+            .await;
+
+        Ok(())
+    }
+
+    async fn set_quota(&mut self, path: &str, quota: Option<usize>) -> Result<(), Error> {
+        let directory = match path.trim() {
+            "" | "/" => self.root_directory(),
+            path => self
+                .current_directory()?
+                .get_item_by_path(path)?
+                .get_directory()?,
+        };
+
+        directory.set_quota(quota);
+
+        self.export().await
+    }
+}
+
+/// Lazily built index of the lowercased contents of text files, keyed by the file reference.
+/// Since a reference points to immutable content, entries never need to be invalidated, but the
+/// least recently used entries are evicted once the cache exceeds [`MAX_CONTENT_INDEX_CACHE_SIZE`]
+#[derive(Clone, Default)]
+struct ContentIndex {
+    inner: Arc<Mutex<ContentIndexInner>>,
+}
+
+#[derive(Default)]
+struct ContentIndexInner {
+    entries: IndexMap<String, Arc<str>>,
+    size: usize,
+}
+
+impl ContentIndexInner {
+    fn get(&mut self, reference: &str) -> Option<Arc<str>> {
+        let index = self.entries.get_index_of(reference)?;
+        let last = self.entries.len() - 1;
+        self.entries.move_index(index, last);
+        self.entries.get_index(last).map(|(_, text)| text.clone())
+    }
+
+    fn insert(&mut self, reference: String, text: Arc<str>) {
+        if text.len() > MAX_CONTENT_INDEX_CACHE_SIZE {
+            return;
+        }
+
+        self.size += text.len();
+        if let Some(previous) = self.entries.insert(reference, text) {
+            self.size -= previous.len();
+        }
+
+        while self.size > MAX_CONTENT_INDEX_CACHE_SIZE {
+            let Some((_, evicted)) = self.entries.shift_remove_index(0) else {
+                break;
+            };
+            self.size -= evicted.len();
+        }
+    }
+}
+
+impl ContentIndex {
+    async fn get(&self, ipfs: &Ipfs, keypair: &Keypair, file: &File) -> Option<Arc<str>> {
+        let reference = file.reference()?;
+
+        if let Some(text) = self.inner.lock().get(&reference) {
+            return Some(text);
+        }
+
+        let path = reference.parse::<IpfsPath>().ok()?;
+        let key = file_key(keypair, file).ok()?;
+
+        let bytes = ByteCollection::new(cat_file(
+            ipfs,
+            path,
+            key.as_deref().map(Vec::as_slice),
+            Some(MAX_CONTENT_INDEX_SIZE),
+        ))
+        .await
+        .ok()?;
+
+        let text: Arc<str> = String::from_utf8_lossy(&bytes).to_lowercase().into();
+
+        self.inner.lock().insert(reference, text.clone());
+
+        Some(text)
+    }
+}
+
+/// Map the full path of every item within the directory to its id and modified time
+fn index_entries(directory: &Directory) -> HashMap<String, (Uuid, DateTime<Utc>)> {
+    let mut entries = HashMap::new();
+    let mut pending = vec![(String::new(), directory.clone())];
+
+    while let Some((prefix, directory)) = pending.pop() {
+        for item in directory.get_items() {
+            let path = format!("{prefix}/{}", item.name());
+            entries.insert(path.clone(), (item.id(), item.modified()));
+            if let Item::Directory(directory) = item {
+                pending.push((path, directory));
+            }
+        }
+    }
+
+    entries
+}
+
+/// Root cid of the contents of every file within the directory
+fn index_references(directory: &Directory) -> HashSet<Cid> {
+    let mut references = HashSet::new();
+    let mut pending = vec![directory.clone()];
+
+    while let Some(directory) = pending.pop() {
+        for item in directory.get_items() {
+            match item {
+                Item::Directory(directory) => pending.push(directory),
+                Item::File(file) => references.extend(
+                    file.reference()
+                        .and_then(|reference| reference.parse::<IpfsPath>().ok())
+                        .and_then(|path| path.root().cid().copied()),
+                ),
+            }
+        }
+    }
+
+    references
+}
+
+/// Ensure that adding `size` bytes to the directory keeps it, along with every directory above it, within their quota.
+/// Directories that also contain `source` are skipped since the data is already accounted for
+fn check_quota(
+    root: &Directory,
+    directory: &Directory,
+    size: usize,
+    source: Option<&Directory>,
+) -> Result<(), Error> {
+    let source_chain = source
+        .map(|source| directory_chain(root, source))
+        .unwrap_or_default();
+
+    for (path, directory) in directory_chain(root, directory) {
+        let Some(quota) = directory.quota() else {
+            continue;
+        };
+
+        if source_chain.iter().any(|(_, source)| source == &directory) {
+            continue;
+        }
+
+        let current = directory.stored_size() + size;
+        if current > quota {
+            return Err(Error::InvalidLength {
+                context: format!("quota of {path}"),
+                current,
+                minimum: None,
+                maximum: Some(quota),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Directories leading from the root to the target, inclusive, along with their full path
+fn directory_chain(root: &Directory, target: &Directory) -> Vec<(String, Directory)> {
+    fn walk(
+        directory: &Directory,
+        path: String,
+        target: &Directory,
+        chain: &mut Vec<(String, Directory)>,
+    ) -> bool {
+        chain.push((path.clone(), directory.clone()));
+
+        if directory == target {
+            return true;
+        }
+
+        for item in directory.get_items() {
+            let Item::Directory(child) = item else {
+                continue;
+            };
+
+            let child_path = format!("{}/{}", path.trim_end_matches('/'), child.name());
+            if walk(&child, child_path, target, chain) {
+                return true;
+            }
+        }
+
+        chain.pop();
+        false
+    }
+
+    let mut chain = vec![];
+    walk(root, String::from("/"), target, &mut chain);
+    chain
+}
+
+/// Collects the image from the stream to remove its metadata, recording the kinds of metadata that were removed.
+///
+/// Note: The image is held in memory since the metadata may be located anywhere within the file
+fn strip_stream(
+    stream: BoxStream<'static, std::io::Result<Bytes>>,
+    max_size: usize,
+    removed: Arc<parking_lot::Mutex<Vec<String>>>,
+) -> BoxStream<'static, std::io::Result<Bytes>> {
+    async_stream::stream! {
+        let data = match ByteCollection::new_with_max_capacity(stream, max_size).await {
+            Ok(data) => data,
+            Err(e) => {
+                yield Err(e);
+                return;
+            }
+        };
+
+        match sanitize::strip_metadata(&data) {
+            Ok(Some((data, kinds))) => {
+                *removed.lock() = kinds;
+                yield Ok(Bytes::from(data));
+            }
+            Ok(None) if !data.is_empty() => yield Ok(data),
+            Ok(None) => {}
+            Err(e) => yield Err(std::io::Error::other(e)),
+        }
+    }
+    .boxed()
+}
+
+/// Encrypt the contents of files that were stored before encryption at rest was introduced, handing
+/// each to the task to be swapped in. The plaintext copies are unpinned afterwards, unless an attachment
+/// that was sent before the file was encrypted still references it
+async fn migrate_plaintext_files(
+    ipfs: Ipfs,
+    root: RootDocumentMap,
+    index: Directory,
+    files: Vec<(String, File)>,
+    mut migrated_tx: mpsc::Sender<MigratedFile>,
+) {
+    let keypair = root.keypair().clone();
+    let mut replaced = vec![];
+
+    for (path, file) in files {
+        let Some(previous) = file.reference() else {
+            continue;
+        };
+
+        let result = async {
+            let (key, wrapped_key) = generate_file_key(&keypair)?;
+            let source = previous.parse::<IpfsPath>()?;
+            let stream = encrypt_stream(&key, cat_file(&ipfs, source, None, None));
+            let encrypted = ipfs.add_unixfs(stream).await?;
+            Ok::<_, Error>((encrypted, wrapped_key))
+        };
+
+        let (encrypted, wrapped_key) = match result.await {
+            Ok(result) => result,
+            Err(e) => {
+                tracing::warn!(error = %e, %path, "Unable to encrypt file");
+                continue;
+            }
+        };
+
+        let (applied_tx, applied_rx) = oneshot::channel();
+
+        let migrated = MigratedFile {
+            path,
+            file,
+            previous: previous.clone(),
+            reference: encrypted.to_string(),
+            wrapped_key,
+            applied: applied_tx,
+        };
+
+        if migrated_tx.send(migrated).await.is_err() {
+            return;
+        }
+
+        match applied_rx.await {
+            Ok(true) => replaced.push(previous),
+            _ => {
+                if let Some(cid) = encrypted.root().cid().copied() {
+                    let _ = ipfs.remove_pin(cid).recursive().await;
+                }
+            }
+        }
+    }
+
+    if replaced.is_empty() {
+        return;
+    }
+
+    tracing::info!(migrated = replaced.len(), "Encrypted existing files");
+
+    // Contents could still be used by files that were not migrated, such as copies or files shared in conversations
+    let mut referenced = root.attachment_references().await;
+    referenced.extend(index_references(&index));
+
+    for reference in replaced {
+        let Some(cid) = reference
+            .parse::<IpfsPath>()
+            .ok()
+            .and_then(|path| path.root().cid().copied())
+        else {
+            continue;
+        };
+
+        if referenced.contains(&cid) {
+            continue;
+        }
+
+        if ipfs.is_pinned(cid).await.unwrap_or_default() {
+            if let Err(e) = ipfs.remove_pin(cid).recursive().await {
+                tracing::warn!(error = %e, %cid, "Unable to unpin plaintext copy");
+                continue;
+            }
+        }
+
+        // Unpinned blocks would otherwise still be served to peers until they are collected
+        if let Err(e) = ipfs.remove_block(cid).recursive().await {
+            tracing::warn!(error = %e, %cid, "Unable to remove plaintext copy");
+        }
+    }
+}
+
+/// Whether the path is within the chat media directory, whose files are shared with the members
+/// of a conversation and are therefore not encrypted at rest
+fn is_shared(path: &str) -> bool {
+    path.trim_start_matches('/')
+        .split('/')
+        .next()
+        .is_some_and(|directory| directory == CHAT_DIRECTORY)
+}
+
+/// Generates a key for the contents of a new file, along with the wrapped copy to be stored with the file
+fn generate_file_key(keypair: &Keypair) -> Result<(Zeroizing<Vec<u8>>, Vec<u8>), Error> {
+    let key = Zeroizing::new(warp::crypto::generate::<32>().to_vec());
+    let wrapped_key = ecdh_encrypt(keypair, None, key.as_slice())?;
+    Ok((key, wrapped_key))
+}
+
+/// Unwraps the key used to encrypt the contents of the file, if it was encrypted
+fn file_key(keypair: &Keypair, file: &File) -> Result<Option<Zeroizing<Vec<u8>>>, Error> {
+    file.encryption_key()
+        .map(|key| ecdh_decrypt(keypair, None, key).map(Zeroizing::new))
+        .transpose()
+}
+
+fn encrypt_stream(
+    key: &[u8],
+    stream: BoxStream<'static, std::io::Result<Bytes>>,
+) -> BoxStream<'static, std::io::Result<Bytes>> {
+    let stream = stream.map_ok(|bytes| bytes.to_vec());
+    Cipher::from_bytes(key)
+        .encrypt_async_stream(stream)
+        .map_ok(Bytes::from)
+        .boxed()
+}
+
+fn decrypt_stream(
+    key: &[u8],
+    stream: BoxStream<'static, std::io::Result<Bytes>>,
+) -> BoxStream<'static, std::io::Result<Bytes>> {
+    let stream = stream.map_ok(|bytes| bytes.to_vec());
+    Cipher::from_bytes(key)
+        .decrypt_async_stream(stream)
+        .map_ok(Bytes::from)
+        .boxed()
+}
+
+/// Streams the contents behind the path, decrypting them if a key is provided
+fn cat_file(
+    ipfs: &Ipfs,
+    path: IpfsPath,
+    key: Option<&[u8]>,
+    max_length: Option<usize>,
+) -> BoxStream<'static, std::io::Result<Bytes>> {
+    let mut stream = ipfs.cat_unixfs(path);
+    if let Some(max_length) = max_length {
+        stream = stream.max_length(max_length);
+    }
+
+    let stream = stream.map_err(std::io::Error::other).boxed();
+
+    match key {
+        Some(key) => decrypt_stream(key, stream),
+        None => stream,
+    }
+}
+
+/// Stores the variants of an image, encrypting them with the key of the file if it has one
+async fn store_variants(
+    ipfs: &Ipfs,
+    key: Option<&[u8]>,
+    variants: Vec<Variant>,
+) -> Vec<ImageVariant> {
+    let mut stored = Vec::with_capacity(variants.len());
+
+    for variant in variants {
+        let size = variant.data.len();
+        let source = futures::stream::once(async move { Ok(variant.data) }).boxed();
+        let stream = match key {
+            Some(key) => encrypt_stream(key, source),
+            None => source,
+        };
+
+        match ipfs.add_unixfs(stream).await {
+            Ok(path) => stored.push(ImageVariant::new(
+                variant.width,
+                variant.height,
+                size,
+                variant.format.into(),
+                &path.to_string(),
+            )),
+            Err(e) => tracing::warn!(error = %e, "Unable to store image variant"),
+        }
+    }
+
+    stored
+}
+
+async fn remove_variants(ipfs: &Ipfs, variants: &[ImageVariant]) {
+    for variant in variants {
+        let Some(cid) = variant
+            .reference()
+            .parse::<IpfsPath>()
+            .ok()
+            .and_then(|path| path.root().cid().copied())
+        else {
+            continue;
+        };
+
+        if ipfs.is_pinned(cid).await.unwrap_or_default() {
+            if let Err(e) = ipfs.remove_pin(cid).recursive().await {
+                tracing::warn!(error = %e, %cid, "Unable to unpin image variant");
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+async fn write_file(
+    path: &std::path::Path,
+    mut stream: BoxStream<'static, std::io::Result<Bytes>>,
+) -> Result<usize, Error> {
+    use futures::AsyncWriteExt;
+    use tokio_util::compat::TokioAsyncWriteCompatExt;
+
+    let mut writer = tokio::fs::File::create(path).await?.compat_write();
+    let mut written = 0;
+
+    while let Some(bytes) = stream.try_next().await? {
+        writer.write_all(&bytes).await?;
+        written += bytes.len();
+    }
+
+    writer.flush().await?;
+    Ok(written)
 }
 
 fn split_file_from_path(name: impl Into<String>) -> Result<(String, Option<String>), Error> {
@@ -1255,6 +2770,8 @@ async fn _remove(ipfs: &Ipfs, root: &Directory, item: &Item) -> Result<(), Error
                 ipfs.remove_pin(cid).recursive().await?;
             }
 
+            remove_variants(ipfs, &file.variants()).await;
+
             let name = item.name();
             if let Err(e) = root.remove_item(&name) {
                 tracing::error!(error = %e, item_name = %name, "unable to remove file");
@@ -1285,3 +2802,139 @@ async fn _remove(ipfs: &Ipfs, root: &Directory, item: &Item) -> Result<(), Error
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use futures::{channel::mpsc, StreamExt, TryStreamExt};
+    use rust_ipfs::{IpfsPath, Keypair, UninitializedIpfsDefault};
+    use warp::constellation::{directory::Directory, file::File};
+
+    use super::{
+        cat_file, migrate_plaintext_files, ContentIndexInner, MAX_CONTENT_INDEX_CACHE_SIZE,
+    };
+    use crate::store::{document::root::RootDocumentMap, ecdh_decrypt};
+
+    #[test]
+    fn content_index_evicts_least_recently_used() {
+        let mut index = ContentIndexInner::default();
+        let text: Arc<str> = "a".repeat(MAX_CONTENT_INDEX_CACHE_SIZE / 2).into();
+
+        index.insert("first".into(), text.clone());
+        index.insert("second".into(), text.clone());
+        assert!(index.get("first").is_some());
+
+        index.insert("third".into(), text.clone());
+        assert!(index.get("second").is_none());
+        assert!(index.get("first").is_some());
+        assert!(index.get("third").is_some());
+        assert!(index.size <= MAX_CONTENT_INDEX_CACHE_SIZE);
+
+        let oversized: Arc<str> = "a".repeat(MAX_CONTENT_INDEX_CACHE_SIZE + 1).into();
+        index.insert("oversized".into(), oversized);
+        assert!(index.get("oversized").is_none());
+        assert_eq!(index.entries.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn migration_removes_plaintext_copy() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let keypair = Keypair::generate_ed25519();
+        let root = RootDocumentMap::new(&ipfs, Some(keypair.clone())).await;
+
+        let data = b"plaintext contents".to_vec();
+        let plaintext = ipfs.add_unixfs(Bytes::from(data.clone())).await?;
+        let cid = plaintext.root().cid().copied().expect("valid path");
+
+        let file = File::new("file.txt");
+        file.set_reference(&plaintext.to_string());
+
+        let index = Directory::new("");
+        index.add_file(file.clone())?;
+
+        let (migrated_tx, mut migrated_rx) = mpsc::channel(0);
+        let migration = tokio::spawn(migrate_plaintext_files(
+            ipfs.clone(),
+            root,
+            index,
+            vec![("/file.txt".into(), file)],
+            migrated_tx,
+        ));
+
+        let migrated = migrated_rx.next().await.expect("file was migrated");
+        assert_eq!(migrated.previous, plaintext.to_string());
+
+        let reference = migrated.reference.parse::<IpfsPath>()?;
+        let key = ecdh_decrypt(&keypair, None, &migrated.wrapped_key)?;
+        migrated.file.set_reference(&migrated.reference);
+        let _ = migrated.applied.send(true);
+        migration.await?;
+
+        assert!(!ipfs.repo().contains(&cid).await?);
+
+        let contents = cat_file(&ipfs, reference, Some(&key), None)
+            .try_collect::<Vec<_>>()
+            .await?
+            .concat();
+        assert_eq!(contents, data);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn migration_keeps_contents_shared_with_other_files() -> anyhow::Result<()> {
+        let ipfs = UninitializedIpfsDefault::new()
+            .start()
+            .await
+            .expect("constructed ipfs instance");
+
+        let keypair = Keypair::generate_ed25519();
+        let root = RootDocumentMap::new(&ipfs, Some(keypair.clone())).await;
+
+        let data = b"plaintext contents".to_vec();
+        let plaintext = ipfs.add_unixfs(Bytes::from(data.clone())).await?;
+        let cid = plaintext.root().cid().copied().expect("valid path");
+
+        // A copy of the file refers to the same contents
+        let index = Directory::new("");
+        let mut files = vec![];
+        for name in ["file.txt", "copy.txt"] {
+            let file = File::new(name);
+            file.set_reference(&plaintext.to_string());
+            index.add_file(file.clone())?;
+            files.push((format!("/{name}"), file));
+        }
+
+        let (migrated_tx, mut migrated_rx) = mpsc::channel(0);
+        let migration = tokio::spawn(migrate_plaintext_files(
+            ipfs.clone(),
+            root,
+            index,
+            files,
+            migrated_tx,
+        ));
+
+        let migrated = migrated_rx.next().await.expect("file was migrated");
+        migrated.file.set_reference(&migrated.reference);
+        let _ = migrated.applied.send(true);
+
+        // The copy was changed while it was being encrypted, so it still refers to the plaintext contents
+        let migrated = migrated_rx.next().await.expect("file was migrated");
+        let _ = migrated.applied.send(false);
+        migration.await?;
+
+        assert!(ipfs.repo().contains(&cid).await?);
+
+        let contents = cat_file(&ipfs, plaintext, None, None)
+            .try_collect::<Vec<_>>()
+            .await?
+            .concat();
+        assert_eq!(contents, data);
+        Ok(())
+    }
+}
@@ -24,7 +24,10 @@ use web_time::Instant;
 
 use crate::shuttle::identity::client::IdentityCommand;
 use crate::shuttle::identity::{RequestEvent, RequestPayload};
-use warp::multipass::identity::{FriendRequest, Identifier, ShortId};
+use warp::multipass::identity::{
+    Contact, ContactUpdate, Device, FriendRequest, Identifier, RecoveryRequest,
+    RevocationCertificate, ShortId,
+};
 use warp::multipass::GetIdentity;
 use warp::{
     constellation::file::FileType,
@@ -34,7 +37,7 @@ use warp::{
     crypto::{DIDKey, Ed25519KeyPair, Fingerprint, DID},
     error::Error,
     multipass::{
-        identity::{Identity, IdentityStatus, SHORT_ID_SIZE},
+        identity::{Identity, IdentityField, IdentityStatus, Presence, SHORT_ID_SIZE},
         MultiPassEventKind,
     },
 };
@@ -43,21 +46,31 @@ use super::payload::PayloadBuilder;
 use super::{
     connected_to_peer,
     document::{
-        cache::IdentityCache, identity::IdentityDocument, image_dag::get_image,
-        root::RootDocumentMap, ResolvedRootDocument, RootDocument,
+        bundle::ExportBundle,
+        cache::IdentityCache,
+        identity::{
+            create_revocation_certificate, verify_revocation_certificate, DeviceAuthorization,
+            IdentityDocument, KeySuccession, PresenceDocument, UsernameClaim,
+        },
+        image_dag::get_image,
+        recovery::RecoveryShareDocument,
+        root::RootDocumentMap,
+        ResolvedRootDocument, RootDocument,
     },
     ecdh_decrypt, ecdh_encrypt,
     event_subscription::EventSubscription,
     payload::PayloadMessage,
     phonebook::PhoneBook,
     queue::Queue,
-    topics::IDENTITY_ANNOUNCEMENT,
+    topics::{IDENTITY_ANNOUNCEMENT, IDENTITY_REVOCATION},
+    MAX_CONTACT_GROUPS, MAX_CONTACT_GROUP_NAME_LENGTH, MAX_CONTACT_NOTES_LENGTH, MAX_DEVICES,
     MAX_IMAGE_SIZE, MAX_METADATA_ENTRIES, MAX_METADATA_KEY_LENGTH, MAX_METADATA_VALUE_LENGTH,
-    SHUTTLE_TIMEOUT,
+    MAX_REQUEST_MESSAGE_LENGTH, MAX_USERNAME_LENGTH, PRESENCE_CHECK_INTERVAL,
+    RECOVERY_REQUEST_EXPIRY, REQUEST_EXPIRY_CHECK_INTERVAL, SHUTTLE_TIMEOUT,
 };
-use crate::rt::{Executor, LocalExecutor};
+use crate::rt::{AbortableJoinHandle, Executor, LocalExecutor};
 use crate::{
-    config::{self, Discovery as DiscoveryConfig},
+    config::{self, Discovery as DiscoveryConfig, Visibility},
     store::{discovery::Discovery, topics::PeerTopic, DidExt, PeerIdExt},
 };
 
@@ -92,24 +105,87 @@ pub struct IdentityStore {
 
     event: EventSubscription<MultiPassEventKind>,
 
+    // Requests for the recovery shares held by this identity, keyed by the identity being recovered
+    /// Pending recovery requests, by the identity being recovered and the device requesting it
+    recovery_requests: Arc<RwLock<HashMap<(DID, DID), RecoveryRequest>>>,
+
+    // Shares collected while recovering an identity from this device
+    recovery: Arc<RwLock<Option<RecoverySession>>>,
+
+    // Activity through the api and custom presence of this identity
+    presence: Arc<parking_lot::Mutex<PresenceState>>,
+
+    // Background tasks that are aborted once the store, and every copy of it, is dropped
+    tasks: Vec<AbortableJoinHandle<()>>,
+
     executor: LocalExecutor,
 }
 
+#[derive(Debug)]
+struct PresenceState {
+    // Last time the node was used through the api
+    last_activity: Instant,
+    idle: bool,
+    // Status to restore once the node is used again after being idle
+    restore: Option<IdentityStatus>,
+    // Time the custom presence expires
+    until: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+struct RecoverySession {
+    did: DID,
+    friends: Vec<DID>,
+    shares: HashMap<DID, RecoveryShareDocument>,
+    identity: Option<IdentityDocument>,
+}
+
+/// Key and last known document of an identity, recovered from the shares of its friends
+pub struct RecoveredIdentity {
+    pub keypair: Keypair,
+    pub identity: Option<IdentityDocument>,
+    pub friends: Vec<DID>,
+}
+
 #[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 #[serde(tag = "direction", rename_all = "lowercase")]
 pub enum Request {
-    In { did: DID, date: DateTime<Utc> },
-    Out { did: DID, date: DateTime<Utc> },
+    In {
+        did: DID,
+        date: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires: Option<DateTime<Utc>>,
+    },
+    Out {
+        did: DID,
+        date: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires: Option<DateTime<Utc>>,
+    },
 }
 
 impl Request {
     pub fn request_in(did: DID) -> Self {
         let date = Utc::now();
-        Request::In { did, date }
+        Request::In {
+            did,
+            date,
+            message: None,
+            expires: None,
+        }
     }
     pub fn request_out(did: DID) -> Self {
         let date = Utc::now();
-        Request::Out { did, date }
+        Request::Out {
+            did,
+            date,
+            message: None,
+            expires: None,
+        }
     }
 }
 
@@ -156,9 +232,36 @@ impl Request {
             Request::Out { date, .. } => *date,
         }
     }
+
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Request::In { message, .. } => message.as_deref(),
+            Request::Out { message, .. } => message.as_deref(),
+        }
+    }
+
+    pub fn expires(&self) -> Option<DateTime<Utc>> {
+        match self {
+            Request::In { expires, .. } => *expires,
+            Request::Out { expires, .. } => *expires,
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires().is_some_and(|expires| expires <= Utc::now())
+    }
+}
+
+impl From<&Request> for FriendRequest {
+    fn from(request: &Request) -> Self {
+        let mut friend_request = FriendRequest::new(request.did().clone(), Some(request.date()));
+        friend_request.set_message(request.message().map(str::to_string));
+        friend_request.set_expires(request.expires());
+        friend_request
+    }
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Hash, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "lowercase", tag = "type")]
 pub enum Event {
     /// Event indicating a friend request
@@ -177,9 +280,19 @@ pub enum Event {
     Unblock,
     /// Indiciation of a response to a request
     Response,
+    /// Share of the recovery secret to hold on behalf of the sender
+    RecoveryShare { share: RecoveryShareDocument },
+    /// Request for the recovery share held on behalf of an identity
+    RecoveryRequest { did: DID },
+    /// Recovery share sent in response to an approved request
+    RecoveryResponse {
+        share: RecoveryShareDocument,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        identity: Option<IdentityDocument>,
+    },
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Hash, Eq)]
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
 pub struct RequestResponsePayload {
     #[serde(default)]
     pub version: RequestResponsePayloadVersion,
@@ -187,6 +300,12 @@ pub struct RequestResponsePayload {
     pub event: Event,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub created: Option<DateTime<Utc>>,
+    /// Intro message sent along with a request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Time after which a request is closed if it has not been answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires: Option<DateTime<Utc>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub signature: Option<Vec<u8>>,
 }
@@ -203,13 +322,20 @@ impl TryFrom<RequestResponsePayload> for RequestPayload {
             Event::Retract => RequestEvent::Retract,
             Event::Block => RequestEvent::Block,
             Event::Unblock => RequestEvent::Unblock,
-            Event::Response => return Err(Error::OtherWithContext("Invalid event type".into())),
+            Event::Response
+            | Event::RecoveryShare { .. }
+            | Event::RecoveryRequest { .. }
+            | Event::RecoveryResponse { .. } => {
+                return Err(Error::OtherWithContext("Invalid event type".into()))
+            }
         };
 
         let payload = RequestPayload {
             sender: req.sender,
             event,
             created: req.created.ok_or(Error::InvalidConversion)?,
+            message: req.message,
+            expires: req.expires,
             original_signature: req.signature.ok_or(Error::InvalidSignature)?,
             signature: vec![],
         };
@@ -237,6 +363,8 @@ impl TryFrom<RequestPayload> for RequestResponsePayload {
             sender: req.sender,
             event,
             created: Some(req.created),
+            message: req.message,
+            expires: req.expires,
             signature: Some(req.original_signature),
         };
 
@@ -270,10 +398,24 @@ impl RequestResponsePayload {
             sender,
             event,
             created: None,
+            message: None,
+            expires: None,
             signature: None,
         }
     }
 
+    /// Create a signed friend request along with an intro message and the time it expires
+    pub fn new_request(
+        keypair: &Keypair,
+        message: Option<String>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<Self, Error> {
+        let mut request = Self::new_unsigned(keypair, Event::Request);
+        request.message = message;
+        request.expires = expires;
+        request.sign(keypair)
+    }
+
     pub fn sign(mut self, keypair: &Keypair) -> Result<Self, Error> {
         self.signature = None;
         self.created = Some(Utc::now());
@@ -371,6 +513,7 @@ impl IdentityStore {
     #[allow(clippy::too_many_arguments)]
     pub async fn new(
         ipfs: &Ipfs,
+        keypair: &Keypair,
         config: &config::Config,
         tx: EventSubscription<MultiPassEventKind>,
         phonebook: &PhoneBook,
@@ -389,7 +532,7 @@ impl IdentityStore {
 
         let event = tx.clone();
 
-        let root_document = RootDocumentMap::new(ipfs, None).await;
+        let root_document = RootDocumentMap::new(ipfs, Some(keypair.clone())).await;
 
         let did_key = root_document
             .keypair()
@@ -400,7 +543,7 @@ impl IdentityStore {
 
         let signal = Default::default();
 
-        let store = Self {
+        let mut store = Self {
             ipfs: ipfs.clone(),
             root_document,
             identity_cache,
@@ -413,6 +556,15 @@ impl IdentityStore {
             phonebook: phonebook.clone(),
             signal,
             span: span.clone(),
+            recovery_requests: Default::default(),
+            recovery: Default::default(),
+            presence: Arc::new(parking_lot::Mutex::new(PresenceState {
+                last_activity: Instant::now(),
+                idle: false,
+                restore: None,
+                until: None,
+            })),
+            tasks: vec![],
             executor: LocalExecutor,
         };
 
@@ -423,6 +575,9 @@ impl IdentityStore {
             async move {
                 if let Ok(ident) = store.own_identity().await {
                     tracing::info!(did = %ident.did_key(), "Identity loaded");
+                    if let Err(e) = store.authorize_local_device().await {
+                        tracing::warn!(error = %e, "Unable to authorize device");
+                    }
                     match store.is_registered().await.is_ok() {
                         true => {
                             if let Err(e) = store.fetch_mailbox().await {
@@ -486,8 +641,14 @@ impl IdentityStore {
                     .pubsub_subscribe(store.did_key.inbox())
                     .await
                     .expect("not subscribed");
+                let revocation_stream = store
+                    .ipfs
+                    .pubsub_subscribe(IDENTITY_REVOCATION)
+                    .await
+                    .expect("not subscribed");
 
                 futures::pin_mut!(identity_announce_stream);
+                futures::pin_mut!(revocation_stream);
                 futures::pin_mut!(event_stream);
                 futures::pin_mut!(friend_stream);
 
@@ -504,6 +665,19 @@ impl IdentityStore {
                 loop {
                     tokio::select! {
                         biased;
+                        Some(message) = revocation_stream.next() => {
+                            let certificate = match serde_json::from_slice::<RevocationCertificate>(&message.data) {
+                                Ok(certificate) => certificate,
+                                Err(e) => {
+                                    tracing::warn!(from = ?message.source, error = %e, "unable to decode revocation certificate");
+                                    continue;
+                                }
+                            };
+
+                            if let Err(e) = store.process_revocation(&certificate).await {
+                                tracing::warn!(did = %certificate.did(), error = %e, "unable to process revocation certificate");
+                            }
+                        }
                         Some(message) = identity_announce_stream.next() => {
                             let payload: PayloadMessage<IdentityDocument> = match PayloadMessage::from_bytes(&message.data) {
                                 Ok(p) => p,
@@ -525,6 +699,24 @@ impl IdentityStore {
 
                             let identity = payload.message().clone();
 
+                            if store.identity_cache.is_revoked(&identity.did).await {
+                                tracing::warn!(did = %identity.did, "ignoring announcement of a revoked key");
+                                continue;
+                            }
+
+                            if payload.cosigner().is_some() {
+                                let authorized = payload
+                                    .original_sender()
+                                    .to_did()
+                                    .map(|device| identity.is_authorized(&device))
+                                    .unwrap_or_default();
+
+                                if !authorized {
+                                    tracing::warn!(%from_did, "identity announced by an unauthorized device");
+                                    continue;
+                                }
+                            }
+
                             //Maybe establish a connection?
                             //Note: Although it would be prefer not to establish a connection, it may be ideal to check to determine
                             //      the actual source of the payload to determine if its a message propagated over the mesh from the peer
@@ -574,6 +766,13 @@ impl IdentityStore {
                                 continue;
                             };
 
+                            let in_did = store.resolve_device(in_did).await;
+
+                            if store.identity_cache.is_revoked(&in_did).await {
+                                tracing::warn!(%in_did, "ignoring event from a revoked key");
+                                continue;
+                            }
+
                             tracing::info!("Received event from {in_did}");
 
                             let event = match ecdh_decrypt(store.root_document().keypair(), Some(&in_did), &message.data).and_then(|bytes| {
@@ -608,6 +807,11 @@ impl IdentityStore {
                                 continue;
                             };
 
+                            if let Err(e) = store.verify_sender(&payload).await {
+                                tracing::warn!(%did, error = %e, "payload was rejected");
+                                continue;
+                            }
+
                             let mut signal = store.signal.write().await.remove(&did);
 
                             tracing::trace!("received payload size: {} bytes", event.data.len());
@@ -657,6 +861,19 @@ impl IdentityStore {
             }
         });
 
+        // Spawned last so the copies of the store held by the tasks above do not keep it running
+        let presence_task = store.executor.spawn_abortable({
+            let store = store.clone();
+            async move { store.presence_task().await }
+        });
+
+        let request_expiry_task = store.executor.spawn_abortable({
+            let store = store.clone();
+            async move { store.request_expiry_task().await }
+        });
+
+        store.tasks = vec![presence_task, request_expiry_task];
+
         Ok(store)
     }
 
@@ -766,11 +983,25 @@ impl IdentityStore {
                 } else {
                     let from = data.sender.clone();
 
+                    if let Some(message) = &data.message {
+                        if message.chars().count() > MAX_REQUEST_MESSAGE_LENGTH {
+                            tracing::warn!(%from, "Request message exceeds the maximum length");
+                            return Ok(());
+                        }
+                    }
+
                     let req = Request::In {
                         did: from.clone(),
                         date: data.created.unwrap_or_else(Utc::now),
+                        message: data.message.clone(),
+                        expires: data.expires,
                     };
 
+                    if req.is_expired() {
+                        tracing::warn!(%from, "Request has expired. Ignoring");
+                        return Ok(());
+                    }
+
                     self.root_document.add_request(&req).await?;
 
                     let _ = self.export_root_document().await;
@@ -787,6 +1018,7 @@ impl IdentityStore {
                     self.emit_event(MultiPassEventKind::FriendRequestReceived {
                         from,
                         date: req.date(),
+                        message: req.message().map(str::to_string),
                     })
                     .await;
                 }
@@ -902,6 +1134,87 @@ impl IdentityStore {
                     let _ = tx.send(Ok(()));
                 }
             }
+            Event::RecoveryShare { share } => {
+                if !self.is_friend(&data.sender).await? {
+                    return Err(Error::FriendDoesntExist);
+                }
+
+                if share.did != data.sender {
+                    return Err(Error::InvalidShare);
+                }
+
+                share.verify()?;
+
+                self.root_document.add_recovery_share(share).await?;
+
+                let _ = self.export_root_document().await;
+
+                self.emit_event(MultiPassEventKind::RecoveryShareReceived { did: data.sender })
+                    .await;
+            }
+            Event::RecoveryRequest { did } => {
+                // Note: The request is sent from a temporary key, since the key of the identity is what is being recovered,
+                //       so it is up to the user to confirm the request out of band before approving it
+                let holds_share = self
+                    .root_document
+                    .get_recovery_shares()
+                    .await?
+                    .iter()
+                    .any(|share| share.did == did);
+
+                if !holds_share {
+                    tracing::warn!(%did, device = %data.sender, "Received recovery request for an identity without a share. Ignoring");
+                    return Err(Error::RecoveryNotSetup);
+                }
+
+                // Note: The time of arrival is used so the sender cannot extend the lifetime of the request
+                let request = RecoveryRequest::new(did.clone(), data.sender.clone(), Utc::now());
+
+                let requests = &mut *self.recovery_requests.write().await;
+                requests.retain(|_, request| !is_recovery_request_expired(request));
+                // Note: Requests are kept per device so another peer cannot replace a pending request
+                requests.insert((did.clone(), data.sender.clone()), request);
+
+                self.emit_event(MultiPassEventKind::RecoveryShareRequested {
+                    did,
+                    device: data.sender,
+                })
+                .await;
+            }
+            Event::RecoveryResponse { share, identity } => {
+                let mut recovery = self.recovery.write().await;
+
+                let Some(session) = recovery.as_mut() else {
+                    tracing::warn!(sender = %data.sender, "Received recovery share without a recovery in progress");
+                    return Ok(());
+                };
+
+                if share.did != session.did || !session.friends.contains(&data.sender) {
+                    return Err(Error::InvalidShare);
+                }
+
+                share.verify()?;
+
+                if let Some(identity) = identity
+                    .filter(|identity| identity.did == session.did && identity.verify().is_ok())
+                {
+                    let newer = session
+                        .identity
+                        .as_ref()
+                        .map(|current| identity.modified > current.modified)
+                        .unwrap_or(true);
+                    if newer {
+                        session.identity = Some(identity);
+                    }
+                }
+
+                session.shares.insert(data.sender.clone(), share);
+
+                drop(recovery);
+
+                self.emit_event(MultiPassEventKind::RecoveryShareApproved { did: data.sender })
+                    .await;
+            }
         };
 
         Ok(())
@@ -932,11 +1245,13 @@ impl IdentityStore {
     pub async fn announce_identity_to_mesh(&self) -> Result<(), Error> {
         if self.config.store_setting().announce_to_mesh {
             let kp = self.ipfs.keypair();
-            let document = self.own_identity_document().await?;
+            let document = self.public_identity_document().await?;
             tracing::debug!("announcing identity to mesh");
-            let payload = PayloadBuilder::new(kp, document)
-                .from_ipfs(&self.ipfs)
-                .await?;
+            let mut builder = PayloadBuilder::new(kp, document);
+            if kp.public() != self.root_document.keypair().public() {
+                builder = builder.cosign(self.root_document.keypair());
+            }
+            let payload = builder.from_ipfs(&self.ipfs).await?;
             let bytes = payload.to_bytes()?;
             match self.ipfs.pubsub_publish(IDENTITY_ANNOUNCEMENT, bytes).await {
                 Ok(_) => tracing::debug!("identity announced to mesh"),
@@ -947,6 +1262,14 @@ impl IdentityStore {
         Ok(())
     }
 
+    /// Copy of our identity with the fields hidden from everyone left out, signed
+    /// again so it can be served to any peer
+    async fn public_identity_document(&self) -> Result<IdentityDocument, Error> {
+        let mut document = self.own_identity_document().await?;
+        document.redacted = self.redact_identity(&mut document, false);
+        document.sign(self.root_document.keypair())
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn request(&self, out_did: &DID, option: RequestOption) -> Result<(), Error> {
         let out_peer_id = out_did.to_peer_id()?;
@@ -1006,8 +1329,9 @@ impl IdentityStore {
 
         identity.metadata.platform = platform;
 
-        let mut metadata = identity.metadata;
-        metadata.platform = platform;
+        identity.redacted = self.redact_identity(&mut identity, is_friend);
+
+        let metadata = identity.metadata;
 
         identity.metadata = Default::default();
 
@@ -1058,6 +1382,44 @@ impl IdentityStore {
         Ok(())
     }
 
+    /// Leave out the fields of the identity that are not visible to the recipient, returning
+    /// true if a field visible to friends was left out
+    fn redact_identity(&self, identity: &mut IdentityDocument, is_friend: bool) -> bool {
+        let visibility = self.config.store_setting().visibility;
+
+        let mut left_out = vec![];
+
+        if !visibility.picture.allows(is_friend)
+            && identity.metadata.profile_picture.take().is_some()
+        {
+            left_out.push(visibility.picture);
+        }
+
+        if !visibility.banner.allows(is_friend) && identity.metadata.profile_banner.take().is_some()
+        {
+            left_out.push(visibility.banner);
+        }
+
+        if !visibility.status_message.allows(is_friend) && identity.status_message.take().is_some()
+        {
+            left_out.push(visibility.status_message);
+        }
+
+        if !visibility.presence.allows(is_friend) {
+            let status = identity.metadata.status.take();
+            let presence = identity.presence.take();
+            if status.is_some() || presence.is_some() {
+                left_out.push(visibility.presence);
+            }
+        }
+
+        if !visibility.platform.allows(is_friend) && identity.metadata.platform.take().is_some() {
+            left_out.push(visibility.platform);
+        }
+
+        left_out.contains(&Visibility::Friends)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn push_profile_picture(&self, out_did: &DID, cid: Cid) -> Result<(), Error> {
         let out_peer_id = out_did.to_peer_id()?;
@@ -1074,6 +1436,19 @@ impl IdentityStore {
             return Ok(());
         };
 
+        let is_friend = self.is_friend(out_did).await.unwrap_or_default();
+
+        if !self
+            .config
+            .store_setting()
+            .visibility
+            .picture
+            .allows(is_friend)
+        {
+            tracing::debug!("Profile picture is not visible to {out_did}");
+            return Ok(());
+        }
+
         if cid != picture_cid {
             tracing::debug!("Requested profile picture does not match current picture.");
             return Ok(());
@@ -1206,6 +1581,19 @@ impl IdentityStore {
             return Ok(());
         };
 
+        let is_friend = self.is_friend(out_did).await.unwrap_or_default();
+
+        if !self
+            .config
+            .store_setting()
+            .visibility
+            .banner
+            .allows(is_friend)
+        {
+            tracing::debug!("Profile banner is not visible to {out_did}");
+            return Ok(());
+        }
+
         if cid != banner_cid {
             return Ok(());
         }
@@ -1295,16 +1683,34 @@ impl IdentityStore {
 
                 let previous_identity = self.identity_cache.get(&identity.did).await.ok();
 
+                // Friends are sent the identity directly, so a copy leaving out the fields only visible to
+                // them, such as the one announced to the mesh, should not replace it
+                if identity.redacted
+                    && previous_identity
+                        .as_ref()
+                        .is_some_and(|document| !document.redacted)
+                    && self.is_friend(&identity.did).await.unwrap_or_default()
+                {
+                    tracing::debug!(did = %identity.did, "Ignoring redacted identity from friend");
+                    return Ok(());
+                }
+
                 self.identity_cache.insert(&identity).await?;
 
+                if previous_identity.is_none() {
+                    self.process_key_succession(&identity).await?;
+                }
+
                 match previous_identity {
                     Some(document) => {
-                        if document.different(&identity) {
+                        let fields = document.changed_fields(&identity);
+                        if !fields.is_empty() {
                             tracing::info!(%identity.did, "Updating local cache");
 
                             tracing::trace!("Emitting identity update event");
                             self.emit_event(MultiPassEventKind::IdentityUpdate {
                                 did: document.did.clone(),
+                                fields,
                             })
                             .await;
 
@@ -1332,7 +1738,10 @@ impl IdentityStore {
                                                     .await?;
                                                 store
                                                     .emit_event(
-                                                        MultiPassEventKind::IdentityUpdate { did },
+                                                        MultiPassEventKind::IdentityUpdate {
+                                                            did,
+                                                            fields: vec![IdentityField::Metadata],
+                                                        },
                                                     )
                                                     .await;
 
@@ -1398,6 +1807,7 @@ impl IdentityStore {
                                                         .emit_event(
                                                             MultiPassEventKind::IdentityUpdate {
                                                                 did,
+                                                                fields: vec![IdentityField::Picture],
                                                             },
                                                         )
                                                         .await;
@@ -1464,6 +1874,7 @@ impl IdentityStore {
                                                             .emit_event(
                                                                 MultiPassEventKind::IdentityUpdate {
                                                                     did,
+                                                                    fields: vec![IdentityField::Banner],
                                                                 },
                                                             )
                                                             .await;
@@ -1482,8 +1893,11 @@ impl IdentityStore {
                         let document_did = identity.did.clone();
 
                         let did = document_did.clone();
-                        self.emit_event(MultiPassEventKind::IdentityUpdate { did })
-                            .await;
+                        self.emit_event(MultiPassEventKind::IdentityUpdate {
+                            did,
+                            fields: vec![],
+                        })
+                        .await;
 
                         if !exclude_images {
                             let picture = identity.metadata.profile_picture;
@@ -1524,6 +1938,7 @@ impl IdentityStore {
                                                         .emit_event(
                                                             MultiPassEventKind::IdentityUpdate {
                                                                 did,
+                                                                fields: vec![IdentityField::Picture],
                                                             },
                                                         )
                                                         .await;
@@ -1562,6 +1977,7 @@ impl IdentityStore {
                                                         .emit_event(
                                                             MultiPassEventKind::IdentityUpdate {
                                                                 did,
+                                                                fields: vec![IdentityField::Banner],
                                                             },
                                                         )
                                                         .await;
@@ -1582,9 +1998,15 @@ impl IdentityStore {
             } => {
                 let cache = self.identity_cache.get(in_did).await?;
 
-                if cache.metadata.profile_picture == Some(cid)
-                    || cache.metadata.profile_banner == Some(cid)
-                {
+                let field = if cache.metadata.profile_picture == Some(cid) {
+                    Some(IdentityField::Picture)
+                } else if cache.metadata.profile_banner == Some(cid) {
+                    Some(IdentityField::Banner)
+                } else {
+                    None
+                };
+
+                if let Some(field) = field {
                     self.executor.dispatch({
                         let store = self.clone();
                         let did = in_did.clone();
@@ -1599,7 +2021,10 @@ impl IdentityStore {
 
                             debug_assert_eq!(added_cid, cid);
                             store
-                                .emit_event(MultiPassEventKind::IdentityUpdate { did })
+                                .emit_event(MultiPassEventKind::IdentityUpdate {
+                                    did,
+                                    fields: vec![field],
+                                })
                                 .await;
                             Ok::<_, Error>(())
                         }
@@ -1651,6 +2076,75 @@ impl IdentityStore {
         self.discovery.discovery_config()
     }
 
+    /// Registries trusted to countersign username claims
+    fn username_registries(&self) -> Vec<DID> {
+        match self.discovery.discovery_config() {
+            DiscoveryConfig::Shuttle { addresses } => addresses
+                .iter()
+                .filter_map(|addr| addr.peer_id())
+                .filter_map(|peer_id| peer_id.to_did().ok())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Claim the current username in the registry of a shuttle node
+    pub async fn claim_username(&mut self) -> Result<(), Error> {
+        let DiscoveryConfig::Shuttle { addresses } = self.discovery.discovery_config() else {
+            return Err(Error::UsernameRegistryUnavailable);
+        };
+
+        let peers = addresses
+            .iter()
+            .filter_map(|addr| addr.peer_id())
+            .collect::<Vec<_>>();
+
+        let mut identity = self.own_identity_document().await?;
+
+        let mut result = Err(Error::UsernameRegistryUnavailable);
+
+        for peer_id in peers {
+            let claim = UsernameClaim::new(
+                self.root_document.keypair(),
+                &identity.username,
+                peer_id.to_did()?,
+            )?;
+
+            let (tx, rx) = futures::channel::oneshot::channel();
+            let _ = self
+                .identity_command
+                .clone()
+                .send(IdentityCommand::ClaimUsername {
+                    peer_id,
+                    claim,
+                    response: tx,
+                })
+                .await;
+
+            match rx.timeout(SHUTTLE_TIMEOUT).await {
+                Ok(Ok(Ok(claim))) => {
+                    claim.verify()?;
+                    identity.username_claim = Some(claim);
+                    return self.identity_update(identity).await;
+                }
+                Ok(Ok(Err(e))) => {
+                    tracing::error!("Error claiming username with {peer_id}: {e}");
+                    result = Err(e);
+                }
+                Ok(Err(Canceled)) => {
+                    tracing::error!("Channel been unexpectedly closed for {peer_id}");
+                    continue;
+                }
+                Err(_) => {
+                    tracing::error!("Request timeout for {peer_id}");
+                    continue;
+                }
+            }
+        }
+
+        result
+    }
+
     #[tracing::instrument(skip(self, extracted))]
     pub async fn import_identity(
         &mut self,
@@ -1660,7 +2154,8 @@ impl IdentityStore {
 
         let identity = extracted.identity.clone();
 
-        let document = RootDocument::import(&self.ipfs, extracted).await?;
+        let document =
+            RootDocument::import(&self.ipfs, self.root_document.keypair(), extracted).await?;
 
         self.root_document.set(document).await?;
 
@@ -1694,9 +2189,43 @@ impl IdentityStore {
             }
         }
 
+        if let Err(e) = self.authorize_local_device().await {
+            tracing::warn!(error = %e, "Unable to authorize device");
+        }
+
+        Ok(identity)
+    }
+
+    #[tracing::instrument(skip(self, bundle))]
+    pub async fn import_bundle(&mut self, bundle: ExportBundle) -> Result<Identity, Error> {
+        let identity = self.import_identity(bundle.root).await?;
+
+        self.root_document
+            .import_conversations(bundle.conversations)
+            .await?;
+
         Ok(identity)
     }
 
+    #[tracing::instrument(skip(self, bundle))]
+    pub async fn merge_bundle(&mut self, bundle: ExportBundle) -> Result<(), Error> {
+        let identity = self.own_identity_document().await?;
+
+        if identity.did != *bundle.manifest.did() {
+            return Err(Error::BundleIdentityMismatch);
+        }
+
+        self.root_document.merge_bundle(bundle).await?;
+
+        if let Ok(friends) = self.friends_list().await {
+            if let Err(e) = self.phonebook().add_friend_list(&friends).await {
+                tracing::warn!(error = %e, "Error adding friends in phonebook");
+            }
+        }
+
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn create_identity(&mut self, username: Option<&str>) -> Result<Identity, Error> {
         let raw_kp = self.get_raw_keypair()?;
@@ -1728,6 +2257,11 @@ impl IdentityStore {
             status_message: None,
             metadata: Default::default(),
             version: Default::default(),
+            devices: vec![],
+            predecessor: None,
+            username_claim: None,
+            presence: None,
+            redacted: false,
             signature: None,
         };
 
@@ -1751,6 +2285,10 @@ impl IdentityStore {
             tracing::warn!(%identity.did, "Unable to export root document: {e}");
         }
 
+        if let Err(e) = self.authorize_local_device().await {
+            tracing::warn!(%identity.did, "Unable to authorize device: {e}");
+        }
+
         let _ = self.announce_identity_to_mesh().await;
 
         identity.resolve()
@@ -1792,6 +2330,10 @@ impl IdentityStore {
             }
         }
 
+        if let Err(e) = self.authorize_local_device().await {
+            tracing::warn!(error = %e, "Unable to authorize device");
+        }
+
         self.own_identity().await
     }
 
@@ -1834,11 +2376,18 @@ impl IdentityStore {
         let package = self.root_document.export_root_cid().await?;
 
         if let DiscoveryConfig::Shuttle { addresses } = self.discovery.discovery_config() {
+            // The root document holds the full identity, so the shuttle is also given
+            // a redacted copy to serve on lookup
+            let identity = self.public_identity_document().await?;
             for peer_id in addresses.iter().filter_map(|addr| addr.peer_id()) {
                 let _ = self
                     .identity_command
                     .clone()
-                    .send(IdentityCommand::UpdateRootDocument { peer_id, package })
+                    .send(IdentityCommand::UpdateRootDocument {
+                        peer_id,
+                        package,
+                        identity: identity.clone(),
+                    })
                     .await;
             }
         }
@@ -1882,6 +2431,7 @@ impl IdentityStore {
     async fn register(&self) -> Result<(), Error> {
         let root_cid = self.root_document.export_root_cid().await?;
         if let DiscoveryConfig::Shuttle { addresses } = self.discovery.discovery_config() {
+            let identity = self.public_identity_document().await?;
             for peer_id in addresses.iter().filter_map(|addr| addr.peer_id()) {
                 let (tx, rx) = futures::channel::oneshot::channel();
                 let _ = self
@@ -1890,6 +2440,7 @@ impl IdentityStore {
                     .send(IdentityCommand::Register {
                         peer_id,
                         root_cid,
+                        identity: identity.clone(),
                         response: tx,
                     })
                     .await;
@@ -1917,6 +2468,47 @@ impl IdentityStore {
         Ok(())
     }
 
+    async fn publish_revocation(&self, certificate: &RevocationCertificate) -> Result<(), Error> {
+        let DiscoveryConfig::Shuttle { addresses } = self.discovery.discovery_config() else {
+            return Err(Error::OtherWithContext(
+                "Revocation certificates are published through shuttle discovery".into(),
+            ));
+        };
+
+        let mut result = Err(Error::Other);
+
+        for peer_id in addresses.iter().filter_map(|addr| addr.peer_id()) {
+            let (tx, rx) = futures::channel::oneshot::channel();
+            let _ = self
+                .identity_command
+                .clone()
+                .send(IdentityCommand::Revoke {
+                    peer_id,
+                    certificate: certificate.clone(),
+                    response: tx,
+                })
+                .await;
+
+            match rx.timeout(SHUTTLE_TIMEOUT).await {
+                Ok(Ok(Ok(_))) => return Ok(()),
+                Ok(Ok(Err(e))) => {
+                    tracing::error!("Error publishing revocation to {peer_id}: {e}");
+                    result = Err(e);
+                }
+                Ok(Err(Canceled)) => {
+                    tracing::error!("Channel been unexpectedly closed for {peer_id}");
+                    continue;
+                }
+                Err(_) => {
+                    tracing::error!("Request timeout for {peer_id}");
+                    continue;
+                }
+            }
+        }
+
+        result
+    }
+
     async fn fetch_mailbox(&mut self) -> Result<(), Error> {
         if let DiscoveryConfig::Shuttle { addresses } = self.discovery.discovery_config() {
             for peer_id in addresses.iter().filter_map(|addr| addr.peer_id()) {
@@ -2240,6 +2832,11 @@ impl IdentityStore {
 
     #[tracing::instrument(skip(self))]
     pub async fn set_identity_status(&mut self, status: IdentityStatus) -> Result<(), Error> {
+        self.presence.lock().restore = None;
+        self.update_status_indicator(status).await
+    }
+
+    async fn update_status_indicator(&mut self, status: IdentityStatus) -> Result<(), Error> {
         self.root_document.set_status_indicator(status).await?;
 
         let _ = self.export_root_document().await;
@@ -2249,43 +2846,489 @@ impl IdentityStore {
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn identity_platform(&self, did: &DID) -> Result<Platform, Error> {
-        let own_did = self
-            .own_identity()
-            .await
-            .map(|identity| identity.did_key())
-            .map_err(|_| Error::OtherWithContext("Identity store may not be initialized".into()))?;
+    pub async fn identity_presence(&self, did: &DID) -> Result<Presence, Error> {
+        let status = self.identity_status(did).await?;
 
-        if own_did.eq(did) {
-            return Ok(self.own_platform());
+        let document = match self.did_key.eq(did) {
+            true => self.own_identity_document().await.ok(),
+            false => self.identity_cache.get(did).await.ok(),
+        };
+
+        let mut presence = document
+            .map(|document| document.presence())
+            .unwrap_or_else(|| Presence::new(status));
+        presence.set_status(status);
+        Ok(presence)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn set_identity_presence(&mut self, presence: Presence) -> Result<(), Error> {
+        let document = PresenceDocument::new(&presence);
+
+        if let Some(document) = &document {
+            document.verify()?;
+            if document.is_expired() {
+                return Err(Error::OtherWithContext(
+                    "Presence has already expired".into(),
+                ));
+            }
         }
 
-        let identity_status = self.identity_status(did).await?;
+        let mut identity = self.own_identity_document().await?;
+        identity.metadata.status = Some(presence.status());
+        identity.presence = document;
 
-        if matches!(identity_status, IdentityStatus::Offline) {
-            return Ok(Platform::Unknown);
+        {
+            let state = &mut *self.presence.lock();
+            state.restore = None;
+            state.until = presence.until();
         }
 
-        self.identity_cache
-            .get(did)
-            .await
-            .ok()
-            .and_then(|cache| cache.metadata.platform)
-            .ok_or(Error::IdentityDoesntExist)
+        self.identity_update(identity).await
     }
 
-    pub fn get_raw_keypair(&self) -> anyhow::Result<ipfs::libp2p::identity::ed25519::Keypair> {
-        self.root_document
-            .keypair()
-            .clone()
-            .try_into_ed25519()
-            .map_err(anyhow::Error::from)
+    /// Mark the node as being used through the api
+    pub fn record_activity(&self) {
+        self.presence.lock().last_activity = Instant::now();
     }
 
-    pub async fn own_identity_document(&self) -> Result<IdentityDocument, Error> {
-        let identity = self.root_document.identity().await?;
-        identity.verify()?;
-        Ok(identity)
+    /// Clear the custom presence once it expires and switch to away while the node is idle
+    async fn presence_task(mut self) {
+        let idle_timeout = self.config.store_setting().idle_timeout;
+
+        if let Ok(identity) = self.own_identity_document().await {
+            self.presence.lock().until = identity.presence.and_then(|presence| presence.until);
+        }
+
+        loop {
+            Delay::new(PRESENCE_CHECK_INTERVAL).await;
+
+            let (until, last_activity, idle) = {
+                let state = &*self.presence.lock();
+                (state.until, state.last_activity, state.idle)
+            };
+
+            if until.is_some_and(|until| until <= Utc::now()) {
+                if let Err(e) = self.clear_expired_presence().await {
+                    tracing::warn!(error = %e, "Unable to clear expired presence");
+                }
+            }
+
+            let Some(timeout) = idle_timeout else {
+                continue;
+            };
+
+            match (last_activity.elapsed() >= timeout, idle) {
+                (true, false) => {
+                    let Ok(identity) = self.own_identity_document().await else {
+                        continue;
+                    };
+
+                    self.presence.lock().idle = true;
+
+                    if !matches!(
+                        identity.metadata.status,
+                        None | Some(IdentityStatus::Online)
+                    ) {
+                        continue;
+                    }
+
+                    tracing::debug!("Identity is idle. Switching to away");
+                    self.presence.lock().restore = Some(IdentityStatus::Online);
+                    if let Err(e) = self.update_status_indicator(IdentityStatus::Away).await {
+                        tracing::warn!(error = %e, "Unable to set status while idle");
+                    }
+                }
+                (false, true) => {
+                    let restore = {
+                        let state = &mut *self.presence.lock();
+                        state.idle = false;
+                        state.restore.take()
+                    };
+
+                    if let Some(status) = restore {
+                        tracing::debug!("Identity is no longer idle. Restoring status");
+                        if let Err(e) = self.update_status_indicator(status).await {
+                            tracing::warn!(error = %e, "Unable to restore status");
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    async fn clear_expired_presence(&mut self) -> Result<(), Error> {
+        let idle = {
+            let state = &mut *self.presence.lock();
+            state.until = None;
+            state.idle
+        };
+
+        let mut identity = self.own_identity_document().await?;
+
+        if !identity
+            .presence
+            .as_ref()
+            .is_some_and(PresenceDocument::is_expired)
+        {
+            return Ok(());
+        }
+
+        identity.presence = None;
+        identity.metadata.status = match idle {
+            true => {
+                self.presence.lock().restore = Some(IdentityStatus::Online);
+                Some(IdentityStatus::Away)
+            }
+            false => Some(IdentityStatus::Online),
+        };
+
+        self.identity_update(identity).await
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn authorize_device(
+        &mut self,
+        device: &DID,
+        name: Option<&str>,
+    ) -> Result<(), Error> {
+        if device.eq(&self.did_key) {
+            return Err(Error::CannotAuthorizeOwnIdentity);
+        }
+
+        let mut identity = self.own_identity_document().await?;
+
+        let authorization = DeviceAuthorization::new(
+            self.root_document.keypair(),
+            device.clone(),
+            name.map(str::to_string),
+        )?;
+
+        authorization.verify(&identity.did)?;
+
+        identity.devices.retain(|entry| entry.device.ne(device));
+        identity.devices.push(authorization);
+
+        if identity.devices.len() > MAX_DEVICES {
+            return Err(Error::InvalidLength {
+                context: "devices".into(),
+                current: identity.devices.len(),
+                minimum: None,
+                maximum: Some(MAX_DEVICES),
+            });
+        }
+
+        identity.modified = Utc::now();
+
+        self.identity_update(identity).await?;
+
+        self.emit_event(MultiPassEventKind::DeviceAuthorized {
+            did: device.clone(),
+        })
+        .await;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn revoke_device(&mut self, device: &DID) -> Result<(), Error> {
+        if self.ipfs.keypair().to_did()?.eq(device) {
+            return Err(Error::CannotRevokeOwnDevice);
+        }
+
+        let mut identity = self.own_identity_document().await?;
+
+        if !identity.devices.iter().any(|entry| entry.device.eq(device)) {
+            return Err(Error::DeviceNotAuthorized);
+        }
+
+        identity.devices.retain(|entry| entry.device.ne(device));
+        identity.modified = Utc::now();
+
+        self.identity_update(identity).await?;
+
+        self.emit_event(MultiPassEventKind::DeviceRevoked {
+            did: device.clone(),
+        })
+        .await;
+
+        Ok(())
+    }
+
+    pub async fn list_devices(&self) -> Result<Vec<Device>, Error> {
+        let identity = self.own_identity_document().await?;
+        Ok(identity.devices.iter().map(Device::from).collect())
+    }
+
+    /// Authorize the key of the running node if it differs from the identity key
+    pub async fn authorize_local_device(&mut self) -> Result<(), Error> {
+        let device = self.ipfs.keypair().to_did()?;
+
+        if device.eq(&self.did_key) {
+            return Ok(());
+        }
+
+        let identity = self.own_identity_document().await?;
+
+        if identity.is_authorized(&device) {
+            return Ok(());
+        }
+
+        self.authorize_device(&device, None).await
+    }
+
+    /// Resolve the key of a device to the identity that authorized it, returning the key as is otherwise
+    pub async fn resolve_device(&self, did: DID) -> DID {
+        match self.identity_cache.get(&did).await {
+            Ok(_) => did,
+            Err(_) => self
+                .identity_cache
+                .get_by_device(&did)
+                .await
+                .map(|document| document.did)
+                .unwrap_or(did),
+        }
+    }
+
+    /// Check that the key of the sender was not revoked and, if the payload was cosigned by an identity, that it was
+    /// signed by one of its authorized devices
+    pub async fn verify_sender<M>(&self, payload: &PayloadMessage<M>) -> Result<(), Error> {
+        let identity = payload.sender().to_did()?;
+
+        if self.identity_cache.is_revoked(&identity).await {
+            return Err(Error::IdentityRevoked);
+        }
+
+        if payload.cosigner().is_none() {
+            return Ok(());
+        }
+
+        let device = payload.original_sender().to_did()?;
+
+        let document = match identity.eq(&self.did_key) {
+            true => self.own_identity_document().await?,
+            false => match self.identity_cache.get(&identity).await {
+                Ok(document) => document,
+                Err(e) => {
+                    // Ask for the document so the payload can be checked once it is sent again
+                    _ = self.request(&identity, RequestOption::Identity).await;
+                    return Err(e);
+                }
+            },
+        };
+
+        if !document.is_authorized(&device) {
+            return Err(Error::DeviceNotAuthorized);
+        }
+
+        Ok(())
+    }
+
+    /// Create the root document for a new key of the identity, returning its cid.
+    /// Note: The current root document is left untouched and the node is expected to be restarted with the new key
+    #[tracing::instrument(skip(self, new_keypair))]
+    pub async fn rotate_identity_key(&mut self, new_keypair: &Keypair) -> Result<Cid, Error> {
+        let succession = KeySuccession::new(self.root_document.keypair(), new_keypair)?;
+        self.root_document.rotate(new_keypair, succession).await
+    }
+
+    pub fn create_revocation_certificate(
+        &self,
+        reason: Option<&str>,
+    ) -> Result<RevocationCertificate, Error> {
+        create_revocation_certificate(self.root_document.keypair(), reason.map(str::to_string))
+    }
+
+    #[tracing::instrument(skip(self, certificate))]
+    pub async fn publish_revocation_certificate(
+        &self,
+        certificate: &RevocationCertificate,
+    ) -> Result<(), Error> {
+        verify_revocation_certificate(certificate)?;
+        self.publish_revocation(certificate).await
+    }
+
+    /// Store a revocation certificate announced to the network, no longer accepting the revoked key
+    async fn process_revocation(
+        &mut self,
+        certificate: &RevocationCertificate,
+    ) -> Result<(), Error> {
+        let did = certificate.did();
+
+        if did.eq(&self.did_key) {
+            tracing::warn!(%did, "own identity key has been revoked");
+        }
+
+        if !self.identity_cache.revoke(certificate).await? {
+            return Ok(());
+        }
+
+        tracing::info!(%did, "identity key has been revoked");
+
+        if self.is_friend(did).await? {
+            if let Err(e) = self.phonebook.remove_friend(did).await {
+                tracing::warn!(%did, error = %e, "unable to remove revoked key from phonebook");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Move a friend or blocked key over to its successor
+    async fn process_key_succession(&mut self, identity: &IdentityDocument) -> Result<(), Error> {
+        let Some(succession) = identity.predecessor.as_ref() else {
+            return Ok(());
+        };
+
+        let previous = &succession.previous;
+        let successor = &identity.did;
+
+        // A revoked key could otherwise hand its friends and blocks over to a key of the attacker
+        if self.identity_cache.is_revoked(previous).await {
+            tracing::warn!(%previous, %successor, "ignoring succession from a revoked key");
+            return Ok(());
+        }
+
+        // Shares held for the previous key would only rebuild the key that was replaced
+        if self
+            .root_document
+            .get_recovery_shares()
+            .await?
+            .iter()
+            .any(|share| share.did.eq(previous))
+        {
+            self.root_document.remove_recovery_share(previous).await?;
+            let _ = self.export_root_document().await;
+        }
+
+        if self.is_blocked(previous).await? {
+            self.root_document.remove_block(previous).await?;
+            if !self.is_blocked(successor).await? {
+                self.root_document.add_block(successor).await?;
+            }
+            let _ = self.export_root_document().await;
+            return Ok(());
+        }
+
+        if !self.is_friend(previous).await? {
+            return Ok(());
+        }
+
+        tracing::info!(%previous, %successor, "friend has rotated their identity key");
+
+        let contact = self
+            .root_document
+            .get_contacts()
+            .await?
+            .into_iter()
+            .find(|contact| contact.did().eq(previous));
+
+        self.root_document.remove_friend(previous).await?;
+        if !self.is_friend(successor).await? {
+            self.root_document.add_friend(successor).await?;
+        }
+
+        // The contact record follows the friend to their new key
+        if let Some(contact) = contact {
+            let mut migrated = Contact::new(successor.clone());
+            migrated.set_nickname(contact.nickname().map(str::to_string));
+            migrated.set_notes(contact.notes().map(str::to_string));
+            migrated.set_groups(contact.groups().to_vec());
+            self.root_document.set_contact(migrated).await?;
+        }
+
+        let _ = self.export_root_document().await;
+
+        if let Err(_e) = self.phonebook.remove_friend(previous).await {
+            error!("Error: {_e}");
+        }
+
+        if let Err(_e) = self.phonebook.add_friend(successor).await {
+            error!("Error: {_e}");
+        }
+
+        self.emit_event(MultiPassEventKind::IdentityKeyRotated {
+            old: previous.clone(),
+            new: successor.clone(),
+        })
+        .await;
+
+        Ok(())
+    }
+
+    /// Merge the root document stored by the shuttle from other devices of the identity,
+    /// returning true if the local document has changed
+    #[tracing::instrument(skip(self))]
+    pub async fn synchronize(&mut self) -> Result<bool, Error> {
+        let identity = self.own_identity_document().await?;
+
+        if identity.devices.is_empty() {
+            return Ok(false);
+        }
+
+        let local = self.root_document.export_root_cid().await?;
+        let remote = self.import_identity_remote().await?;
+
+        if local == remote {
+            return Ok(false);
+        }
+
+        if !self.root_document.merge(remote).await? {
+            return Ok(false);
+        }
+
+        tracing::info!("Root document synchronized from another device");
+
+        if let Ok(friends) = self.friends_list().await {
+            if let Err(_e) = self.phonebook.add_friend_list(&friends).await {
+                error!("Error adding friends in phonebook: {_e}");
+            }
+        }
+
+        let _ = self.export_root_document().await;
+
+        Ok(true)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn identity_platform(&self, did: &DID) -> Result<Platform, Error> {
+        let own_did = self
+            .own_identity()
+            .await
+            .map(|identity| identity.did_key())
+            .map_err(|_| Error::OtherWithContext("Identity store may not be initialized".into()))?;
+
+        if own_did.eq(did) {
+            return Ok(self.own_platform());
+        }
+
+        let identity_status = self.identity_status(did).await?;
+
+        if matches!(identity_status, IdentityStatus::Offline) {
+            return Ok(Platform::Unknown);
+        }
+
+        self.identity_cache
+            .get(did)
+            .await
+            .ok()
+            .and_then(|cache| cache.metadata.platform)
+            .ok_or(Error::IdentityDoesntExist)
+    }
+
+    pub fn get_raw_keypair(&self) -> anyhow::Result<ipfs::libp2p::identity::ed25519::Keypair> {
+        self.root_document
+            .keypair()
+            .clone()
+            .try_into_ed25519()
+            .map_err(anyhow::Error::from)
+    }
+
+    pub async fn own_identity_document(&self) -> Result<IdentityDocument, Error> {
+        let identity = self.root_document.identity().await?;
+        identity.verify()?;
+        Ok(identity)
     }
 
     pub async fn own_identity(&self) -> Result<Identity, Error> {
@@ -2301,8 +3344,10 @@ impl IdentityStore {
             None => IndexMap::new(),
         };
 
+        let verified = identity.has_verified_username(&self.username_registries());
         let mut identity: Identity = identity.into();
         identity.set_metadata(metadata);
+        identity.set_verified(verified);
         Ok(identity)
     }
 
@@ -2426,7 +3471,12 @@ impl IdentityStore {
 
 impl IdentityStore {
     #[tracing::instrument(skip(self))]
-    pub async fn send_request(&mut self, pubkey: &DID) -> Result<(), Error> {
+    pub async fn send_request(
+        &mut self,
+        pubkey: &DID,
+        message: Option<String>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
         let local_public_key = self.did_key.clone();
 
         if local_public_key.eq(pubkey) {
@@ -2459,7 +3509,32 @@ impl IdentityStore {
             return Err(Error::FriendRequestExist);
         }
 
-        let payload = RequestResponsePayload::new(self.root_document.keypair(), Event::Request)?;
+        if let Some(message) = &message {
+            let len = message.chars().count();
+            if len == 0 || len > MAX_REQUEST_MESSAGE_LENGTH {
+                return Err(Error::InvalidLength {
+                    context: "request message".into(),
+                    current: len,
+                    minimum: Some(1),
+                    maximum: Some(MAX_REQUEST_MESSAGE_LENGTH),
+                });
+            }
+        }
+
+        let expires = expires.or_else(|| {
+            self.config
+                .store_setting()
+                .friend_request_expiry
+                .and_then(|duration| chrono::Duration::from_std(duration).ok())
+                .map(|duration| Utc::now() + duration)
+        });
+
+        if expires.is_some_and(|expires| expires <= Utc::now()) {
+            return Err(Error::FriendRequestExpired);
+        }
+
+        let payload =
+            RequestResponsePayload::new_request(self.root_document.keypair(), message, expires)?;
 
         self.broadcast_request(pubkey, &payload, true, true).await
     }
@@ -2560,6 +3635,41 @@ impl IdentityStore {
         self.broadcast_request(pubkey, &payload, false, true).await
     }
 
+    /// Close outgoing requests and remove incoming requests once they expire
+    async fn request_expiry_task(mut self) {
+        loop {
+            Delay::new(REQUEST_EXPIRY_CHECK_INTERVAL).await;
+
+            let Ok(list) = self.list_all_raw_request().await else {
+                continue;
+            };
+
+            for request in list.iter().filter(|request| request.is_expired()) {
+                match request {
+                    Request::Out { did, .. } => {
+                        tracing::info!(%did, "Outgoing request expired. Closing");
+                        if let Err(e) = self.close_request(did).await {
+                            tracing::warn!(%did, error = %e, "Unable to close expired request");
+                        }
+                    }
+                    Request::In { did, .. } => {
+                        tracing::info!(%did, "Incoming request expired. Removing");
+                        if self.root_document.remove_request(request).await.is_err() {
+                            continue;
+                        }
+
+                        let _ = self.export_root_document().await;
+
+                        self.emit_event(MultiPassEventKind::IncomingFriendRequestClosed {
+                            did: did.clone(),
+                        })
+                        .await;
+                    }
+                }
+            }
+        }
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn has_request_from(&self, pubkey: &DID) -> Result<bool, Error> {
         self.list_incoming_request().await.map(|list| {
@@ -2650,6 +3760,213 @@ impl IdentityStore {
     }
 }
 
+impl IdentityStore {
+    /// Split the recovery secret of the identity between friends, replacing any shares that were previously sent
+    #[tracing::instrument(skip(self))]
+    pub async fn setup_social_recovery(
+        &mut self,
+        friends: &[DID],
+        threshold: u8,
+    ) -> Result<(), Error> {
+        let mut list: Vec<DID> = vec![];
+        for friend in friends {
+            if !list.contains(friend) {
+                list.push(friend.clone());
+            }
+        }
+
+        if list.is_empty() || list.len() > u8::MAX as usize {
+            return Err(Error::InvalidShareThreshold);
+        }
+
+        for friend in &list {
+            if !self.is_friend(friend).await? {
+                return Err(Error::FriendDoesntExist);
+            }
+        }
+
+        let shares = RecoveryShareDocument::split(
+            self.root_document.keypair(),
+            threshold,
+            list.len() as u8,
+        )?;
+
+        for (friend, share) in list.iter().zip(shares) {
+            let payload = RequestResponsePayload::new(
+                self.root_document.keypair(),
+                Event::RecoveryShare { share },
+            )?;
+
+            self.broadcast_request(friend, &payload, false, true)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_recovery_shares(&self) -> Result<Vec<DID>, Error> {
+        self.root_document
+            .get_recovery_shares()
+            .await
+            .map(|list| list.into_iter().map(|share| share.did).collect())
+    }
+
+    pub async fn list_recovery_requests(&self) -> Result<Vec<RecoveryRequest>, Error> {
+        let requests = &mut *self.recovery_requests.write().await;
+        requests.retain(|_, request| !is_recovery_request_expired(request));
+        Ok(requests.values().cloned().collect())
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn approve_recovery_request(&mut self, did: &DID, device: &DID) -> Result<(), Error> {
+        let request = self
+            .recovery_requests
+            .write()
+            .await
+            .remove(&(did.clone(), device.clone()))
+            .filter(|request| !is_recovery_request_expired(request))
+            .ok_or(Error::RecoveryRequestDoesntExist)?;
+
+        let share = self
+            .root_document
+            .get_recovery_shares()
+            .await?
+            .into_iter()
+            .find(|share| share.did.eq(did))
+            .ok_or(Error::RecoveryNotSetup)?;
+
+        let identity = self.identity_cache.get(did).await.ok();
+
+        let payload = RequestResponsePayload::new(
+            self.root_document.keypair(),
+            Event::RecoveryResponse { share, identity },
+        )?;
+
+        self.broadcast_request(request.device(), &payload, false, true)
+            .await
+    }
+
+    pub async fn deny_recovery_request(&mut self, did: &DID, device: &DID) -> Result<(), Error> {
+        self.recovery_requests
+            .write()
+            .await
+            .remove(&(did.clone(), device.clone()))
+            .map(|_| ())
+            .ok_or(Error::RecoveryRequestDoesntExist)
+    }
+
+    /// Request the recovery shares held by friends of the identity. This is expected to be done from a temporary key
+    #[tracing::instrument(skip(self))]
+    pub async fn request_recovery(&mut self, did: &DID, friends: &[DID]) -> Result<(), Error> {
+        if friends.is_empty() {
+            return Err(Error::InsufficientShares);
+        }
+
+        self.recovery.write().await.replace(RecoverySession {
+            did: did.clone(),
+            friends: friends.to_vec(),
+            shares: HashMap::new(),
+            identity: None,
+        });
+
+        let payload = RequestResponsePayload::new(
+            self.root_document.keypair(),
+            Event::RecoveryRequest { did: did.clone() },
+        )?;
+
+        for friend in friends {
+            if let Err(e) = self.broadcast_request(friend, &payload, false, true).await {
+                tracing::warn!(%friend, error = %e, "Unable to request recovery share");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Attempt to recover the key of the identity from the shares received so far,
+    /// returning `None` if more shares are needed
+    pub async fn try_recover(&self) -> Result<Option<RecoveredIdentity>, Error> {
+        let recovery = self.recovery.read().await;
+        let session = recovery.as_ref().ok_or(Error::RecoveryNotSetup)?;
+
+        let shares = session.shares.values().cloned().collect::<Vec<_>>();
+
+        let keypair = match RecoveryShareDocument::combine(&shares) {
+            Ok(keypair) => keypair,
+            Err(Error::InsufficientShares) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        Ok(Some(RecoveredIdentity {
+            keypair,
+            identity: session.identity.clone(),
+            friends: session.shares.keys().cloned().collect(),
+        }))
+    }
+
+    /// Rebuild the identity after its key has been recovered, preferring the root document stored by the shuttle
+    /// and falling back to the identity document provided by friends
+    #[tracing::instrument(skip(self, identity))]
+    pub async fn recover_identity(
+        &mut self,
+        identity: Option<IdentityDocument>,
+        friends: &[DID],
+    ) -> Result<Identity, Error> {
+        match self.import_identity_remote_resolve().await {
+            Ok(identity) => return Ok(identity),
+            Err(e) => {
+                tracing::warn!(error = %e, "Unable to import root document. Rebuilding identity from friends")
+            }
+        }
+
+        let identity = identity.ok_or(Error::IdentityDoesntExist)?;
+
+        identity.verify()?;
+
+        if identity.did != self.did_key {
+            return Err(Error::IdentityInvalid);
+        }
+
+        let ident_cid = self.ipfs.put_dag(identity).await?;
+
+        let root_document = RootDocument {
+            identity: ident_cid,
+            ..Default::default()
+        };
+
+        self.root_document.set(root_document).await?;
+
+        // Share holders were friends of the identity when the shares were sent to them
+        for friend in friends {
+            if let Err(e) = self.root_document.add_friend(friend).await {
+                tracing::warn!(%friend, error = %e, "Unable to restore friend");
+            }
+        }
+
+        if let Err(_e) = self.phonebook.add_friend_list(friends).await {
+            error!("Error adding friends in phonebook: {_e}");
+        }
+
+        let identity = self.root_document.identity().await?;
+
+        if let Err(e) = self.register().await {
+            tracing::warn!(%identity.did, "Unable to register to external node: {e}. Identity will not be discoverable offline");
+        }
+
+        if let Err(e) = self.export_root_document().await {
+            tracing::warn!(%identity.did, "Unable to export root document: {e}");
+        }
+
+        if let Err(e) = self.authorize_local_device().await {
+            tracing::warn!(%identity.did, "Unable to authorize device: {e}");
+        }
+
+        let _ = self.announce_identity_to_mesh().await;
+
+        identity.resolve()
+    }
+}
+
 impl IdentityStore {
     pub async fn block_by_list(&self) -> Result<Vec<DID>, Error> {
         self.root_document.get_block_by().await
@@ -2734,6 +4051,134 @@ impl IdentityStore {
         self.friends_list().await.map(|list| list.contains(pubkey))
     }
 
+    pub async fn list_contacts(&self) -> Result<Vec<Contact>, Error> {
+        self.root_document.get_contacts().await
+    }
+
+    pub async fn get_contact(&self, did: &DID) -> Result<Contact, Error> {
+        if !self.is_friend(did).await? {
+            return Err(Error::FriendDoesntExist);
+        }
+
+        let contact = self
+            .root_document
+            .get_contacts()
+            .await?
+            .into_iter()
+            .find(|contact| contact.did().eq(did))
+            .unwrap_or_else(|| Contact::new(did.clone()));
+
+        Ok(contact)
+    }
+
+    #[tracing::instrument(skip(self))]
+    pub async fn update_contact(&mut self, did: &DID, option: ContactUpdate) -> Result<(), Error> {
+        let mut contact = self.get_contact(did).await?;
+
+        match option {
+            ContactUpdate::Nickname(nickname) => {
+                let nickname = nickname
+                    .map(|nickname| nickname.trim().to_string())
+                    .filter(|nickname| !nickname.is_empty());
+
+                if let Some(nickname) = &nickname {
+                    if nickname.chars().count() > MAX_USERNAME_LENGTH {
+                        return Err(Error::InvalidLength {
+                            context: "nickname".into(),
+                            current: nickname.chars().count(),
+                            minimum: None,
+                            maximum: Some(MAX_USERNAME_LENGTH),
+                        });
+                    }
+                }
+
+                contact.set_nickname(nickname);
+            }
+            ContactUpdate::Notes(notes) => {
+                let notes = notes.filter(|notes| !notes.trim().is_empty());
+
+                if let Some(notes) = &notes {
+                    if notes.len() > MAX_CONTACT_NOTES_LENGTH {
+                        return Err(Error::InvalidLength {
+                            context: "notes".into(),
+                            current: notes.len(),
+                            minimum: None,
+                            maximum: Some(MAX_CONTACT_NOTES_LENGTH),
+                        });
+                    }
+                }
+
+                contact.set_notes(notes);
+            }
+            ContactUpdate::AddGroup(group) => {
+                let group = group.trim().to_string();
+
+                if group.is_empty() || group.chars().count() > MAX_CONTACT_GROUP_NAME_LENGTH {
+                    return Err(Error::InvalidLength {
+                        context: "group".into(),
+                        current: group.chars().count(),
+                        minimum: Some(1),
+                        maximum: Some(MAX_CONTACT_GROUP_NAME_LENGTH),
+                    });
+                }
+
+                let mut groups = contact.groups().to_vec();
+
+                if groups.contains(&group) {
+                    return Ok(());
+                }
+
+                if groups.len() >= MAX_CONTACT_GROUPS {
+                    return Err(Error::InvalidLength {
+                        context: "groups".into(),
+                        current: groups.len() + 1,
+                        minimum: None,
+                        maximum: Some(MAX_CONTACT_GROUPS),
+                    });
+                }
+
+                groups.push(group);
+                contact.set_groups(groups);
+            }
+            ContactUpdate::RemoveGroup(group) => {
+                let mut groups = contact.groups().to_vec();
+                let len = groups.len();
+                groups.retain(|item| item.ne(group.trim()));
+
+                if groups.len() == len {
+                    return Ok(());
+                }
+
+                contact.set_groups(groups);
+            }
+        }
+
+        contact.set_modified(Utc::now());
+
+        self.root_document.set_contact(contact).await?;
+        let _ = self.export_root_document().await;
+
+        self.emit_event(MultiPassEventKind::ContactUpdated { did: did.clone() })
+            .await;
+
+        Ok(())
+    }
+
+    pub async fn list_contact_groups(&self) -> Result<Vec<String>, Error> {
+        let mut groups = self
+            .root_document
+            .get_contacts()
+            .await?
+            .into_iter()
+            .flat_map(|contact| contact.groups().to_vec())
+            .collect::<Vec<_>>();
+
+        groups.sort();
+        groups.dedup();
+
+        Ok(groups)
+    }
+
     #[tracing::instrument(skip(self))]
     pub async fn subscribe(
         &self,
@@ -2757,10 +4202,9 @@ impl IdentityStore {
     pub async fn list_incoming_request(&self) -> Result<Vec<FriendRequest>, Error> {
         self.list_all_raw_request().await.map(|list| {
             list.into_iter()
-                .filter_map(|request| match request {
-                    Request::In { did, date } => Some(FriendRequest::new(did, Some(date))),
-                    _ => None,
-                })
+                .filter(|request| request.r#type() == RequestType::Incoming)
+                .filter(|request| !request.is_expired())
+                .map(|request| FriendRequest::from(&request))
                 .collect::<Vec<_>>()
         })
     }
@@ -2776,10 +4220,9 @@ impl IdentityStore {
     pub async fn list_outgoing_request(&self) -> Result<Vec<FriendRequest>, Error> {
         self.list_all_raw_request().await.map(|list| {
             list.into_iter()
-                .filter_map(|request| match request {
-                    Request::Out { did, date } => Some(FriendRequest::new(did, Some(date))),
-                    _ => None,
-                })
+                .filter(|request| request.r#type() == RequestType::Outgoing)
+                .filter(|request| !request.is_expired())
+                .map(|request| FriendRequest::from(&request))
                 .collect::<Vec<_>>()
         })
     }
@@ -2804,6 +4247,8 @@ impl IdentityStore {
             let outgoing_request = Request::Out {
                 did: recipient.clone(),
                 date: payload.created.unwrap_or_else(Utc::now),
+                message: payload.message.clone(),
+                expires: payload.expires,
             };
 
             outgoing_request_date.replace(outgoing_request.date());
@@ -2820,7 +4265,9 @@ impl IdentityStore {
         let payload_bytes = serde_json::to_vec(&payload)?;
 
         let bytes = ecdh_encrypt(kp, Some(recipient), payload_bytes)?;
-        let message = PayloadBuilder::new(kp, bytes).build()?;
+        let message = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(kp)
+            .build()?;
 
         let message_bytes = message.to_bytes()?;
 
@@ -2936,7 +4383,15 @@ async fn resolve_identity(store: &IdentityStore, identity: IdentityDocument) ->
         None => IndexMap::new(),
     };
 
+    let verified = identity.has_verified_username(&store.username_registries());
     let mut identity: Identity = identity.into();
     identity.set_metadata(metadata);
+    identity.set_verified(verified);
     identity
 }
+
+fn is_recovery_request_expired(request: &RecoveryRequest) -> bool {
+    chrono::Duration::from_std(RECOVERY_REQUEST_EXPIRY)
+        .map(|expiry| request.date() + expiry <= Utc::now())
+        .unwrap_or_default()
+}
@@ -47,6 +47,10 @@ impl Keystore {
         Ok(())
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.recipient_key.is_empty()
+    }
+
     pub fn exist(&self, recipient: &DID) -> bool {
         self.recipient_key.contains_key(recipient)
     }
@@ -78,6 +82,22 @@ impl Keystore {
             .map(|list| list.len())
             .ok_or(Error::PublicKeyDoesntExist)
     }
+
+    /// Re-encrypt every key in the keystore from one keypair to another
+    pub fn rekey(&self, keypair: &Keypair, new_keypair: &Keypair) -> Result<Self, Error> {
+        let mut recipient_key = HashMap::new();
+        for (recipient, list) in &self.recipient_key {
+            let mut set = BTreeSet::new();
+            for entry in list {
+                let mut key = super::ecdh_decrypt(keypair, None, entry)?;
+                let new_key = super::ecdh_encrypt(new_keypair, None, &key)?;
+                key.zeroize();
+                set.insert(KeyEntry::new(entry.id, new_key));
+            }
+            recipient_key.insert(recipient.clone(), set);
+        }
+        Ok(Self { recipient_key })
+    }
 }
 
 #[allow(dead_code)]
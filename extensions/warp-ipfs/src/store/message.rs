@@ -51,7 +51,7 @@ use crate::rt::{AbortableJoinHandle, Executor, LocalExecutor};
 use warp::raygun::{ConversationImage, GroupPermissionOpt};
 use warp::{
     constellation::ConstellationProgressStream,
-    crypto::DID,
+    crypto::{cipher::CipherSuite, DID},
     error::Error,
     multipass::MultiPassEventKind,
     raygun::{
@@ -61,7 +61,7 @@ use warp::{
     },
 };
 
-const CHAT_DIRECTORY: &str = "chat_media";
+pub(crate) const CHAT_DIRECTORY: &str = "chat_media";
 
 pub type DownloadStream = BoxStream<'static, Result<Bytes, std::io::Error>>;
 
@@ -79,6 +79,7 @@ impl MessageStore {
         event: EventSubscription<RayGunEventKind>,
         identity: &IdentityStore,
         message_command: mpsc::Sender<MessageCommand>,
+        cipher_suite: CipherSuite,
     ) -> Self {
         let executor = LocalExecutor;
         tracing::info!("Initializing MessageStore");
@@ -98,6 +99,7 @@ impl MessageStore {
             event,
             message_command,
             queue: Default::default(),
+            cipher_suite,
             executor,
         };
 
@@ -132,6 +134,12 @@ impl MessageStore {
 }
 
 impl MessageStore {
+    /// Start tasks for conversations that were added to the root document from another device
+    pub async fn sync_conversations(&self) {
+        let inner = &mut *self.inner.write().await;
+        inner.sync_conversations().await
+    }
+
     pub async fn get_conversation(&self, id: Uuid) -> Result<Conversation, Error> {
         let document = self.get(id).await?;
         Ok(document.into())
@@ -647,6 +655,7 @@ impl MessageStore {
         conversation_id: Uuid,
         message_id: Uuid,
         file: &str,
+        size: Option<u32>,
     ) -> Result<DownloadStream, Error> {
         let inner = &*self.inner.read().await;
         let conversation_meta = inner
@@ -660,6 +669,7 @@ impl MessageStore {
             .send(ConversationTaskCommand::DownloadAttachmentStream {
                 message_id,
                 file: file.to_owned(),
+                size,
                 response: tx,
             })
             .await;
@@ -920,6 +930,11 @@ impl ConversationTask {
                         }
                     };
 
+                    if let Err(e) = self.identity.verify_sender(&payload).await {
+                        tracing::warn!(%sender, error = %e, "payload was rejected");
+                        continue;
+                    }
+
                     let data = match ecdh_decrypt(self.identity.root_document().keypair(), Some(&sender), payload.message()) {
                         Ok(d) => d,
                         Err(e) => {
@@ -969,6 +984,7 @@ struct ConversationInner {
     message_command: mpsc::Sender<MessageCommand>,
     // Note: Temporary
     queue: HashMap<DID, Vec<Queue>>,
+    cipher_suite: CipherSuite,
     executor: LocalExecutor,
 }
 
@@ -1019,6 +1035,26 @@ impl ConversationInner {
         }
     }
 
+    async fn sync_conversations(&mut self) {
+        let mut stream = self.list_stream().await;
+        while let Some(conversation) = stream.next().await {
+            let conversation_id = conversation.id();
+
+            if self.conversation_task.contains_key(&conversation_id) {
+                continue;
+            }
+
+            if let Err(e) = self.create_conversation_task(conversation_id).await {
+                tracing::error!(id = %conversation_id, error = %e, "Failed to load conversation");
+                continue;
+            }
+
+            self.event
+                .emit(RayGunEventKind::ConversationCreated { conversation_id })
+                .await;
+        }
+    }
+
     async fn create_conversation_task(&mut self, conversation_id: Uuid) -> Result<(), Error> {
         let (ctx, crx) = mpsc::channel(256);
 
@@ -1032,6 +1068,7 @@ impl ConversationInner {
             crx,
             self.message_command.clone(),
             self.event.clone(),
+            self.cipher_suite,
         )
         .await?;
 
@@ -1105,7 +1142,8 @@ impl ConversationInner {
 
         let bytes = ecdh_encrypt(self.root.keypair(), Some(did), serde_json::to_vec(&event)?)?;
 
-        let payload = PayloadBuilder::new(self.root.keypair(), bytes)
+        let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(self.root.keypair())
             .from_ipfs(&self.ipfs)
             .await?;
 
@@ -1224,7 +1262,8 @@ impl ConversationInner {
         for (did, peer_id) in peer_id_list {
             let bytes = ecdh_encrypt(self.root.keypair(), Some(&did), &event)?;
 
-            let payload = PayloadBuilder::new(self.root.keypair(), bytes)
+            let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+                .cosign(self.root.keypair())
                 .from_ipfs(&self.ipfs)
                 .await?;
 
@@ -1413,7 +1452,8 @@ impl ConversationInner {
 
         let bytes = ecdh_encrypt(keypair, Some(did), serde_json::to_vec(&request)?)?;
 
-        let payload = PayloadBuilder::new(keypair, bytes)
+        let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(keypair)
             .from_ipfs(&self.ipfs)
             .await?;
 
@@ -1497,7 +1537,8 @@ impl ConversationInner {
                     let keypair = self.root.keypair();
                     let bytes = ecdh_encrypt(keypair, Some(&recipient), &event)?;
 
-                    let payload = PayloadBuilder::new(keypair, bytes)
+                    let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+                        .cosign(keypair)
                         .from_ipfs(&self.ipfs)
                         .await?;
 
@@ -1598,7 +1639,8 @@ impl ConversationInner {
 
         let bytes = ecdh_encrypt(keypair, Some(did_key), &event)?;
 
-        let payload = PayloadBuilder::new(keypair, bytes)
+        let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(keypair)
             .from_ipfs(&self.ipfs)
             .await?;
 
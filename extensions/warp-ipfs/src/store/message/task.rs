@@ -33,7 +33,10 @@ use warp::raygun::{
     Messages, MessagesType, RayGunEventKind,
 };
 use warp::{
-    crypto::{cipher::Cipher, generate},
+    crypto::{
+        cipher::{Cipher, CipherSuite},
+        generate,
+    },
     error::Error,
     raygun::{
         ConversationType, GroupPermission, ImplGroupPermissions, MessageEventKind, PinState,
@@ -62,7 +65,7 @@ use crate::{
     store::{
         conversation::ConversationDocument,
         document::root::RootDocumentMap,
-        ecdh_decrypt, ecdh_encrypt,
+        ecdh_decrypt, ecdh_encrypt, ecdh_encrypt_with_suite,
         files::FileStore,
         identity::IdentityStore,
         keystore::Keystore,
@@ -172,6 +175,7 @@ pub enum ConversationTaskCommand {
     DownloadAttachmentStream {
         message_id: Uuid,
         file: String,
+        size: Option<u32>,
         response: oneshot::Sender<Result<DownloadStream, Error>>,
     },
     SendEvent {
@@ -256,6 +260,8 @@ pub struct ConversationTask {
     //TODO: replace queue
     queue: HashMap<DID, Vec<QueueItem>>,
 
+    cipher_suite: CipherSuite,
+
     terminate: ConversationTermination,
 }
 
@@ -298,6 +304,7 @@ impl ConversationTask {
         command_rx: futures::channel::mpsc::Receiver<ConversationTaskCommand>,
         message_command: futures::channel::mpsc::Sender<MessageCommand>,
         event_subscription: EventSubscription<RayGunEventKind>,
+        cipher_suite: CipherSuite,
     ) -> Result<Self, Error> {
         let document = root.get_conversation_document(conversation_id).await?;
         let main_topic = document.topic();
@@ -334,11 +341,16 @@ impl ConversationTask {
             message_command,
             command_rx,
             queue: Default::default(),
+            cipher_suite,
             terminate: ConversationTermination::default(),
         };
 
         task.keystore = match task.document.conversation_type() {
-            ConversationType::Direct => Keystore::new(),
+            // Note: Direct conversations only have a keystore once the identity key was rotated
+            ConversationType::Direct => root
+                .get_conversation_keystore(conversation_id)
+                .await
+                .unwrap_or_default(),
             ConversationType::Group => {
                 match root.get_conversation_keystore(conversation_id).await {
                     Ok(store) => store,
@@ -734,9 +746,10 @@ impl ConversationTask {
             ConversationTaskCommand::DownloadAttachmentStream {
                 message_id,
                 file,
+                size,
                 response,
             } => {
-                let result = self.download_stream(message_id, &file).await;
+                let result = self.download_stream(message_id, &file, size).await;
                 let _ = response.send(result);
             }
             ConversationTaskCommand::SendEvent { event, response } => {
@@ -895,7 +908,8 @@ impl ConversationTask {
 
         let bytes = ecdh_encrypt(keypair, Some(did_key), &event)?;
 
-        let payload = PayloadBuilder::new(keypair, bytes)
+        let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(keypair)
             .from_ipfs(&self.ipfs)
             .await?;
 
@@ -1019,6 +1033,8 @@ impl ConversationTask {
         let data = PayloadMessage::<Vec<u8>>::from_bytes(&msg.data)?;
         let sender = data.sender().to_did()?;
 
+        self.identity.verify_sender(&data).await?;
+
         let keypair = self.root.keypair();
 
         let own_did = keypair.to_did()?;
@@ -1184,7 +1200,8 @@ impl ConversationTask {
 
         let bytes = ecdh_encrypt(keypair, Some(did), serde_json::to_vec(&request)?)?;
 
-        let payload = PayloadBuilder::new(keypair, bytes)
+        let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(keypair)
             .from_ipfs(&self.ipfs)
             .await?;
 
@@ -1298,7 +1315,14 @@ impl ConversationTask {
         let message_id = message.id();
         let keystore = pubkey_or_keystore(&*self)?;
 
-        let message = MessageDocument::new(&self.ipfs, keypair, message, keystore.as_ref()).await?;
+        let message = MessageDocument::new(
+            &self.ipfs,
+            keypair,
+            message,
+            keystore.as_ref(),
+            self.cipher_suite,
+        )
+        .await?;
 
         let message_cid = self
             .document
@@ -1402,7 +1426,15 @@ impl ConversationTask {
         message.set_modified(Utc::now());
 
         message_document
-            .update(&self.ipfs, keypair, message, None, keystore.as_ref(), None)
+            .update(
+                &self.ipfs,
+                keypair,
+                message,
+                None,
+                keystore.as_ref(),
+                None,
+                self.cipher_suite,
+            )
             .await?;
 
         let nonce = message_document.nonce_from_message()?;
@@ -1496,7 +1528,14 @@ impl ConversationTask {
 
         let keystore = pubkey_or_keystore(&*self)?;
 
-        let message = MessageDocument::new(&self.ipfs, keypair, message, keystore.as_ref()).await?;
+        let message = MessageDocument::new(
+            &self.ipfs,
+            keypair,
+            message,
+            keystore.as_ref(),
+            self.cipher_suite,
+        )
+        .await?;
 
         let message_id = message.id;
 
@@ -1622,7 +1661,15 @@ impl ConversationTask {
         };
 
         message_document
-            .update(&self.ipfs, keypair, message, None, keystore.as_ref(), None)
+            .update(
+                &self.ipfs,
+                keypair,
+                message,
+                None,
+                keystore.as_ref(),
+                None,
+                self.cipher_suite,
+            )
             .await?;
 
         let message_cid = self
@@ -1713,7 +1760,15 @@ impl ConversationTask {
                 entry.push(own_did.clone());
 
                 message_document
-                    .update(&self.ipfs, keypair, message, None, keystore.as_ref(), None)
+                    .update(
+                        &self.ipfs,
+                        keypair,
+                        message,
+                        None,
+                        keystore.as_ref(),
+                        None,
+                        self.cipher_suite,
+                    )
                     .await?;
 
                 message_cid = self
@@ -1747,7 +1802,15 @@ impl ConversationTask {
                 };
 
                 message_document
-                    .update(&self.ipfs, keypair, message, None, keystore.as_ref(), None)
+                    .update(
+                        &self.ipfs,
+                        keypair,
+                        message,
+                        None,
+                        keystore.as_ref(),
+                        None,
+                        self.cipher_suite,
+                    )
                     .await?;
 
                 message_cid = self
@@ -1825,9 +1888,12 @@ impl ConversationTask {
 
         let key = self.conversation_key(None)?;
 
-        let bytes = Cipher::direct_encrypt(&event, &key)?;
+        let bytes = Cipher::from(&key)
+            .with_suite(self.cipher_suite)
+            .encrypt(&event, None)?;
 
-        let payload = PayloadBuilder::new(self.root.keypair(), bytes)
+        let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(self.root.keypair())
             .from_ipfs(&self.ipfs)
             .await?;
 
@@ -2183,12 +2249,24 @@ impl ConversationTask {
                 }
 
                 let document = FileDocument::new(&self.ipfs, &file).await?;
-                let cid = document
-                    .reference
-                    .as_ref()
-                    .and_then(|reference| IpfsPath::from_str(reference).ok())
-                    .and_then(|path| path.root().cid().copied())
-                    .ok_or(Error::OtherWithContext("invalid reference".into()))?;
+                let cid = match file.encryption_key() {
+                    // Note: Files encrypted at rest cannot be read by the members of the conversation,
+                    //       so the decrypted contents are stored instead
+                    Some(_) => {
+                        let bytes = self.file.get_buffer(&path).await?;
+                        let path = self.ipfs.add_unixfs(bytes).pin(false).await?;
+                        path.root()
+                            .cid()
+                            .copied()
+                            .ok_or(Error::OtherWithContext("invalid reference".into()))?
+                    }
+                    None => document
+                        .reference
+                        .as_ref()
+                        .and_then(|reference| IpfsPath::from_str(reference).ok())
+                        .and_then(|path| path.root().cid().copied())
+                        .ok_or(Error::OtherWithContext("invalid reference".into()))?,
+                };
 
                 (cid, document.size, extension)
             }
@@ -2251,6 +2329,7 @@ impl ConversationTask {
             link: cid,
             size: size as _,
             mime: ext,
+            encrypted: false,
         };
 
         let cid = self.ipfs.put_dag(dag).await?;
@@ -2460,6 +2539,7 @@ impl ConversationTask {
         let keystore = pubkey_or_keystore(&*self)?;
         let ipfs = self.ipfs.clone();
         let own_did = self.identity.did_key();
+        let cipher_suite = self.cipher_suite;
 
         let keypair = keypair.clone();
 
@@ -2474,6 +2554,33 @@ impl ConversationTask {
 
             for file in files {
                 let kind = LocationKind::from(&file);
+
+                // Note: Files encrypted at rest cannot be read by the members of the conversation, so the contents
+                //       are uploaded to the chat media directory instead of referencing the file directly
+                let file = match file {
+                    Location::Constellation { path } => {
+                        let encrypted = constellation
+                            .root_directory()
+                            .get_item_by_path(&path)
+                            .and_then(|item| item.get_file())
+                            .ok()
+                            .filter(|file| file.encryption_key().is_some());
+
+                        match encrypted {
+                            Some(f) => match constellation.get_stream(&path).await {
+                                Ok(stream) => Location::Stream { name: f.name(), size: Some(f.size()), stream },
+                                Err(e) => {
+                                    let name = f.name();
+                                    streams.insert(kind, stream::once(async { (Progression::ProgressFailed { name, last_size: None, error: e }, None) }).boxed());
+                                    continue;
+                                }
+                            },
+                            None => Location::Constellation { path },
+                        }
+                    }
+                    location => location,
+                };
+
                 match file {
                     Location::Constellation { path } => {
                         match constellation
@@ -2482,6 +2589,12 @@ impl ConversationTask {
                             .and_then(|item| item.get_file())
                         {
                             Ok(f) => {
+                                // Files uploaded before a thumbnailer existed for their type would be missing a preview
+                                if f.thumbnail_reference().is_none() {
+                                    if let Err(e) = constellation.sync_ref(&path).await {
+                                        tracing::debug!(%conversation_id, error = %e, "Unable to generate preview for {path}");
+                                    }
+                                }
                                 streams.insert(kind, stream::once(async { (Progression::ProgressComplete { name: f.name(), total: Some(f.size()) }, Some(f)) }).boxed());
                             },
                             Err(e) => {
@@ -2549,7 +2662,7 @@ impl ConversationTask {
                         let stream = async_stream::stream! {
                             while let Some(item) = progress.next().await {
                                 match item {
-                                    item @ Progression::CurrentProgress { .. } => {
+                                    item @ (Progression::CurrentProgress { .. } | Progression::MetadataRemoved { .. }) => {
                                         yield (item, None);
                                     },
                                     item @ Progression::ProgressComplete { .. } => {
@@ -2637,7 +2750,7 @@ impl ConversationTask {
                             let stream = async_stream::stream! {
                                 while let Some(item) = progress.next().await {
                                     match item {
-                                        item @ Progression::CurrentProgress { .. } => {
+                                        item @ (Progression::CurrentProgress { .. } | Progression::MetadataRemoved { .. }) => {
                                             yield (item, None);
                                         },
                                         item @ Progression::ProgressComplete { .. } => {
@@ -2683,7 +2796,8 @@ impl ConversationTask {
                     message.set_replied(reply_id);
 
                     let message =
-                        MessageDocument::new(&ipfs, &keypair, message, keystore.as_ref()).await?;
+                        MessageDocument::new(&ipfs, &keypair, message, keystore.as_ref(), cipher_suite)
+                            .await?;
 
                     let (tx, rx) = oneshot::channel();
                     _ = atx.send((message, tx)).await;
@@ -2780,6 +2894,7 @@ impl ConversationTask {
         &self,
         message_id: Uuid,
         file: &str,
+        size: Option<u32>,
     ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
         let members = self
             .document
@@ -2803,7 +2918,7 @@ impl ConversationTask {
             .find(|attachment| attachment.name == file)
             .ok_or(Error::FileNotFound)?;
 
-        let stream = attachment.download_stream(&self.ipfs, &members, None);
+        let stream = attachment.download_stream(&self.ipfs, &members, size, None);
 
         Ok(stream)
     }
@@ -2820,9 +2935,12 @@ impl ConversationTask {
 
         let key = self.conversation_key(None)?;
 
-        let bytes = Cipher::direct_encrypt(&event, &key)?;
+        let bytes = Cipher::from(&key)
+            .with_suite(self.cipher_suite)
+            .encrypt(&event, None)?;
 
-        let payload = PayloadBuilder::new(keypair, bytes)
+        let payload = PayloadBuilder::new(self.ipfs.keypair(), bytes)
+            .cosign(keypair)
             .from_ipfs(&self.ipfs)
             .await?;
 
@@ -3120,6 +3238,7 @@ async fn message_event(
                     (!signature.is_empty() && sender.ne(&own_did)).then_some(signature),
                     keystore.as_ref(),
                     Some(nonce.as_slice()),
+                    this.cipher_suite,
                 )
                 .await?;
 
@@ -3204,7 +3323,15 @@ async fn message_event(
             };
 
             message_document
-                .update(&this.ipfs, keypair, message, None, keystore.as_ref(), None)
+                .update(
+                    &this.ipfs,
+                    keypair,
+                    message,
+                    None,
+                    keystore.as_ref(),
+                    None,
+                    this.cipher_suite,
+                )
                 .await?;
 
             this.document
@@ -3255,7 +3382,15 @@ async fn message_event(
                     entry.push(reactor.clone());
 
                     message_document
-                        .update(&this.ipfs, keypair, message, None, keystore.as_ref(), None)
+                        .update(
+                            &this.ipfs,
+                            keypair,
+                            message,
+                            None,
+                            keystore.as_ref(),
+                            None,
+                            this.cipher_suite,
+                        )
                         .await?;
 
                     this.document
@@ -3294,7 +3429,15 @@ async fn message_event(
                     };
 
                     message_document
-                        .update(&this.ipfs, keypair, message, None, keystore.as_ref(), None)
+                        .update(
+                            &this.ipfs,
+                            keypair,
+                            message,
+                            None,
+                            keystore.as_ref(),
+                            None,
+                            this.cipher_suite,
+                        )
                         .await?;
 
                     this.document
@@ -3545,6 +3688,8 @@ async fn process_request_response_event(
 
     let sender = payload.sender().to_did()?;
 
+    this.identity.verify_sender(&payload).await?;
+
     let data = ecdh_decrypt(keypair, Some(&sender), payload.message())?;
 
     let event = serde_json::from_slice::<ConversationRequestResponse>(&data)?;
@@ -3583,7 +3728,8 @@ async fn process_request_response_event(
                     }
                 };
 
-                let key = ecdh_encrypt(keypair, Some(&sender), raw_key)?;
+                let key =
+                    ecdh_encrypt_with_suite(keypair, Some(&sender), raw_key, this.cipher_suite)?;
 
                 let response = ConversationRequestResponse::Response {
                     conversation_id,
@@ -3592,9 +3738,15 @@ async fn process_request_response_event(
 
                 let topic = this.document.exchange_topic(&sender);
 
-                let bytes = ecdh_encrypt(keypair, Some(&sender), serde_json::to_vec(&response)?)?;
+                let bytes = ecdh_encrypt_with_suite(
+                    keypair,
+                    Some(&sender),
+                    serde_json::to_vec(&response)?,
+                    this.cipher_suite,
+                )?;
 
-                let payload = PayloadBuilder::new(keypair, bytes)
+                let payload = PayloadBuilder::new(this.ipfs.keypair(), bytes)
+                    .cosign(keypair)
                     .from_ipfs(&this.ipfs)
                     .await?;
 
@@ -3717,6 +3869,8 @@ async fn process_conversation_event(
     let payload = PayloadMessage::<Vec<u8>>::from_bytes(&message.data)?;
     let sender = payload.sender().to_did()?;
 
+    this.identity.verify_sender(&payload).await?;
+
     let key = this.conversation_key(Some(&sender))?;
 
     let data = Cipher::direct_decrypt(payload.message(), &key)?;
@@ -3866,7 +4020,11 @@ fn pubkey_or_keystore(conversation: &ConversationTask) -> Result<Either<DID, Key
                 .cloned()
                 .ok_or(Error::InvalidConversation)?;
 
-            Either::Left(member)
+            // Keys exchanged with the previous identity key are kept in the keystore after it was rotated
+            match conversation.keystore.exist(&own_did) {
+                true => Either::Right(conversation.keystore.clone()),
+                false => Either::Left(member),
+            }
         }
         ConversationType::Group => Either::Right(conversation.keystore.clone()),
     };
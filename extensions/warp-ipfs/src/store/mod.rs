@@ -19,7 +19,7 @@ use uuid::Uuid;
 use ipfs::{libp2p::identity::KeyType, Keypair, PeerId, PublicKey};
 use warp::{
     crypto::{
-        cipher::Cipher,
+        cipher::{Cipher, CipherSuite},
         did_key::{Generate, ECDH},
         hash::sha256_hash,
         zeroize::Zeroizing,
@@ -37,6 +37,12 @@ pub const MAX_IMAGE_SIZE: usize = 2_097_152;
 pub const MIN_USERNAME_LENGTH: usize = 4;
 pub const MAX_USERNAME_LENGTH: usize = 64;
 pub const MAX_STATUS_LENGTH: usize = 512;
+pub const MAX_PRESENCE_EMOJI_LENGTH: usize = 32;
+pub const MAX_DEVICES: usize = 16;
+pub const MAX_DEVICE_NAME_LENGTH: usize = 64;
+pub const MAX_CONTACT_NOTES_LENGTH: usize = 1_024;
+pub const MAX_CONTACT_GROUPS: usize = 32;
+pub const MAX_CONTACT_GROUP_NAME_LENGTH: usize = 64;
 pub const MIN_MESSAGE_SIZE: usize = 1;
 pub const MAX_MESSAGE_SIZE: usize = 4_096;
 pub const MAX_ATTACHMENT: usize = 32;
@@ -44,10 +50,15 @@ pub const MIN_ATTACHMENT: usize = 1;
 pub const MAX_CONVERSATIONS: usize = 1_000;
 pub const MAX_FRIENDS: usize = 1_000;
 pub const MAX_REQUEST: usize = 1_000;
+pub const MAX_REQUEST_MESSAGE_LENGTH: usize = 256;
 pub const MAX_METADATA_KEY_LENGTH: usize = 32;
 pub const MAX_METADATA_VALUE_LENGTH: usize = 128;
 pub const MAX_METADATA_ENTRIES: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 64;
+pub const MAX_ITEM_TAGS: usize = 32;
 pub const MAX_THUMBNAIL_STREAM_SIZE: usize = 20 * 1024 * 1024;
+pub const MAX_CONTENT_INDEX_SIZE: usize = 1024 * 1024;
+pub const MAX_CONTENT_INDEX_CACHE_SIZE: usize = 32 * 1024 * 1024;
 pub const MAX_CONVERSATION_ICON_SIZE: usize = 4 * 1024 * 1024;
 pub const MAX_CONVERSATION_BANNER_SIZE: usize = 8 * 1024 * 1024;
 
@@ -68,6 +79,9 @@ pub(super) mod topics {
     /// Topic to announce identity updates to the network
     pub const IDENTITY_ANNOUNCEMENT: &str = "/identity/announce/v0";
 
+    /// Topic to announce revoked identity keys to the network
+    pub const IDENTITY_REVOCATION: &str = "/identity/revoke/v0";
+
     pub trait PeerTopic: Display {
         fn inbox(&self) -> String {
             format!("/id/{self}/inbox")
@@ -116,6 +130,10 @@ pub(super) mod ds_key {
             self.base() + "/cache"
         }
 
+        fn revoked(&self) -> String {
+            self.base() + "/revoked"
+        }
+
         fn messaging_queue(&self) -> String {
             self.base() + "/messaging_queue"
         }
@@ -153,6 +171,9 @@ pub(super) mod ds_key {
     }
 }
 const SHUTTLE_TIMEOUT: Duration = Duration::from_secs(60);
+const PRESENCE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const REQUEST_EXPIRY_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+const RECOVERY_REQUEST_EXPIRY: Duration = Duration::from_secs(60 * 60 * 24);
 
 pub trait PeerIdExt {
     fn to_public_key(&self) -> Result<PublicKey, anyhow::Error>;
@@ -472,8 +493,19 @@ pub(crate) fn ecdh_encrypt<K: AsRef<[u8]>>(
     recipient: Option<&DID>,
     data: K,
 ) -> Result<Vec<u8>, Error> {
-    let prik = Zeroizing::new(ecdh_shared_key(did, recipient)?);
-    let data = Cipher::direct_encrypt(data.as_ref(), &prik)?;
+    ecdh_encrypt_with_suite(did, recipient, data, CipherSuite::default())
+}
+
+pub(crate) fn ecdh_encrypt_with_suite<K: AsRef<[u8]>>(
+    keypair: &Keypair,
+    recipient: Option<&DID>,
+    data: K,
+    suite: CipherSuite,
+) -> Result<Vec<u8>, Error> {
+    let prik = Zeroizing::new(ecdh_shared_key(keypair, recipient)?);
+    let data = Cipher::from(prik.as_slice())
+        .with_suite(suite)
+        .encrypt(data.as_ref(), None)?;
 
     Ok(data)
 }
@@ -574,6 +606,30 @@ pub async fn connected_to_peer<I: Into<PeerType>>(
     })
 }
 
+/// JSON encoding of the value with the keys of every object sorted, so the encoding of maps does not depend
+/// on the order of their entries, as is the case for a `HashMap`
+pub(crate) fn canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>, Error> {
+    fn sort(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries = map
+                    .into_iter()
+                    .map(|(key, value)| (key, sort(value)))
+                    .collect::<Vec<_>>();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                serde_json::Value::Object(entries.into_iter().collect())
+            }
+            serde_json::Value::Array(list) => {
+                serde_json::Value::Array(list.into_iter().map(sort).collect())
+            }
+            value => value,
+        }
+    }
+
+    let value = sort(serde_json::to_value(value)?);
+    serde_json::to_vec(&value).map_err(Error::from)
+}
+
 pub fn extract_data_slice<const N: usize>(data: &[u8]) -> (&[u8], &[u8]) {
     let extracted = &data[data.len() - N..];
     let payload = &data[..data.len() - N];
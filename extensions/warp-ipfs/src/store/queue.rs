@@ -289,7 +289,9 @@ impl QueueEntry {
 
                             let bytes = ecdh_encrypt(kp, Some(&recipient), payload_bytes)?;
 
-                            let message = PayloadBuilder::new(kp, bytes).build()?;
+                            let message = PayloadBuilder::new(entry.ipfs.keypair(), bytes)
+                                .cosign(kp)
+                                .build()?;
 
                             let message_bytes = message.to_bytes()?;
 
@@ -0,0 +1,175 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, Rgb, RgbImage};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::DecoderOptions,
+    errors::Error as DecodeError,
+    formats::FormatOptions,
+    io::{MediaSourceStream, MediaSourceStreamOptions},
+    meta::MetadataOptions,
+    probe::Hint,
+};
+use warp::error::Error;
+
+use super::{encode_preview, Thumbnailer};
+use crate::utils::ExtensionType;
+
+const WAVEFORM_WIDTH: u32 = 512;
+const WAVEFORM_HEIGHT: u32 = 256;
+const BAR_WIDTH: u32 = 4;
+const BAR_GAP: u32 = 2;
+
+/// Number of frames that are reduced into a single peak while decoding, keeping memory bounded
+/// regardless of the length of the audio
+const FRAMES_PER_PEAK: usize = 1024;
+
+const BACKGROUND: Rgb<u8> = Rgb([32, 34, 48]);
+const WAVE: Rgb<u8> = Rgb([96, 160, 255]);
+
+/// Waveform of the audio, decoded using symphonia
+pub struct AudioThumbnailer;
+
+impl Thumbnailer for AudioThumbnailer {
+    fn render(
+        &self,
+        data: &[u8],
+        extension: ExtensionType,
+        width: u32,
+        height: u32,
+        _: bool,
+    ) -> Result<(ExtensionType, Vec<u8>), Error> {
+        let peaks = decode_peaks(data, extension)?;
+        let waveform = render_waveform(&peaks, WAVEFORM_WIDTH, WAVEFORM_HEIGHT);
+        encode_preview(DynamicImage::ImageRgb8(waveform), width, height)
+    }
+}
+
+/// Decode the audio, returning the peak amplitude of every [`FRAMES_PER_PEAK`] frames
+fn decode_peaks(data: &[u8], extension: ExtensionType) -> Result<Vec<f32>, Error> {
+    let source = MediaSourceStream::new(
+        Box::new(Cursor::new(data.to_vec())),
+        MediaSourceStreamOptions::default(),
+    );
+
+    let mut hint = Hint::new();
+    hint.mime_type(&extension.to_string());
+
+    let probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .map_err(anyhow::Error::from)?;
+
+    let mut format = probed.format;
+
+    let track = format
+        .default_track()
+        .ok_or(Error::OtherWithContext("no audio track found".into()))?;
+    let track_id = track.id;
+
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions::default())
+        .map_err(anyhow::Error::from)?;
+
+    let mut peaks = vec![];
+    let mut peak = 0f32;
+    let mut frames = 0;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(DecodeError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(anyhow::Error::from(e).into()),
+        };
+
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(decoded) => decoded,
+            // Skip over corrupted packets
+            Err(DecodeError::DecodeError(_)) => continue,
+            Err(e) => return Err(anyhow::Error::from(e).into()),
+        };
+
+        let spec = *decoded.spec();
+        let channels = spec.channels.count().max(1);
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        buffer.copy_interleaved_ref(decoded);
+
+        for frame in buffer.samples().chunks(channels) {
+            peak = frame
+                .iter()
+                .fold(peak, |peak, sample| peak.max(sample.abs()));
+            frames += 1;
+            if frames == FRAMES_PER_PEAK {
+                peaks.push(peak);
+                peak = 0.0;
+                frames = 0;
+            }
+        }
+    }
+
+    if frames > 0 {
+        peaks.push(peak);
+    }
+
+    if peaks.is_empty() {
+        return Err(Error::OtherWithContext("audio contains no samples".into()));
+    }
+
+    Ok(peaks)
+}
+
+/// Draw the peaks as vertical bars mirrored around the center of the image
+fn render_waveform(peaks: &[f32], width: u32, height: u32) -> RgbImage {
+    let mut image = RgbImage::from_pixel(width, height, BACKGROUND);
+
+    let bars = (width / (BAR_WIDTH + BAR_GAP)).max(1) as usize;
+    let center = height / 2;
+
+    for bar in 0..bars {
+        let start = bar * peaks.len() / bars;
+        let end = ((bar + 1) * peaks.len() / bars)
+            .max(start + 1)
+            .min(peaks.len());
+
+        let amplitude = peaks
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .fold(0f32, |peak, value| peak.max(*value))
+            .min(1.0);
+
+        let half = ((amplitude * center as f32) as u32).max(1);
+        let x = bar as u32 * (BAR_WIDTH + BAR_GAP);
+
+        for py in center.saturating_sub(half)..(center + half).min(height) {
+            for px in x..(x + BAR_WIDTH).min(width) {
+                image.put_pixel(px, py, WAVE);
+            }
+        }
+    }
+
+    image
+}
+
+#[cfg(test)]
+mod test {
+    use super::{render_waveform, BACKGROUND, WAVE};
+
+    #[test]
+    fn waveform_follows_peaks() {
+        let image = render_waveform(&[0.0, 1.0], 12, 10);
+        // The quiet half is a minimal line while the loud half spans the full height
+        assert_eq!(*image.get_pixel(0, 0), BACKGROUND);
+        assert_eq!(*image.get_pixel(0, 5), WAVE);
+        assert_eq!(*image.get_pixel(6, 0), WAVE);
+        assert_eq!(*image.get_pixel(6, 9), WAVE);
+    }
+}
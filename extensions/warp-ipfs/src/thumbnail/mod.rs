@@ -0,0 +1,676 @@
+use bytes::Bytes;
+use image::{
+    codecs::gif::{GifDecoder, GifEncoder, Repeat},
+    AnimationDecoder, DynamicImage, Frame, ImageFormat, ImageReader,
+};
+use rust_ipfs::{Ipfs, IpfsPath};
+use std::{
+    collections::BTreeMap,
+    ffi::OsStr,
+    fmt::Display,
+    hash::Hash,
+    io::{BufRead, Seek},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use std::io::{self, Cursor};
+
+#[allow(unused_imports)]
+use std::{io::ErrorKind, path::Path};
+
+use tokio::sync::Mutex;
+use warp::{
+    constellation::file::FileType,
+    crypto::{cipher::Cipher, zeroize::Zeroizing},
+    error::Error,
+};
+use web_time::Instant;
+
+mod audio;
+mod pdf;
+mod text;
+#[cfg(all(feature = "video-thumbnail", not(target_arch = "wasm32")))]
+mod video;
+
+use crate::rt::{Executor, LocalExecutor};
+use crate::utils::ByteCollection;
+use crate::{store::document::image_dag::ImageDag, utils::ExtensionType};
+
+static GLOBAL_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ThumbnailId(usize);
+
+impl Display for ThumbnailId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl core::ops::Deref for ThumbnailId {
+    type Target = usize;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Default for ThumbnailId {
+    fn default() -> Self {
+        ThumbnailId(GLOBAL_ID.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+type TaskMap = BTreeMap<ThumbnailId, crate::rt::JoinHandle<Result<Thumbnail, Error>>>;
+
+/// Thumbnail stored in ipfs, along with the resized copies of the source when it is an image
+#[derive(Debug, Clone)]
+pub struct Thumbnail {
+    pub format: ExtensionType,
+    pub path: IpfsPath,
+    pub data: Bytes,
+    pub variants: Vec<Variant>,
+}
+
+/// Resized copy of an image. This is not stored so the owner of the file could encrypt it if needed
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub width: u32,
+    pub height: u32,
+    pub format: ExtensionType,
+    pub data: Bytes,
+}
+
+/// Renders a thumbnail for a specific kind of file
+pub trait Thumbnailer: Send + Sync + 'static {
+    /// Render a thumbnail of `data` that fits within `width` and `height`, returning the encoded thumbnail
+    /// and its format. `output_exact` requests the thumbnail to keep the format of the source when possible
+    fn render(
+        &self,
+        data: &[u8],
+        extension: ExtensionType,
+        width: u32,
+        height: u32,
+        output_exact: bool,
+    ) -> Result<(ExtensionType, Vec<u8>), Error>;
+}
+
+/// Collection of [`Thumbnailer`] keyed by mime type (eg `application/pdf`) or by a
+/// wildcard of the top level type (eg `image/*`)
+#[derive(Clone)]
+pub struct ThumbnailerRegistry {
+    thumbnailers: BTreeMap<String, Arc<dyn Thumbnailer>>,
+}
+
+impl Default for ThumbnailerRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+        registry.register("image/*", ImageThumbnailer);
+        registry.register("application/pdf", pdf::PdfThumbnailer);
+        registry.register("text/*", text::TextThumbnailer);
+        registry.register("audio/*", audio::AudioThumbnailer);
+        #[cfg(all(feature = "video-thumbnail", not(target_arch = "wasm32")))]
+        registry.register("video/*", video::VideoThumbnailer);
+        registry
+    }
+}
+
+impl ThumbnailerRegistry {
+    /// Create a registry without any thumbnailers
+    pub fn new() -> Self {
+        Self {
+            thumbnailers: BTreeMap::new(),
+        }
+    }
+
+    /// Register a thumbnailer for the mime type, replacing any that was registered before it
+    pub fn register(&mut self, mime: impl Into<String>, thumbnailer: impl Thumbnailer) {
+        self.thumbnailers
+            .insert(mime.into().to_lowercase(), Arc::new(thumbnailer));
+    }
+
+    /// Find the thumbnailer for the file type. An exact mime type match takes precedence over a wildcard
+    pub fn get(&self, file_type: &FileType) -> Option<Arc<dyn Thumbnailer>> {
+        let FileType::Mime(media) = file_type else {
+            return None;
+        };
+
+        let ty = media.ty().as_str().to_lowercase();
+        let subty = media.subty().as_str().to_lowercase();
+
+        self.thumbnailers
+            .get(&format!("{ty}/{subty}"))
+            .or_else(|| self.thumbnailers.get(&format!("{ty}/*")))
+            .cloned()
+    }
+
+    /// Find the thumbnailer for the extension, erroring if none is registered for it
+    pub fn thumbnailer(&self, extension: ExtensionType) -> Result<Arc<dyn Thumbnailer>, Error> {
+        let file_type = FileType::from(extension);
+        match self.get(&file_type) {
+            Some(thumbnailer) => Ok(thumbnailer),
+            None if file_type == FileType::Generic => Err(Error::Other),
+            None => Err(Error::Unimplemented),
+        }
+    }
+
+    /// Render a thumbnail of the data using the thumbnailer registered for the extension
+    pub fn render(
+        &self,
+        data: &[u8],
+        extension: ExtensionType,
+        width: u32,
+        height: u32,
+        output_exact: bool,
+    ) -> Result<(ExtensionType, Bytes), Error> {
+        let thumbnailer = self.thumbnailer(extension)?;
+        let (ty, data) = thumbnailer.render(data, extension, width, height, output_exact)?;
+        Ok((ty, Bytes::from(data)))
+    }
+}
+
+#[derive(Clone)]
+pub struct ThumbnailGenerator {
+    ipfs: Ipfs,
+    registry: Arc<ThumbnailerRegistry>,
+    variant_sizes: Arc<[u32]>,
+    tasks: Arc<Mutex<TaskMap>>,
+    executor: LocalExecutor,
+}
+
+impl ThumbnailGenerator {
+    pub fn new(ipfs: &Ipfs) -> Self {
+        Self::with_registry(ipfs, ThumbnailerRegistry::default())
+    }
+
+    pub fn with_registry(ipfs: &Ipfs, registry: ThumbnailerRegistry) -> Self {
+        Self {
+            ipfs: ipfs.clone(),
+            registry: Arc::new(registry),
+            variant_sizes: Arc::new([]),
+            tasks: Arc::default(),
+            executor: LocalExecutor,
+        }
+    }
+
+    /// Set the sizes, along the longest side, of the variants produced for images
+    pub fn set_variant_sizes(&mut self, sizes: &[u32]) {
+        self.variant_sizes = Arc::from(sizes);
+    }
+
+    /// Check if there is a thumbnailer for the file name
+    pub fn supports(&self, name: &str) -> bool {
+        let extension = Path::new(name)
+            .extension()
+            .and_then(OsStr::to_str)
+            .map(ExtensionType::from)
+            .unwrap_or(ExtensionType::Other);
+        self.registry.get(&extension.into()).is_some()
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn insert<P: AsRef<Path>>(
+        &self,
+        path: P,
+        width: u32,
+        height: u32,
+        output_exact: bool,
+        max_size: usize,
+        key: Option<&[u8]>,
+    ) -> Result<ThumbnailId, Error> {
+        let path = path.as_ref();
+        if !path.is_file() {
+            return Err(io::Error::from(ErrorKind::NotFound).into());
+        }
+
+        let id = ThumbnailId::default();
+        let own_path = path.to_path_buf();
+
+        let ipfs = self.ipfs.clone();
+        let registry = self.registry.clone();
+        let sizes = self.variant_sizes.clone();
+        let key = key.map(|key| Zeroizing::new(key.to_vec()));
+
+        let handle = self.executor.spawn(async move {
+            let instance = Instant::now();
+            //TODO: Read file header to determine real file type for anything like images, videos and documents.
+            let extension = own_path
+                .extension()
+                .and_then(OsStr::to_str)
+                .map(ExtensionType::from)
+                .unwrap_or(ExtensionType::Other);
+
+            // Avoid reading the file if there is nothing that could render it
+            registry.thumbnailer(extension)?;
+
+            // Note: Like streams, files that exceed the max size will not have a thumbnail generated
+            let size = tokio::fs::metadata(&own_path).await?.len() as usize;
+            if size > max_size {
+                return Err(Error::InvalidLength {
+                    context: "thumbnail source".into(),
+                    current: size,
+                    minimum: None,
+                    maximum: Some(max_size),
+                });
+            }
+
+            let result = tokio::task::spawn_blocking(move || {
+                let data = std::fs::read(own_path)?;
+                render(
+                    &registry,
+                    &sizes,
+                    &data,
+                    extension,
+                    width,
+                    height,
+                    output_exact,
+                )
+            })
+            .await
+            .map_err(anyhow::Error::from)?;
+
+            let stop = instance.elapsed();
+
+            tracing::trace!("Took: {}ms to complete task for {}", stop.as_millis(), id);
+
+            let ((ty, data), variants) = result?;
+
+            store_thumbnail(&ipfs, ty, data, variants, key.as_deref()).await
+        });
+
+        self.tasks.lock().await.insert(id, handle);
+
+        Ok(id)
+    }
+
+    pub async fn insert_stream<
+        N: AsRef<str>,
+        S: Stream<Item = io::Result<Bytes>> + Unpin + Send + 'static,
+    >(
+        &self,
+        name: N,
+        stream: S,
+        width: u32,
+        height: u32,
+        output_exact: bool,
+        max_size: usize,
+        key: Option<&[u8]>,
+    ) -> ThumbnailId {
+        let name = PathBuf::from(name.as_ref());
+
+        // Note: We have a max of 20mb for the thumbnail generation from stream so if a file exceeds this capacity
+        // the thumbnail will not be generated.
+        // TODO: We could probably check the signature of the stream first before deciding what to do with it. If its an invalid
+        //       stream we could error out and prevent any attempts of generating the thumbnail.
+
+        let bytes = ByteCollection::new_with_max_capacity(stream, max_size);
+
+        let id = ThumbnailId::default();
+
+        let ipfs = self.ipfs.clone();
+        let registry = self.registry.clone();
+        let sizes = self.variant_sizes.clone();
+        let key = key.map(|key| Zeroizing::new(key.to_vec()));
+
+        let handle = self.executor.spawn(async move {
+            let instant = Instant::now();
+
+            let extension = name
+                .extension()
+                .and_then(OsStr::to_str)
+                .map(ExtensionType::from)
+                .unwrap_or(ExtensionType::Other);
+
+            // Avoid collecting the stream if there is nothing that could render it
+            registry.thumbnailer(extension)?;
+            let data = bytes.await?;
+            let result = render_blocking(
+                registry,
+                sizes,
+                data,
+                extension,
+                width,
+                height,
+                output_exact,
+            )
+            .await;
+
+            let stop = instant.elapsed();
+
+            let ((ty, data), variants) = result?;
+
+            tracing::trace!("Took: {}ms to complete for {}", stop.as_millis(), id);
+
+            store_thumbnail(&ipfs, ty, data, variants, key.as_deref()).await
+        });
+
+        self.tasks.lock().await.insert(id, handle);
+
+        id
+    }
+
+    pub async fn insert_buffer<S: AsRef<str>>(
+        &self,
+        name: S,
+        buffer: &[u8],
+        width: u32,
+        height: u32,
+        output_exact: bool,
+        key: Option<&[u8]>,
+    ) -> ThumbnailId {
+        let name = PathBuf::from(name.as_ref());
+
+        let buffer = buffer.to_vec();
+
+        let id = ThumbnailId::default();
+
+        let ipfs = self.ipfs.clone();
+        let registry = self.registry.clone();
+        let sizes = self.variant_sizes.clone();
+        let key = key.map(|key| Zeroizing::new(key.to_vec()));
+
+        let handle = self.executor.spawn(async move {
+            let instance = Instant::now();
+
+            let extension = name
+                .extension()
+                .and_then(OsStr::to_str)
+                .map(ExtensionType::from)
+                .unwrap_or(ExtensionType::Other);
+
+            let result = render_blocking(
+                registry,
+                sizes,
+                buffer,
+                extension,
+                width,
+                height,
+                output_exact,
+            )
+            .await;
+
+            let stop = instance.elapsed();
+
+            let ((ty, data), variants) = result?;
+
+            tracing::trace!("Took: {}ms to complete for {}", stop.as_millis(), id);
+
+            store_thumbnail(&ipfs, ty, data, variants, key.as_deref()).await
+        });
+
+        self.tasks.lock().await.insert(id, handle);
+
+        id
+    }
+
+    pub async fn get(&self, id: ThumbnailId) -> Result<Thumbnail, Error> {
+        let task = self.tasks.lock().await.remove(&id);
+        let task = task.ok_or(Error::Other)?;
+        task.await.map_err(anyhow::Error::from)?
+    }
+}
+
+/// Render the thumbnail and the variants of the data
+fn render(
+    registry: &ThumbnailerRegistry,
+    sizes: &[u32],
+    data: &[u8],
+    extension: ExtensionType,
+    width: u32,
+    height: u32,
+    output_exact: bool,
+) -> Result<((ExtensionType, Bytes), Vec<Variant>), Error> {
+    let thumbnail = registry.render(data, extension, width, height, output_exact)?;
+    // The variants are optional, so the thumbnail is kept even if they could not be produced
+    let variants = generate_variants(data, extension, sizes).unwrap_or_else(|e| {
+        tracing::warn!(error = %e, "Unable to generate image variants");
+        vec![]
+    });
+    Ok((thumbnail, variants))
+}
+
+/// Render away from the executor since a thumbnailer can spend a while on large or malformed input
+#[cfg(not(target_arch = "wasm32"))]
+async fn render_blocking<D: AsRef<[u8]> + Send + 'static>(
+    registry: Arc<ThumbnailerRegistry>,
+    sizes: Arc<[u32]>,
+    data: D,
+    extension: ExtensionType,
+    width: u32,
+    height: u32,
+    output_exact: bool,
+) -> Result<((ExtensionType, Bytes), Vec<Variant>), Error> {
+    tokio::task::spawn_blocking(move || {
+        render(
+            &registry,
+            &sizes,
+            data.as_ref(),
+            extension,
+            width,
+            height,
+            output_exact,
+        )
+    })
+    .await
+    .map_err(anyhow::Error::from)?
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn render_blocking<D: AsRef<[u8]>>(
+    registry: Arc<ThumbnailerRegistry>,
+    sizes: Arc<[u32]>,
+    data: D,
+    extension: ExtensionType,
+    width: u32,
+    height: u32,
+    output_exact: bool,
+) -> Result<((ExtensionType, Bytes), Vec<Variant>), Error> {
+    render(
+        &registry,
+        &sizes,
+        data.as_ref(),
+        extension,
+        width,
+        height,
+        output_exact,
+    )
+}
+
+/// Store the thumbnail, encrypting it with the key of the file it belongs to if it has one
+async fn store_thumbnail(
+    ipfs: &Ipfs,
+    ty: ExtensionType,
+    data: Bytes,
+    variants: Vec<Variant>,
+    key: Option<&[u8]>,
+) -> Result<Thumbnail, Error> {
+    let path = match key {
+        Some(key) => {
+            let source = futures::stream::once(futures::future::ready(Ok(data.to_vec())));
+            let stream = Cipher::from_bytes(key)
+                .encrypt_async_stream(source)
+                .map_ok(Bytes::from)
+                .boxed();
+            ipfs.add_unixfs(stream).await?
+        }
+        None => ipfs.add_unixfs(data.clone()).await?,
+    };
+
+    let link = *path.root().cid().expect("valid cid");
+
+    let image_dag = ImageDag {
+        link,
+        size: data.len() as _,
+        mime: ty.into(),
+        encrypted: key.is_some(),
+    };
+
+    let cid = ipfs.put_dag(image_dag).await?;
+
+    Ok(Thumbnail {
+        format: ty,
+        path: IpfsPath::from(cid),
+        data,
+        variants,
+    })
+}
+
+/// Produce a resized copy of the image for each size that is smaller than the image itself.
+/// Images with transparency are encoded as (lossless) webp while everything else is encoded as jpeg,
+/// which is far smaller for photos. Animated images and anything that is not a raster image are skipped
+pub fn generate_variants(
+    data: &[u8],
+    extension: ExtensionType,
+    sizes: &[u32],
+) -> Result<Vec<Variant>, Error> {
+    let format = match ImageFormat::try_from(extension) {
+        Ok(ImageFormat::Gif) | Err(_) => return Ok(vec![]),
+        Ok(format) => format,
+    };
+
+    if sizes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let image = ImageReader::with_format(Cursor::new(data), format)
+        .decode()
+        .map_err(anyhow::Error::from)?;
+
+    let dimension = image.width().max(image.height());
+    let alpha = image.color().has_alpha();
+
+    let mut variants = vec![];
+
+    for &size in sizes.iter().filter(|size| **size > 0 && **size < dimension) {
+        let resized = image.thumbnail(size, size);
+        let (resized, format) = match alpha {
+            true => (
+                DynamicImage::ImageRgba8(resized.into_rgba8()),
+                ImageFormat::WebP,
+            ),
+            false => (
+                DynamicImage::ImageRgb8(resized.into_rgb8()),
+                ImageFormat::Jpeg,
+            ),
+        };
+
+        let mut buffer = Cursor::new(vec![]);
+        resized
+            .write_to(&mut buffer, format)
+            .map_err(anyhow::Error::from)?;
+
+        variants.push(Variant {
+            width: resized.width(),
+            height: resized.height(),
+            format: ExtensionType::try_from(format)?,
+            data: Bytes::from(buffer.into_inner()),
+        });
+    }
+
+    Ok(variants)
+}
+
+/// Encode a rendered preview as a jpeg that fits within `width` and `height`
+pub(crate) fn encode_preview(
+    image: DynamicImage,
+    width: u32,
+    height: u32,
+) -> Result<(ExtensionType, Vec<u8>), Error> {
+    let width = width.min(image.width());
+    let height = height.min(image.height());
+
+    // Jpeg does not support an alpha channel
+    let thumbnail = DynamicImage::ImageRgb8(image.thumbnail(width, height).into_rgb8());
+
+    let mut buffer = Cursor::new(vec![]);
+    thumbnail
+        .write_to(&mut buffer, ImageFormat::Jpeg)
+        .map_err(anyhow::Error::from)?;
+    Ok((ExtensionType::JPG, buffer.into_inner()))
+}
+
+/// Thumbnails for images that can be decoded by the `image` crate, including animated gifs
+pub struct ImageThumbnailer;
+
+impl Thumbnailer for ImageThumbnailer {
+    fn render(
+        &self,
+        data: &[u8],
+        extension: ExtensionType,
+        width: u32,
+        height: u32,
+        output_exact: bool,
+    ) -> Result<(ExtensionType, Vec<u8>), Error> {
+        let format: ImageFormat = extension.try_into()?;
+        let output_format = match (output_exact, format) {
+            (false, _) => ImageFormat::Jpeg,
+            (true, format) => format,
+        };
+        let t_buffer = generate_thumbnail(Cursor::new(data), output_format, width, height)?;
+        Ok((
+            ExtensionType::try_from(output_format)?,
+            t_buffer.into_inner(),
+        ))
+    }
+}
+
+pub fn generate_thumbnail<R: BufRead + Seek>(
+    data: R,
+    output_format: ImageFormat,
+    width: u32,
+    height: u32,
+) -> Result<Cursor<Vec<u8>>, anyhow::Error> {
+    let mut t_buffer = Cursor::new(vec![]);
+    if output_format == ImageFormat::Gif {
+        let decoder = GifDecoder::new(data)?;
+        let frames = decoder.into_frames().collect_frames()?;
+        let frames = frames.iter().map(|frame| {
+            let buffer = frame.buffer().clone();
+            let width = width.min(buffer.width());
+            let height = height.min(buffer.height());
+            let img = DynamicImage::ImageRgba8(buffer).thumbnail(width, height);
+            Frame::from_parts(img.into(), frame.left(), frame.top(), frame.delay())
+        });
+        let mut encoder = GifEncoder::new(&mut t_buffer);
+        encoder.set_repeat(Repeat::Infinite)?;
+        encoder.encode_frames(frames)?;
+    } else {
+        let image = ImageReader::new(data).with_guessed_format()?.decode()?;
+
+        let width = width.min(image.width());
+        let height = height.min(image.height());
+
+        let thumbnail = image.thumbnail(width, height);
+
+        thumbnail.write_to(&mut t_buffer, output_format)?;
+    }
+    Ok(t_buffer)
+}
+
+#[cfg(test)]
+mod test {
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use std::io::Cursor;
+
+    use super::generate_variants;
+    use crate::utils::ExtensionType;
+
+    #[test]
+    fn variants_smaller_than_image() -> anyhow::Result<()> {
+        let mut buffer = Cursor::new(vec![]);
+        DynamicImage::ImageRgb8(RgbImage::new(300, 200)).write_to(&mut buffer, ImageFormat::Png)?;
+
+        let variants = generate_variants(buffer.get_ref(), ExtensionType::PNG, &[64, 256, 1024])?;
+        assert_eq!(variants.len(), 2);
+        assert_eq!((variants[0].width, variants[0].height), (64, 43));
+        assert_eq!(variants[1].width, 256);
+        assert!(variants.iter().all(|v| v.format == ExtensionType::JPG));
+
+        assert!(generate_variants(buffer.get_ref(), ExtensionType::TXT, &[64])?.is_empty());
+        Ok(())
+    }
+}
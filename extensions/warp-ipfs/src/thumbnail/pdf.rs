@@ -0,0 +1,1154 @@
+use std::collections::HashMap;
+
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use lopdf::{content::Content, Dictionary, Document, Object, ObjectId, Stream};
+use tiny_skia::{
+    Color, FillRule, FilterQuality, IntSize, Mask, Paint, Path, PathBuilder, Pixmap, PixmapPaint,
+    Rect, Stroke, Transform,
+};
+use warp::error::Error;
+
+use super::{encode_preview, Thumbnailer};
+use crate::utils::ExtensionType;
+
+const PAGE_HEIGHT: u32 = 640;
+
+/// US letter, used when the page does not have a valid media box
+const DEFAULT_MEDIA_BOX: [f32; 4] = [0.0, 0.0, 612.0, 792.0];
+
+/// Limit on nested form xobjects, which guards against forms that draw themselves
+const MAX_FORM_DEPTH: usize = 8;
+
+/// Limit on the operations run for a page, counting each time a form runs its content again
+const MAX_OPERATIONS: usize = 200_000;
+
+/// Share of the operation budget taken by each image, since every draw decodes the image again
+const IMAGE_COST: usize = 4_000;
+
+/// Images with more pixels than this are skipped rather than decoded
+const MAX_IMAGE_PIXELS: u64 = 4096 * 4096;
+
+/// Codes widths can be given for, since two byte codes cannot exceed it
+const MAX_CODE: i64 = u16::MAX as i64;
+
+/// Preview of the first page of a pdf, rendered from the drawing operations of the page.
+///
+/// Note: Paths, colors, clipping and embedded images are drawn. Fonts are not rasterized, so
+///       text is drawn as blocks at the position and size of each glyph. Shadings, patterns and
+///       images in formats other than jpeg or raw samples are left out
+pub struct PdfThumbnailer;
+
+impl Thumbnailer for PdfThumbnailer {
+    fn render(
+        &self,
+        data: &[u8],
+        _: ExtensionType,
+        width: u32,
+        height: u32,
+        _: bool,
+    ) -> Result<(ExtensionType, Vec<u8>), Error> {
+        let document = Document::load_mem(data).map_err(anyhow::Error::from)?;
+        let page = render_first_page(&document, PAGE_HEIGHT)?;
+        encode_preview(DynamicImage::ImageRgba8(page), width, height)
+    }
+}
+
+/// Render the first page of the document with the given height, keeping the proportions of the page
+pub(super) fn render_first_page(document: &Document, height: u32) -> Result<RgbaImage, Error> {
+    let page_id = document
+        .get_pages()
+        .into_values()
+        .next()
+        .ok_or(Error::OtherWithContext("pdf has no pages".into()))?;
+
+    let [left, bottom, right, top] = media_box(document, page_id).unwrap_or(DEFAULT_MEDIA_BOX);
+    let scale = height as f32 / (top - bottom);
+    let width = (((right - left) * scale) as u32).clamp(height / 4, height * 4);
+
+    let mut pixmap = Pixmap::new(width, height).ok_or(Error::Other)?;
+    pixmap.fill(Color::WHITE);
+
+    // Pdf places the origin at the bottom left of the page while the pixmap places it at the top left
+    let base = Transform::from_row(scale, 0.0, 0.0, -scale, -left * scale, top * scale);
+
+    let resources = inherited(document, page_id, b"Resources")
+        .and_then(|object| object.as_dict().ok())
+        .cloned()
+        .unwrap_or_default();
+
+    // A page that cannot be fully decoded is still drawn with whatever was read up to that point
+    let content = document
+        .get_page_content(page_id)
+        .ok()
+        .and_then(|content| Content::decode(&content).ok())
+        .map(|content| content.operations)
+        .unwrap_or_default();
+
+    let mut canvas = Canvas {
+        document,
+        pixmap: &mut pixmap,
+        base,
+        fonts: HashMap::new(),
+        budget: MAX_OPERATIONS,
+    };
+
+    canvas.draw(&content, &resources, GraphicsState::default(), 0);
+
+    let image = RgbaImage::from_raw(width, height, pixmap.take()).ok_or(Error::Other)?;
+    Ok(image)
+}
+
+/// Look up a key on the page, following the page tree for inherited attributes
+fn inherited<'a>(document: &'a Document, page_id: ObjectId, key: &[u8]) -> Option<&'a Object> {
+    let mut dictionary = document.get_dictionary(page_id).ok()?;
+    // Note: The depth is limited to guard against a malformed page tree that references itself
+    for _ in 0..32 {
+        if let Ok(object) = dictionary.get(key) {
+            return document.dereference(object).ok().map(|(_, object)| object);
+        }
+        let parent = dictionary
+            .get(b"Parent")
+            .and_then(Object::as_reference)
+            .ok()?;
+        dictionary = document.get_dictionary(parent).ok()?;
+    }
+    None
+}
+
+/// Bounds of the page, as left, bottom, right and top
+fn media_box(document: &Document, page_id: ObjectId) -> Option<[f32; 4]> {
+    let values = numbers(inherited(document, page_id, b"MediaBox")?.as_array().ok()?);
+    let [x1, y1, x2, y2] = values[..] else {
+        return None;
+    };
+    let bounds = [x1.min(x2), y1.min(y2), x1.max(x2), y1.max(y2)];
+    (bounds[2] - bounds[0] > 0.0 && bounds[3] - bounds[1] > 0.0).then_some(bounds)
+}
+
+fn numbers(operands: &[Object]) -> Vec<f32> {
+    operands
+        .iter()
+        .filter_map(|object| object.as_float().ok())
+        .collect()
+}
+
+fn matrix(values: &[f32]) -> Option<Transform> {
+    let [a, b, c, d, e, f] = values[..] else {
+        return None;
+    };
+    let transform = Transform::from_row(a, b, c, d, e, f);
+    transform.is_finite().then_some(transform)
+}
+
+fn rgba(color: Color) -> [u8; 4] {
+    let color = color.to_color_u8();
+    [color.red(), color.green(), color.blue(), color.alpha()]
+}
+
+/// Convert the components of a color, picking the color space from the number of components
+fn color(components: &[f32]) -> Option<Color> {
+    let clamp = |value: f32| value.clamp(0.0, 1.0);
+    match *components {
+        [gray] => Color::from_rgba(clamp(gray), clamp(gray), clamp(gray), 1.0),
+        [r, g, b] => Color::from_rgba(clamp(r), clamp(g), clamp(b), 1.0),
+        [c, m, y, k] => Color::from_rgba(
+            (1.0 - clamp(c)) * (1.0 - clamp(k)),
+            (1.0 - clamp(m)) * (1.0 - clamp(k)),
+            (1.0 - clamp(y)) * (1.0 - clamp(k)),
+            1.0,
+        ),
+        _ => None,
+    }
+}
+
+#[derive(Clone)]
+struct GraphicsState {
+    ctm: Transform,
+    fill: Color,
+    stroke: Color,
+    line_width: f32,
+    clip: Option<Mask>,
+    text: TextState,
+}
+
+impl Default for GraphicsState {
+    fn default() -> Self {
+        Self {
+            ctm: Transform::identity(),
+            fill: Color::BLACK,
+            stroke: Color::BLACK,
+            line_width: 1.0,
+            clip: None,
+            text: TextState::default(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct TextState {
+    font: Vec<u8>,
+    size: f32,
+    char_spacing: f32,
+    word_spacing: f32,
+    horizontal_scale: f32,
+    leading: f32,
+    rise: f32,
+    mode: i64,
+}
+
+impl Default for TextState {
+    fn default() -> Self {
+        Self {
+            font: vec![],
+            size: 0.0,
+            char_spacing: 0.0,
+            word_spacing: 0.0,
+            horizontal_scale: 1.0,
+            leading: 0.0,
+            rise: 0.0,
+            mode: 0,
+        }
+    }
+}
+
+/// Glyph widths of a font, in thousandths of the font size
+struct FontMetrics {
+    two_byte: bool,
+    widths: HashMap<u32, f32>,
+    default_width: f32,
+}
+
+impl FontMetrics {
+    fn load(document: &Document, font: &Dictionary) -> Self {
+        let deref = |object: &Object| -> Option<Object> {
+            document
+                .dereference(object)
+                .ok()
+                .map(|(_, object)| object.clone())
+        };
+
+        let mut widths = HashMap::new();
+
+        if font.get(b"Subtype").and_then(Object::as_name).ok() == Some(b"Type0".as_slice()) {
+            let descendant = font
+                .get(b"DescendantFonts")
+                .ok()
+                .and_then(deref)
+                .and_then(|fonts| fonts.as_array().ok()?.first().cloned())
+                .and_then(|font| deref(&font))
+                .and_then(|font| font.as_dict().ok().cloned())
+                .unwrap_or_default();
+
+            let default_width = descendant
+                .get(b"DW")
+                .and_then(Object::as_float)
+                .unwrap_or(1000.0);
+
+            // The widths are given as either `first [w1 w2 ...]` or `first last w`
+            let list = descendant
+                .get(b"W")
+                .ok()
+                .and_then(deref)
+                .and_then(|list| list.as_array().ok().cloned())
+                .unwrap_or_default();
+            let mut entries = list.iter();
+            while let Some(first) = entries.next().and_then(|first| first.as_i64().ok()) {
+                match entries.next().and_then(deref) {
+                    Some(Object::Array(list)) => {
+                        for (code, width) in (first..=MAX_CODE).zip(numbers(&list)) {
+                            if code >= 0 {
+                                widths.insert(code as u32, width);
+                            }
+                        }
+                    }
+                    Some(last) => {
+                        let (Ok(last), Some(Ok(width))) =
+                            (last.as_i64(), entries.next().map(Object::as_float))
+                        else {
+                            break;
+                        };
+                        for code in first.max(0)..=last.min(MAX_CODE) {
+                            widths.insert(code as u32, width);
+                        }
+                    }
+                    None => break,
+                }
+            }
+
+            return Self {
+                two_byte: true,
+                widths,
+                default_width,
+            };
+        }
+
+        let first_char = font
+            .get(b"FirstChar")
+            .and_then(Object::as_i64)
+            .unwrap_or_default();
+        let list = font
+            .get(b"Widths")
+            .ok()
+            .and_then(deref)
+            .and_then(|list| list.as_array().ok().map(|list| numbers(list)))
+            .unwrap_or_default();
+        for (code, width) in (first_char..=MAX_CODE).zip(list) {
+            if code >= 0 {
+                widths.insert(code as u32, width);
+            }
+        }
+
+        // Standard fonts do not have to list their widths, so an average width is used
+        Self {
+            two_byte: false,
+            widths,
+            default_width: 500.0,
+        }
+    }
+
+    fn codes(&self, text: &[u8]) -> Vec<u32> {
+        match self.two_byte {
+            true => text
+                .chunks(2)
+                .map(|code| code.iter().fold(0, |code, byte| code << 8 | *byte as u32))
+                .collect(),
+            false => text.iter().map(|code| *code as u32).collect(),
+        }
+    }
+
+    fn width(&self, code: u32) -> f32 {
+        self.widths
+            .get(&code)
+            .copied()
+            .unwrap_or(self.default_width)
+            / 1000.0
+    }
+}
+
+struct Canvas<'a> {
+    document: &'a Document,
+    pixmap: &'a mut Pixmap,
+    base: Transform,
+    fonts: HashMap<Vec<u8>, FontMetrics>,
+    /// Operations left to run before drawing stops
+    budget: usize,
+}
+
+impl Canvas<'_> {
+    fn draw(
+        &mut self,
+        operations: &[lopdf::content::Operation],
+        resources: &Dictionary,
+        state: GraphicsState,
+        depth: usize,
+    ) {
+        let mut state = state;
+        let mut stack: Vec<GraphicsState> = vec![];
+        let mut path = PathBuilder::new();
+        let mut pending_clip: Option<FillRule> = None;
+        let mut text_matrix = Transform::identity();
+        let mut line_matrix = Transform::identity();
+
+        // Fonts are cached by name, which is only unique within a single resource dictionary
+        if depth > 0 {
+            self.fonts.clear();
+        }
+
+        for operation in operations {
+            // Forms that draw each other many times over would otherwise keep the page drawing
+            // long after the nesting limit was reached
+            let Some(budget) = self.budget.checked_sub(1) else {
+                return;
+            };
+            self.budget = budget;
+
+            let operands = numbers(&operation.operands);
+            match operation.operator.as_str() {
+                "q" => stack.push(state.clone()),
+                "Q" => {
+                    if let Some(previous) = stack.pop() {
+                        state = previous;
+                    }
+                }
+                "cm" => {
+                    if let Some(transform) = matrix(&operands) {
+                        state.ctm = state.ctm.pre_concat(transform);
+                    }
+                }
+                "w" => {
+                    if let Some(width) = operands.first() {
+                        state.line_width = width.abs();
+                    }
+                }
+                "g" | "rg" | "k" | "sc" | "scn" => {
+                    if let Some(color) = color(&operands) {
+                        state.fill = color;
+                    }
+                }
+                "G" | "RG" | "K" | "SC" | "SCN" => {
+                    if let Some(color) = color(&operands) {
+                        state.stroke = color;
+                    }
+                }
+                "cs" => state.fill = Color::BLACK,
+                "CS" => state.stroke = Color::BLACK,
+                "m" => {
+                    if let [x, y] = operands[..] {
+                        path.move_to(x, y);
+                    }
+                }
+                "l" => {
+                    if let [x, y] = operands[..] {
+                        path.line_to(x, y);
+                    }
+                }
+                "c" => {
+                    if let [x1, y1, x2, y2, x, y] = operands[..] {
+                        path.cubic_to(x1, y1, x2, y2, x, y);
+                    }
+                }
+                "v" => {
+                    if let (Some(start), [x2, y2, x, y]) = (path.last_point(), &operands[..]) {
+                        path.cubic_to(start.x, start.y, *x2, *y2, *x, *y);
+                    }
+                }
+                "y" => {
+                    if let [x1, y1, x, y] = operands[..] {
+                        path.cubic_to(x1, y1, x, y, x, y);
+                    }
+                }
+                "h" => path.close(),
+                "re" => {
+                    if let [x, y, width, height] = operands[..] {
+                        let (x, width) = (x.min(x + width), width.abs());
+                        let (y, height) = (y.min(y + height), height.abs());
+                        if let Some(rect) = Rect::from_xywh(x, y, width, height) {
+                            path.push_rect(rect);
+                        }
+                    }
+                }
+                "W" => pending_clip = Some(FillRule::Winding),
+                "W*" => pending_clip = Some(FillRule::EvenOdd),
+                operator @ ("f" | "F" | "f*" | "S" | "s" | "B" | "B*" | "b" | "b*" | "n") => {
+                    if matches!(operator, "s" | "b" | "b*") {
+                        path.close();
+                    }
+                    let Some(finished) = std::mem::take(&mut path).finish() else {
+                        pending_clip = None;
+                        continue;
+                    };
+                    let even_odd = operator.ends_with('*');
+                    let fill_rule = match even_odd {
+                        true => FillRule::EvenOdd,
+                        false => FillRule::Winding,
+                    };
+                    if matches!(operator, "f" | "F" | "f*" | "B" | "B*" | "b" | "b*") {
+                        self.fill(&finished, fill_rule, &state);
+                    }
+                    if matches!(operator, "S" | "s" | "B" | "B*" | "b" | "b*") {
+                        self.stroke(&finished, &state);
+                    }
+                    // The clip takes effect after the path is painted
+                    if let Some(fill_rule) = pending_clip.take() {
+                        self.clip(&finished, fill_rule, &mut state);
+                    }
+                }
+                "BT" => {
+                    text_matrix = Transform::identity();
+                    line_matrix = Transform::identity();
+                }
+                "Tf" => {
+                    if let [name, size] = &operation.operands[..] {
+                        state.text.font = name.as_name().map(<[u8]>::to_vec).unwrap_or_default();
+                        state.text.size = size.as_float().unwrap_or_default();
+                    }
+                }
+                "Tc" => state.text.char_spacing = operands.first().copied().unwrap_or_default(),
+                "Tw" => state.text.word_spacing = operands.first().copied().unwrap_or_default(),
+                "Tz" => {
+                    state.text.horizontal_scale = operands.first().copied().unwrap_or(100.0) / 100.0
+                }
+                "TL" => state.text.leading = operands.first().copied().unwrap_or_default(),
+                "Ts" => state.text.rise = operands.first().copied().unwrap_or_default(),
+                "Tr" => {
+                    state.text.mode = operation
+                        .operands
+                        .first()
+                        .and_then(|mode| mode.as_i64().ok())
+                        .unwrap_or_default()
+                }
+                operator @ ("Td" | "TD") => {
+                    if let [x, y] = operands[..] {
+                        if operator == "TD" {
+                            state.text.leading = -y;
+                        }
+                        line_matrix = line_matrix.pre_concat(Transform::from_translate(x, y));
+                        text_matrix = line_matrix;
+                    }
+                }
+                "Tm" => {
+                    if let Some(transform) = matrix(&operands) {
+                        line_matrix = transform;
+                        text_matrix = transform;
+                    }
+                }
+                "T*" => {
+                    line_matrix =
+                        line_matrix.pre_concat(Transform::from_translate(0.0, -state.text.leading));
+                    text_matrix = line_matrix;
+                }
+                operator @ ("Tj" | "'" | "\"" | "TJ") => {
+                    if operator == "\"" {
+                        if let [word_spacing, char_spacing, ..] = operands[..] {
+                            state.text.word_spacing = word_spacing;
+                            state.text.char_spacing = char_spacing;
+                        }
+                    }
+                    if matches!(operator, "'" | "\"") {
+                        line_matrix = line_matrix
+                            .pre_concat(Transform::from_translate(0.0, -state.text.leading));
+                        text_matrix = line_matrix;
+                    }
+                    let parts = match operation.operands.last() {
+                        Some(Object::Array(parts)) if operator == "TJ" => parts.as_slice(),
+                        Some(text) => std::slice::from_ref(text),
+                        None => continue,
+                    };
+                    self.show_text(parts, resources, &state, &mut text_matrix);
+                }
+                "Do" => {
+                    let Some(name) = operation
+                        .operands
+                        .first()
+                        .and_then(|name| name.as_name().ok())
+                    else {
+                        continue;
+                    };
+                    self.draw_xobject(name, resources, &state, depth);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn transform(&self, state: &GraphicsState) -> Transform {
+        self.base.pre_concat(state.ctm)
+    }
+
+    fn fill(&mut self, path: &Path, fill_rule: FillRule, state: &GraphicsState) {
+        let mut paint = Paint::default();
+        paint.set_color(state.fill);
+        paint.anti_alias = true;
+        let transform = self.transform(state);
+        self.pixmap
+            .fill_path(path, &paint, fill_rule, transform, state.clip.as_ref());
+    }
+
+    fn stroke(&mut self, path: &Path, state: &GraphicsState) {
+        let mut paint = Paint::default();
+        paint.set_color(state.stroke);
+        paint.anti_alias = true;
+        let stroke = Stroke {
+            width: state.line_width,
+            ..Default::default()
+        };
+        let transform = self.transform(state);
+        self.pixmap
+            .stroke_path(path, &paint, &stroke, transform, state.clip.as_ref());
+    }
+
+    fn clip(&mut self, path: &Path, fill_rule: FillRule, state: &mut GraphicsState) {
+        let transform = self.transform(state);
+        match state.clip.as_mut() {
+            Some(mask) => mask.intersect_path(path, fill_rule, true, transform),
+            None => {
+                let Some(mut mask) = Mask::new(self.pixmap.width(), self.pixmap.height()) else {
+                    return;
+                };
+                mask.fill_path(path, fill_rule, true, transform);
+                state.clip = Some(mask);
+            }
+        }
+    }
+
+    fn font(&mut self, resources: &Dictionary, name: &[u8]) -> &FontMetrics {
+        let document = self.document;
+        self.fonts.entry(name.to_vec()).or_insert_with(|| {
+            let font = resources
+                .get_deref(b"Font", document)
+                .and_then(Object::as_dict)
+                .and_then(|fonts| fonts.get_deref(name, document))
+                .and_then(Object::as_dict)
+                .cloned()
+                .unwrap_or_default();
+            FontMetrics::load(document, &font)
+        })
+    }
+
+    /// Draw each glyph as a block covering the lower part of its advance, which roughly matches the
+    /// space taken up by lowercase letters
+    fn show_text(
+        &mut self,
+        parts: &[Object],
+        resources: &Dictionary,
+        state: &GraphicsState,
+        text_matrix: &mut Transform,
+    ) {
+        let text = &state.text;
+        let font = self.font(resources, &text.font);
+
+        let mut glyphs = vec![];
+        let mut position = *text_matrix;
+
+        for part in parts {
+            match part {
+                Object::String(bytes, _) => {
+                    for code in font.codes(bytes) {
+                        let width = font.width(code);
+                        if code != 32 && width > 0.0 {
+                            glyphs.push((position, width));
+                        }
+                        let word_spacing = match !font.two_byte && code == 32 {
+                            true => text.word_spacing,
+                            false => 0.0,
+                        };
+                        let advance = (width * text.size + text.char_spacing + word_spacing)
+                            * text.horizontal_scale;
+                        position = position.pre_concat(Transform::from_translate(advance, 0.0));
+                    }
+                }
+                adjustment => {
+                    if let Ok(adjustment) = adjustment.as_float() {
+                        let advance = -adjustment / 1000.0 * text.size * text.horizontal_scale;
+                        position = position.pre_concat(Transform::from_translate(advance, 0.0));
+                    }
+                }
+            }
+        }
+
+        *text_matrix = position;
+
+        // Invisible text, such as the text layer of a scanned page, is only used for selection
+        if text.mode == 3 || text.mode == 7 {
+            return;
+        }
+
+        let mut path = PathBuilder::new();
+        for (position, width) in glyphs {
+            let glyph = Transform::from_row(
+                text.size * text.horizontal_scale,
+                0.0,
+                0.0,
+                text.size,
+                0.0,
+                text.rise,
+            );
+            let Some(rect) = Rect::from_xywh(width * 0.1, 0.0, width * 0.8, 0.5) else {
+                continue;
+            };
+            let Some(rect) = PathBuilder::from_rect(rect).transform(position.pre_concat(glyph))
+            else {
+                continue;
+            };
+            path.push_path(&rect);
+        }
+
+        let Some(path) = path.finish() else {
+            return;
+        };
+
+        // Blocks are drawn lighter than the ink so that lines of text do not read as solid bars
+        let mut paint = Paint::default();
+        let mut ink = state.fill;
+        ink.apply_opacity(0.45);
+        paint.set_color(ink);
+        paint.anti_alias = true;
+        let transform = self.transform(state);
+        self.pixmap.fill_path(
+            &path,
+            &paint,
+            FillRule::Winding,
+            transform,
+            state.clip.as_ref(),
+        );
+    }
+
+    fn draw_xobject(
+        &mut self,
+        name: &[u8],
+        resources: &Dictionary,
+        state: &GraphicsState,
+        depth: usize,
+    ) {
+        let document = self.document;
+        let Ok(stream) = resources
+            .get_deref(b"XObject", document)
+            .and_then(Object::as_dict)
+            .and_then(|xobjects| xobjects.get_deref(name, document))
+            .and_then(Object::as_stream)
+        else {
+            return;
+        };
+
+        match stream.dict.get(b"Subtype").and_then(Object::as_name) {
+            Ok(b"Image") => {
+                let Some(budget) = self.budget.checked_sub(IMAGE_COST) else {
+                    self.budget = 0;
+                    return;
+                };
+                self.budget = budget;
+
+                let Some(image) = decode_image(document, stream, state.fill) else {
+                    return;
+                };
+                // Images are drawn into the unit square, with the first row at the top
+                let (width, height) = (image.width() as f32, image.height() as f32);
+                let transform = self.transform(state).pre_concat(Transform::from_row(
+                    1.0 / width,
+                    0.0,
+                    0.0,
+                    -1.0 / height,
+                    0.0,
+                    1.0,
+                ));
+                let paint = PixmapPaint {
+                    quality: FilterQuality::Bilinear,
+                    ..Default::default()
+                };
+                self.pixmap.draw_pixmap(
+                    0,
+                    0,
+                    image.as_ref(),
+                    &paint,
+                    transform,
+                    state.clip.as_ref(),
+                );
+            }
+            Ok(b"Form") if depth < MAX_FORM_DEPTH => {
+                let Ok(content) = stream
+                    .get_plain_content()
+                    .and_then(|content| Content::decode(&content))
+                else {
+                    return;
+                };
+
+                let mut state = state.clone();
+                if let Some(transform) = stream
+                    .dict
+                    .get(b"Matrix")
+                    .and_then(Object::as_array)
+                    .ok()
+                    .and_then(|values| matrix(&numbers(values)))
+                {
+                    state.ctm = state.ctm.pre_concat(transform);
+                }
+
+                // Forms without their own resources use the resources of the page
+                let form_resources = stream
+                    .dict
+                    .get_deref(b"Resources", document)
+                    .and_then(Object::as_dict)
+                    .unwrap_or(resources)
+                    .clone();
+
+                self.draw(&content.operations, &form_resources, state, depth + 1);
+
+                // The cached fonts belong to the form resources
+                self.fonts.clear();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Decode an image xobject into a pixmap. Image masks are painted with the fill color
+fn decode_image(document: &Document, stream: &Stream, fill: Color) -> Option<Pixmap> {
+    let dict = &stream.dict;
+    let width = dict.get(b"Width").and_then(Object::as_i64).ok()?;
+    let height = dict.get(b"Height").and_then(Object::as_i64).ok()?;
+    if width <= 0 || height <= 0 || (width as u64) * (height as u64) > MAX_IMAGE_PIXELS {
+        return None;
+    }
+    let (width, height) = (width as u32, height as u32);
+
+    let filters = stream.filters().unwrap_or_default();
+
+    let mut image = match filters.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["DCTDecode"] => image::load_from_memory_with_format(&stream.content, ImageFormat::Jpeg)
+            .ok()?
+            .into_rgba8(),
+        _ => {
+            let data = without_image_subtype(stream).get_plain_content().ok()?;
+            decode_samples(document, dict, &data, width, height, fill)?
+        }
+    };
+
+    if image.dimensions() != (width, height) {
+        return None;
+    }
+
+    if let Some(alpha) = dict
+        .get(b"SMask")
+        .and_then(Object::as_reference)
+        .and_then(|id| document.get_object(id))
+        .and_then(Object::as_stream)
+        .ok()
+        .and_then(|mask| {
+            let data = without_image_subtype(mask).get_plain_content().ok()?;
+            let mask_width = mask.dict.get(b"Width").and_then(Object::as_i64).ok()?;
+            let mask_height = mask.dict.get(b"Height").and_then(Object::as_i64).ok()?;
+            let bits = mask
+                .dict
+                .get(b"BitsPerComponent")
+                .and_then(Object::as_i64)
+                .ok()?;
+            ((mask_width, mask_height, bits) == (width as i64, height as i64, 8)
+                && data.len() >= (width * height) as usize)
+                .then_some(data)
+        })
+    {
+        for (pixel, alpha) in image.pixels_mut().zip(alpha) {
+            pixel.0[3] = ((pixel.0[3] as u16 * alpha as u16) / 255) as u8;
+        }
+    }
+
+    // The pixmap holds premultiplied colors
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let premultiply = |value: u8| ((value as u16 * a as u16) / 255) as u8;
+        pixel.0 = [premultiply(r), premultiply(g), premultiply(b), a];
+    }
+
+    Pixmap::from_vec(image.into_raw(), IntSize::from_wh(width, height)?)
+}
+
+/// `lopdf` refuses to decode the content of images, though the filters used are the same as any
+/// other stream
+fn without_image_subtype(stream: &Stream) -> Stream {
+    let mut stream = stream.clone();
+    stream.dict.remove(b"Subtype");
+    stream
+}
+
+/// Convert uncompressed image samples to rgba according to the color space of the image
+fn decode_samples(
+    document: &Document,
+    dict: &Dictionary,
+    data: &[u8],
+    width: u32,
+    height: u32,
+    fill: Color,
+) -> Option<RgbaImage> {
+    let image_mask = dict
+        .get(b"ImageMask")
+        .and_then(Object::as_bool)
+        .unwrap_or_default();
+
+    let bits = match image_mask {
+        true => 1,
+        false => dict
+            .get(b"BitsPerComponent")
+            .and_then(Object::as_i64)
+            .ok()?,
+    };
+    if !matches!(bits, 1 | 2 | 4 | 8 | 16) {
+        return None;
+    }
+
+    let space = match image_mask {
+        true => ColorSpace::Gray,
+        false => ColorSpace::load(document, dict.get(b"ColorSpace").ok()?, 0)?,
+    };
+
+    let components = space.components();
+    let row_bytes = (width as usize * components * bits as usize).div_ceil(8);
+    if data.len() < row_bytes * height as usize {
+        return None;
+    }
+
+    let max = ((1u32 << bits) - 1) as f32;
+    let sample = |row: &[u8], index: usize| -> u32 {
+        match bits {
+            8 => row[index] as u32,
+            16 => (row[index * 2] as u32) << 8 | row[index * 2 + 1] as u32,
+            _ => {
+                let bit = index * bits as usize;
+                let byte = row[bit / 8] as u32;
+                (byte >> (8 - bits as usize - bit % 8)) & ((1 << bits) - 1)
+            }
+        }
+    };
+
+    let fill = rgba(fill);
+    let mut image = RgbaImage::new(width, height);
+    for (y, row) in data.chunks(row_bytes).take(height as usize).enumerate() {
+        for x in 0..width as usize {
+            let pixel = match (&space, image_mask) {
+                // Samples of 0 paint the fill color while samples of 1 leave the page untouched
+                (_, true) => match sample(row, x) {
+                    0 => [fill[0], fill[1], fill[2], 255],
+                    _ => [0, 0, 0, 0],
+                },
+                (ColorSpace::Indexed { base, palette }, _) => {
+                    let index = sample(row, x) as usize * base.components();
+                    let entry = palette.get(index..index + base.components())?;
+                    let values = entry.iter().map(|v| *v as f32 / 255.0).collect::<Vec<_>>();
+                    rgba(color(&values)?)
+                }
+                (space, _) => {
+                    let values = (0..space.components())
+                        .map(|component| sample(row, x * components + component) as f32 / max)
+                        .collect::<Vec<_>>();
+                    rgba(color(&values)?)
+                }
+            };
+            image.put_pixel(x as u32, y as u32, image::Rgba(pixel));
+        }
+    }
+
+    Some(image)
+}
+
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Indexed {
+        base: Box<ColorSpace>,
+        palette: Vec<u8>,
+    },
+}
+
+impl ColorSpace {
+    fn load(document: &Document, object: &Object, depth: usize) -> Option<Self> {
+        // Note: An indexed base cannot be indexed itself, but the depth is still limited to guard
+        //       against a color space that references itself
+        if depth > 2 {
+            return None;
+        }
+        let (_, object) = document.dereference(object).ok()?;
+        match object {
+            Object::Name(name) => match name.as_slice() {
+                b"DeviceGray" | b"CalGray" | b"G" => Some(Self::Gray),
+                b"DeviceRGB" | b"CalRGB" | b"RGB" => Some(Self::Rgb),
+                b"DeviceCMYK" | b"CMYK" => Some(Self::Cmyk),
+                _ => None,
+            },
+            Object::Array(list) => {
+                let family = list.first()?.as_name().ok()?;
+                match family {
+                    b"ICCBased" => {
+                        let (_, profile) = document.dereference(list.get(1)?).ok()?;
+                        let components = profile.as_stream().ok()?.dict.get(b"N").ok()?;
+                        match components.as_i64().ok()? {
+                            1 => Some(Self::Gray),
+                            3 => Some(Self::Rgb),
+                            4 => Some(Self::Cmyk),
+                            _ => None,
+                        }
+                    }
+                    b"Indexed" | b"I" => {
+                        let base = Self::load(document, list.get(1)?, depth + 1)?;
+                        let (_, lookup) = document.dereference(list.get(3)?).ok()?;
+                        let palette = match lookup {
+                            Object::String(bytes, _) => bytes.clone(),
+                            Object::Stream(stream) => stream.get_plain_content().ok()?,
+                            _ => return None,
+                        };
+                        Some(Self::Indexed {
+                            base: Box::new(base),
+                            palette,
+                        })
+                    }
+                    b"CalGray" => Some(Self::Gray),
+                    b"CalRGB" | b"Lab" => Some(Self::Rgb),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn components(&self) -> usize {
+        match self {
+            Self::Gray | Self::Indexed { .. } => 1,
+            Self::Rgb => 3,
+            Self::Cmyk => 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use lopdf::{
+        content::{Content, Operation},
+        dictionary, Document, Object, Stream,
+    };
+
+    use super::{render_first_page, ColorSpace, FontMetrics};
+
+    fn document(operations: Vec<Operation>) -> Document {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let content = Content { operations };
+        let content_id = document.add_object(Stream::new(
+            dictionary! {},
+            content.encode().expect("valid content"),
+        ));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+                "Resources" => dictionary! {
+                    "Font" => dictionary! { "F1" => font_id },
+                },
+                "MediaBox" => vec![0.into(), 0.into(), 200.into(), 100.into()],
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        document
+    }
+
+    #[test]
+    fn renders_paths_and_text() -> anyhow::Result<()> {
+        let mut document = document(vec![
+            // Red square in the bottom left corner of the page
+            Operation::new("rg", vec![1.into(), 0.into(), 0.into()]),
+            Operation::new("re", vec![0.into(), 0.into(), 50.into(), 50.into()]),
+            Operation::new("f", vec![]),
+            // Text along the top of the page
+            Operation::new("BT", vec![]),
+            Operation::new("Tf", vec!["F1".into(), 20.into()]),
+            Operation::new("Td", vec![100.into(), 70.into()]),
+            Operation::new("Tj", vec![Object::string_literal("hello")]),
+            Operation::new("ET", vec![]),
+        ]);
+
+        let mut buffer = vec![];
+        document.save_to(&mut buffer)?;
+        let document = Document::load_mem(&buffer)?;
+
+        let page = render_first_page(&document, 100)?;
+        assert_eq!(page.dimensions(), (200, 100));
+
+        assert_eq!(page.get_pixel(25, 75).0, [255, 0, 0, 255]);
+        assert_eq!(page.get_pixel(75, 25).0, [255, 255, 255, 255]);
+
+        // The glyphs cover the baseline of the text, but not the space above it
+        let row = 100 - 72;
+        assert!((100..150).any(|x| page.get_pixel(x, row).0 != [255, 255, 255, 255]));
+        assert!((0..200).all(|x| page.get_pixel(x, 5).0 == [255, 255, 255, 255]));
+        Ok(())
+    }
+
+    #[test]
+    fn clip_limits_painting() -> anyhow::Result<()> {
+        let document = document(vec![
+            Operation::new("re", vec![0.into(), 0.into(), 100.into(), 100.into()]),
+            Operation::new("W", vec![]),
+            Operation::new("n", vec![]),
+            Operation::new("g", vec![0.into()]),
+            Operation::new("re", vec![0.into(), 0.into(), 200.into(), 100.into()]),
+            Operation::new("f", vec![]),
+        ]);
+
+        let page = render_first_page(&document, 100)?;
+        assert_eq!(page.get_pixel(50, 50).0, [0, 0, 0, 255]);
+        assert_eq!(page.get_pixel(150, 50).0, [255, 255, 255, 255]);
+        Ok(())
+    }
+
+    #[test]
+    fn forms_drawing_themselves_stop_within_budget() -> anyhow::Result<()> {
+        let mut document = document(vec![Operation::new("Do", vec!["X1".into()])]);
+
+        // Without a budget, the form would be drawn 8^8 times before reaching the nesting limit
+        let mut operations = vec![
+            Operation::new("g", vec![0.into()]),
+            Operation::new("re", vec![0.into(), 0.into(), 50.into(), 50.into()]),
+            Operation::new("f", vec![]),
+        ];
+        operations.extend((0..8).map(|_| Operation::new("Do", vec!["X1".into()])));
+        let form_id = document.add_object(Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Form",
+                "BBox" => vec![0.into(), 0.into(), 200.into(), 100.into()],
+            },
+            Content { operations }.encode()?,
+        ));
+
+        let pages_id = document.catalog()?.get(b"Pages")?.as_reference()?;
+        document
+            .get_dictionary_mut(pages_id)?
+            .get_mut(b"Resources")?
+            .as_dict_mut()?
+            .set("XObject", dictionary! { "X1" => form_id });
+
+        let page = render_first_page(&document, 100)?;
+        assert_eq!(page.get_pixel(25, 75).0, [0, 0, 0, 255]);
+        Ok(())
+    }
+
+    #[test]
+    fn self_referencing_color_space_is_rejected() {
+        let mut document = Document::with_version("1.5");
+        let id = document.new_object_id();
+        document.objects.insert(
+            id,
+            Object::Array(vec![
+                "Indexed".into(),
+                id.into(),
+                255.into(),
+                Object::string_literal(""),
+            ]),
+        );
+
+        assert!(ColorSpace::load(&document, &Object::Reference(id), 0).is_none());
+    }
+
+    #[test]
+    fn widths_past_the_code_range_are_ignored() {
+        let document = Document::with_version("1.5");
+        let font = dictionary! {
+            "Subtype" => "Type1",
+            "FirstChar" => i64::MAX - 1,
+            "Widths" => vec![600.into(), 600.into(), 600.into()],
+        };
+
+        let metrics = FontMetrics::load(&document, &font);
+        assert!(metrics.widths.is_empty());
+
+        let font = dictionary! {
+            "Subtype" => "Type0",
+            "DescendantFonts" => vec![Object::Dictionary(dictionary! {
+                "W" => vec![
+                    (i64::MAX - 1).into(),
+                    vec![Object::from(600)].into(),
+                    (i64::MAX - 1).into(),
+                    i64::MAX.into(),
+                    600.into(),
+                ],
+            })],
+        };
+
+        let metrics = FontMetrics::load(&document, &font);
+        assert!(metrics.widths.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use image::{DynamicImage, Rgb, RgbImage};
+use warp::error::Error;
+
+use super::{encode_preview, Thumbnailer};
+use crate::utils::ExtensionType;
+
+const PAGE_WIDTH: u32 = 480;
+const PAGE_HEIGHT: u32 = 640;
+
+/// Number of characters that fit on a single line of the page
+const COLUMNS: u32 = 48;
+
+const PAPER: Rgb<u8> = Rgb([255, 255, 255]);
+const INK: Rgb<u8> = Rgb([150, 150, 150]);
+const HEADING_INK: Rgb<u8> = Rgb([70, 70, 70]);
+
+/// Preview of plain text and markdown, drawn as the layout of the lines on a page
+pub struct TextThumbnailer;
+
+impl Thumbnailer for TextThumbnailer {
+    fn render(
+        &self,
+        data: &[u8],
+        extension: ExtensionType,
+        width: u32,
+        height: u32,
+        _: bool,
+    ) -> Result<(ExtensionType, Vec<u8>), Error> {
+        let text = String::from_utf8_lossy(data);
+        let lines = match extension {
+            ExtensionType::MD => markdown_lines(&text),
+            _ => text.lines().map(Line::text).collect(),
+        };
+        let page = render_page(&lines, PAGE_WIDTH, PAGE_HEIGHT);
+        encode_preview(DynamicImage::ImageRgb8(page), width, height)
+    }
+}
+
+/// A line of text to be drawn onto a page
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Line {
+    pub text: String,
+    pub indent: u32,
+    pub heading: bool,
+}
+
+impl Line {
+    pub fn text(text: &str) -> Self {
+        let trimmed = text.trim_start();
+        Self {
+            indent: (text.len() - trimmed.len()) as u32,
+            text: trimmed.to_string(),
+            heading: false,
+        }
+    }
+}
+
+fn markdown_lines(text: &str) -> Vec<Line> {
+    let mut in_code = false;
+    text.lines()
+        .filter_map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") {
+                in_code = !in_code;
+                return None;
+            }
+            if in_code {
+                return Some(Line::text(line));
+            }
+            if trimmed.starts_with('#') {
+                return Some(Line {
+                    text: trimmed.trim_start_matches('#').trim().to_string(),
+                    indent: 0,
+                    heading: true,
+                });
+            }
+            let mut line = Line::text(line);
+            for marker in ["- ", "* ", "+ ", "> "] {
+                if let Some(text) = line.text.strip_prefix(marker) {
+                    line.text = text.to_string();
+                    line.indent += 2;
+                    break;
+                }
+            }
+            Some(line)
+        })
+        .collect()
+}
+
+/// Draw the lines onto a page, with each word being drawn as a bar the width of the word.
+/// Text would not be legible at the size of a thumbnail, so only the shape of the text is kept
+pub(super) fn render_page(lines: &[Line], width: u32, height: u32) -> RgbImage {
+    let mut page = RgbImage::from_pixel(width, height, PAPER);
+
+    let margin = width / 10;
+    let char_width = ((width - margin * 2) / COLUMNS).max(1);
+    let bottom = height.saturating_sub(margin);
+
+    let mut y = margin;
+
+    for line in lines {
+        let (glyph_height, ink) = match line.heading {
+            true => (char_width * 2, HEADING_INK),
+            false => (char_width + char_width / 2, INK),
+        };
+        let line_height = glyph_height + char_width;
+
+        let indent = line.indent.min(COLUMNS / 2);
+        let mut column = indent;
+
+        for word in line.text.split_whitespace() {
+            let len = word.chars().count() as u32;
+            if column > indent && column + len > COLUMNS {
+                column = indent;
+                y += line_height;
+            }
+            if y + glyph_height > bottom {
+                return page;
+            }
+
+            let x = margin + column * char_width;
+            let word_width = len.min(COLUMNS - column) * char_width;
+            fill(&mut page, x, y, word_width, glyph_height, ink);
+
+            column += len + 1;
+        }
+
+        y += line_height;
+        if y > bottom {
+            break;
+        }
+    }
+
+    page
+}
+
+fn fill(image: &mut RgbImage, x: u32, y: u32, width: u32, height: u32, color: Rgb<u8>) {
+    for py in y..(y + height).min(image.height()) {
+        for px in x..(x + width).min(image.width()) {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{markdown_lines, render_page, Line, PAPER};
+
+    #[test]
+    fn markdown_headings_and_lists() {
+        let lines = markdown_lines("# Title\n\n- item\n```\n# not a heading\n```");
+        assert_eq!(
+            lines,
+            vec![
+                Line {
+                    text: "Title".into(),
+                    indent: 0,
+                    heading: true
+                },
+                Line::text(""),
+                Line {
+                    text: "item".into(),
+                    indent: 2,
+                    heading: false
+                },
+                Line::text("# not a heading"),
+            ]
+        );
+    }
+
+    #[test]
+    fn page_contains_text() {
+        let empty = render_page(&[], 120, 160);
+        assert!(empty.pixels().all(|pixel| *pixel == PAPER));
+
+        let page = render_page(&[Line::text("hello world")], 120, 160);
+        assert!(page.pixels().any(|pixel| *pixel != PAPER));
+    }
+}
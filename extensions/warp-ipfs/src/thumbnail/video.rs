@@ -0,0 +1,127 @@
+use ffmpeg_next::{
+    format::{input, Pixel},
+    media::Type,
+    software::scaling::{Context as Scaler, Flags},
+    util::frame::video::Video,
+};
+use image::{DynamicImage, RgbImage};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{fs::OpenOptions, io::Write, path::PathBuf};
+use warp::error::Error;
+
+use super::{encode_preview, Thumbnailer};
+use crate::utils::ExtensionType;
+
+/// First frame of a video, decoded with ffmpeg.
+///
+/// Note: This requires the ffmpeg libraries to be available on the system, so it is only registered
+///       when the `video-thumbnail` feature is enabled
+pub struct VideoThumbnailer;
+
+impl Thumbnailer for VideoThumbnailer {
+    fn render(
+        &self,
+        data: &[u8],
+        _: ExtensionType,
+        width: u32,
+        height: u32,
+        _: bool,
+    ) -> Result<(ExtensionType, Vec<u8>), Error> {
+        // ffmpeg reads its input from a path, so the data is written to a temporary file first
+        let file = SpooledFile::new(data)?;
+        let frame = first_frame(&file.path)?;
+        drop(file);
+        encode_preview(DynamicImage::ImageRgb8(frame), width, height)
+    }
+}
+
+/// Temporary file only readable by the current user, which is removed once dropped
+/// so the contents are not left behind if decoding fails or panics
+struct SpooledFile {
+    path: PathBuf,
+}
+
+impl SpooledFile {
+    fn new(data: &[u8]) -> std::io::Result<Self> {
+        let path = std::env::temp_dir().join(format!("warp-thumbnail-{}", uuid::Uuid::new_v4()));
+
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+
+        let mut file = options.open(&path)?;
+        let spooled = SpooledFile { path };
+        file.write_all(data)?;
+        Ok(spooled)
+    }
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn first_frame(path: &std::path::Path) -> Result<RgbImage, Error> {
+    ffmpeg_next::init().map_err(anyhow::Error::from)?;
+
+    let mut context = input(&path).map_err(anyhow::Error::from)?;
+
+    let stream = context
+        .streams()
+        .best(Type::Video)
+        .ok_or(Error::OtherWithContext("no video stream found".into()))?;
+    let index = stream.index();
+
+    let mut decoder = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())
+        .and_then(|context| context.decoder().video())
+        .map_err(anyhow::Error::from)?;
+
+    let mut scaler = Scaler::get(
+        decoder.format(),
+        decoder.width(),
+        decoder.height(),
+        Pixel::RGB24,
+        decoder.width(),
+        decoder.height(),
+        Flags::BILINEAR,
+    )
+    .map_err(anyhow::Error::from)?;
+
+    for (stream, packet) in context.packets() {
+        if stream.index() != index {
+            continue;
+        }
+
+        decoder.send_packet(&packet).map_err(anyhow::Error::from)?;
+
+        let mut decoded = Video::empty();
+        if decoder.receive_frame(&mut decoded).is_err() {
+            continue;
+        }
+
+        let mut rgb = Video::empty();
+        scaler
+            .run(&decoded, &mut rgb)
+            .map_err(anyhow::Error::from)?;
+
+        // Rows of the frame may be padded, so only the pixels of each row are copied
+        let (frame_width, frame_height) = (rgb.width(), rgb.height());
+        let stride = rgb.stride(0);
+        let row = frame_width as usize * 3;
+        let pixels = rgb
+            .data(0)
+            .chunks(stride)
+            .take(frame_height as usize)
+            .flat_map(|line| &line[..row])
+            .copied()
+            .collect::<Vec<_>>();
+
+        return RgbImage::from_raw(frame_width, frame_height, pixels)
+            .ok_or(Error::OtherWithContext("invalid video frame".into()));
+    }
+
+    Err(Error::OtherWithContext("video contains no frames".into()))
+}
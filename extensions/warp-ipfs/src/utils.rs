@@ -46,6 +46,16 @@ pub enum ExtensionType {
     DOCX,
     #[display(fmt = "text/plain")]
     TXT,
+    #[display(fmt = "text/markdown")]
+    MD,
+    #[display(fmt = "audio/mpeg")]
+    MP3,
+    #[display(fmt = "audio/wav")]
+    WAV,
+    #[display(fmt = "audio/ogg")]
+    OGG,
+    #[display(fmt = "audio/flac")]
+    FLAC,
     #[display(fmt = "application/octet-stream")]
     Other,
 }
@@ -66,6 +76,11 @@ impl From<&str> for ExtensionType {
             "mkv" => Self::MKV,
             "pdf" => Self::PDF,
             "txt" => Self::TXT,
+            "md" | "markdown" => Self::MD,
+            "mp3" => Self::MP3,
+            "wav" => Self::WAV,
+            "ogg" | "oga" => Self::OGG,
+            "flac" => Self::FLAC,
             "docx" => Self::DOCX,
             "doc" => Self::DOC,
             _ => Self::Other,
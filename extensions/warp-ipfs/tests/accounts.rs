@@ -5,12 +5,18 @@ mod test {
 
     use std::time::Duration;
 
-    use crate::common::{self, create_account, create_accounts};
+    use crate::common::{self, create_account, create_accounts, create_instance, mesh_connect};
     use futures::StreamExt;
+    use rust_ipfs::Ipfs;
+    use uuid::Uuid;
     use warp::constellation::file::FileType;
-    use warp::multipass::identity::{IdentityStatus, IdentityUpdate, Platform};
+    use warp::constellation::Constellation;
+    use warp::crypto::DID;
+    use warp::multipass::identity::{Identity, IdentityStatus, IdentityUpdate, Platform, Presence};
+    use warp::raygun::{GroupPermissions, RayGun, RayGunEventKind, RayGunStream};
     use warp::tesseract::Tesseract;
-    use warp_ipfs::WarpIpfsBuilder;
+    use warp::SingleHandle;
+    use warp_ipfs::{config::Visibility, WarpIpfsBuilder, WarpIpfsInstance};
 
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::wasm_bindgen_test as async_test;
@@ -20,7 +26,10 @@ mod test {
 
     #[cfg(not(target_arch = "wasm32"))]
     use tokio::test as async_test;
-    use warp::multipass::{IdentityInformation, LocalIdentity, MultiPass};
+    use warp::multipass::{
+        BundleImportMode, ExportSection, Friends, IdentityInformation, ImportLocation,
+        LocalIdentity, MultiPass, MultiPassEvent, MultiPassEventKind, MultiPassImportExport,
+    };
 
     #[async_test]
     async fn create_identity() -> anyhow::Result<()> {
@@ -203,6 +212,42 @@ mod test {
         Ok(())
     }
 
+    #[async_test]
+    async fn identity_presence_expires() -> anyhow::Result<()> {
+        let (mut account, did, _) = create_account(
+            Some("JohnDoe"),
+            None,
+            Some("test::identity_presence_expires".into()),
+        )
+        .await?;
+
+        let mut presence = Presence::new(IdentityStatus::Busy);
+        presence.set_emoji(Some("📅".into()));
+        presence.set_text(Some("In a meeting".into()));
+        presence.set_until(Some(chrono::Utc::now() + chrono::Duration::seconds(2)));
+
+        account.set_identity_presence(presence.clone()).await?;
+
+        let current = account.identity_presence(&did).await?;
+        assert_eq!(current, presence);
+        assert_eq!(account.identity_status(&did).await?, IdentityStatus::Busy);
+
+        let current = crate::common::timeout(Duration::from_secs(30), async {
+            loop {
+                if let Ok(current) = account.identity_presence(&did).await {
+                    if current.text().is_none() {
+                        break current;
+                    }
+                }
+                futures_timer::Delay::new(Duration::from_millis(200)).await;
+            }
+        })
+        .await?;
+
+        assert_eq!(current, Presence::new(IdentityStatus::Online));
+        Ok(())
+    }
+
     #[async_test]
     async fn get_identity_status() -> anyhow::Result<()> {
         let accounts = create_accounts(vec![
@@ -252,6 +297,127 @@ mod test {
         Ok(())
     }
 
+    #[async_test]
+    async fn identity_field_visibility() -> anyhow::Result<()> {
+        let (account_a, _, _) = create_account(
+            Some("JohnDoe"),
+            None,
+            Some("test::identity_field_visibility".into()),
+        )
+        .await?;
+
+        let mut config = common::test_config();
+        config.store_setting_mut().visibility.status_message = Visibility::Friends;
+        config.store_setting_mut().visibility.platform = Visibility::Nobody;
+
+        let (mut account_b, did_b, _) =
+            common::create_account_with_config(Some("JaneDoe"), None, config).await?;
+
+        account_b
+            .update_identity(IdentityUpdate::StatusMessage(Some("Friends only".into())))
+            .await?;
+
+        let nodes = [&account_a, &account_b]
+            .into_iter()
+            .map(|account| {
+                account
+                    .handle()
+                    .expect("Handle accessible")
+                    .downcast_ref::<Ipfs>()
+                    .cloned()
+                    .unwrap()
+            })
+            .collect();
+
+        common::mesh_connect(nodes).await?;
+
+        let identity_b = crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Ok(identity) = account_a.get_identity(did_b.clone()).await {
+                    break identity;
+                }
+                futures_timer::Delay::new(Duration::from_millis(200)).await;
+            }
+        })
+        .await?;
+
+        assert_eq!(identity_b.username(), "JaneDoe");
+        assert_eq!(identity_b.status_message(), None);
+        assert!(account_a.identity_platform(&did_b).await.is_err());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn identity_field_visibility_for_friends() -> anyhow::Result<()> {
+        let (account_a, _account_b, did_b) =
+            friends_with_status_message("test::identity_field_visibility_for_friends").await?;
+
+        let identity_b = wait_for_status_message(&account_a, &did_b).await?;
+        assert_eq!(identity_b.status_message().as_deref(), Some("Friends only"));
+        assert!(account_a.identity_platform(&did_b).await.is_err());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn redacted_announcement_does_not_replace_friend_identity() -> anyhow::Result<()> {
+        let (account_a, _account_b, did_b) = friends_with_status_message(
+            "test::redacted_announcement_does_not_replace_friend_identity",
+        )
+        .await?;
+
+        wait_for_status_message(&account_a, &did_b).await?;
+
+        // The identity is announced to the mesh, without the status message, along with every push
+        for _ in 0..10 {
+            futures_timer::Delay::new(Duration::from_millis(500)).await;
+            let identity_b = account_a.get_identity(did_b.clone()).await?;
+            assert_eq!(identity_b.status_message().as_deref(), Some("Friends only"));
+        }
+        Ok(())
+    }
+
+    /// Two connected friends, with the second one having a status message only visible to friends
+    async fn friends_with_status_message(
+        context: &str,
+    ) -> anyhow::Result<(WarpIpfsInstance, WarpIpfsInstance, DID)> {
+        let (mut account_a, _, _) =
+            create_account(Some("JohnDoe"), None, Some(context.into())).await?;
+
+        let mut config = common::test_config();
+        config.store_setting_mut().visibility.status_message = Visibility::Friends;
+        config.store_setting_mut().visibility.platform = Visibility::Nobody;
+
+        let (mut account_b, did_b, _) =
+            common::create_account_with_config(Some("JaneDoe"), None, config).await?;
+
+        account_b
+            .update_identity(IdentityUpdate::StatusMessage(Some("Friends only".into())))
+            .await?;
+
+        mesh_connect(vec![ipfs(&account_a), ipfs(&account_b)]).await?;
+        add_friend(&mut account_a, &mut account_b).await?;
+
+        Ok((account_a, account_b, did_b))
+    }
+
+    async fn wait_for_status_message(
+        account: &WarpIpfsInstance,
+        did: &DID,
+    ) -> anyhow::Result<Identity> {
+        let identity = common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Ok(identity) = account.get_identity(did.clone()).await {
+                    if identity.status_message().is_some() {
+                        break identity;
+                    }
+                }
+                futures_timer::Delay::new(Duration::from_millis(200)).await;
+            }
+        })
+        .await?;
+        Ok(identity)
+    }
+
     #[async_test]
     async fn identity_platform() -> anyhow::Result<()> {
         let (account, did, _) = create_account(
@@ -387,4 +553,361 @@ mod test {
         assert_eq!(platform_b, Platform::Desktop);
         Ok(())
     }
+
+    #[async_test]
+    async fn authorize_and_revoke_device() -> anyhow::Result<()> {
+        let (mut account, did, _) = create_account(
+            Some("JohnDoe"),
+            None,
+            Some("test::authorize_and_revoke_device".into()),
+        )
+        .await?;
+
+        let device = warp::crypto::DID::default();
+
+        assert!(account.list_devices().await?.is_empty());
+
+        account.authorize_device(&device, Some("Laptop")).await?;
+
+        let devices = account.list_devices().await?;
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].did(), &device);
+        assert_eq!(devices[0].name(), Some("Laptop"));
+
+        assert!(account.authorize_device(&did, None).await.is_err());
+
+        account.revoke_device(&device).await?;
+        assert!(account.list_devices().await?.is_empty());
+        assert!(account.revoke_device(&device).await.is_err());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn create_revocation_certificate() -> anyhow::Result<()> {
+        let (mut account, did, _) = create_account(
+            Some("JohnDoe"),
+            None,
+            Some("test::create_revocation_certificate".into()),
+        )
+        .await?;
+
+        let certificate = account
+            .create_revocation_certificate(Some("lost device"))
+            .await?;
+
+        assert_eq!(certificate.did(), &did);
+        assert_eq!(certificate.reason(), Some("lost device"));
+        assert!(!certificate.signature().is_empty());
+
+        // Certificates are published through a shuttle node, which is not used here
+        assert!(account
+            .publish_revocation_certificate(&certificate)
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    // Note: Rotation needs the store to outlive the node, which is not kept in memory
+    #[cfg(not(target_arch = "wasm32"))]
+    #[async_test]
+    async fn rotate_identity_key_and_import() -> anyhow::Result<()> {
+        let path = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let mut config = common::test_config();
+        *config.path_mut() = Some(path.clone());
+        *config.persist_mut() = true;
+
+        let (mut account, did, _) =
+            common::create_account_with_config(Some("JohnDoe"), None, config).await?;
+
+        let profile = account.rotate_identity_key().await?;
+        let phrase = profile
+            .passphrase()
+            .expect("phrase of the new key")
+            .to_string();
+        let new_did = profile.identity().did_key();
+
+        assert_ne!(new_did, did);
+        assert_eq!(
+            warp::crypto::keypair::did_from_mnemonic(&phrase, None)?,
+            new_did
+        );
+        assert_eq!(account.identity().await?.did_key(), new_did);
+
+        let mut buffer = vec![];
+        account
+            .export_identity(ImportLocation::Memory {
+                buffer: &mut buffer,
+            })
+            .await?;
+
+        let mut instance =
+            create_instance(Some("test::rotate_identity_key_and_import".into())).await;
+        let identity = instance
+            .import_identity(warp::multipass::IdentityImportOption::Locate {
+                location: ImportLocation::Memory {
+                    buffer: &mut buffer,
+                },
+                passphrase: phrase.clone(),
+            })
+            .await?;
+        assert_eq!(identity.did_key(), new_did);
+        assert_eq!(identity.username(), "JohnDoe");
+
+        let mut buffer = vec![];
+        account
+            .export_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer,
+                },
+                &ExportSection::all(),
+            )
+            .await?;
+
+        let mut instance =
+            create_instance(Some("test::rotate_identity_key_and_import".into())).await;
+        let identity = instance
+            .import_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer,
+                },
+                &phrase,
+                BundleImportMode::Replace,
+            )
+            .await?;
+        assert_eq!(identity.did_key(), new_did);
+
+        drop(account);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn claim_username_without_registry() -> anyhow::Result<()> {
+        let (mut account, _, _) = create_account(
+            Some("JohnDoe"),
+            None,
+            Some("test::claim_username_without_registry".into()),
+        )
+        .await?;
+
+        // Usernames are claimed in the registry of a shuttle node, which is not used here
+        assert!(account.claim_username().await.is_err());
+
+        let identity = account.identity().await?;
+        assert!(!identity.verified());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn export_and_import_bundle() -> anyhow::Result<()> {
+        let passphrase =
+            "morning caution dose lab six actress pond humble pause enact virtual train";
+        let (mut account, did, _) = create_account(
+            Some("JohnDoe"),
+            Some(passphrase),
+            Some("test::export_and_import_bundle".into()),
+        )
+        .await?;
+
+        let mut buffer = vec![];
+        let manifest = account
+            .export_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer,
+                },
+                &[ExportSection::Friends, ExportSection::Messages],
+            )
+            .await?;
+
+        assert_eq!(manifest.did(), &did);
+        assert!(manifest.contains(ExportSection::Friends));
+        assert!(!manifest.contains(ExportSection::ConversationKeys));
+
+        let mut instance = create_instance(Some("test::export_and_import_bundle".into())).await;
+
+        // Verifying leaves the bundle in place
+        let verified = instance
+            .verify_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer,
+                },
+                passphrase,
+            )
+            .await?;
+        assert_eq!(verified, manifest);
+        assert!(!buffer.is_empty());
+
+        assert!(instance
+            .verify_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer.clone(),
+                },
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+            )
+            .await
+            .is_err());
+
+        // Merging requires an identity to be loaded
+        assert!(instance
+            .import_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer.clone(),
+                },
+                passphrase,
+                BundleImportMode::Merge,
+            )
+            .await
+            .is_err());
+
+        let identity = instance
+            .import_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer.clone(),
+                },
+                passphrase,
+                BundleImportMode::Replace,
+            )
+            .await?;
+        assert_eq!(identity.did_key(), did);
+        assert_eq!(identity.username(), "JohnDoe");
+
+        // Each device goes on with its own friend, conversation and files
+        let (mut friend_a, did_friend_a, _) = create_account(
+            Some("JaneDoe"),
+            None,
+            Some("test::export_and_import_bundle".into()),
+        )
+        .await?;
+        let (mut friend_b, did_friend_b, _) = create_account(
+            Some("JoeDoe"),
+            None,
+            Some("test::export_and_import_bundle".into()),
+        )
+        .await?;
+
+        mesh_connect(vec![ipfs(&account), ipfs(&friend_a)]).await?;
+        mesh_connect(vec![ipfs(&instance), ipfs(&friend_b)]).await?;
+
+        add_friend(&mut account, &mut friend_a).await?;
+        add_friend(&mut instance, &mut friend_b).await?;
+
+        let (conversation_a, message_a) = group_with_message(&mut account, "from a").await?;
+        let (conversation_b, message_b) = group_with_message(&mut instance, "from b").await?;
+
+        account.put_buffer("a.txt", b"a").await?;
+        account.create_directory("a", false).await?;
+        instance.put_buffer("b.txt", b"b").await?;
+        instance.create_directory("b", false).await?;
+
+        let mut buffer = vec![];
+        account
+            .export_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer,
+                },
+                &ExportSection::all(),
+            )
+            .await?;
+
+        let identity = instance
+            .import_bundle(
+                ImportLocation::Memory {
+                    buffer: &mut buffer,
+                },
+                passphrase,
+                BundleImportMode::Merge,
+            )
+            .await?;
+        assert_eq!(identity.did_key(), did);
+
+        assert!(instance.has_friend(&did_friend_a).await?);
+        assert!(instance.has_friend(&did_friend_b).await?);
+
+        let conversations = instance
+            .list_conversations()
+            .await?
+            .iter()
+            .map(|conversation| conversation.id())
+            .collect::<Vec<_>>();
+        assert!(conversations.contains(&conversation_a));
+        assert!(conversations.contains(&conversation_b));
+
+        // The message from the other device can only be read with the imported conversation key
+        let message = instance.get_message(conversation_a, message_a).await?;
+        assert_eq!(message.lines(), vec!["from a".to_string()]);
+        let message = instance.get_message(conversation_b, message_b).await?;
+        assert_eq!(message.lines(), vec!["from b".to_string()]);
+
+        let root = instance.root_directory();
+        for name in ["a.txt", "a", "b.txt", "b"] {
+            assert!(root.has_item(name), "{name} is missing from the index");
+        }
+        Ok(())
+    }
+
+    fn ipfs(instance: &WarpIpfsInstance) -> Ipfs {
+        instance
+            .handle()
+            .expect("Handle accessible")
+            .downcast_ref::<Ipfs>()
+            .cloned()
+            .unwrap()
+    }
+
+    async fn add_friend(
+        account: &mut WarpIpfsInstance,
+        friend: &mut WarpIpfsInstance,
+    ) -> anyhow::Result<()> {
+        let mut subscribe_a = account.multipass_subscribe().await?;
+        let mut subscribe_b = friend.multipass_subscribe().await?;
+        let did = friend.identity().await?.did_key();
+        account.send_request(&did).await?;
+
+        common::timeout(Duration::from_secs(60), async {
+            let did = loop {
+                if let Some(MultiPassEventKind::FriendRequestReceived { from, .. }) =
+                    subscribe_b.next().await
+                {
+                    break from;
+                }
+            };
+            friend.accept_request(&did).await
+        })
+        .await??;
+
+        common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MultiPassEventKind::FriendAdded { .. }) = subscribe_a.next().await {
+                    break;
+                }
+            }
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Create a group conversation holding a single message, returning the conversation and message id
+    async fn group_with_message(
+        account: &mut WarpIpfsInstance,
+        line: &str,
+    ) -> anyhow::Result<(Uuid, Uuid)> {
+        let mut subscribe = account.raygun_subscribe().await?;
+        account
+            .create_group_conversation(None, vec![], GroupPermissions::new())
+            .await?;
+
+        let conversation_id = common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(RayGunEventKind::ConversationCreated { conversation_id }) =
+                    subscribe.next().await
+                {
+                    break conversation_id;
+                }
+            }
+        })
+        .await?;
+
+        let message_id = account.send(conversation_id, vec![line.into()]).await?;
+        Ok((conversation_id, message_id))
+    }
 }
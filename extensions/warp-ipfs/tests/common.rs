@@ -9,7 +9,7 @@ use warp::{
     SingleHandle,
 };
 use warp_ipfs::{
-    config::{Bootstrap, Discovery},
+    config::{Bootstrap, Config, Discovery},
     WarpIpfsBuilder, WarpIpfsInstance,
 };
 
@@ -56,9 +56,40 @@ pub async fn mesh_connect(nodes: Vec<Ipfs>) -> anyhow::Result<()> {
 pub async fn create_account(
     username: Option<&str>,
     passphrase: Option<&str>,
-    _: Option<String>,
+    context: Option<String>,
 ) -> anyhow::Result<(WarpIpfsInstance, DID, Identity)> {
-    let mut config = warp_ipfs::config::Config::development();
+    let mut instance = create_instance(context).await;
+
+    let profile = instance.create_identity(username, passphrase).await?;
+    let identity = profile.identity().clone();
+
+    Ok((instance, identity.did_key(), identity))
+}
+
+#[allow(dead_code)]
+pub async fn create_account_with_config(
+    username: Option<&str>,
+    passphrase: Option<&str>,
+    config: Config,
+) -> anyhow::Result<(WarpIpfsInstance, DID, Identity)> {
+    let mut instance = create_instance_with_config(config).await;
+
+    let profile = instance.create_identity(username, passphrase).await?;
+    let identity = profile.identity().clone();
+
+    Ok((instance, identity.did_key(), identity))
+}
+
+/// Instance with an unlocked tesseract but without an identity
+#[allow(dead_code)]
+pub async fn create_instance(_: Option<String>) -> WarpIpfsInstance {
+    create_instance_with_config(test_config()).await
+}
+
+/// Configuration used by the instances in the tests
+#[allow(dead_code)]
+pub fn test_config() -> Config {
+    let mut config = Config::development();
     *config.listen_on_mut() = vec![Multiaddr::empty().with(Protocol::Memory(0))];
     config.ipfs_setting_mut().memory_transport = true;
     config.store_setting_mut().discovery = Discovery::None;
@@ -69,15 +100,16 @@ pub async fn create_account(
     config.store_setting_mut().auto_push = Some(Duration::from_secs(1));
 
     *config.bootstrap_mut() = Bootstrap::None;
+    config
+}
 
-    let mut instance = WarpIpfsBuilder::default().set_config(config).await;
+#[allow(dead_code)]
+pub async fn create_instance_with_config(config: Config) -> WarpIpfsInstance {
+    let instance = WarpIpfsBuilder::default().set_config(config).await;
 
     instance.tesseract().unlock(b"internal pass").unwrap();
 
-    let profile = instance.create_identity(username, passphrase).await?;
-    let identity = profile.identity().clone();
-
-    Ok((instance, identity.did_key(), identity))
+    instance
 }
 
 #[allow(dead_code)]
@@ -109,6 +141,31 @@ pub async fn create_accounts(
     Ok(accounts)
 }
 
+/// Accounts using their own configuration, connected to one another
+#[allow(dead_code)]
+pub async fn create_accounts_with_config(
+    infos: Vec<(Option<&str>, Option<&str>, Config)>,
+) -> anyhow::Result<Vec<(WarpIpfsInstance, DID, Identity)>> {
+    let mut accounts = vec![];
+    let mut nodes = vec![];
+    for (username, passphrase, config) in infos {
+        let account = create_account_with_config(username, passphrase, config).await?;
+        let ipfs = account
+            .0
+            .handle()
+            .expect("Handle accessible")
+            .downcast_ref::<Ipfs>()
+            .cloned()
+            .unwrap();
+        nodes.push(ipfs);
+        accounts.push(account);
+    }
+
+    mesh_connect(nodes).await?;
+
+    Ok(accounts)
+}
+
 #[allow(dead_code)]
 pub async fn timeout<F>(duration: Duration, future: F) -> Result<F::Output, std::io::Error>
 where
@@ -6,6 +6,7 @@ mod test {
     use std::time::Duration;
     use warp::{
         constellation::Progression,
+        crypto::cipher::CipherSuite,
         multipass::MultiPassEventKind,
         raygun::{
             AttachmentKind, ConversationType, Location, MessageEvent, MessageEventKind,
@@ -13,7 +14,7 @@ mod test {
         },
     };
 
-    use crate::common::{create_accounts, PROFILE_IMAGE};
+    use crate::common::{create_accounts, create_accounts_with_config, test_config, PROFILE_IMAGE};
 
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::wasm_bindgen_test as async_test;
@@ -364,6 +365,10 @@ mod test {
                 AttachmentKind::AttachedProgress(_location, Progression::ProgressFailed { .. }) => {
                     unreachable!("should not fail")
                 }
+                AttachmentKind::AttachedProgress(
+                    _location,
+                    Progression::MetadataRemoved { .. },
+                ) => {}
                 AttachmentKind::Pending(result) => {
                     result?;
                 }
@@ -412,6 +417,15 @@ mod test {
         let data = stream.try_collect::<Vec<_>>().await?.concat();
 
         assert_eq!(data, PROFILE_IMAGE);
+
+        let variant = file.best_variant(64).expect("variant exist");
+        let stream = instance_b
+            .download_variant_stream(conversation_id, message_a.id(), "image.png", 64)
+            .await?;
+
+        let data = stream.try_collect::<Vec<_>>().await?.concat();
+
+        assert_eq!(data.len(), variant.size());
         Ok(())
     }
 
@@ -492,6 +506,10 @@ mod test {
                 AttachmentKind::AttachedProgress(_location, Progression::ProgressFailed { .. }) => {
                     unreachable!("should not fail")
                 }
+                AttachmentKind::AttachedProgress(
+                    _location,
+                    Progression::MetadataRemoved { .. },
+                ) => {}
                 AttachmentKind::Pending(result) => {
                     result?;
                 }
@@ -648,6 +666,120 @@ mod test {
         Ok(())
     }
 
+    #[async_test]
+    async fn messages_between_cipher_suites() -> anyhow::Result<()> {
+        let mut config = test_config();
+        config.store_setting_mut().cipher_suite = CipherSuite::XChaCha20Poly1305;
+
+        let accounts =
+            create_accounts_with_config(vec![(None, None, config), (None, None, test_config())])
+                .await?;
+
+        let (mut instance_a, _, _) = accounts.first().cloned().unwrap();
+        let (mut instance_b, did_b, _) = accounts.last().cloned().unwrap();
+
+        let mut chat_subscribe_a = instance_a.raygun_subscribe().await?;
+        let mut chat_subscribe_b = instance_b.raygun_subscribe().await?;
+
+        instance_a.create_conversation(&did_b).await?;
+
+        let conversation_id = crate::common::timeout(Duration::from_secs(60), async {
+            let mut id_a = None;
+            let mut id_b = None;
+            loop {
+                tokio::select! {
+                    Some(RayGunEventKind::ConversationCreated { conversation_id }) = chat_subscribe_a.next() => {
+                        id_a.replace(conversation_id);
+                    },
+                    Some(RayGunEventKind::ConversationCreated { conversation_id }) = chat_subscribe_b.next() => {
+                        id_b.replace(conversation_id);
+                    },
+                }
+
+                if id_a.is_some() && id_b.is_some() {
+                    assert_eq!(id_a, id_b);
+                    break id_a.expect("valid conversation_id")
+                }
+            }
+        }).await?;
+
+        let mut conversation_a = instance_a.get_conversation_stream(conversation_id).await?;
+        let mut conversation_b = instance_b.get_conversation_stream(conversation_id).await?;
+
+        // Sent with XChaCha20-Poly1305, while the recipient uses AES-256-GCM
+        instance_a
+            .send(conversation_id, vec!["Hello, World".into()])
+            .await?;
+
+        let message_a = crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MessageEventKind::MessageSent {
+                    conversation_id,
+                    message_id,
+                }) = conversation_a.next().await
+                {
+                    break instance_a.get_message(conversation_id, message_id).await;
+                }
+            }
+        })
+        .await??;
+
+        let message_b = crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MessageEventKind::MessageReceived {
+                    conversation_id,
+                    message_id,
+                }) = conversation_b.next().await
+                {
+                    break instance_b.get_message(conversation_id, message_id).await;
+                }
+            }
+        })
+        .await??;
+
+        assert_eq!(message_a, message_b);
+
+        // The edit is encrypted by the recipient with the nonce, and therefore the suite, of the sender
+        instance_a
+            .edit(conversation_id, message_a.id(), vec!["New Message".into()])
+            .await?;
+
+        let message_b = crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MessageEventKind::MessageEdited {
+                    conversation_id,
+                    message_id,
+                }) = conversation_b.next().await
+                {
+                    break instance_b.get_message(conversation_id, message_id).await;
+                }
+            }
+        })
+        .await??;
+
+        assert_eq!(message_b.lines(), ["New Message".to_string()]);
+
+        instance_b
+            .send(conversation_id, vec!["Hello, back".into()])
+            .await?;
+
+        let message_a = crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MessageEventKind::MessageReceived {
+                    conversation_id,
+                    message_id,
+                }) = conversation_a.next().await
+                {
+                    break instance_a.get_message(conversation_id, message_id).await;
+                }
+            }
+        })
+        .await??;
+
+        assert_eq!(message_a.lines(), ["Hello, back".to_string()]);
+        Ok(())
+    }
+
     #[async_test]
     async fn edit_message_in_conversation() -> anyhow::Result<()> {
         let accounts = create_accounts(vec![
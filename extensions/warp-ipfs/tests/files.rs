@@ -15,7 +15,8 @@ mod test {
 
     #[cfg(not(target_arch = "wasm32"))]
     use tokio::test as async_test;
-    use warp::constellation::Constellation;
+    use warp::constellation::search::{NamePattern, SearchOptions};
+    use warp::constellation::{Constellation, ConstellationEvent, ConstellationEventKind};
 
     #[async_test]
     async fn create_directory() -> anyhow::Result<()> {
@@ -89,6 +90,112 @@ mod test {
         Ok(())
     }
 
+    #[async_test]
+    async fn image_variants() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        let root_directory = fs.root_directory();
+        fs.put_buffer("image.png", PROFILE_IMAGE.into()).await?;
+
+        let file = root_directory.get_item("image.png")?.get_file()?;
+        let variants = file.variants();
+        let widths = variants.iter().map(|v| v.width()).collect::<Vec<_>>();
+        assert_eq!(widths, vec![64, 256]);
+
+        let stream = fs.get_variant_stream("image.png", 100).await?;
+        let data = stream.try_collect::<Vec<_>>().await?.concat();
+        assert_eq!(data.len(), variants[1].size());
+
+        // None of the variants are large enough, so the original is used instead
+        let stream = fs.get_variant_stream("image.png", 1024).await?;
+        let data = stream.try_collect::<Vec<_>>().await?.concat();
+        assert_eq!(data, PROFILE_IMAGE);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn file_encrypted_at_rest() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        let root_directory = fs.root_directory();
+        fs.put_buffer("image.png", PROFILE_IMAGE.into()).await?;
+
+        let file = root_directory.get_item("image.png")?.get_file()?;
+        assert!(file.encryption_key().is_some());
+        assert_eq!(file.size(), PROFILE_IMAGE.len());
+
+        let data = fs.get_buffer("image.png").await?;
+        assert_eq!(data, PROFILE_IMAGE);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn strip_metadata_from_attachment() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        fs.create_directory("chat_media", false).await?;
+
+        // Insert a text chunk after the header of the image
+        let text = b"Comment\0taken at home";
+        let mut image = PROFILE_IMAGE[..33].to_vec();
+        image.extend((text.len() as u32).to_be_bytes());
+        image.extend(b"tEXt");
+        image.extend(text);
+        image.extend([0; 4]);
+        image.extend(&PROFILE_IMAGE[33..]);
+
+        let stream = stream::iter(vec![Ok(image.into())]).boxed();
+        let mut status = fs.put_stream("/chat_media/image.png", None, stream).await?;
+
+        let mut removed = vec![];
+        while let Some(progress) = status.next().await {
+            match progress {
+                warp::constellation::Progression::MetadataRemoved { removed: kinds, .. } => {
+                    removed = kinds;
+                }
+                warp::constellation::Progression::ProgressFailed { .. } => {
+                    unreachable!("should not fail")
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(removed, vec!["text".to_string()]);
+        let data = fs.get_buffer("/chat_media/image.png").await?;
+        assert_eq!(data, PROFILE_IMAGE);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn strip_metadata_from_buffer() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        fs.create_directory("chat_media", false).await?;
+        let mut events = fs.constellation_subscribe_path("/chat_media").await?;
+
+        let text = b"Comment\0taken at home";
+        let mut image = PROFILE_IMAGE[..33].to_vec();
+        image.extend((text.len() as u32).to_be_bytes());
+        image.extend(b"tEXt");
+        image.extend(text);
+        image.extend([0; 4]);
+        image.extend(&PROFILE_IMAGE[33..]);
+
+        fs.put_buffer("/chat_media/image.png", image.into()).await?;
+
+        let event = events.next().await.expect("event");
+        assert!(matches!(
+            event,
+            ConstellationEventKind::MetadataRemoved { path, removed }
+                if path == "/chat_media/image.png" && removed == vec!["text".to_string()]
+        ));
+
+        let file = fs
+            .root_directory()
+            .get_item_by_path("/chat_media/image.png")?
+            .get_file()?;
+        let mut hash = warp::constellation::file::Hash::default();
+        hash.hash_from_slice(PROFILE_IMAGE)?;
+        assert_eq!(file.hash().sha256(), hash.sha256());
+        Ok(())
+    }
+
     #[async_test]
     async fn upload_file_to_directory() -> anyhow::Result<()> {
         let (mut fs, _, _) = create_account(None, None, None).await?;
@@ -163,6 +270,100 @@ mod test {
         Ok(())
     }
 
+    #[async_test]
+    async fn move_file_between_directories() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        let root_directory = fs.root_directory();
+        fs.create_directory("/my/storage", true).await?;
+        fs.create_directory("/my/archive", true).await?;
+
+        fs.put_buffer("/my/storage/image.png", PROFILE_IMAGE.into())
+            .await?;
+
+        fs.move_item("/my/storage/image.png", "/my/archive").await?;
+
+        assert!(root_directory
+            .get_item_by_path("/my/archive/image.png")
+            .is_ok());
+        assert!(root_directory
+            .get_item_by_path("/my/storage/image.png")
+            .is_err());
+
+        assert!(fs.move_item("/my", "/my/archive").await.is_err());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn directory_quota() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        fs.create_directory("/images", false).await?;
+        fs.create_directory("/archive", false).await?;
+        fs.set_quota("/images", Some(PROFILE_IMAGE.len() + 10))
+            .await?;
+
+        fs.put_buffer("/images/image.png", PROFILE_IMAGE.into())
+            .await?;
+        assert!(fs
+            .put_buffer("/images/icon.png", PROFILE_IMAGE.into())
+            .await
+            .is_err());
+
+        fs.put_buffer("/archive/icon.png", PROFILE_IMAGE.into())
+            .await?;
+        assert!(fs.move_item("/archive/icon.png", "/images").await.is_err());
+
+        let stored = fs
+            .root_directory()
+            .get_item_by_path("/images/image.png")?
+            .get_file()?
+            .stored_size();
+        let variants = stored - PROFILE_IMAGE.len();
+
+        let report = fs.usage_report()?;
+        let images = report.get("/images").expect("directory exist");
+        assert_eq!(images.size(), stored);
+        assert_eq!(
+            images.remaining(),
+            Some((PROFILE_IMAGE.len() + 10).saturating_sub(stored))
+        );
+        assert_eq!(report.root().size(), stored * 2);
+        assert_eq!(report.variants(), variants * 2);
+        assert_eq!(
+            report.file_types().get("image/png"),
+            Some(&(PROFILE_IMAGE.len() * 2))
+        );
+        assert!(report.thumbnails() > 0);
+        Ok(())
+    }
+
+    #[async_test]
+    async fn path_events() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        let mut events = fs.constellation_subscribe_path("/my/archive").await?;
+
+        fs.create_directory("/my/storage", true).await?;
+        fs.create_directory("/my/archive", true).await?;
+        fs.put_buffer("/my/storage/image.png", PROFILE_IMAGE.into())
+            .await?;
+        fs.move_item("/my/storage/image.png", "/my/archive").await?;
+
+        let event = events.next().await.expect("event");
+        assert!(
+            matches!(event, ConstellationEventKind::DirectoryCreated { path, .. } if path == "/my/archive")
+        );
+
+        let item = fs
+            .root_directory()
+            .get_item_by_path("/my/archive/image.png")?;
+        let event = events.next().await.expect("event");
+        assert!(matches!(
+            event,
+            ConstellationEventKind::Moved { id, from, to, .. }
+                if id == item.id() && from == "/my/storage/image.png" && to == "/my/archive/image.png"
+        ));
+        Ok(())
+    }
+
     #[async_test]
     async fn check_thumbnail_of_file() -> anyhow::Result<()> {
         let (mut fs, _, _) = create_account(None, None, None).await?;
@@ -170,14 +371,198 @@ mod test {
         let data: &[u8] = b"hello, world!";
         fs.put_buffer("image.png", PROFILE_IMAGE.into()).await?;
         fs.put_buffer("data.txt", data.into()).await?;
+        fs.put_buffer("data.bin", data.into()).await?;
 
         assert!(root_directory.has_item("image.png"));
         assert!(root_directory.has_item("data.txt"));
         //because this is an image, we should check to see if a thumbnail was produced
         let item = root_directory.get_item("image.png")?;
         assert!(!item.thumbnail().is_empty());
+        //text is previewed by the text thumbnailer
         let item = root_directory.get_item("data.txt")?;
+        assert!(!item.thumbnail().is_empty());
+        //there is no thumbnailer for generic files
+        let item = root_directory.get_item("data.bin")?;
         assert!(item.thumbnail().is_empty());
         Ok(())
     }
+
+    #[async_test]
+    async fn search_files() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        fs.create_directory("/docs", false).await?;
+        fs.put_buffer("image.png", PROFILE_IMAGE.into()).await?;
+        fs.put_buffer("/docs/todo.txt", b"buy milk"[..].into())
+            .await?;
+        fs.put_buffer("/docs/notes.txt", b"call the Warp team"[..].into())
+            .await?;
+
+        let items = fs
+            .search(SearchOptions::default().set_name(NamePattern::Glob("*.txt".into())))
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(items.len(), 2);
+
+        let items = fs
+            .search(SearchOptions::default().set_mime("image/*"))
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name(), "image.png");
+
+        let items = fs
+            .search(SearchOptions::default().set_content("warp"))
+            .await?
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name(), "notes.txt");
+        assert_eq!(items[0].path(), "/docs/notes.txt");
+        Ok(())
+    }
+
+    #[async_test]
+    async fn tag_and_metadata() -> anyhow::Result<()> {
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        fs.create_directory("/projects", false).await?;
+        fs.put_buffer("image.png", PROFILE_IMAGE.into()).await?;
+        fs.put_buffer("/projects/plan.txt", b"plan"[..].into())
+            .await?;
+
+        fs.add_tag("image.png", "warp").await?;
+        fs.add_tag("/projects/plan.txt", "warp").await?;
+        fs.set_item_metadata("/projects/plan.txt", "status", Some("draft"))
+            .await?;
+
+        let items = fs.list_by_tag("warp")?;
+        assert_eq!(items.len(), 2);
+
+        let item = fs.root_directory().get_item_by_path("/projects/plan.txt")?;
+        assert_eq!(
+            item.metadata().get("status").map(String::as_str),
+            Some("draft")
+        );
+
+        fs.remove_tag("image.png", "warp").await?;
+        fs.set_item_metadata("/projects/plan.txt", "status", None)
+            .await?;
+
+        assert_eq!(fs.list_by_tag("warp")?.len(), 1);
+        assert!(item.metadata().is_empty());
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[async_test]
+    async fn sync_local_directory() -> anyhow::Result<()> {
+        use warp::constellation::sync::{ConstellationSync, SyncEvent, SyncOptions};
+
+        let (mut fs, _, _) = create_account(None, None, None).await?;
+        let local = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(local.join("docs")).await?;
+        tokio::fs::write(local.join("docs/notes.txt"), b"local notes").await?;
+
+        fs.create_directory("/backup", false).await?;
+        fs.put_buffer("/backup/remote.txt", b"remote"[..].into())
+            .await?;
+
+        let mut sync =
+            ConstellationSync::new(fs.clone(), &local, "/backup", SyncOptions::default());
+        let events = sync.sync().await?;
+        assert!(events.iter().any(
+            |event| matches!(event, SyncEvent::Uploaded { path } if path == "docs/notes.txt")
+        ));
+        assert!(events
+            .iter()
+            .any(|event| matches!(event, SyncEvent::Downloaded { path } if path == "remote.txt")));
+
+        assert!(fs
+            .root_directory()
+            .get_item_by_path("/backup/docs/notes.txt")
+            .is_ok());
+        assert_eq!(tokio::fs::read(local.join("remote.txt")).await?, b"remote");
+
+        // Changing the local copy replaces the remote copy, keeping its tags
+        fs.add_tag("/backup/docs/notes.txt", "work").await?;
+        tokio::fs::write(local.join("docs/notes.txt"), b"updated notes").await?;
+        let events = sync.sync().await?;
+        assert!(events.iter().any(
+            |event| matches!(event, SyncEvent::Uploaded { path } if path == "docs/notes.txt")
+        ));
+        assert_eq!(
+            fs.get_buffer("/backup/docs/notes.txt").await?.as_ref(),
+            b"updated notes"
+        );
+        assert!(fs
+            .root_directory()
+            .get_item_by_path("/backup/docs/notes.txt")?
+            .has_tag("work"));
+        assert_eq!(
+            fs.root_directory()
+                .get_item_by_path("/backup/docs")?
+                .get_directory()?
+                .get_items()
+                .len(),
+            1
+        );
+
+        // Deleting locally should remove the remote copy on the next pass
+        tokio::fs::remove_file(local.join("docs/notes.txt")).await?;
+        let events = sync.sync().await?;
+        assert!(events.iter().any(
+            |event| matches!(event, SyncEvent::RemovedRemote { path } if path == "docs/notes.txt")
+        ));
+        assert!(fs
+            .root_directory()
+            .get_item_by_path("/backup/docs/notes.txt")
+            .is_err());
+
+        tokio::fs::remove_dir_all(&local).await?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[async_test]
+    async fn watch_local_directory() -> anyhow::Result<()> {
+        use std::time::Duration;
+        use warp::constellation::sync::{ConstellationSync, SyncEvent, SyncOptions};
+
+        let (fs, _, _) = create_account(None, None, None).await?;
+        let local = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        tokio::fs::create_dir_all(&local).await?;
+
+        // The interval is long enough that only a reported change would start a pass
+        let options = SyncOptions::default().set_interval(Duration::from_secs(600));
+        let mut events = ConstellationSync::new(fs.clone(), &local, "/watched", options).watch();
+
+        crate::common::timeout(Duration::from_secs(30), async {
+            while let Some(event) = events.next().await {
+                if let SyncEvent::Completed = event {
+                    break;
+                }
+            }
+        })
+        .await?;
+
+        tokio::fs::write(local.join("notes.txt"), b"local notes").await?;
+
+        crate::common::timeout(Duration::from_secs(30), async {
+            while let Some(event) = events.next().await {
+                if matches!(event, SyncEvent::Uploaded { path } if path == "notes.txt") {
+                    break;
+                }
+            }
+        })
+        .await?;
+
+        assert!(fs
+            .root_directory()
+            .get_item_by_path("/watched/notes.txt")
+            .is_ok());
+
+        tokio::fs::remove_dir_all(&local).await?;
+        Ok(())
+    }
 }
@@ -3,9 +3,18 @@ pub mod common;
 mod test {
     use std::time::Duration;
 
-    use crate::common::{create_account, create_accounts};
+    use chrono::Utc;
+
+    use crate::common::{create_account, create_accounts, create_instance, mesh_connect};
     use futures::StreamExt;
-    use warp::multipass::{Friends, MultiPassEvent, MultiPassEventKind};
+    use rust_ipfs::Ipfs;
+    use warp::error::Error;
+    use warp::multipass::identity::ContactUpdate;
+    use warp::multipass::{
+        Friends, IdentityImportOption, LocalIdentity, MultiPassEvent, MultiPassEventKind,
+        MultiPassImportExport,
+    };
+    use warp::SingleHandle;
 
     #[cfg(target_arch = "wasm32")]
     use wasm_bindgen_test::wasm_bindgen_test as async_test;
@@ -57,6 +66,118 @@ mod test {
         Ok(())
     }
 
+    #[async_test]
+    async fn friend_request_with_message_and_expiry() -> anyhow::Result<()> {
+        let accounts = create_accounts(vec![
+            (
+                Some("JohnDoe"),
+                None,
+                Some("test::friend_request_with_message_and_expiry".into()),
+            ),
+            (
+                Some("JaneDoe"),
+                None,
+                Some("test::friend_request_with_message_and_expiry".into()),
+            ),
+        ])
+        .await?;
+
+        let (mut account_a, did_a, _) = accounts.first().cloned().unwrap();
+        let (mut account_b, did_b, _) = accounts.last().cloned().unwrap();
+
+        let mut subscribe_a = account_a.multipass_subscribe().await?;
+        let mut subscribe_b = account_b.multipass_subscribe().await?;
+
+        let expires = Utc::now() + chrono::Duration::seconds(10);
+        account_a
+            .send_request_with(&did_b, Some("Hello from JohnDoe".into()), Some(expires))
+            .await?;
+
+        let (from, message) = crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MultiPassEventKind::FriendRequestReceived { from, message, .. }) =
+                    subscribe_b.next().await
+                {
+                    break (from, message);
+                }
+            }
+        })
+        .await?;
+
+        assert_eq!(from, did_a);
+        assert_eq!(message.as_deref(), Some("Hello from JohnDoe"));
+
+        let incoming = account_b.list_incoming_request().await?;
+        assert_eq!(incoming.len(), 1);
+        assert_eq!(incoming[0].message(), Some("Hello from JohnDoe"));
+        assert_eq!(
+            incoming[0].expires().map(|date| date.timestamp()),
+            Some(expires.timestamp())
+        );
+
+        crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MultiPassEventKind::OutgoingFriendRequestClosed { did }) =
+                    subscribe_a.next().await
+                {
+                    if did == did_b {
+                        break;
+                    }
+                }
+            }
+        })
+        .await?;
+
+        assert!(account_a.list_outgoing_request().await?.is_empty());
+        assert!(account_b.list_incoming_request().await?.is_empty());
+
+        let past = Utc::now() - chrono::Duration::seconds(1);
+        assert!(account_a
+            .send_request_with(&did_b, None, Some(past))
+            .await
+            .is_err());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn friend_request_message_length_is_counted_in_characters() -> anyhow::Result<()> {
+        let accounts = create_accounts(vec![
+            (
+                Some("JohnDoe"),
+                None,
+                Some("test::friend_request_message_length_is_counted_in_characters".into()),
+            ),
+            (
+                Some("JaneDoe"),
+                None,
+                Some("test::friend_request_message_length_is_counted_in_characters".into()),
+            ),
+        ])
+        .await?;
+
+        let (mut account_a, _, _) = accounts.first().cloned().unwrap();
+        let (_, did_b, _) = accounts.last().cloned().unwrap();
+
+        match account_a
+            .send_request_with(&did_b, Some("é".repeat(257)), None)
+            .await
+        {
+            Err(Error::InvalidLength {
+                current, maximum, ..
+            }) => {
+                assert_eq!(current, 257);
+                assert_eq!(maximum, Some(256));
+            }
+            result => panic!("unexpected result: {result:?}"),
+        }
+
+        account_a
+            .send_request_with(&did_b, Some("é".repeat(256)), None)
+            .await?;
+        assert_eq!(account_a.list_outgoing_request().await?.len(), 1);
+        Ok(())
+    }
+
     #[async_test]
     async fn remove_friend() -> anyhow::Result<()> {
         let accounts = create_accounts(vec![
@@ -383,4 +504,402 @@ mod test {
 
         Ok(())
     }
+
+    #[async_test]
+    async fn contact_records() -> anyhow::Result<()> {
+        let accounts = create_accounts(vec![
+            (Some("JohnDoe"), None, Some("test::contact_records".into())),
+            (Some("JaneDoe"), None, Some("test::contact_records".into())),
+        ])
+        .await?;
+
+        let (mut account_a, _, _) = accounts.first().cloned().unwrap();
+        let (mut account_b, did_b, _) = accounts.last().cloned().unwrap();
+
+        let mut subscribe_a = account_a.multipass_subscribe().await?;
+        let mut subscribe_b = account_b.multipass_subscribe().await?;
+
+        // Contact records are only kept for friends
+        assert!(account_a
+            .update_contact(&did_b, ContactUpdate::Nickname(Some("Jane".into())))
+            .await
+            .is_err());
+
+        account_a.send_request(&did_b).await?;
+
+        crate::common::timeout(Duration::from_secs(60), async {
+            let did = loop {
+                if let Some(MultiPassEventKind::FriendRequestReceived { from, .. }) =
+                    subscribe_b.next().await
+                {
+                    break from;
+                }
+            };
+            account_b.accept_request(&did).await
+        })
+        .await??;
+
+        crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MultiPassEventKind::FriendAdded { .. }) = subscribe_a.next().await {
+                    break;
+                }
+            }
+        })
+        .await?;
+
+        let contact = account_a.get_contact(&did_b).await?;
+        assert!(contact.is_empty());
+
+        account_a
+            .update_contact(&did_b, ContactUpdate::Nickname(Some("Jane".into())))
+            .await?;
+
+        crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MultiPassEventKind::ContactUpdated { did }) = subscribe_a.next().await {
+                    break did;
+                }
+            }
+        })
+        .await?;
+
+        account_a
+            .update_contact(
+                &did_b,
+                ContactUpdate::Notes(Some("Met at the conference".into())),
+            )
+            .await?;
+        account_a
+            .update_contact(&did_b, ContactUpdate::AddGroup("Work".into()))
+            .await?;
+
+        let contact = account_a.get_contact(&did_b).await?;
+        assert_eq!(contact.nickname(), Some("Jane"));
+        assert_eq!(contact.notes(), Some("Met at the conference"));
+        assert_eq!(contact.groups(), ["Work".to_string()]);
+        assert_eq!(account_a.list_contacts().await?.len(), 1);
+        assert_eq!(
+            account_a.list_contact_groups().await?,
+            vec!["Work".to_string()]
+        );
+
+        account_a
+            .update_contact(&did_b, ContactUpdate::RemoveGroup("Work".into()))
+            .await?;
+        assert!(account_a.list_contact_groups().await?.is_empty());
+
+        // Removing the friend removes their contact record
+        account_a.remove_friend(&did_b).await?;
+        assert!(account_a.list_contacts().await?.is_empty());
+        Ok(())
+    }
+
+    #[async_test]
+    async fn social_recovery() -> anyhow::Result<()> {
+        let accounts = create_accounts(vec![
+            (Some("JohnDoe"), None, Some("test::social_recovery".into())),
+            (Some("JaneDoe"), None, Some("test::social_recovery".into())),
+            (Some("JohnDoe2"), None, Some("test::social_recovery".into())),
+        ])
+        .await?;
+
+        let (mut account_a, did_a, _) = accounts[0].clone();
+        let (mut account_b, did_b, _) = accounts[1].clone();
+        let (mut account_c, did_c, _) = accounts[2].clone();
+
+        let mut subscribe_a = account_a.multipass_subscribe().await?;
+        let mut subscribe_b = account_b.multipass_subscribe().await?;
+        let mut subscribe_c = account_c.multipass_subscribe().await?;
+
+        for (account, subscribe, did) in [
+            (&mut account_b, &mut subscribe_b, &did_b),
+            (&mut account_c, &mut subscribe_c, &did_c),
+        ] {
+            account_a.send_request(did).await?;
+
+            crate::common::timeout(Duration::from_secs(60), async {
+                let did = loop {
+                    if let Some(MultiPassEventKind::FriendRequestReceived { from, .. }) =
+                        subscribe.next().await
+                    {
+                        break from;
+                    }
+                };
+                account.accept_request(&did).await
+            })
+            .await??;
+
+            crate::common::timeout(Duration::from_secs(60), async {
+                loop {
+                    if let Some(MultiPassEventKind::FriendAdded { .. }) = subscribe_a.next().await {
+                        break;
+                    }
+                }
+            })
+            .await?;
+        }
+
+        account_a
+            .setup_social_recovery(&[did_b.clone(), did_c.clone()], 2)
+            .await?;
+
+        for subscribe in [&mut subscribe_b, &mut subscribe_c] {
+            crate::common::timeout(Duration::from_secs(60), async {
+                loop {
+                    if let Some(MultiPassEventKind::RecoveryShareReceived { did }) =
+                        subscribe.next().await
+                    {
+                        assert_eq!(did, did_a);
+                        break;
+                    }
+                }
+            })
+            .await?;
+        }
+
+        assert_eq!(account_b.list_recovery_shares().await?, vec![did_a.clone()]);
+        assert_eq!(account_c.list_recovery_shares().await?, vec![did_a.clone()]);
+
+        // Recover the identity on a new device without the key of the identity
+        let mut account_d = create_instance(Some("test::social_recovery".into())).await;
+        let account_d_handle = account_d.clone();
+
+        let recover = account_d.import_identity(IdentityImportOption::Recover {
+            did: did_a.clone(),
+            friends: vec![did_b.clone(), did_c.clone()],
+        });
+
+        let approve = async {
+            // Wait for the temporary node to start so it could be connected to the friends
+            let ipfs = loop {
+                if let Some(ipfs) = account_d_handle
+                    .handle()
+                    .ok()
+                    .and_then(|handle| handle.downcast_ref::<Ipfs>().cloned())
+                {
+                    break ipfs;
+                }
+                futures_timer::Delay::new(Duration::from_millis(100)).await;
+            };
+
+            let mut nodes = vec![ipfs];
+            for account in [&account_b, &account_c] {
+                nodes.push(
+                    account
+                        .handle()?
+                        .downcast_ref::<Ipfs>()
+                        .cloned()
+                        .expect("Handle accessible"),
+                );
+            }
+            mesh_connect(nodes).await?;
+
+            for (account, subscribe) in [
+                (&mut account_b, &mut subscribe_b),
+                (&mut account_c, &mut subscribe_c),
+            ] {
+                crate::common::timeout(Duration::from_secs(60), async {
+                    let (did, device) = loop {
+                        if let Some(MultiPassEventKind::RecoveryShareRequested { did, device }) =
+                            subscribe.next().await
+                        {
+                            break (did, device);
+                        }
+                    };
+                    assert_eq!(account.list_recovery_requests().await?.len(), 1);
+                    account.approve_recovery_request(&did, &device).await
+                })
+                .await??;
+            }
+
+            Ok::<_, anyhow::Error>(())
+        };
+
+        let (recovered, approved) = crate::common::timeout(Duration::from_secs(120), async {
+            futures::join!(recover, approve)
+        })
+        .await?;
+
+        approved?;
+        let identity = recovered?;
+
+        assert_eq!(identity.did_key(), did_a);
+        assert_eq!(account_d.identity().await?.did_key(), did_a);
+        assert!(account_d.has_friend(&did_b).await?);
+        Ok(())
+    }
+
+    // Note: Rotation needs the store to outlive the node, which is not kept in memory
+    #[cfg(not(target_arch = "wasm32"))]
+    #[async_test]
+    async fn friend_migrated_to_rotated_key() -> anyhow::Result<()> {
+        let (mut account_a, did_a, mut account_b, path) = rotating_friends().await?;
+
+        account_b
+            .update_contact(&did_a, ContactUpdate::Nickname(Some("John".into())))
+            .await?;
+
+        let mut subscribe_b = account_b.multipass_subscribe().await?;
+
+        let new_did = account_a.rotate_identity_key().await?.identity().did_key();
+        connect(&account_a, &account_b).await?;
+
+        let (old, new) = crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MultiPassEventKind::IdentityKeyRotated { old, new }) =
+                    subscribe_b.next().await
+                {
+                    break (old, new);
+                }
+            }
+        })
+        .await?;
+
+        assert_eq!(old, did_a);
+        assert_eq!(new, new_did);
+        assert!(account_b.has_friend(&new_did).await?);
+        assert!(!account_b.has_friend(&did_a).await?);
+        assert_eq!(
+            account_b.get_contact(&new_did).await?.nickname(),
+            Some("John")
+        );
+
+        drop(account_a);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[async_test]
+    async fn succession_from_revoked_key_is_ignored() -> anyhow::Result<()> {
+        let (mut account_a, did_a, mut account_b, path) = rotating_friends().await?;
+
+        let certificate = account_a
+            .create_revocation_certificate(Some("stolen key"))
+            .await?;
+        let ipfs_a = account_a
+            .handle()?
+            .downcast_ref::<Ipfs>()
+            .cloned()
+            .expect("Handle accessible");
+
+        // Certificates are otherwise relayed by a shuttle node, so it is announced to the topic directly.
+        // The revoked identity is removed from the cache once the certificate is stored
+        crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                ipfs_a
+                    .pubsub_publish("/identity/revoke/v0", serde_json::to_vec(&certificate)?)
+                    .await?;
+                futures_timer::Delay::new(Duration::from_millis(200)).await;
+                if account_b.get_identity(did_a.clone()).await.is_err() {
+                    break Ok::<_, anyhow::Error>(());
+                }
+            }
+        })
+        .await??;
+
+        let mut subscribe_b = account_b.multipass_subscribe().await?;
+
+        let new_did = account_a.rotate_identity_key().await?.identity().did_key();
+        connect(&account_a, &account_b).await?;
+
+        crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if account_b.get_identity(new_did.clone()).await.is_ok() {
+                    break;
+                }
+                futures_timer::Delay::new(Duration::from_millis(200)).await;
+            }
+        })
+        .await?;
+
+        let rotated = crate::common::timeout(Duration::from_secs(5), async {
+            loop {
+                if let Some(MultiPassEventKind::IdentityKeyRotated { .. }) =
+                    subscribe_b.next().await
+                {
+                    break;
+                }
+            }
+        })
+        .await;
+
+        assert!(rotated.is_err());
+        assert!(!account_b.has_friend(&new_did).await?);
+
+        drop(account_a);
+        let _ = std::fs::remove_dir_all(path);
+        Ok(())
+    }
+
+    /// Two friends, with the first one using a persistent store so its key could be rotated
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn rotating_friends() -> anyhow::Result<(
+        warp_ipfs::WarpIpfsInstance,
+        warp::crypto::DID,
+        warp_ipfs::WarpIpfsInstance,
+        std::path::PathBuf,
+    )> {
+        let path = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+        let mut config = crate::common::test_config();
+        *config.path_mut() = Some(path.clone());
+        *config.persist_mut() = true;
+
+        let accounts = crate::common::create_accounts_with_config(vec![
+            (Some("JohnDoe"), None, config),
+            (Some("JaneDoe"), None, crate::common::test_config()),
+        ])
+        .await?;
+
+        let (mut account_a, did_a, _) = accounts.first().cloned().unwrap();
+        let (mut account_b, did_b, _) = accounts.last().cloned().unwrap();
+
+        let mut subscribe_a = account_a.multipass_subscribe().await?;
+        let mut subscribe_b = account_b.multipass_subscribe().await?;
+        account_a.send_request(&did_b).await?;
+
+        crate::common::timeout(Duration::from_secs(60), async {
+            let did = loop {
+                if let Some(MultiPassEventKind::FriendRequestReceived { from, .. }) =
+                    subscribe_b.next().await
+                {
+                    break from;
+                }
+            };
+            account_b.accept_request(&did).await
+        })
+        .await??;
+
+        crate::common::timeout(Duration::from_secs(60), async {
+            loop {
+                if let Some(MultiPassEventKind::FriendAdded { .. }) = subscribe_a.next().await {
+                    break;
+                }
+            }
+        })
+        .await?;
+
+        Ok((account_a, did_a, account_b, path))
+    }
+
+    /// Connect the accounts again after the node of one of them was restarted
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn connect(
+        account_a: &warp_ipfs::WarpIpfsInstance,
+        account_b: &warp_ipfs::WarpIpfsInstance,
+    ) -> anyhow::Result<()> {
+        let nodes = [account_a, account_b]
+            .into_iter()
+            .map(|account| {
+                account
+                    .handle()
+                    .expect("Handle accessible")
+                    .downcast_ref::<Ipfs>()
+                    .cloned()
+                    .unwrap()
+            })
+            .collect();
+        mesh_connect(nodes).await
+    }
 }
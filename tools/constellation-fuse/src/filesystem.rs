@@ -0,0 +1,1206 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, SeekFrom};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use bytes::{Bytes, BytesMut};
+use fuser::{
+    FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory, ReplyEmpty,
+    ReplyEntry, ReplyOpen, ReplyWrite, Request, TimeOrNow,
+};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+use libc::c_int;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::runtime::Handle;
+use warp::constellation::directory::Directory;
+use warp::constellation::item::Item;
+use warp::constellation::{Constellation, Progression};
+use warp::error::Error;
+
+use crate::inode::{self, InodeTable, ROOT_INODE};
+
+const TTL: Duration = Duration::from_secs(1);
+const BLOCK_SIZE: u32 = 512;
+
+/// Size of the chunks read from a spooled file when uploading it
+const UPLOAD_CHUNK_SIZE: usize = 256 * 1024;
+
+/// File that has been opened by the kernel. Reads are served from a download of the file
+/// until it is written to, at which point its contents are spooled to a temporary file that
+/// is uploaded to constellation when the handle is flushed.
+struct OpenFile {
+    inode: u64,
+    source: Source,
+    dirty: bool,
+}
+
+enum Source {
+    /// Contents are read from constellation, with the download started on the first read
+    Remote(Option<Download>),
+    /// Contents are held in a temporary file on disk
+    Spooled(File),
+}
+
+impl OpenFile {
+    /// Size of the contents held by the handle, if they differ from the file in constellation
+    fn spooled_size(&self) -> Option<u64> {
+        match &self.source {
+            Source::Spooled(file) => file.metadata().ok().map(|metadata| metadata.len()),
+            Source::Remote(_) => None,
+        }
+    }
+}
+
+/// Download of a file that is consumed as the kernel reads through it
+struct Download {
+    stream: BoxStream<'static, std::io::Result<Bytes>>,
+    /// Offset of the first byte of `chunk` within the file
+    position: usize,
+    chunk: Bytes,
+}
+
+impl Download {
+    /// Reads up to `size` bytes from `offset`, which cannot come before the current chunk
+    async fn read(&mut self, mut offset: usize, size: usize) -> std::io::Result<Bytes> {
+        let mut data = BytesMut::with_capacity(size);
+        while data.len() < size {
+            let end = self.position + self.chunk.len();
+            if offset >= end {
+                let Some(chunk) = self.stream.next().await.transpose()? else {
+                    break;
+                };
+                self.position = end;
+                self.chunk = chunk;
+                continue;
+            }
+            let start = offset - self.position;
+            let length = (size - data.len()).min(self.chunk.len() - start);
+            data.extend_from_slice(&self.chunk[start..start + length]);
+            offset += length;
+        }
+        Ok(data.freeze())
+    }
+}
+
+/// Exposes a `Constellation` instance as a FUSE filesystem.
+///
+/// Calls from the kernel are synchronous so each operation is driven to completion
+/// on the tokio runtime that was supplied with the handle.
+pub struct ConstellationFs<C: Constellation> {
+    constellation: C,
+    handle: Handle,
+    inodes: InodeTable,
+    open_files: HashMap<u64, OpenFile>,
+    next_handle: u64,
+    uid: u32,
+    gid: u32,
+}
+
+impl<C: Constellation> ConstellationFs<C> {
+    pub fn new(constellation: C, handle: Handle) -> Self {
+        // Safety: getuid and getgid are always successful
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            constellation,
+            handle,
+            inodes: InodeTable::default(),
+            open_files: HashMap::new(),
+            next_handle: 1,
+            uid,
+            gid,
+        }
+    }
+
+    fn path(&self, inode: u64) -> Result<String, c_int> {
+        self.inodes
+            .path(inode)
+            .map(ToString::to_string)
+            .ok_or(libc::ENOENT)
+    }
+
+    fn item(&self, path: &str) -> Result<Item, c_int> {
+        let root = self.constellation.root_directory();
+        match path.is_empty() {
+            true => Ok(Item::from(&root)),
+            false => root.get_item_by_path(path).map_err(errno),
+        }
+    }
+
+    fn directory(&self, path: &str) -> Result<Directory, c_int> {
+        self.item(path)?.get_directory().map_err(errno)
+    }
+
+    fn attr(&self, inode: u64, item: &Item) -> FileAttr {
+        let (kind, perm, size) = match item {
+            Item::Directory(_) => (FileType::Directory, 0o755, 0),
+            Item::File(_) => {
+                // Report the size of any pending writes so the kernel does not truncate reads
+                let size = self
+                    .open_files
+                    .values()
+                    .filter(|file| file.inode == inode)
+                    .find_map(OpenFile::spooled_size)
+                    .unwrap_or(item.size() as u64);
+                (FileType::RegularFile, 0o644, size)
+            }
+        };
+
+        let modified = SystemTime::from(item.modified());
+        let created = SystemTime::from(item.creation());
+
+        FileAttr {
+            ino: inode,
+            size,
+            blocks: size.div_ceil(BLOCK_SIZE as u64),
+            atime: modified,
+            mtime: modified,
+            ctime: modified,
+            crtime: created,
+            kind,
+            perm,
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+
+    /// Attributes for a file that exists only as an open handle and has yet to be written
+    fn pending_attr(&self, inode: u64) -> FileAttr {
+        let now = SystemTime::now();
+        let size = self
+            .open_files
+            .values()
+            .filter(|file| file.inode == inode)
+            .find_map(OpenFile::spooled_size)
+            .unwrap_or_default();
+        FileAttr {
+            ino: inode,
+            size,
+            blocks: size.div_ceil(BLOCK_SIZE as u64),
+            atime: now,
+            mtime: now,
+            ctime: now,
+            crtime: now,
+            kind: FileType::RegularFile,
+            perm: 0o644,
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+
+    pub fn lookup_path(&mut self, parent: u64, name: &str) -> Result<FileAttr, c_int> {
+        let path = inode::join(&self.path(parent)?, name);
+        match self.item(&path) {
+            Ok(item) => {
+                let inode = self.inodes.get_or_insert(&path);
+                Ok(self.attr(inode, &item))
+            }
+            // Misses are only answered for files that exist as an open handle so that
+            // looking up paths that do not exist does not allocate inodes
+            Err(e) => match self.inodes.get(&path) {
+                Some(inode) if self.is_pending(inode) => Ok(self.pending_attr(inode)),
+                _ => Err(e),
+            },
+        }
+    }
+
+    pub fn getattr_inode(&self, inode: u64) -> Result<FileAttr, c_int> {
+        let path = self.path(inode)?;
+        match self.item(&path) {
+            Ok(item) => Ok(self.attr(inode, &item)),
+            Err(_) if self.is_pending(inode) => Ok(self.pending_attr(inode)),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn list(&mut self, inode: u64) -> Result<Vec<(u64, FileType, String)>, c_int> {
+        let path = self.path(inode)?;
+        let directory = self.directory(&path)?;
+
+        let (parent, _) = inode::split(&path);
+        let parent = match path.is_empty() {
+            true => ROOT_INODE,
+            false => self.inodes.get_or_insert(parent),
+        };
+
+        let mut entries = vec![
+            (inode, FileType::Directory, ".".to_string()),
+            (parent, FileType::Directory, "..".to_string()),
+        ];
+
+        for item in directory.get_items() {
+            let name = item.name();
+            let kind = match item {
+                Item::Directory(_) => FileType::Directory,
+                Item::File(_) => FileType::RegularFile,
+            };
+            let inode = self.inodes.get_or_insert(&inode::join(&path, &name));
+            entries.push((inode, kind, name));
+        }
+
+        Ok(entries)
+    }
+
+    pub fn open_inode(&mut self, inode: u64, truncate: bool) -> Result<u64, c_int> {
+        let path = self.path(inode)?;
+        if !self.is_pending(inode) {
+            self.item(&path)?.get_file().map_err(errno)?;
+        }
+
+        let source = match truncate {
+            true => Source::Spooled(spool_file().map_err(io_errno)?),
+            false => Source::Remote(None),
+        };
+
+        let file = OpenFile {
+            inode,
+            source,
+            dirty: truncate,
+        };
+
+        Ok(self.insert_handle(file))
+    }
+
+    pub fn create_file(&mut self, parent: u64, name: &str) -> Result<(FileAttr, u64), c_int> {
+        let path = inode::join(&self.path(parent)?, name);
+        let directory = self.directory(&self.path(parent)?)?;
+        if directory.has_item(name) {
+            return Err(libc::EEXIST);
+        }
+
+        let inode = self.inodes.get_or_insert(&path);
+        let handle = self.insert_handle(OpenFile {
+            inode,
+            source: Source::Spooled(spool_file().map_err(io_errno)?),
+            dirty: true,
+        });
+
+        Ok((self.pending_attr(inode), handle))
+    }
+
+    pub fn read_handle(&mut self, handle: u64, offset: usize, size: usize) -> Result<Bytes, c_int> {
+        let file = self.open_files.get(&handle).ok_or(libc::EBADF)?;
+        let path = self.path(file.inode)?;
+
+        let file = self.open_files.get_mut(&handle).ok_or(libc::EBADF)?;
+        let download = match &mut file.source {
+            Source::Spooled(file) => return read_spooled(file, offset, size).map_err(io_errno),
+            Source::Remote(download) => download,
+        };
+
+        // Reading back from an earlier offset starts the download over, which is rare since
+        // the kernel mostly reads files in order
+        if download
+            .as_ref()
+            .map_or(true, |download| offset < download.position)
+        {
+            let stream = self
+                .handle
+                .block_on(self.constellation.get_stream(&path))
+                .map_err(errno)?;
+            *download = Some(Download {
+                stream,
+                position: 0,
+                chunk: Bytes::new(),
+            });
+        }
+
+        let download = download.as_mut().ok_or(libc::EIO)?;
+        self.handle
+            .block_on(download.read(offset, size))
+            .map_err(io_errno)
+    }
+
+    pub fn write_handle(
+        &mut self,
+        handle: u64,
+        offset: usize,
+        bytes: &[u8],
+    ) -> Result<usize, c_int> {
+        let file = self.spool(handle, true)?;
+        file.write_all_at(bytes, offset as u64).map_err(io_errno)?;
+        self.set_dirty(handle);
+        Ok(bytes.len())
+    }
+
+    pub fn truncate(&mut self, inode: u64, handle: Option<u64>, size: usize) -> Result<(), c_int> {
+        let handle = match handle.or_else(|| self.handle_for(inode)) {
+            Some(handle) => handle,
+            None => {
+                // Not opened so write the new contents straight back
+                let handle = self.open_inode(inode, false)?;
+                let result = self
+                    .resize(handle, size)
+                    .and_then(|_| self.flush_handle(handle));
+                self.open_files.remove(&handle);
+                return result;
+            }
+        };
+
+        self.resize(handle, size)
+    }
+
+    pub fn release_handle(&mut self, handle: u64) -> Result<(), c_int> {
+        let result = self.flush_handle(handle);
+        self.open_files.remove(&handle);
+        result
+    }
+
+    /// Writes the contents of the handle back to constellation if it was modified.
+    ///
+    /// Existing files are uploaded under a temporary name and only swapped in once the
+    /// upload succeeded, carrying over the attributes of the file being replaced
+    pub fn flush_handle(&mut self, handle: u64) -> Result<(), c_int> {
+        let Some(file) = self.open_files.get_mut(&handle) else {
+            return Err(libc::EBADF);
+        };
+
+        if !file.dirty {
+            return Ok(());
+        }
+
+        let Source::Spooled(spooled) = &file.source else {
+            return Ok(());
+        };
+
+        let spooled = spooled.try_clone().map_err(io_errno)?;
+        let inode = file.inode;
+        let path = self.path(inode)?;
+
+        match self.item(&path) {
+            Ok(existing) => {
+                let (parent, name) = inode::split(&path);
+                let staged = inode::join(
+                    parent,
+                    &self.unused_name(&[parent], &format!(".part-{name}"))?,
+                );
+
+                if let Err(e) = self.upload(&staged, spooled) {
+                    let _ = self
+                        .handle
+                        .block_on(self.constellation.remove(&staged, false));
+                    return Err(e);
+                }
+
+                let uploaded = self.item(&staged)?;
+                copy_attributes(&existing, &uploaded);
+
+                if let Err(e) = self.replace(&staged, &path) {
+                    let _ = self
+                        .handle
+                        .block_on(self.constellation.remove(&staged, false));
+                    return Err(e);
+                }
+            }
+            Err(_) => self.upload(&path, spooled)?,
+        }
+
+        if let Some(file) = self.open_files.get_mut(&handle) {
+            file.dirty = false;
+        }
+
+        Ok(())
+    }
+
+    pub fn make_directory(&mut self, parent: u64, name: &str) -> Result<FileAttr, c_int> {
+        let path = inode::join(&self.path(parent)?, name);
+        self.handle
+            .block_on(self.constellation.create_directory(&path, true))
+            .map_err(errno)?;
+
+        let inode = self.inodes.get_or_insert(&path);
+        let item = self.item(&path)?;
+        Ok(self.attr(inode, &item))
+    }
+
+    pub fn remove_item(&mut self, parent: u64, name: &str, directory: bool) -> Result<(), c_int> {
+        let path = inode::join(&self.path(parent)?, name);
+        let item = self.item(&path)?;
+
+        match (directory, &item) {
+            (true, Item::File(_)) => return Err(libc::ENOTDIR),
+            (false, Item::Directory(_)) => return Err(libc::EISDIR),
+            _ => {}
+        }
+
+        self.handle
+            .block_on(self.constellation.remove(&path, false))
+            .map_err(errno)?;
+
+        self.inodes.remove(&path);
+        Ok(())
+    }
+
+    pub fn rename_item(
+        &mut self,
+        parent: u64,
+        name: &str,
+        new_parent: u64,
+        new_name: &str,
+    ) -> Result<(), c_int> {
+        let parent = self.path(parent)?;
+        let new_parent = self.path(new_parent)?;
+        let from = inode::join(&parent, name);
+        let to = inode::join(&new_parent, new_name);
+
+        // Everything is validated up front so nothing is changed when the rename cannot succeed
+        let source = self.item(&from)?;
+        if from == to {
+            return Ok(());
+        }
+
+        if source.is_directory()
+            && (new_parent == from || new_parent.starts_with(&format!("{from}/")))
+        {
+            return Err(libc::EINVAL);
+        }
+
+        let replacing = match self.directory(&new_parent)?.get_item(new_name) {
+            Ok(existing) => {
+                match (&source, &existing) {
+                    (Item::File(_), Item::Directory(_)) => return Err(libc::EISDIR),
+                    (Item::Directory(_), Item::File(_)) => return Err(libc::ENOTDIR),
+                    (Item::Directory(_), Item::Directory(directory))
+                        if !directory.get_items().is_empty() =>
+                    {
+                        return Err(libc::ENOTEMPTY)
+                    }
+                    _ => {}
+                }
+                true
+            }
+            Err(_) => false,
+        };
+
+        // Replacing an existing item is expected of rename, so it is set aside and only
+        // removed once the item is in place, or restored if it could not be
+        let aside = match replacing {
+            true => Some(self.set_aside(&to)?),
+            false => None,
+        };
+
+        if let Err(e) = self.relocate(&parent, name, &new_parent, new_name) {
+            if let Some(aside) = aside {
+                if let Err(e) = self.rename_path(&aside, new_name) {
+                    tracing::error!(path = %to, error = %e, "unable to restore replaced item");
+                }
+            }
+            return Err(e);
+        }
+
+        if let Some(aside) = aside {
+            if let Err(e) = self
+                .handle
+                .block_on(self.constellation.remove(&aside, true))
+            {
+                tracing::warn!(path = %aside, error = %e, "unable to remove replaced item");
+            }
+        }
+
+        self.inodes.rename(&from, &to);
+        Ok(())
+    }
+
+    /// Moves `name` from `parent` into `new_parent` under `new_name`. Should any step fail, the
+    /// steps that were applied are undone so the item is not left part way
+    fn relocate(
+        &mut self,
+        parent: &str,
+        name: &str,
+        new_parent: &str,
+        new_name: &str,
+    ) -> Result<(), c_int> {
+        let from = inode::join(parent, name);
+
+        if parent == new_parent {
+            return self.rename_path(&from, new_name);
+        }
+
+        // The item is given a name that is free in both directories so the move cannot collide
+        let staged = match name == new_name {
+            true => name.to_string(),
+            false => self.unused_name(&[parent, new_parent], new_name)?,
+        };
+
+        if staged != name {
+            self.rename_path(&from, &staged)?;
+        }
+
+        let staged_from = inode::join(parent, &staged);
+        if let Err(e) = self.move_path(&staged_from, new_parent) {
+            if staged != name {
+                let _ = self.rename_path(&staged_from, name);
+            }
+            return Err(e);
+        }
+
+        let moved = inode::join(new_parent, &staged);
+        if staged != new_name {
+            if let Err(e) = self.rename_path(&moved, new_name) {
+                let _ = self
+                    .move_path(&moved, parent)
+                    .and_then(|_| self.rename_path(&staged_from, name));
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Swaps the item at `staged` in place of the one at `path`, which is removed once the swap succeeded
+    fn replace(&mut self, staged: &str, path: &str) -> Result<(), c_int> {
+        let (_, name) = inode::split(path);
+        let aside = self.set_aside(path)?;
+
+        if let Err(e) = self.rename_path(staged, name) {
+            if let Err(e) = self.rename_path(&aside, name) {
+                tracing::error!(%path, error = %e, "unable to restore replaced item");
+            }
+            return Err(e);
+        }
+
+        if let Err(e) = self
+            .handle
+            .block_on(self.constellation.remove(&aside, true))
+        {
+            tracing::warn!(path = %aside, error = %e, "unable to remove replaced item");
+        }
+
+        Ok(())
+    }
+
+    /// Renames the item at `path` to an unused name within the same directory, returning its new path
+    fn set_aside(&mut self, path: &str) -> Result<String, c_int> {
+        let (parent, name) = inode::split(path);
+        let aside = self.unused_name(&[parent], &format!(".old-{name}"))?;
+        self.rename_path(path, &aside)?;
+        Ok(inode::join(parent, &aside))
+    }
+
+    /// Returns `name`, or a variation of it, that is not used within any of the directories
+    fn unused_name(&self, directories: &[&str], name: &str) -> Result<String, c_int> {
+        let directories = directories
+            .iter()
+            .map(|path| self.directory(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let unused = |name: &str| {
+            directories
+                .iter()
+                .all(|directory| !directory.has_item(name))
+        };
+
+        std::iter::once(name.to_string())
+            .chain((1..).map(|count| format!(".{count}-{name}")))
+            .find(|name| unused(name))
+            .ok_or(libc::EEXIST)
+    }
+
+    fn rename_path(&mut self, path: &str, name: &str) -> Result<(), c_int> {
+        self.handle
+            .block_on(self.constellation.rename(path, name))
+            .map_err(errno)
+    }
+
+    fn move_path(&mut self, path: &str, directory: &str) -> Result<(), c_int> {
+        self.handle
+            .block_on(self.constellation.move_item(path, directory))
+            .map_err(errno)
+    }
+
+    /// Uploads the contents of a spooled file, reading it in chunks as the upload progresses
+    fn upload(&mut self, path: &str, spooled: File) -> Result<(), c_int> {
+        let size = spooled.metadata().map_err(io_errno)?.len() as usize;
+        let constellation = &mut self.constellation;
+        self.handle
+            .block_on(async {
+                // Note: The cursor is shared with the handle, which is fine since the handle
+                //       only uses positional reads and writes
+                let mut file = tokio::fs::File::from_std(spooled);
+                file.seek(SeekFrom::Start(0))
+                    .await
+                    .map_err(anyhow::Error::from)?;
+
+                let stream = futures::stream::try_unfold(file, |mut file| async move {
+                    let mut chunk = BytesMut::with_capacity(UPLOAD_CHUNK_SIZE);
+                    match file.read_buf(&mut chunk).await? {
+                        0 => Ok(None),
+                        _ => Ok(Some((chunk.freeze(), file))),
+                    }
+                })
+                .boxed();
+
+                let mut progress = constellation.put_stream(path, Some(size), stream).await?;
+
+                while let Some(event) = progress.next().await {
+                    if let Progression::ProgressFailed { error, .. } = event {
+                        return Err(error);
+                    }
+                }
+
+                Ok::<_, Error>(())
+            })
+            .map_err(errno)
+    }
+
+    fn insert_handle(&mut self, file: OpenFile) -> u64 {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.open_files.insert(handle, file);
+        handle
+    }
+
+    fn handle_for(&self, inode: u64) -> Option<u64> {
+        self.open_files
+            .iter()
+            .find(|(_, file)| file.inode == inode)
+            .map(|(handle, _)| *handle)
+    }
+
+    fn is_pending(&self, inode: u64) -> bool {
+        self.open_files
+            .values()
+            .any(|file| file.inode == inode && file.dirty)
+    }
+
+    fn set_dirty(&mut self, handle: u64) {
+        if let Some(file) = self.open_files.get_mut(&handle) {
+            file.dirty = true;
+        }
+    }
+
+    fn resize(&mut self, handle: u64, size: usize) -> Result<(), c_int> {
+        // Contents past the new size would be dropped anyway, so nothing is copied when emptying the file
+        let file = self.spool(handle, size > 0)?;
+        file.set_len(size as u64).map_err(io_errno)?;
+        self.set_dirty(handle);
+        Ok(())
+    }
+
+    /// Moves the contents of the handle into a temporary file so they can be modified, copying the
+    /// current contents of the file from constellation when `keep` is set
+    fn spool(&mut self, handle: u64, keep: bool) -> Result<&File, c_int> {
+        let file = self.open_files.get(&handle).ok_or(libc::EBADF)?;
+
+        if let Source::Remote(_) = file.source {
+            let path = self.path(file.inode)?;
+            let spooled = spool_file().map_err(io_errno)?;
+
+            if keep {
+                self.handle
+                    .block_on(async {
+                        let mut stream = self.constellation.get_stream(&path).await?;
+                        let mut offset = 0;
+                        while let Some(bytes) = stream.next().await {
+                            let bytes = bytes.map_err(anyhow::Error::from)?;
+                            spooled
+                                .write_all_at(&bytes, offset)
+                                .map_err(anyhow::Error::from)?;
+                            offset += bytes.len() as u64;
+                        }
+                        Ok::<_, Error>(())
+                    })
+                    .map_err(errno)?;
+            }
+
+            if let Some(file) = self.open_files.get_mut(&handle) {
+                file.source = Source::Spooled(spooled);
+            }
+        }
+
+        match self.open_files.get(&handle).map(|file| &file.source) {
+            Some(Source::Spooled(file)) => Ok(file),
+            _ => Err(libc::EBADF),
+        }
+    }
+}
+
+impl<C: Constellation> Filesystem for ConstellationFs<C> {
+    fn lookup(&mut self, _: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.lookup_path(parent, &name.to_string_lossy()) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn getattr(&mut self, _: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.getattr_inode(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn setattr(
+        &mut self,
+        _: &Request<'_>,
+        ino: u64,
+        _: Option<u32>,
+        _: Option<u32>,
+        _: Option<u32>,
+        size: Option<u64>,
+        _: Option<TimeOrNow>,
+        _: Option<TimeOrNow>,
+        _: Option<SystemTime>,
+        fh: Option<u64>,
+        _: Option<SystemTime>,
+        _: Option<SystemTime>,
+        _: Option<SystemTime>,
+        _: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        if let Some(size) = size {
+            if let Err(e) = self.truncate(ino, fh, size as usize) {
+                reply.error(e);
+                return;
+            }
+        }
+
+        match self.getattr_inode(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _: &Request<'_>,
+        ino: u64,
+        _: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let entries = match self.list(ino) {
+            Ok(entries) => entries,
+            Err(e) => {
+                reply.error(e);
+                return;
+            }
+        };
+
+        for (index, (inode, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(inode, (index + 1) as i64, kind, name) {
+                break;
+            }
+        }
+
+        reply.ok();
+    }
+
+    fn open(&mut self, _: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        match self.open_inode(ino, flags & libc::O_TRUNC != 0) {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn create(
+        &mut self,
+        _: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _: u32,
+        _: u32,
+        _: i32,
+        reply: ReplyCreate,
+    ) {
+        match self.create_file(parent, &name.to_string_lossy()) {
+            Ok((attr, fh)) => reply.created(&TTL, &attr, 0, fh, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _: &Request<'_>,
+        _: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _: i32,
+        _: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.read_handle(fh, offset as usize, size as usize) {
+            Ok(data) => reply.data(&data),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _: &Request<'_>,
+        _: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _: u32,
+        _: i32,
+        _: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        match self.write_handle(fh, offset as usize, data) {
+            Ok(size) => reply.written(size as u32),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn flush(&mut self, _: &Request<'_>, _: u64, fh: u64, _: u64, reply: ReplyEmpty) {
+        match self.flush_handle(fh) {
+            Ok(_) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _: &Request<'_>,
+        _: u64,
+        fh: u64,
+        _: i32,
+        _: Option<u64>,
+        _: bool,
+        reply: ReplyEmpty,
+    ) {
+        match self.release_handle(fh) {
+            Ok(_) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn mkdir(
+        &mut self,
+        _: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _: u32,
+        _: u32,
+        reply: ReplyEntry,
+    ) {
+        match self.make_directory(parent, &name.to_string_lossy()) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn unlink(&mut self, _: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove_item(parent, &name.to_string_lossy(), false) {
+            Ok(_) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn rmdir(&mut self, _: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove_item(parent, &name.to_string_lossy(), true) {
+            Ok(_) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        _: u32,
+        reply: ReplyEmpty,
+    ) {
+        match self.rename_item(
+            parent,
+            &name.to_string_lossy(),
+            newparent,
+            &newname.to_string_lossy(),
+        ) {
+            Ok(_) => reply.ok(),
+            Err(e) => reply.error(e),
+        }
+    }
+}
+
+/// Carries the attributes that are kept by the user over to the item replacing it
+fn copy_attributes(from: &Item, to: &Item) {
+    to.set_favorite(from.favorite());
+    to.set_description(&from.description());
+    if let (Item::File(from), Item::File(to)) = (from, to) {
+        to.set_tags(from.tags());
+        to.set_metadata(from.metadata());
+    }
+}
+
+/// Creates a file in the temporary directory to hold the contents of a handle.
+///
+/// Note: The file is unlinked as soon as it is created, so its contents are only reachable
+///       through the handle and are removed once it is closed, even if the process exits early
+fn spool_file() -> std::io::Result<File> {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+
+    let directory = std::env::temp_dir();
+    loop {
+        let path = directory.join(format!(
+            ".constellation-fuse-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+
+        match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+        {
+            Ok(file) => {
+                std::fs::remove_file(&path)?;
+                return Ok(file);
+            }
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Reads up to `size` bytes at `offset` from a spooled file, stopping early at the end of the file
+fn read_spooled(file: &File, offset: usize, size: usize) -> std::io::Result<Bytes> {
+    let mut data = vec![0; size];
+    let mut read = 0;
+    while read < size {
+        match file.read_at(&mut data[read..], (offset + read) as u64) {
+            Ok(0) => break,
+            Ok(length) => read += length,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    data.truncate(read);
+    Ok(Bytes::from(data))
+}
+
+fn io_errno(error: std::io::Error) -> c_int {
+    error.raw_os_error().unwrap_or(libc::EIO)
+}
+
+/// Converts a constellation error into the closest matching errno
+fn errno(error: Error) -> c_int {
+    match error {
+        Error::InvalidItem
+        | Error::InvalidFile
+        | Error::InvalidPath
+        | Error::InvalidDirectory
+        | Error::FileNotFound
+        | Error::DirectoryNotFound => libc::ENOENT,
+        Error::DuplicateName | Error::FileExist | Error::DirectoryExist => libc::EEXIST,
+        Error::DirectoryNotEmpty => libc::ENOTEMPTY,
+        Error::ItemNotFile => libc::EISDIR,
+        Error::ItemNotDirectory | Error::InvalidConversion => libc::ENOTDIR,
+        Error::DirParadox => libc::EINVAL,
+        Error::InvalidLength { .. } => libc::ENAMETOOLONG,
+        error => {
+            tracing::error!(%error, "constellation operation failed");
+            libc::EIO
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rust_ipfs::{Multiaddr, Protocol};
+    use warp::constellation::Constellation;
+    use warp::multipass::MultiPass;
+    use warp_ipfs::config::{Bootstrap, Discovery};
+    use warp_ipfs::{WarpIpfsBuilder, WarpIpfsInstance};
+
+    use super::ConstellationFs;
+    use crate::inode::ROOT_INODE;
+
+    async fn create_instance() -> anyhow::Result<WarpIpfsInstance> {
+        let mut config = warp_ipfs::config::Config::development();
+        *config.listen_on_mut() = vec![Multiaddr::empty().with(Protocol::Memory(0))];
+        config.ipfs_setting_mut().memory_transport = true;
+        config.store_setting_mut().discovery = Discovery::None;
+        config.ipfs_setting_mut().relay_client.relay_address = vec![];
+        config.ipfs_setting_mut().mdns.enable = false;
+        *config.bootstrap_mut() = Bootstrap::None;
+
+        let mut instance = WarpIpfsBuilder::default().set_config(config).await;
+        instance.tesseract().unlock(b"internal pass")?;
+        instance.create_identity(None, None).await?;
+        Ok(instance)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn file_operations() -> anyhow::Result<()> {
+        let instance = create_instance().await?;
+        let handle = tokio::runtime::Handle::current();
+
+        // Operations block on the runtime so they are driven from a blocking thread like fuse would
+        let instance = tokio::task::spawn_blocking(move || {
+            let mut fs = ConstellationFs::new(instance, handle);
+
+            let docs = fs.make_directory(ROOT_INODE, "docs").unwrap();
+            let (attr, fh) = fs.create_file(docs.ino, "notes.txt").unwrap();
+            assert_eq!(fs.write_handle(fh, 0, b"hello, world").unwrap(), 12);
+            fs.release_handle(fh).unwrap();
+
+            let fh = fs.open_inode(attr.ino, false).unwrap();
+            assert_eq!(&fs.read_handle(fh, 7, 64).unwrap()[..], b"world");
+            fs.release_handle(fh).unwrap();
+
+            fs.make_directory(ROOT_INODE, "archive").unwrap();
+            fs.rename_item(docs.ino, "notes.txt", ROOT_INODE, "old-notes.txt")
+                .unwrap();
+            assert_eq!(
+                fs.getattr_inode(attr.ino).unwrap().size,
+                b"hello, world".len() as u64
+            );
+
+            let entries = fs.list(ROOT_INODE).unwrap();
+            assert!(entries.iter().any(|(_, _, name)| name == "old-notes.txt"));
+
+            fs.remove_item(ROOT_INODE, "old-notes.txt", false).unwrap();
+            fs.remove_item(ROOT_INODE, "docs", true).unwrap();
+            assert!(fs.lookup_path(ROOT_INODE, "docs").is_err());
+
+            fs.constellation
+        })
+        .await?;
+
+        assert_eq!(instance.root_directory().get_items().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rewrite_and_replace() -> anyhow::Result<()> {
+        let instance = create_instance().await?;
+        let handle = tokio::runtime::Handle::current();
+
+        let instance = tokio::task::spawn_blocking(move || {
+            let mut fs = ConstellationFs::new(instance, handle);
+
+            let (attr, fh) = fs.create_file(ROOT_INODE, "notes.txt").unwrap();
+            fs.write_handle(fh, 0, b"first").unwrap();
+            fs.release_handle(fh).unwrap();
+
+            let item = fs.item("notes.txt").unwrap();
+            item.set_favorite(true);
+            item.add_tag("work");
+            item.insert_metadata("project", "warp");
+
+            // Rewriting the file keeps its attributes and leaves no temporary items behind
+            let fh = fs.open_inode(attr.ino, true).unwrap();
+            fs.write_handle(fh, 0, b"second").unwrap();
+            fs.release_handle(fh).unwrap();
+
+            let item = fs.item("notes.txt").unwrap();
+            assert_eq!(item.size(), b"second".len());
+            assert!(item.favorite());
+            assert!(item.has_tag("work"));
+            assert_eq!(
+                item.metadata().get("project").map(String::as_str),
+                Some("warp")
+            );
+            assert_eq!(fs.list(ROOT_INODE).unwrap().len(), 3);
+
+            // Renaming over an existing file replaces it
+            let (draft, fh) = fs.create_file(ROOT_INODE, "draft.txt").unwrap();
+            fs.write_handle(fh, 0, b"draft").unwrap();
+            fs.release_handle(fh).unwrap();
+            fs.rename_item(ROOT_INODE, "draft.txt", ROOT_INODE, "notes.txt")
+                .unwrap();
+
+            let notes = fs.lookup_path(ROOT_INODE, "notes.txt").unwrap();
+            assert_eq!(notes.ino, draft.ino);
+            let fh = fs.open_inode(notes.ino, false).unwrap();
+            assert_eq!(&fs.read_handle(fh, 0, 64).unwrap()[..], b"draft");
+            fs.release_handle(fh).unwrap();
+
+            // A rename that cannot succeed leaves both items untouched
+            let docs = fs.make_directory(ROOT_INODE, "docs").unwrap();
+            assert_eq!(
+                fs.rename_item(ROOT_INODE, "notes.txt", ROOT_INODE, "docs"),
+                Err(libc::EISDIR)
+            );
+            assert!(fs.item("notes.txt").is_ok());
+            assert!(fs.item("docs").is_ok());
+
+            // Moving into another directory under a new name
+            fs.rename_item(ROOT_INODE, "notes.txt", docs.ino, "archived.txt")
+                .unwrap();
+            assert!(fs.item("docs/archived.txt").is_ok());
+            assert_eq!(fs.path(notes.ino).unwrap(), "docs/archived.txt");
+
+            // Looking up missing paths does not allocate inodes
+            assert!(fs.lookup_path(ROOT_INODE, "missing.txt").is_err());
+            assert!(fs.inodes.get("missing.txt").is_none());
+
+            fs.constellation
+        })
+        .await?;
+
+        assert_eq!(instance.root_directory().get_items().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn reads_and_writes_by_offset() -> anyhow::Result<()> {
+        let instance = create_instance().await?;
+        let handle = tokio::runtime::Handle::current();
+
+        tokio::task::spawn_blocking(move || {
+            let mut fs = ConstellationFs::new(instance, handle);
+            let data = (0..=255u8).cycle().take(1 << 20).collect::<Vec<_>>();
+
+            // Writes that arrive out of order are placed at their offsets
+            let (attr, fh) = fs.create_file(ROOT_INODE, "data.bin").unwrap();
+            fs.write_handle(fh, 4096, &data[4096..]).unwrap();
+            fs.write_handle(fh, 0, &data[..4096]).unwrap();
+            assert_eq!(fs.getattr_inode(attr.ino).unwrap().size, data.len() as u64);
+            fs.release_handle(fh).unwrap();
+
+            // Reads can move forward and back through the file
+            let fh = fs.open_inode(attr.ino, false).unwrap();
+            assert_eq!(
+                &fs.read_handle(fh, 1000, 100).unwrap()[..],
+                &data[1000..1100]
+            );
+            assert_eq!(
+                &fs.read_handle(fh, 500_000, 100).unwrap()[..],
+                &data[500_000..500_100]
+            );
+            assert_eq!(&fs.read_handle(fh, 10, 10).unwrap()[..], &data[10..20]);
+            assert_eq!(fs.read_handle(fh, data.len() - 5, 64).unwrap().len(), 5);
+
+            // Writing into the middle of the file keeps the rest of its contents
+            fs.write_handle(fh, 100, b"patched").unwrap();
+            fs.release_handle(fh).unwrap();
+
+            let mut expected = data.clone();
+            expected[100..107].copy_from_slice(b"patched");
+            let fh = fs.open_inode(attr.ino, false).unwrap();
+            assert_eq!(
+                &fs.read_handle(fh, 0, expected.len()).unwrap()[..],
+                &expected[..]
+            );
+            fs.release_handle(fh).unwrap();
+
+            // Truncating a file that is not open writes the shortened contents back
+            fs.truncate(attr.ino, None, 50).unwrap();
+            assert_eq!(fs.getattr_inode(attr.ino).unwrap().size, 50);
+        })
+        .await?;
+
+        Ok(())
+    }
+}
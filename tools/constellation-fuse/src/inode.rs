@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+pub const ROOT_INODE: u64 = 1;
+
+/// Maps inode numbers handed out to the kernel to paths within constellation.
+/// Paths are stored relative to the root directory without a leading slash, with
+/// the root itself being an empty string.
+#[derive(Debug)]
+pub struct InodeTable {
+    paths: HashMap<u64, String>,
+    inodes: HashMap<String, u64>,
+    next_inode: u64,
+}
+
+impl Default for InodeTable {
+    fn default() -> Self {
+        let mut table = Self {
+            paths: HashMap::new(),
+            inodes: HashMap::new(),
+            next_inode: ROOT_INODE + 1,
+        };
+        table.paths.insert(ROOT_INODE, String::new());
+        table.inodes.insert(String::new(), ROOT_INODE);
+        table
+    }
+}
+
+impl InodeTable {
+    pub fn path(&self, inode: u64) -> Option<&str> {
+        self.paths.get(&inode).map(String::as_str)
+    }
+
+    pub fn get(&self, path: &str) -> Option<u64> {
+        self.inodes.get(path).copied()
+    }
+
+    /// Returns the inode for the path, allocating a new one if it has not been seen yet
+    pub fn get_or_insert(&mut self, path: &str) -> u64 {
+        if let Some(inode) = self.inodes.get(path) {
+            return *inode;
+        }
+
+        let inode = self.next_inode;
+        self.next_inode += 1;
+        self.paths.insert(inode, path.to_string());
+        self.inodes.insert(path.to_string(), inode);
+        inode
+    }
+
+    /// Removes the path, along with anything underneath it
+    pub fn remove(&mut self, path: &str) {
+        let prefix = format!("{path}/");
+        self.inodes.retain(|p, inode| {
+            let retain = p != path && !p.starts_with(&prefix);
+            if !retain {
+                self.paths.remove(inode);
+            }
+            retain
+        });
+    }
+
+    /// Moves the path, along with anything underneath it, keeping the existing inodes
+    pub fn rename(&mut self, from: &str, to: &str) {
+        let prefix = format!("{from}/");
+        let moved = self
+            .inodes
+            .iter()
+            .filter(|(p, _)| p.as_str() == from || p.starts_with(&prefix))
+            .map(|(p, inode)| (p.clone(), *inode))
+            .collect::<Vec<_>>();
+
+        self.remove(to);
+
+        for (old_path, inode) in moved {
+            self.inodes.remove(&old_path);
+            let path = format!("{to}{}", &old_path[from.len()..]);
+            self.paths.insert(inode, path.clone());
+            self.inodes.insert(path, inode);
+        }
+    }
+}
+
+/// Joins the name onto the parent path
+pub fn join(parent: &str, name: &str) -> String {
+    match parent.is_empty() {
+        true => name.to_string(),
+        false => format!("{parent}/{name}"),
+    }
+}
+
+/// Splits the path into its parent and name
+pub fn split(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some((parent, name)) => (parent, name),
+        None => ("", path),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{join, split, InodeTable, ROOT_INODE};
+
+    #[test]
+    fn allocate_and_remove() {
+        let mut table = InodeTable::default();
+        assert_eq!(table.path(ROOT_INODE), Some(""));
+
+        let docs = table.get_or_insert("docs");
+        let readme = table.get_or_insert("docs/readme.md");
+        assert_eq!(table.get_or_insert("docs"), docs);
+        assert_ne!(docs, readme);
+
+        table.remove("docs");
+        assert!(table.path(docs).is_none());
+        assert!(table.path(readme).is_none());
+        assert_eq!(table.path(ROOT_INODE), Some(""));
+    }
+
+    #[test]
+    fn rename_keeps_inodes() {
+        let mut table = InodeTable::default();
+        let docs = table.get_or_insert("docs");
+        let readme = table.get_or_insert("docs/readme.md");
+        let other = table.get_or_insert("documents");
+
+        table.rename("docs", "archive/docs");
+        assert_eq!(table.path(docs), Some("archive/docs"));
+        assert_eq!(table.path(readme), Some("archive/docs/readme.md"));
+        assert_eq!(table.path(other), Some("documents"));
+        assert_eq!(table.get_or_insert("archive/docs/readme.md"), readme);
+    }
+
+    #[test]
+    fn join_and_split() {
+        assert_eq!(join("", "docs"), "docs");
+        assert_eq!(join("docs", "readme.md"), "docs/readme.md");
+        assert_eq!(split("docs/readme.md"), ("docs", "readme.md"));
+        assert_eq!(split("readme.md"), ("", "readme.md"));
+    }
+}
@@ -0,0 +1,109 @@
+#[cfg(target_os = "linux")]
+mod filesystem;
+#[cfg(target_os = "linux")]
+mod inode;
+
+#[cfg(target_os = "linux")]
+mod mount {
+    use std::path::{Path, PathBuf};
+
+    use clap::Parser;
+    use fuser::MountOption;
+
+    use warp::crypto::zeroize::Zeroizing;
+    use warp::multipass::LocalIdentity;
+    use warp::tesseract::Tesseract;
+    use warp_ipfs::config::Discovery;
+    use warp_ipfs::{WarpIpfsBuilder, WarpIpfsInstance};
+
+    use crate::filesystem::ConstellationFs;
+
+    #[derive(Debug, Parser)]
+    #[clap(name = "constellation-fuse")]
+    struct Opt {
+        /// Path to directory
+        #[clap(long)]
+        path: PathBuf,
+
+        /// Name of the tesseract keystore
+        #[clap(long)]
+        keystore: Option<String>,
+
+        /// Password to unlock keystore
+        #[clap(long)]
+        password: Option<String>,
+
+        /// Directory to mount constellation onto
+        mountpoint: PathBuf,
+    }
+
+    async fn setup<P: AsRef<Path>>(
+        path: P,
+        keystore: Option<String>,
+        passphrase: Zeroizing<String>,
+    ) -> anyhow::Result<WarpIpfsInstance> {
+        let path = path.as_ref();
+        let keystore_path = path.join(keystore.unwrap_or("tesseract_store".into()));
+
+        let tesseract = Tesseract::from_file(keystore_path)?;
+        tesseract.unlock(passphrase.as_bytes())?;
+
+        let mut config = warp_ipfs::config::Config::production(path);
+        config.store_setting_mut().discovery = Discovery::None;
+        config.ipfs_setting_mut().mdns.enable = false;
+        *config.enable_relay_mut() = false;
+
+        let instance = WarpIpfsBuilder::default()
+            .set_tesseract(tesseract)
+            .set_config(config)
+            .await;
+
+        //validating that account exist
+        _ = instance.identity().await?;
+        Ok(instance)
+    }
+
+    pub async fn run() -> anyhow::Result<()> {
+        let opt = Opt::parse();
+
+        let password = Zeroizing::new(match opt.password {
+            Some(password) => password,
+            None => rpassword::prompt_password("Enter A Password: ")?,
+        });
+
+        let instance = setup(&opt.path, opt.keystore.clone(), password).await?;
+
+        let fs = ConstellationFs::new(instance, tokio::runtime::Handle::current());
+
+        let options = [
+            MountOption::FSName("constellation".into()),
+            MountOption::DefaultPermissions,
+            MountOption::NoDev,
+            MountOption::NoSuid,
+        ];
+
+        // The session is unmounted once it is dropped
+        let session = fuser::spawn_mount2(fs, &opt.mountpoint, &options)?;
+
+        println!(
+            "Constellation mounted at {}. Press Ctrl+C to unmount",
+            opt.mountpoint.display()
+        );
+
+        tokio::signal::ctrl_c().await?;
+
+        session.join();
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    mount::run().await
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("constellation-fuse is only supported on linux");
+}
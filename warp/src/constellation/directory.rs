@@ -5,6 +5,7 @@ use crate::error::Error;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use derive_more::Display;
+use indexmap::{IndexMap, IndexSet};
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
@@ -56,6 +57,18 @@ pub struct Directory {
     /// List of `Item`, which would represents either `File` or `Directory`
     items: Arc<RwLock<Vec<Item>>>,
 
+    /// Custom key/value metadata of the `Directory`
+    #[serde(default)]
+    metadata: Arc<RwLock<IndexMap<String, String>>>,
+
+    /// Tags attached to the `Directory`
+    #[serde(default)]
+    tags: Arc<RwLock<IndexSet<String>>>,
+
+    /// Maximum total size of the contents of the `Directory`
+    #[serde(default)]
+    quota: Arc<RwLock<Option<usize>>>,
+
     /// Path of directory
     #[serde(default)]
     path: Arc<String>,
@@ -70,6 +83,8 @@ impl std::fmt::Debug for Directory {
             .field("name", &self.name())
             .field("description", &self.description())
             .field("favorite", &self.favorite())
+            .field("tags", &self.tags())
+            .field("quota", &self.quota())
             .field("creation", &self.creation())
             .field("modified", &self.modified())
             .field("items", &self.items)
@@ -108,6 +123,9 @@ impl Default for Directory {
             modified: Arc::new(RwLock::new(timestamp)),
             directory_type: Default::default(),
             items: Default::default(),
+            metadata: Default::default(),
+            tags: Default::default(),
+            quota: Default::default(),
             path: Arc::new("/".into()),
             signal: Arc::default(),
         }
@@ -622,6 +640,73 @@ impl Directory {
         *self.favorite.read()
     }
 
+    /// Get the custom metadata of the directory
+    pub fn metadata(&self) -> IndexMap<String, String> {
+        self.metadata.read().clone()
+    }
+
+    /// Replace the custom metadata of the directory
+    pub fn set_metadata(&self, metadata: IndexMap<String, String>) {
+        *self.metadata.write() = metadata;
+        self.set_modified(None);
+        self.signal();
+    }
+
+    /// Insert a metadata entry, returning the previous value, if any
+    pub fn insert_metadata(&self, key: &str, value: &str) -> Option<String> {
+        let previous = self
+            .metadata
+            .write()
+            .insert(key.to_string(), value.to_string());
+        self.set_modified(None);
+        self.signal();
+        previous
+    }
+
+    /// Remove a metadata entry, returning the value, if any
+    pub fn remove_metadata(&self, key: &str) -> Option<String> {
+        let value = self.metadata.write().shift_remove(key)?;
+        self.set_modified(None);
+        self.signal();
+        Some(value)
+    }
+
+    /// Get the tags of the directory
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.read().iter().cloned().collect()
+    }
+
+    /// Replace the tags of the directory
+    pub fn set_tags(&self, tags: Vec<String>) {
+        *self.tags.write() = IndexSet::from_iter(tags);
+        self.set_modified(None);
+        self.signal();
+    }
+
+    /// Add a tag to the directory. Returns `false` if the tag was already present
+    pub fn add_tag(&self, tag: &str) -> bool {
+        if !self.tags.write().insert(tag.to_string()) {
+            return false;
+        }
+        self.set_modified(None);
+        self.signal();
+        true
+    }
+
+    /// Remove a tag from the directory. Returns `false` if the tag was not present
+    pub fn remove_tag(&self, tag: &str) -> bool {
+        if !self.tags.write().shift_remove(tag) {
+            return false;
+        }
+        self.set_modified(None);
+        self.signal();
+        true
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.read().contains(tag)
+    }
+
     pub fn description(&self) -> String {
         self.description.read().to_owned()
     }
@@ -635,6 +720,23 @@ impl Directory {
         self.get_items().iter().map(Item::size).sum()
     }
 
+    /// Space used to store the contents of the directory, including the image variants of files
+    pub fn stored_size(&self) -> usize {
+        self.get_items().iter().map(Item::stored_size).sum()
+    }
+
+    /// Maximum total size of the contents of the directory, if any
+    pub fn quota(&self) -> Option<usize> {
+        *self.quota.read()
+    }
+
+    /// Set the maximum total size of the contents of the directory.
+    /// Supplying `None` will remove the quota
+    pub fn set_quota(&self, quota: Option<usize>) {
+        *self.quota.write() = quota;
+        self.signal();
+    }
+
     pub fn set_creation(&self, creation: DateTime<Utc>) {
         *self.creation.write() = creation
     }
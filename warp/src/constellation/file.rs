@@ -3,6 +3,7 @@ use crate::error::Error;
 use bytes::Bytes;
 use chrono::{DateTime, Utc};
 use derive_more::Display;
+use indexmap::{IndexMap, IndexSet};
 use mediatype;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
@@ -80,6 +81,23 @@ pub struct File {
     /// External reference pointing to the source of the file
     reference: Arc<RwLock<Option<String>>>,
 
+    /// Resized copies of the file when it is an image
+    #[serde(default)]
+    variants: Arc<RwLock<Vec<ImageVariant>>>,
+
+    /// Key used to decrypt the contents pointed to by the reference.
+    /// Note: This is wrapped by the owner of the file and is not usable as-is
+    #[serde(default)]
+    encryption_key: Arc<RwLock<Option<Vec<u8>>>>,
+
+    /// Custom key/value metadata of the `File`
+    #[serde(default)]
+    metadata: Arc<RwLock<IndexMap<String, String>>>,
+
+    /// Tags attached to the `File`
+    #[serde(default)]
+    tags: Arc<RwLock<IndexSet<String>>>,
+
     /// Path to file
     #[serde(default)]
     path: Arc<String>,
@@ -97,6 +115,7 @@ impl std::fmt::Debug for File {
             .field("thumbnail", &self.thumbnail_format())
             .field("reference", &self.reference())
             .field("favorite", &self.favorite())
+            .field("tags", &self.tags())
             .field("creation", &self.creation())
             .field("modified", &self.modified())
             .field("path", &self.path())
@@ -136,6 +155,10 @@ impl Default for File {
             file_type: Default::default(),
             hash: Default::default(),
             reference: Default::default(),
+            encryption_key: Default::default(),
+            variants: Default::default(),
+            metadata: Default::default(),
+            tags: Default::default(),
             path: Arc::new("/".into()),
             signal: Arc::default(),
         }
@@ -240,6 +263,95 @@ impl File {
         *self.favorite.read()
     }
 
+    /// Get the custom metadata of the file
+    pub fn metadata(&self) -> IndexMap<String, String> {
+        self.metadata.read().clone()
+    }
+
+    /// Replace the custom metadata of the file
+    pub fn set_metadata(&self, metadata: IndexMap<String, String>) {
+        *self.metadata.write() = metadata;
+        *self.modified.write() = Utc::now();
+        self.signal();
+    }
+
+    /// Insert a metadata entry, returning the previous value, if any
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use warp::constellation::file::File;
+    ///
+    /// let file = File::new("test.txt");
+    /// file.insert_metadata("project", "warp");
+    ///
+    /// assert_eq!(file.metadata().get("project").map(String::as_str), Some("warp"));
+    /// ```
+    pub fn insert_metadata(&self, key: &str, value: &str) -> Option<String> {
+        let previous = self
+            .metadata
+            .write()
+            .insert(key.to_string(), value.to_string());
+        *self.modified.write() = Utc::now();
+        self.signal();
+        previous
+    }
+
+    /// Remove a metadata entry, returning the value, if any
+    pub fn remove_metadata(&self, key: &str) -> Option<String> {
+        let value = self.metadata.write().shift_remove(key)?;
+        *self.modified.write() = Utc::now();
+        self.signal();
+        Some(value)
+    }
+
+    /// Get the tags of the file
+    pub fn tags(&self) -> Vec<String> {
+        self.tags.read().iter().cloned().collect()
+    }
+
+    /// Replace the tags of the file
+    pub fn set_tags(&self, tags: Vec<String>) {
+        *self.tags.write() = IndexSet::from_iter(tags);
+        *self.modified.write() = Utc::now();
+        self.signal();
+    }
+
+    /// Add a tag to the file. Returns `false` if the tag was already present
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use warp::constellation::file::File;
+    ///
+    /// let file = File::new("test.txt");
+    /// assert!(file.add_tag("work"));
+    /// assert!(!file.add_tag("work"));
+    /// assert!(file.has_tag("work"));
+    /// ```
+    pub fn add_tag(&self, tag: &str) -> bool {
+        if !self.tags.write().insert(tag.to_string()) {
+            return false;
+        }
+        *self.modified.write() = Utc::now();
+        self.signal();
+        true
+    }
+
+    /// Remove a tag from the file. Returns `false` if the tag was not present
+    pub fn remove_tag(&self, tag: &str) -> bool {
+        if !self.tags.write().shift_remove(tag) {
+            return false;
+        }
+        *self.modified.write() = Utc::now();
+        self.signal();
+        true
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.read().contains(tag)
+    }
+
     /// Set the reference of the file
     ///
     /// # Examples
@@ -277,6 +389,17 @@ impl File {
         *self.size.read()
     }
 
+    /// Space used to store the file, including its image variants
+    pub fn stored_size(&self) -> usize {
+        self.size()
+            + self
+                .variants
+                .read()
+                .iter()
+                .map(ImageVariant::size)
+                .sum::<usize>()
+    }
+
     /// Set the size the file
     ///
     /// # Examples
@@ -312,6 +435,32 @@ impl File {
         *self.hash.write() = hash;
     }
 
+    pub fn encryption_key(&self) -> Option<Vec<u8>> {
+        self.encryption_key.read().clone()
+    }
+
+    pub fn set_encryption_key(&self, key: Option<Vec<u8>>) {
+        *self.encryption_key.write() = key;
+        self.signal();
+    }
+
+    /// Resized copies of the image, ordered from smallest to largest
+    pub fn variants(&self) -> Vec<ImageVariant> {
+        self.variants.read().clone()
+    }
+
+    pub fn set_variants(&self, mut variants: Vec<ImageVariant>) {
+        variants.sort_by_key(ImageVariant::dimension);
+        *self.variants.write() = variants;
+        self.signal();
+    }
+
+    /// Select the variant best suited to display the image at `size` pixels along its longest side.
+    /// Returns `None` when the original would be a better fit than any of the variants
+    pub fn best_variant(&self, size: u32) -> Option<ImageVariant> {
+        ImageVariant::select(&self.variants.read(), size).cloned()
+    }
+
     pub fn set_file_type(&self, file_type: FileType) {
         *self.file_type.write() = file_type;
         self.signal();
@@ -383,6 +532,63 @@ impl File {
     }
 }
 
+/// Resized copy of an image, stored alongside the original
+#[derive(Clone, Deserialize, Serialize, Debug, PartialEq, Eq)]
+pub struct ImageVariant {
+    width: u32,
+    height: u32,
+    size: usize,
+    format: FormatType,
+    reference: String,
+}
+
+impl ImageVariant {
+    pub fn new(width: u32, height: u32, size: usize, format: FormatType, reference: &str) -> Self {
+        Self {
+            width,
+            height,
+            size,
+            format,
+            reference: reference.to_string(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Length of the longest side of the image
+    pub fn dimension(&self) -> u32 {
+        self.width.max(self.height)
+    }
+
+    /// Size of the encoded image in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn format(&self) -> FormatType {
+        self.format.clone()
+    }
+
+    /// External reference pointing to the image
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+
+    /// Select the smallest variant that is at least `size` pixels along its longest side
+    pub fn select(variants: &[ImageVariant], size: u32) -> Option<&ImageVariant> {
+        variants
+            .iter()
+            .filter(|variant| variant.dimension() >= size)
+            .min_by_key(|variant| variant.dimension())
+    }
+}
+
 #[derive(Default, Clone, Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub struct Hash {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -498,7 +704,8 @@ impl Hash {
 
 #[cfg(test)]
 mod test {
-    use super::File;
+    use super::{File, ImageVariant};
+    use crate::constellation::item::FormatType;
 
     #[test]
     fn name_length() {
@@ -513,4 +720,26 @@ mod test {
         assert_eq!(long_file.name(), &long_name[..256]);
         assert_ne!(long_file.name(), &long_name[..255]);
     }
+
+    #[test]
+    fn best_variant() {
+        let file = File::new("image.png");
+        assert!(file.best_variant(64).is_none());
+
+        file.set_variants(
+            [1024, 64, 256]
+                .into_iter()
+                .map(|size| {
+                    ImageVariant::new(size, size / 2, 0, FormatType::Generic, &size.to_string())
+                })
+                .collect(),
+        );
+
+        assert_eq!(file.variants()[0].width(), 64);
+        assert_eq!(file.best_variant(32).map(|v| v.width()), Some(64));
+        assert_eq!(file.best_variant(100).map(|v| v.width()), Some(256));
+        assert_eq!(file.best_variant(1024).map(|v| v.width()), Some(1024));
+        // The original would be needed for anything larger
+        assert!(file.best_variant(2048).is_none());
+    }
 }
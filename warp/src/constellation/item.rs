@@ -10,6 +10,7 @@ use super::directory::Directory;
 use super::file::{File, FileType};
 use crate::error::Error;
 use derive_more::Display;
+use indexmap::IndexMap;
 
 /// `Item` is a type that handles both `File` and `Directory`
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -175,6 +176,14 @@ impl Item {
         }
     }
 
+    /// Space used to store the item, including the image variants of files
+    pub fn stored_size(&self) -> usize {
+        match self {
+            Item::File(file) => file.stored_size(),
+            Item::Directory(directory) => directory.stored_size(),
+        }
+    }
+
     pub fn thumbnail_format(&self) -> FormatType {
         match self {
             Item::File(file) => file.thumbnail_format(),
@@ -203,6 +212,61 @@ impl Item {
         }
     }
 
+    /// Get the custom metadata of `Item`
+    pub fn metadata(&self) -> IndexMap<String, String> {
+        match self {
+            Item::File(file) => file.metadata(),
+            Item::Directory(directory) => directory.metadata(),
+        }
+    }
+
+    /// Insert a metadata entry into `Item`, returning the previous value, if any
+    pub fn insert_metadata(&self, key: &str, value: &str) -> Option<String> {
+        match self {
+            Item::File(file) => file.insert_metadata(key, value),
+            Item::Directory(directory) => directory.insert_metadata(key, value),
+        }
+    }
+
+    /// Remove a metadata entry from `Item`, returning the value, if any
+    pub fn remove_metadata(&self, key: &str) -> Option<String> {
+        match self {
+            Item::File(file) => file.remove_metadata(key),
+            Item::Directory(directory) => directory.remove_metadata(key),
+        }
+    }
+
+    /// Get the tags of `Item`
+    pub fn tags(&self) -> Vec<String> {
+        match self {
+            Item::File(file) => file.tags(),
+            Item::Directory(directory) => directory.tags(),
+        }
+    }
+
+    /// Add a tag to `Item`. Returns `false` if the tag was already present
+    pub fn add_tag(&self, tag: &str) -> bool {
+        match self {
+            Item::File(file) => file.add_tag(tag),
+            Item::Directory(directory) => directory.add_tag(tag),
+        }
+    }
+
+    /// Remove a tag from `Item`. Returns `false` if the tag was not present
+    pub fn remove_tag(&self, tag: &str) -> bool {
+        match self {
+            Item::File(file) => file.remove_tag(tag),
+            Item::Directory(directory) => directory.remove_tag(tag),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        match self {
+            Item::File(file) => file.has_tag(tag),
+            Item::Directory(directory) => directory.has_tag(tag),
+        }
+    }
+
     /// Rename the name of `Item`
     pub fn rename(&self, name: &str) -> Result<(), Error> {
         let name = name.trim();
@@ -2,6 +2,10 @@
 pub mod directory;
 pub mod file;
 pub mod item;
+pub mod search;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod sync;
+pub mod usage;
 
 use std::path::{Path, PathBuf};
 
@@ -13,26 +17,171 @@ use chrono::{DateTime, Utc};
 
 use directory::Directory;
 use futures::stream::BoxStream;
-use futures::Stream;
-
+use futures::{Stream, StreamExt};
+use item::Item;
+use search::{ConstellationSearchStream, SearchOptions};
+use usage::UsageReport;
+use uuid::Uuid;
+
+/// Events emitted by `Constellation`.
+///
+/// Paths are the full path of the item from the root directory (eg `/docs/notes.txt`)
 #[derive(Debug, Clone)]
 pub enum ConstellationEventKind {
+    /// Progress of a file currently being uploaded
+    Progress {
+        path: String,
+        current: usize,
+        total: Option<usize>,
+    },
     Uploaded {
+        id: Uuid,
+        path: String,
         filename: String,
         size: Option<usize>,
     },
     Downloaded {
+        id: Uuid,
+        path: String,
         filename: String,
         size: Option<usize>,
         location: Option<PathBuf>,
     },
     Deleted {
+        id: Uuid,
+        path: String,
         item_name: String,
     },
     Renamed {
+        id: Uuid,
+        old_path: String,
+        new_path: String,
         old_item_name: String,
         new_item_name: String,
     },
+    Moved {
+        id: Uuid,
+        item_name: String,
+        from: String,
+        to: String,
+    },
+    DirectoryCreated {
+        id: Uuid,
+        path: String,
+    },
+    MetadataChanged {
+        id: Uuid,
+        path: String,
+        item_name: String,
+        key: String,
+        value: Option<String>,
+    },
+    TagsChanged {
+        id: Uuid,
+        path: String,
+        item_name: String,
+        tags: Vec<String>,
+    },
+    /// Metadata was removed from an image before it was stored
+    MetadataRemoved {
+        path: String,
+        /// kind of metadata that was removed from the file (eg `EXIF` or `XMP`)
+        removed: Vec<String>,
+    },
+    /// Index was replaced by one imported from elsewhere, such as another device
+    IndexSynced {
+        added: Vec<String>,
+        removed: Vec<String>,
+        modified: Vec<String>,
+    },
+}
+
+impl ConstellationEventKind {
+    /// Paths that are affected by the event
+    pub fn paths(&self) -> Vec<&str> {
+        match self {
+            ConstellationEventKind::Progress { path, .. }
+            | ConstellationEventKind::Uploaded { path, .. }
+            | ConstellationEventKind::Downloaded { path, .. }
+            | ConstellationEventKind::Deleted { path, .. }
+            | ConstellationEventKind::DirectoryCreated { path, .. }
+            | ConstellationEventKind::MetadataChanged { path, .. }
+            | ConstellationEventKind::TagsChanged { path, .. }
+            | ConstellationEventKind::MetadataRemoved { path, .. } => vec![path],
+            ConstellationEventKind::Renamed {
+                old_path, new_path, ..
+            } => vec![old_path, new_path],
+            ConstellationEventKind::Moved { from, to, .. } => vec![from, to],
+            ConstellationEventKind::IndexSynced {
+                added,
+                removed,
+                modified,
+            } => added
+                .iter()
+                .chain(removed)
+                .chain(modified)
+                .map(String::as_str)
+                .collect(),
+        }
+    }
+
+    /// Narrow the event down to the paths under `prefix`, returning `None` if no path is affected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///     use warp::constellation::ConstellationEventKind;
+    ///
+    ///     let event = ConstellationEventKind::IndexSynced {
+    ///         added: vec!["/docs/a.txt".into(), "/images/b.png".into()],
+    ///         removed: vec![],
+    ///         modified: vec![],
+    ///     };
+    ///
+    ///     let Some(ConstellationEventKind::IndexSynced { added, .. }) = event.filter_prefix("/docs") else {
+    ///         unreachable!()
+    ///     };
+    ///     assert_eq!(added, vec!["/docs/a.txt".to_string()]);
+    /// ```
+    pub fn filter_prefix(self, prefix: &str) -> Option<Self> {
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        let matches = |path: &str| {
+            prefix == "/"
+                || path == prefix
+                || path
+                    .strip_prefix(&prefix)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+
+        match self {
+            ConstellationEventKind::IndexSynced {
+                added,
+                removed,
+                modified,
+            } => {
+                let added = added.into_iter().filter(|p| matches(p)).collect::<Vec<_>>();
+                let removed = removed
+                    .into_iter()
+                    .filter(|p| matches(p))
+                    .collect::<Vec<_>>();
+                let modified = modified
+                    .into_iter()
+                    .filter(|p| matches(p))
+                    .collect::<Vec<_>>();
+
+                if added.is_empty() && removed.is_empty() && modified.is_empty() {
+                    return None;
+                }
+
+                Some(ConstellationEventKind::IndexSynced {
+                    added,
+                    removed,
+                    modified,
+                })
+            }
+            event => event.paths().into_iter().any(matches).then_some(event),
+        }
+    }
 }
 
 pub struct ConstellationEventStream(pub BoxStream<'static, ConstellationEventKind>);
@@ -76,6 +225,13 @@ pub enum Progression {
         /// error of why it failed, if any
         error: Error,
     },
+    MetadataRemoved {
+        /// name of the file
+        name: String,
+
+        /// kind of metadata that was removed from the file (eg `EXIF` or `XMP`)
+        removed: Vec<String>,
+    },
 }
 
 pub type ConstellationProgressStream = BoxStream<'static, Progression>;
@@ -91,7 +247,7 @@ pub trait Constellation: ConstellationEvent + Extension + Sync + Send + SingleHa
 
     /// Current size of the file system
     fn current_size(&self) -> usize {
-        self.root_directory().size()
+        self.root_directory().stored_size()
     }
 
     /// Max size allowed in the file system
@@ -191,6 +347,16 @@ pub trait Constellation: ConstellationEvent + Extension + Sync + Send + SingleHa
         Err(Error::Unimplemented)
     }
 
+    /// Used to download the variant of an image best suited to display it at `size` pixels along
+    /// its longest side, falling back to the original when there is no such variant
+    async fn get_variant_stream(
+        &self,
+        _: &str,
+        _: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        Err(Error::Unimplemented)
+    }
+
     /// Used to rename a file or directory in the filesystem
     async fn rename(&mut self, _: &str, _: &str) -> Result<(), Error> {
         Err(Error::Unimplemented)
@@ -215,6 +381,44 @@ pub trait Constellation: ConstellationEvent + Extension + Sync + Send + SingleHa
     async fn sync_ref(&mut self, _: &str) -> Result<(), Error> {
         Err(Error::Unimplemented)
     }
+
+    /// Used to search the filesystem for items matching the options
+    async fn search(&self, _: SearchOptions) -> Result<ConstellationSearchStream, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Used to set a metadata entry on a file or directory.
+    /// Supplying `None` as the value will remove the entry
+    async fn set_item_metadata(&mut self, _: &str, _: &str, _: Option<&str>) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Used to add a tag to a file or directory
+    async fn add_tag(&mut self, _: &str, _: &str) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Used to remove a tag from a file or directory
+    async fn remove_tag(&mut self, _: &str, _: &str) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List all items throughout the filesystem with the tag
+    fn list_by_tag(&self, tag: &str) -> Result<Vec<Item>, Error> {
+        let matcher = SearchOptions::default().set_tag(tag).matcher()?;
+        Ok(self.root_directory().search(&matcher))
+    }
+
+    /// Used to limit the total size of the contents of a directory.
+    /// Supplying `None` will remove the quota
+    async fn set_quota(&mut self, _: &str, _: Option<usize>) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Report of the space used throughout the filesystem
+    fn usage_report(&self) -> Result<UsageReport, Error> {
+        Ok(UsageReport::new(&self.root_directory()))
+    }
 }
 
 #[async_trait::async_trait]
@@ -223,6 +427,20 @@ pub trait ConstellationEvent: Sync + Send {
     async fn constellation_subscribe(&mut self) -> Result<ConstellationEventStream, Error> {
         Err(Error::Unimplemented)
     }
+
+    /// Subscribe to a stream of events affecting items under the path
+    async fn constellation_subscribe_path(
+        &mut self,
+        prefix: &str,
+    ) -> Result<ConstellationEventStream, Error> {
+        let prefix = prefix.to_string();
+        let stream = self.constellation_subscribe().await?;
+        let stream = stream.filter_map(move |event| {
+            let event = event.filter_prefix(&prefix);
+            async move { event }
+        });
+        Ok(ConstellationEventStream(stream.boxed()))
+    }
 }
 
 /// Types that would be used for import and export
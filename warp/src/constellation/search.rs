@@ -0,0 +1,388 @@
+#![allow(clippy::result_large_err)]
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+use futures::stream::BoxStream;
+use regex::{Regex, RegexBuilder};
+
+use super::directory::Directory;
+use super::file::FileType;
+use super::item::{Item, ItemType};
+use crate::error::Error;
+
+pub type ConstellationSearchStream = BoxStream<'static, Item>;
+
+/// Pattern used to match against the name of an `Item`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamePattern {
+    /// Name contains the text, ignoring case
+    Contains(String),
+    /// Shell-style glob where `*` matches any run of characters and `?` matches a single character
+    Glob(String),
+    /// Regular expression matched against the whole name
+    Regex(String),
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    path: Option<String>,
+    name: Option<NamePattern>,
+    item_type: Option<ItemType>,
+    mime: Option<String>,
+    size_range: Option<Range<usize>>,
+    date_range: Option<Range<DateTime<Utc>>>,
+    favorite: bool,
+    tag: Option<String>,
+    content: Option<String>,
+    limit: Option<usize>,
+}
+
+impl SearchOptions {
+    /// Only search within the directory at this path
+    pub fn set_path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub fn set_name(mut self, pattern: NamePattern) -> Self {
+        self.name = Some(pattern);
+        self
+    }
+
+    pub fn set_item_type(mut self, item_type: ItemType) -> Self {
+        self.item_type = Some(item_type);
+        self
+    }
+
+    /// Filter files by mime type. Either a full type (eg `image/png`) or a
+    /// top-level type with a wildcard (eg `image/*`)
+    pub fn set_mime(mut self, mime: &str) -> Self {
+        self.mime = Some(mime.to_lowercase());
+        self
+    }
+
+    pub fn set_size_range(mut self, range: Range<usize>) -> Self {
+        self.size_range = Some(range);
+        self
+    }
+
+    /// Filter by the modified date of the item
+    pub fn set_date_range(mut self, range: Range<DateTime<Utc>>) -> Self {
+        self.date_range = Some(range);
+        self
+    }
+
+    pub fn set_favorite(mut self) -> Self {
+        self.favorite = true;
+        self
+    }
+
+    /// Only match items that have the tag
+    pub fn set_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Search the contents of text files for the keyword.
+    /// Note: Files that are not text will not match when this is set
+    pub fn set_content(mut self, keyword: &str) -> Self {
+        self.content = Some(keyword.to_string());
+        self
+    }
+
+    pub fn set_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+}
+
+impl SearchOptions {
+    pub fn path(&self) -> Option<&str> {
+        self.path.as_deref()
+    }
+
+    pub fn name(&self) -> Option<&NamePattern> {
+        self.name.as_ref()
+    }
+
+    pub fn item_type(&self) -> Option<ItemType> {
+        self.item_type
+    }
+
+    pub fn mime(&self) -> Option<&str> {
+        self.mime.as_deref()
+    }
+
+    pub fn size_range(&self) -> Option<Range<usize>> {
+        self.size_range.clone()
+    }
+
+    pub fn date_range(&self) -> Option<Range<DateTime<Utc>>> {
+        self.date_range.clone()
+    }
+
+    pub fn favorite(&self) -> bool {
+        self.favorite
+    }
+
+    pub fn tag(&self) -> Option<&str> {
+        self.tag.as_deref()
+    }
+
+    pub fn content(&self) -> Option<&str> {
+        self.content.as_deref()
+    }
+
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+}
+
+impl SearchOptions {
+    /// Compile the options into a `SearchMatcher`, validating any name pattern
+    pub fn matcher(&self) -> Result<SearchMatcher, Error> {
+        let name = match &self.name {
+            Some(NamePattern::Contains(text)) => {
+                Some(compile(&format!(".*{}.*", regex::escape(text)))?)
+            }
+            Some(NamePattern::Glob(glob)) => Some(compile(&glob_to_regex(glob))?),
+            Some(NamePattern::Regex(expr)) => Some(compile(&format!("^(?:{expr})$"))?),
+            None => None,
+        };
+
+        Ok(SearchMatcher {
+            name,
+            options: self.clone(),
+        })
+    }
+}
+
+/// Matches an `Item` against the metadata filters of `SearchOptions`.
+/// Content filtering is left to the implementation since it requires reading the file.
+#[derive(Debug, Clone)]
+pub struct SearchMatcher {
+    name: Option<Regex>,
+    options: SearchOptions,
+}
+
+impl SearchMatcher {
+    pub fn options(&self) -> &SearchOptions {
+        &self.options
+    }
+
+    /// Check to see if `Item` satisfies the filters, excluding the content filter
+    pub fn matches(&self, item: &Item) -> bool {
+        if let Some(regex) = &self.name {
+            if !regex.is_match(&item.name()) {
+                return false;
+            }
+        }
+
+        if let Some(item_type) = self.options.item_type {
+            if item.item_type() != item_type {
+                return false;
+            }
+        }
+
+        if self.options.favorite && !item.favorite() {
+            return false;
+        }
+
+        if let Some(tag) = &self.options.tag {
+            if !item.has_tag(tag) {
+                return false;
+            }
+        }
+
+        if let Some(range) = &self.options.date_range {
+            if !range.contains(&item.modified()) {
+                return false;
+            }
+        }
+
+        if let Some(range) = &self.options.size_range {
+            if !range.contains(&item.size()) {
+                return false;
+            }
+        }
+
+        if let Some(mime) = &self.options.mime {
+            let Item::File(file) = item else {
+                return false;
+            };
+
+            if !mime_matches(mime, &file.file_type()) {
+                return false;
+            }
+        }
+
+        if self.options.content.is_some() && !item.is_file() {
+            return false;
+        }
+
+        true
+    }
+}
+
+impl Directory {
+    /// Walk the `Directory` and its children, returning every `Item` that matches the `SearchMatcher`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    ///     use warp::constellation::{directory::Directory, file::File};
+    ///     use warp::constellation::search::{NamePattern, SearchOptions};
+    ///
+    ///     let root = Directory::new("root");
+    ///     let sub = Directory::new("reports");
+    ///     sub.add_item(File::new("2023.pdf")).unwrap();
+    ///     sub.add_item(File::new("2024.pdf")).unwrap();
+    ///     root.add_item(sub).unwrap();
+    ///     root.add_item(File::new("notes.txt")).unwrap();
+    ///
+    ///     let matcher = SearchOptions::default()
+    ///         .set_name(NamePattern::Glob("*.pdf".into()))
+    ///         .matcher()
+    ///         .unwrap();
+    ///
+    ///     assert_eq!(root.search(&matcher).len(), 2);
+    /// ```
+    pub fn search(&self, matcher: &SearchMatcher) -> Vec<Item> {
+        let mut list = Vec::new();
+        for item in self.get_items() {
+            if matcher.matches(&item) {
+                list.push(item.clone());
+            }
+
+            if let Item::Directory(directory) = &item {
+                list.extend(directory.search(matcher));
+            }
+        }
+        list
+    }
+}
+
+/// Check to see if the file type is considered to be text
+pub fn is_text(file_type: &FileType) -> bool {
+    match file_type {
+        FileType::Mime(mime) => {
+            let essence = mime.essence().to_string().to_lowercase();
+            essence.starts_with("text/")
+                || matches!(
+                    essence.as_str(),
+                    "application/json" | "application/xml" | "application/toml"
+                )
+        }
+        FileType::Generic => false,
+    }
+}
+
+fn mime_matches(filter: &str, file_type: &FileType) -> bool {
+    let FileType::Mime(mime) = file_type else {
+        return false;
+    };
+
+    let essence = mime.essence().to_string().to_lowercase();
+
+    match filter.strip_suffix("/*") {
+        Some(ty) => essence.split('/').next() == Some(ty),
+        None => essence == filter,
+    }
+}
+
+fn compile(expr: &str) -> Result<Regex, Error> {
+    RegexBuilder::new(expr)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| Error::OtherWithContext(e.to_string()))
+}
+
+fn glob_to_regex(glob: &str) -> String {
+    let mut expr = String::from("^");
+    for ch in glob.chars() {
+        match ch {
+            '*' => expr.push_str(".*"),
+            '?' => expr.push('.'),
+            ch => expr.push_str(&regex::escape(&ch.to_string())),
+        }
+    }
+    expr.push('$');
+    expr
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NamePattern, SearchOptions};
+    use crate::constellation::{directory::Directory, file::File, item::Item};
+
+    fn index() -> Directory {
+        let root = Directory::new("root");
+        let docs = Directory::new("docs");
+        let favorite = File::new("readme.md");
+        favorite.set_favorite(true);
+        favorite.set_size(10);
+        docs.add_item(favorite).unwrap();
+        docs.add_item(File::new("draft.md")).unwrap();
+        root.add_item(docs).unwrap();
+
+        let image = File::new("image.png");
+        image.set_size(2048);
+        image.set_file_type(crate::constellation::file::FileType::Mime(
+            "image/png".parse().unwrap(),
+        ));
+        root.add_item(image).unwrap();
+        root
+    }
+
+    #[test]
+    fn name_patterns() {
+        let root = index();
+
+        let matcher = SearchOptions::default()
+            .set_name(NamePattern::Glob("*.MD".into()))
+            .matcher()
+            .unwrap();
+        assert_eq!(root.search(&matcher).len(), 2);
+
+        let matcher = SearchOptions::default()
+            .set_name(NamePattern::Regex("d(raft|ocs).*".into()))
+            .matcher()
+            .unwrap();
+        assert_eq!(root.search(&matcher).len(), 2);
+
+        let matcher = SearchOptions::default()
+            .set_name(NamePattern::Contains("mage".into()))
+            .matcher()
+            .unwrap();
+        assert_eq!(root.search(&matcher).len(), 1);
+
+        assert!(SearchOptions::default()
+            .set_name(NamePattern::Regex("(".into()))
+            .matcher()
+            .is_err());
+    }
+
+    #[test]
+    fn metadata_filters() {
+        let root = index();
+
+        let matcher = SearchOptions::default().set_favorite().matcher().unwrap();
+        let items = root.search(&matcher);
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name(), "readme.md");
+
+        let matcher = SearchOptions::default()
+            .set_mime("image/*")
+            .matcher()
+            .unwrap();
+        let items = root.search(&matcher);
+        assert_eq!(items.len(), 1);
+        assert!(matches!(items[0], Item::File(_)));
+
+        let matcher = SearchOptions::default()
+            .set_size_range(1024..4096)
+            .matcher()
+            .unwrap();
+        assert_eq!(root.search(&matcher)[0].name(), "image.png");
+    }
+}
@@ -0,0 +1,959 @@
+#![allow(clippy::result_large_err)]
+//! Two-way synchronization between a local directory and a path within `Constellation`.
+//!
+//! Each pass compares both sides against a snapshot taken at the end of the previous
+//! pass, which allows changes and deletions to be told apart from conflicting edits.
+//! The snapshot is stored within the local directory under [`STATE_FILE`].
+//!
+//! A remote file is only replaced once its new copy was uploaded to [`STAGING_DIRECTORY`].
+//!
+//! When watching, a pass is made whenever the local directory or the remote path reports
+//! a change, along with a full pass on the interval in case a change was missed.
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::channel::mpsc;
+use futures::stream::BoxStream;
+use futures::{FutureExt, StreamExt};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tokio::io::AsyncWriteExt;
+use uuid::Uuid;
+
+use super::directory::Directory;
+use super::file::Hash;
+use super::item::Item;
+use super::{Constellation, Progression};
+use crate::crypto::multihash::sha2_256_multihash_digest;
+use crate::crypto::sha2::{Digest, Sha256};
+use crate::error::Error;
+
+/// Name of the file within the local directory used to store the sync snapshot
+pub const STATE_FILE: &str = ".constellation-sync.json";
+
+/// Directory within the remote path that files are uploaded to before they replace the remote copy.
+/// It is not synced, and a local file or directory using the name is left alone
+pub const STAGING_DIRECTORY: &str = ".constellation-sync-staging";
+
+/// How long to wait for a burst of changes to settle before starting a pass
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+pub type ConstellationSyncStream = BoxStream<'static, SyncEvent>;
+
+/// How to resolve a file that has been modified on both sides since the last sync
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Keep the remote copy under the original name and upload the local copy
+    /// under a new name marked as a conflict
+    #[default]
+    KeepBoth,
+    /// Keep whichever copy was modified last
+    NewestWins,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncOptions {
+    interval: Duration,
+    policy: ConflictPolicy,
+}
+
+impl Default for SyncOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(300),
+            policy: ConflictPolicy::default(),
+        }
+    }
+}
+
+impl SyncOptions {
+    /// How often a full pass is made when watching, even if neither side reported a change
+    pub fn set_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    pub fn set_policy(mut self, policy: ConflictPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+}
+
+impl SyncOptions {
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn policy(&self) -> ConflictPolicy {
+        self.policy
+    }
+}
+
+#[derive(Debug)]
+pub enum SyncEvent {
+    /// Local file was uploaded to constellation
+    Uploaded { path: String },
+    /// Remote file was downloaded to the local directory
+    Downloaded { path: String },
+    /// Local file was removed since it was removed from constellation
+    RemovedLocal { path: String },
+    /// Remote file was removed since it was removed locally
+    RemovedRemote { path: String },
+    /// File was modified on both sides and resolved using the `ConflictPolicy`.
+    /// `conflict_path` is set when a copy was kept under a new name.
+    Conflict {
+        path: String,
+        conflict_path: Option<String>,
+    },
+    /// Operation on the path failed. The path will be retried on the next pass
+    Failed { path: String, error: Error },
+    /// Pass has finished
+    Completed,
+}
+
+/// Action that is required to bring a path in sync
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    None,
+    Upload,
+    Download,
+    RemoveLocal,
+    RemoveRemote,
+    Conflict,
+    /// Deleted on both sides
+    Forget,
+}
+
+/// Decide what to do with a path given the hash on each side and the hashes each side had at the last sync
+fn plan(
+    local: Option<&str>,
+    remote: Option<&str>,
+    base_local: Option<&str>,
+    base_remote: Option<&str>,
+) -> Action {
+    match (local, remote) {
+        (Some(local), Some(remote)) if local == remote => Action::None,
+        (Some(local), Some(remote)) => {
+            match (base_local == Some(local), base_remote == Some(remote)) {
+                (true, true) => Action::None,
+                (true, false) => Action::Download,
+                (false, true) => Action::Upload,
+                (false, false) => Action::Conflict,
+            }
+        }
+        (Some(local), None) => match base_local == Some(local) {
+            true => Action::RemoveLocal,
+            false => Action::Upload,
+        },
+        (None, Some(remote)) => match base_remote == Some(remote) {
+            true => Action::RemoveRemote,
+            false => Action::Download,
+        },
+        (None, None) => Action::Forget,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Snapshot {
+    hash: String,
+    /// Hash of the remote copy when it differs from the local file, such as when the
+    /// store removed the metadata of an image while uploading it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remote_hash: Option<String>,
+    modified: DateTime<Utc>,
+    size: u64,
+}
+
+impl Snapshot {
+    fn remote_hash(&self) -> &str {
+        self.remote_hash.as_deref().unwrap_or(&self.hash)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LocalFile {
+    hash: String,
+    modified: DateTime<Utc>,
+    size: u64,
+}
+
+#[derive(Debug, Clone)]
+struct RemoteFile {
+    hash: String,
+    modified: DateTime<Utc>,
+}
+
+/// Keeps a local directory mirrored with a path within `Constellation`
+///
+/// # Examples
+///
+/// ```ignore
+///     use warp::constellation::sync::{ConflictPolicy, ConstellationSync, SyncOptions};
+///
+///     let options = SyncOptions::default().set_policy(ConflictPolicy::NewestWins);
+///     let sync = ConstellationSync::new(constellation, "/home/user/Documents", "/documents", options);
+///     let mut events = sync.watch();
+///     while let Some(event) = events.next().await {
+///         println!("{event:?}");
+///     }
+/// ```
+pub struct ConstellationSync<C: Constellation> {
+    constellation: C,
+    local: PathBuf,
+    remote: String,
+    options: SyncOptions,
+    state: HashMap<String, Snapshot>,
+    /// Hashes of remote files that were uploaded without one, along with the modified time and size they apply to
+    remote_hashes: HashMap<Uuid, (DateTime<Utc>, usize, String)>,
+}
+
+impl<C: Constellation + 'static> ConstellationSync<C> {
+    pub fn new<P: AsRef<Path>>(
+        constellation: C,
+        local: P,
+        remote: &str,
+        options: SyncOptions,
+    ) -> Self {
+        let local = local.as_ref().to_path_buf();
+        let state = std::fs::read(local.join(STATE_FILE))
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default();
+
+        Self {
+            constellation,
+            local,
+            remote: remote.trim_matches('/').to_string(),
+            options,
+            state,
+            remote_hashes: HashMap::new(),
+        }
+    }
+
+    /// Run a single pass, returning the events that occurred
+    pub async fn sync(&mut self) -> Result<Vec<SyncEvent>, Error> {
+        tokio::fs::create_dir_all(&self.local).await?;
+
+        let mut remote_root = self.remote_directory().await?;
+        if remote_root.has_item(STAGING_DIRECTORY) {
+            self.recover_staged(&remote_root).await?;
+            remote_root = self.remote_directory().await?;
+        }
+
+        let local = self.scan_local().await?;
+        let remote = self.scan_remote(&remote_root).await?;
+
+        let paths = local
+            .keys()
+            .chain(remote.keys())
+            .chain(self.state.keys())
+            .cloned()
+            .collect::<BTreeSet<_>>();
+
+        let mut events = vec![];
+
+        for path in paths {
+            let local_file = local.get(&path);
+            let remote_file = remote.get(&path);
+            let snapshot = self.state.get(&path);
+
+            let action = plan(
+                local_file.map(|file| file.hash.as_str()),
+                remote_file.map(|file| file.hash.as_str()),
+                snapshot.map(|snapshot| snapshot.hash.as_str()),
+                snapshot.map(Snapshot::remote_hash),
+            );
+
+            let result = match action {
+                Action::None => {
+                    if let Some(file) = local_file {
+                        let remote_hash = remote_file.map(|file| file.hash.clone());
+                        self.record(&path, file, remote_hash);
+                    }
+                    continue;
+                }
+                Action::Forget => {
+                    self.state.remove(&path);
+                    continue;
+                }
+                Action::Upload => self
+                    .upload(&path)
+                    .await
+                    .map(|_| SyncEvent::Uploaded { path: path.clone() }),
+                Action::Download => self
+                    .download(&path)
+                    .await
+                    .map(|_| SyncEvent::Downloaded { path: path.clone() }),
+                Action::RemoveLocal => self
+                    .remove_local(&path)
+                    .await
+                    .map(|_| SyncEvent::RemovedLocal { path: path.clone() }),
+                Action::RemoveRemote => self
+                    .remove_remote(&path)
+                    .await
+                    .map(|_| SyncEvent::RemovedRemote { path: path.clone() }),
+                Action::Conflict => {
+                    let (Some(local_file), Some(remote_file)) = (local_file, remote_file) else {
+                        continue;
+                    };
+                    self.resolve(&path, local_file, remote_file).await
+                }
+            };
+
+            match result {
+                Ok(event) => events.push(event),
+                Err(error) => events.push(SyncEvent::Failed { path, error }),
+            }
+        }
+
+        self.save_state().await?;
+        events.push(SyncEvent::Completed);
+
+        Ok(events)
+    }
+
+    /// Continuously sync whenever either side changes, yielding each event as it occurs
+    pub fn watch(mut self) -> ConstellationSyncStream {
+        let stream = async_stream::stream! {
+            let (local_tx, mut local_rx) = mpsc::unbounded();
+
+            // The watcher stops once dropped so it is held for as long as the stream
+            let _watcher = match watch_local(&self.local, local_tx) {
+                Ok(watcher) => Some(watcher),
+                Err(error) => {
+                    yield SyncEvent::Failed {
+                        path: self.local.to_string_lossy().to_string(),
+                        error,
+                    };
+                    None
+                }
+            };
+
+            let remote = self.remote.clone();
+            let mut remote_rx = match self.constellation.constellation_subscribe_path(&remote).await {
+                Ok(stream) => stream.boxed(),
+                Err(_) => futures::stream::pending().boxed(),
+            };
+
+            loop {
+                match self.sync().await {
+                    Ok(events) => {
+                        for event in events {
+                            yield event;
+                        }
+                    }
+                    Err(error) => {
+                        yield SyncEvent::Failed {
+                            path: self.remote.clone(),
+                            error,
+                        };
+                    }
+                }
+
+                tokio::select! {
+                    _ = local_rx.next() => {},
+                    _ = remote_rx.next() => {},
+                    _ = tokio::time::sleep(self.options.interval) => {},
+                }
+
+                // Changes made by the pass itself, along with the rest of a burst, are folded into the next pass
+                tokio::time::sleep(DEBOUNCE).await;
+                while let Ok(Some(_)) = local_rx.try_next() {}
+                while let Some(Some(_)) = remote_rx.next().now_or_never() {}
+            }
+        };
+
+        stream.boxed()
+    }
+
+    fn remote_path(&self, path: &str) -> String {
+        match self.remote.is_empty() {
+            true => path.to_string(),
+            false => format!("{}/{path}", self.remote),
+        }
+    }
+
+    async fn remote_directory(&mut self) -> Result<Directory, Error> {
+        let root = self.constellation.root_directory();
+        if self.remote.is_empty() {
+            return Ok(root);
+        }
+
+        if root.get_item_by_path(&self.remote).is_err() {
+            self.constellation
+                .create_directory(&self.remote, true)
+                .await?;
+        }
+
+        self.constellation
+            .root_directory()
+            .get_item_by_path(&self.remote)?
+            .get_directory()
+    }
+
+    async fn scan_local(&self) -> Result<HashMap<String, LocalFile>, Error> {
+        let mut files = HashMap::new();
+        let mut pending = vec![self.local.clone()];
+
+        while let Some(directory) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&directory).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                let metadata = entry.metadata().await?;
+
+                let Some(relative) = relative_path(&self.local, &path) else {
+                    continue;
+                };
+
+                if relative == STATE_FILE || relative == STAGING_DIRECTORY {
+                    continue;
+                }
+
+                if metadata.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                if !metadata.is_file() {
+                    continue;
+                }
+
+                let modified = metadata
+                    .modified()
+                    .map(DateTime::<Utc>::from)
+                    .unwrap_or_else(|_| Utc::now());
+                let size = metadata.len();
+
+                // Skip hashing files that have not changed since the last pass
+                let hash = match self.state.get(&relative) {
+                    Some(snapshot) if snapshot.modified == modified && snapshot.size == size => {
+                        snapshot.hash.clone()
+                    }
+                    _ => tokio::task::spawn_blocking(move || {
+                        let mut hash = Hash::default();
+                        hash.hash_from_file(&path)?;
+                        Ok::<_, Error>(hash.sha256().unwrap_or_default())
+                    })
+                    .await
+                    .map_err(anyhow::Error::from)??,
+                };
+
+                files.insert(
+                    relative,
+                    LocalFile {
+                        hash,
+                        modified,
+                        size,
+                    },
+                );
+            }
+        }
+
+        Ok(files)
+    }
+
+    async fn scan_remote(
+        &mut self,
+        directory: &Directory,
+    ) -> Result<HashMap<String, RemoteFile>, Error> {
+        let mut files = HashMap::new();
+        let mut pending = vec![(String::new(), directory.clone())];
+
+        while let Some((prefix, directory)) = pending.pop() {
+            for item in directory.get_items() {
+                if prefix.is_empty() && item.name() == STAGING_DIRECTORY {
+                    continue;
+                }
+
+                let path = match prefix.is_empty() {
+                    true => item.name(),
+                    false => format!("{prefix}/{}", item.name()),
+                };
+
+                let file = match item {
+                    Item::Directory(directory) => {
+                        pending.push((path, directory));
+                        continue;
+                    }
+                    Item::File(file) => file,
+                };
+
+                // Files uploaded without a hash have their contents hashed instead, which is
+                // kept until the file changes so it is not downloaded on every pass
+                let hash = match file.hash().sha256() {
+                    Some(hash) => hash,
+                    None => match self.remote_hashes.get(&file.id()) {
+                        Some((modified, size, hash))
+                            if *modified == file.modified() && *size == file.size() =>
+                        {
+                            hash.clone()
+                        }
+                        _ => {
+                            let hash = self.hash_remote(&path).await?;
+                            self.remote_hashes
+                                .insert(file.id(), (file.modified(), file.size(), hash.clone()));
+                            hash
+                        }
+                    },
+                };
+
+                files.insert(
+                    path,
+                    RemoteFile {
+                        hash,
+                        modified: file.modified(),
+                    },
+                );
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Hashes the contents of a remote file chunk by chunk so it is never held in memory whole
+    async fn hash_remote(&self, path: &str) -> Result<String, Error> {
+        let mut stream = self
+            .constellation
+            .get_stream(&self.remote_path(path))
+            .await?;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = stream.next().await {
+            hasher.update(chunk?);
+        }
+
+        let mut hash = Hash::default();
+        hash.set_sha256hash(&sha2_256_multihash_digest(&hasher.finalize())?);
+        Ok(hash.sha256().unwrap_or_default())
+    }
+
+    /// Uploads the local file. An existing remote copy is only replaced once the upload succeeded
+    async fn upload(&mut self, path: &str) -> Result<(), Error> {
+        let local_path = self.local.join(path);
+        self.upload_file(path, &local_path).await?;
+        self.record_uploaded(path).await
+    }
+
+    /// Uploads a local file to the path, staging the upload when there is a remote copy to replace
+    async fn upload_file(&mut self, path: &str, local_path: &Path) -> Result<(), Error> {
+        let remote_path = self.remote_path(path);
+
+        let (parent, name) = match remote_path.rsplit_once('/') {
+            Some((parent, name)) => (parent, name),
+            None => ("", remote_path.as_str()),
+        };
+
+        let root = self.constellation.root_directory();
+
+        if !parent.is_empty() && root.get_item_by_path(parent).is_err() {
+            self.constellation.create_directory(parent, true).await?;
+        }
+
+        let Ok(existing) = root.get_item_by_path(&remote_path) else {
+            return self.put(&remote_path, local_path).await;
+        };
+
+        // The new copy is uploaded to `<slot>/new` and the remote copy is moved into the slot while they are swapped,
+        // so an interrupted swap can be rolled back by `recover_staged`
+        let slot = self.staging_slot(path);
+        let staged = format!("{slot}/new/{name}");
+
+        if root.get_item_by_path(&slot).is_ok() {
+            self.constellation.remove(&slot, true).await?;
+        }
+        self.constellation
+            .create_directory(&format!("{slot}/new"), true)
+            .await?;
+
+        if let Err(e) = self.put(&staged, local_path).await {
+            let _ = self.constellation.remove(&slot, true).await;
+            return Err(e);
+        }
+
+        let uploaded = self
+            .constellation
+            .root_directory()
+            .get_item_by_path(&staged)?;
+        uploaded.set_favorite(existing.favorite());
+        if let (Item::File(existing), Item::File(uploaded)) = (&existing, &uploaded) {
+            uploaded.set_tags(existing.tags());
+            uploaded.set_metadata(existing.metadata());
+        }
+
+        if let Err(e) = self.constellation.move_item(&remote_path, &slot).await {
+            let _ = self.constellation.remove(&slot, true).await;
+            return Err(e);
+        }
+
+        if let Err(e) = self.constellation.move_item(&staged, parent).await {
+            // The slot is kept if the remote copy could not be moved back, so it is restored on the next pass
+            if self
+                .constellation
+                .move_item(&format!("{slot}/{name}"), parent)
+                .await
+                .is_ok()
+            {
+                let _ = self.constellation.remove(&slot, true).await;
+            }
+            return Err(e);
+        }
+
+        if let Err(e) = self.constellation.remove(&slot, true).await {
+            tracing::warn!(path = %slot, error = %e, "unable to remove replaced file");
+        }
+
+        Ok(())
+    }
+
+    /// Path of the directory within [`STAGING_DIRECTORY`] used while replacing the remote copy of the path
+    fn staging_slot(&self, path: &str) -> String {
+        self.remote_path(&format!("{STAGING_DIRECTORY}/{}", hex::encode(path)))
+    }
+
+    /// Restore the remote copies left within [`STAGING_DIRECTORY`] by a swap that was interrupted, then
+    /// remove the uploads that were never swapped in
+    async fn recover_staged(&mut self, remote_root: &Directory) -> Result<(), Error> {
+        let Ok(staging) = remote_root
+            .get_item_by_path(STAGING_DIRECTORY)
+            .and_then(|item| item.get_directory())
+        else {
+            return Ok(());
+        };
+
+        for slot in staging.get_items() {
+            let slot_path = self.remote_path(&format!("{STAGING_DIRECTORY}/{}", slot.name()));
+
+            let path = hex::decode(slot.name())
+                .ok()
+                .and_then(|path| String::from_utf8(path).ok());
+
+            if let (Some(path), Ok(slot)) = (path, slot.get_directory()) {
+                let remote_path = self.remote_path(&path);
+                let (parent, name) = remote_path
+                    .rsplit_once('/')
+                    .unwrap_or(("", remote_path.as_str()));
+                let root = self.constellation.root_directory();
+
+                if slot.has_item(name) && root.get_item_by_path(&remote_path).is_err() {
+                    self.constellation
+                        .move_item(&format!("{slot_path}/{name}"), parent)
+                        .await?;
+                }
+            }
+
+            self.constellation.remove(&slot_path, true).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn put(&mut self, remote_path: &str, local_path: &Path) -> Result<(), Error> {
+        let mut progress = self
+            .constellation
+            .put(remote_path, &local_path.to_string_lossy())
+            .await?;
+
+        while let Some(event) = progress.next().await {
+            if let Progression::ProgressFailed { error, .. } = event {
+                return Err(error);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn download(&mut self, path: &str) -> Result<(), Error> {
+        let remote_path = self.remote_path(path);
+        let local_path = self.local.join(path);
+
+        if let Some(parent) = local_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut stream = self.constellation.get_stream(&remote_path).await?;
+
+        // Write to a temporary file first so a failed download does not leave a partial file behind
+        let mut partial = local_path.clone().into_os_string();
+        partial.push(".partial");
+
+        let result = async {
+            let mut file = tokio::fs::File::create(&partial).await?;
+            while let Some(bytes) = stream.next().await {
+                file.write_all(&bytes?).await?;
+            }
+            file.sync_all().await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&partial).await;
+            return Err(e);
+        }
+
+        tokio::fs::rename(&partial, &local_path).await?;
+
+        self.record_local(path).await
+    }
+
+    async fn remove_local(&mut self, path: &str) -> Result<(), Error> {
+        tokio::fs::remove_file(self.local.join(path)).await?;
+        self.state.remove(path);
+        Ok(())
+    }
+
+    async fn remove_remote(&mut self, path: &str) -> Result<(), Error> {
+        let remote_path = self.remote_path(path);
+        self.constellation.remove(&remote_path, false).await?;
+        self.state.remove(path);
+        Ok(())
+    }
+
+    async fn resolve(
+        &mut self,
+        path: &str,
+        local: &LocalFile,
+        remote: &RemoteFile,
+    ) -> Result<SyncEvent, Error> {
+        match self.options.policy {
+            ConflictPolicy::NewestWins => {
+                match local.modified > remote.modified {
+                    true => self.upload(path).await?,
+                    false => self.download(path).await?,
+                }
+
+                Ok(SyncEvent::Conflict {
+                    path: path.to_string(),
+                    conflict_path: None,
+                })
+            }
+            ConflictPolicy::KeepBoth => {
+                // The local copy is only renamed once it was uploaded under the conflict name, so a failed
+                // upload leaves it in place to be resolved on the next pass
+                let conflict_path = conflict_name(path, Utc::now());
+                let local_path = self.local.join(path);
+                self.upload_file(&conflict_path, &local_path).await?;
+
+                if let Err(e) =
+                    tokio::fs::rename(&local_path, self.local.join(&conflict_path)).await
+                {
+                    let remote_path = self.remote_path(&conflict_path);
+                    let _ = self.constellation.remove(&remote_path, false).await;
+                    return Err(e.into());
+                }
+
+                self.record_uploaded(&conflict_path).await?;
+                self.download(path).await?;
+
+                Ok(SyncEvent::Conflict {
+                    path: path.to_string(),
+                    conflict_path: Some(conflict_path),
+                })
+            }
+        }
+    }
+
+    /// Record the local file along with the hash the store reports for the uploaded copy, which differs
+    /// from the local hash when the store changes the contents (eg by removing the metadata of an image)
+    async fn record_uploaded(&mut self, path: &str) -> Result<(), Error> {
+        self.record_local(path).await?;
+
+        let remote_hash = self
+            .constellation
+            .root_directory()
+            .get_item_by_path(&self.remote_path(path))
+            .and_then(|item| item.get_file())
+            .ok()
+            .and_then(|file| file.hash().sha256());
+
+        if let Some(snapshot) = self.state.get_mut(path) {
+            snapshot.remote_hash = remote_hash.filter(|hash| hash != &snapshot.hash);
+        }
+
+        Ok(())
+    }
+
+    async fn record_local(&mut self, path: &str) -> Result<(), Error> {
+        let local_path = self.local.join(path);
+        let metadata = tokio::fs::metadata(&local_path).await?;
+        let hash = tokio::task::spawn_blocking(move || {
+            let mut hash = Hash::default();
+            hash.hash_from_file(&local_path)?;
+            Ok::<_, Error>(hash.sha256().unwrap_or_default())
+        })
+        .await
+        .map_err(anyhow::Error::from)??;
+
+        self.record(
+            path,
+            &LocalFile {
+                hash,
+                modified: metadata
+                    .modified()
+                    .map(DateTime::<Utc>::from)
+                    .unwrap_or_else(|_| Utc::now()),
+                size: metadata.len(),
+            },
+            None,
+        );
+        Ok(())
+    }
+
+    fn record(&mut self, path: &str, file: &LocalFile, remote_hash: Option<String>) {
+        self.state.insert(
+            path.to_string(),
+            Snapshot {
+                remote_hash: remote_hash.filter(|hash| hash != &file.hash),
+                hash: file.hash.clone(),
+                modified: file.modified,
+                size: file.size,
+            },
+        );
+    }
+
+    async fn save_state(&self) -> Result<(), Error> {
+        let data = serde_json::to_vec(&self.state)?;
+        tokio::fs::write(self.local.join(STATE_FILE), data).await?;
+        Ok(())
+    }
+}
+
+/// Watch the local directory, sending a notification for every change other than those made to
+/// the sync snapshot or to partially downloaded files
+fn watch_local(path: &Path, tx: mpsc::UnboundedSender<()>) -> Result<RecommendedWatcher, Error> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+
+        if event.kind.is_access() || event.paths.iter().all(|path| is_internal(path)) {
+            return;
+        }
+
+        let _ = tx.unbounded_send(());
+    })
+    .map_err(anyhow::Error::from)?;
+
+    watcher
+        .watch(path, RecursiveMode::Recursive)
+        .map_err(anyhow::Error::from)?;
+
+    Ok(watcher)
+}
+
+/// Files written by the sync itself that are not synced
+fn is_internal(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    name.is_some_and(|name| name == STATE_FILE || name.ends_with(".partial"))
+}
+
+/// Path relative to the root using `/` as the separator
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+    let relative = path.strip_prefix(root).ok()?;
+    let components = relative
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>()?;
+    Some(components.join("/"))
+}
+
+/// Name used for the local copy of a file when keeping both sides of a conflict
+fn conflict_name(path: &str, time: DateTime<Utc>) -> String {
+    let (parent, name) = match path.rsplit_once('/') {
+        Some((parent, name)) => (Some(parent), name),
+        None => (None, path),
+    };
+
+    let stamp = time.format("%Y-%m-%d %H%M%S");
+    let name = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => {
+            format!("{stem} (conflict {stamp}).{extension}")
+        }
+        _ => format!("{name} (conflict {stamp})"),
+    };
+
+    match parent {
+        Some(parent) => format!("{parent}/{name}"),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{TimeZone, Utc};
+
+    use super::{conflict_name, plan, Action};
+
+    #[test]
+    fn plan_actions() {
+        assert_eq!(plan(Some("a"), Some("a"), None, None), Action::None);
+        assert_eq!(plan(Some("a"), None, None, None), Action::Upload);
+        assert_eq!(plan(None, Some("a"), None, None), Action::Download);
+        assert_eq!(
+            plan(Some("a"), None, Some("a"), Some("a")),
+            Action::RemoveLocal
+        );
+        assert_eq!(
+            plan(None, Some("a"), Some("a"), Some("a")),
+            Action::RemoveRemote
+        );
+        assert_eq!(
+            plan(Some("b"), Some("a"), Some("a"), Some("a")),
+            Action::Upload
+        );
+        assert_eq!(
+            plan(Some("a"), Some("b"), Some("a"), Some("a")),
+            Action::Download
+        );
+        assert_eq!(
+            plan(Some("b"), Some("c"), Some("a"), Some("a")),
+            Action::Conflict
+        );
+        assert_eq!(plan(Some("b"), Some("c"), None, None), Action::Conflict);
+        assert_eq!(plan(Some("b"), None, Some("a"), Some("a")), Action::Upload);
+        assert_eq!(plan(None, None, Some("a"), Some("a")), Action::Forget);
+    }
+
+    #[test]
+    fn plan_actions_with_rewritten_remote() {
+        // The store removed the metadata of the image, so the remote copy has a different hash
+        assert_eq!(
+            plan(Some("a"), Some("s"), Some("a"), Some("s")),
+            Action::None
+        );
+        assert_eq!(
+            plan(Some("b"), Some("s"), Some("a"), Some("s")),
+            Action::Upload
+        );
+        assert_eq!(
+            plan(Some("a"), Some("t"), Some("a"), Some("s")),
+            Action::Download
+        );
+        assert_eq!(
+            plan(None, Some("s"), Some("a"), Some("s")),
+            Action::RemoveRemote
+        );
+        assert_eq!(
+            plan(Some("a"), None, Some("a"), Some("s")),
+            Action::RemoveLocal
+        );
+    }
+
+    #[test]
+    fn conflict_names() {
+        let time = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        assert_eq!(
+            conflict_name("docs/notes.txt", time),
+            "docs/notes (conflict 2024-01-02 030405).txt"
+        );
+        assert_eq!(
+            conflict_name("README", time),
+            "README (conflict 2024-01-02 030405)"
+        );
+        assert_eq!(
+            conflict_name(".env", time),
+            ".env (conflict 2024-01-02 030405)"
+        );
+    }
+}
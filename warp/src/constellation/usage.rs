@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::directory::Directory;
+use super::item::Item;
+
+/// Breakdown of the space used throughout the filesystem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageReport {
+    root: DirectoryUsage,
+    file_types: BTreeMap<String, usize>,
+    thumbnails: usize,
+    variants: usize,
+}
+
+/// Space used by a directory, including everything underneath it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirectoryUsage {
+    name: String,
+    path: String,
+    size: usize,
+    files: usize,
+    quota: Option<usize>,
+    directories: Vec<DirectoryUsage>,
+}
+
+impl UsageReport {
+    /// Build a report from the contents of the directory
+    pub fn new(root: &Directory) -> Self {
+        let mut file_types = BTreeMap::new();
+        let mut thumbnails = 0;
+        let mut variants = 0;
+        let root = DirectoryUsage::new(
+            root,
+            String::from("/"),
+            &mut file_types,
+            &mut thumbnails,
+            &mut variants,
+        );
+        Self {
+            root,
+            file_types,
+            thumbnails,
+            variants,
+        }
+    }
+
+    /// Usage of the root directory
+    pub fn root(&self) -> &DirectoryUsage {
+        &self.root
+    }
+
+    /// Total size of files, keyed by their file type (eg `image/png` or `generic`)
+    pub fn file_types(&self) -> &BTreeMap<String, usize> {
+        &self.file_types
+    }
+
+    /// Space used by the thumbnails of files and directories
+    pub fn thumbnails(&self) -> usize {
+        self.thumbnails
+    }
+
+    /// Space used by the resized variants of images, which is included in the size of their directories
+    pub fn variants(&self) -> usize {
+        self.variants
+    }
+
+    /// Usage of the directory at the path
+    pub fn get(&self, path: &str) -> Option<&DirectoryUsage> {
+        path.split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(&self.root, |usage, name| {
+                usage
+                    .directories
+                    .iter()
+                    .find(|directory| directory.name == name)
+            })
+    }
+}
+
+impl DirectoryUsage {
+    fn new(
+        directory: &Directory,
+        path: String,
+        file_types: &mut BTreeMap<String, usize>,
+        thumbnails: &mut usize,
+        variants: &mut usize,
+    ) -> Self {
+        let mut size = 0;
+        let mut files = 0;
+        let mut directories = vec![];
+
+        *thumbnails += directory.thumbnail().len();
+
+        for item in directory.get_items() {
+            match item {
+                Item::File(file) => {
+                    size += file.stored_size();
+                    *variants += file.stored_size() - file.size();
+                    files += 1;
+                    *thumbnails += file.thumbnail().len();
+                    *file_types.entry(file.file_type().to_string()).or_default() += file.size();
+                }
+                Item::Directory(directory) => {
+                    let path = match path.as_str() {
+                        "/" => format!("/{}", directory.name()),
+                        parent => format!("{parent}/{}", directory.name()),
+                    };
+                    let usage =
+                        DirectoryUsage::new(&directory, path, file_types, thumbnails, variants);
+                    size += usage.size;
+                    files += usage.files;
+                    directories.push(usage);
+                }
+            }
+        }
+
+        Self {
+            name: directory.name(),
+            path,
+            size,
+            files,
+            quota: directory.quota(),
+            directories,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Full path of the directory (eg `/docs/notes`)
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Total size of the files within the directory, including subdirectories and image variants
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of files within the directory, including subdirectories
+    pub fn files(&self) -> usize {
+        self.files
+    }
+
+    pub fn quota(&self) -> Option<usize> {
+        self.quota
+    }
+
+    /// Space left before the quota is reached, if the directory has one
+    pub fn remaining(&self) -> Option<usize> {
+        self.quota.map(|quota| quota.saturating_sub(self.size))
+    }
+
+    pub fn directories(&self) -> &[DirectoryUsage] {
+        &self.directories
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::UsageReport;
+    use crate::constellation::{
+        directory::Directory,
+        file::{File, FileType, ImageVariant},
+        item::FormatType,
+    };
+
+    #[test]
+    fn usage_report() -> anyhow::Result<()> {
+        let root = Directory::new("root");
+        let docs = Directory::new("docs");
+        docs.set_quota(Some(100));
+
+        let notes = File::new("notes.txt");
+        notes.set_size(40);
+        notes.set_file_type(FileType::Mime("text/plain".parse()?));
+        let image = File::new("image.png");
+        image.set_size(60);
+        image.set_thumbnail(vec![0u8; 10]);
+        image.set_variants(vec![ImageVariant::new(
+            320,
+            240,
+            20,
+            FormatType::Generic,
+            "/ipfs/variant",
+        )]);
+
+        docs.add_item(notes)?;
+        root.add_item(docs)?;
+        root.add_item(image)?;
+
+        let report = UsageReport::new(&root);
+        assert_eq!(report.root().size(), 120);
+        assert_eq!(report.root().files(), 2);
+        assert_eq!(report.thumbnails(), 10);
+        assert_eq!(report.variants(), 20);
+        assert_eq!(report.file_types().get("text/plain"), Some(&40));
+        assert_eq!(report.file_types().get("generic"), Some(&60));
+
+        let docs = report.get("/docs").expect("directory exist");
+        assert_eq!(docs.path(), "/docs");
+        assert_eq!(docs.size(), 40);
+        assert_eq!(docs.remaining(), Some(60));
+        assert!(report.get("/missing").is_none());
+        Ok(())
+    }
+}
@@ -6,26 +6,112 @@ use std::io::{Read, Write};
 use std::io::ErrorKind;
 
 use crate::crypto::hash::sha256_hash;
+use derive_more::Display;
 use futures::{stream, AsyncRead, AsyncReadExt, Stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
 use zeroize::Zeroize;
 
 use crate::error::Error;
 
 use aes_gcm::aead::stream::{DecryptorBE32, EncryptorBE32};
 use aes_gcm::{
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
     Aes256Gcm,
 };
+use chacha20poly1305::XChaCha20Poly1305;
 
 type Result<T> = std::result::Result<T, Error>;
 
 const AES256_GCM_TAG_SIZE: usize = 16;
+// Chunk size of streams written before the header was introduced
 const AES256_GCM_ENCRYPTION_BUF_SIZE: usize = 512;
 const AES256_GCM_DECRYPTION_BUF_SIZE: usize = AES256_GCM_ENCRYPTION_BUF_SIZE + AES256_GCM_TAG_SIZE;
 
+// Data encrypted in one piece with a suite other than AES-256-GCM starts with a header made of the magic and the
+// cipher suite, which is authenticated along with the data, followed by the ciphertext and the nonce. Data encrypted
+// with AES-256-GCM, along with data written before the header was introduced, is only made of the ciphertext and the
+// nonce, so it can still be read by releases that do not know of the header
+const DATA_MAGIC_SIZE: usize = 8;
+const DATA_MAGIC: &[u8; DATA_MAGIC_SIZE] = b"WARPDATA";
+const DATA_HEADER_SIZE: usize = DATA_MAGIC_SIZE + 1;
+
+// Streams start with a header made of the magic, the version, the cipher suite and the nonce prefix.
+// Every chunk is encrypted with a nonce made of the prefix, a counter and a flag marking the last chunk
+const STREAM_MAGIC_SIZE: usize = 8;
+const STREAM_MAGIC: &[u8; STREAM_MAGIC_SIZE] = b"WARPSTRM";
+const STREAM_VERSION: u8 = 2;
+// Streams of the first version have no cipher suite in their header and are always AES-256-GCM
+const STREAM_VERSION_V1: u8 = 1;
+// Size of the nonce prefix of streams written before the header was introduced
+const LEGACY_STREAM_NONCE_SIZE: usize = 7;
+const STREAM_CHUNK_SIZE: usize = 64 * 1024;
+// Both suites use a 16 byte tag
+const STREAM_ENCRYPTED_CHUNK_SIZE: usize = STREAM_CHUNK_SIZE + AES256_GCM_TAG_SIZE;
+
+/// Authenticated encryption algorithm used by [`Cipher`]
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum CipherSuite {
+    /// AES-256-GCM with a random 96bit nonce
+    #[default]
+    #[display(fmt = "aes256-gcm")]
+    #[serde(rename = "aes256-gcm")]
+    Aes256Gcm,
+    /// XChaCha20-Poly1305 with a random 192bit nonce. Faster on devices without AES instructions
+    /// and safe to use with random nonces for a large number of messages
+    #[display(fmt = "xchacha20-poly1305")]
+    #[serde(rename = "xchacha20-poly1305")]
+    XChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    /// Size of the nonce in bytes
+    pub fn nonce_size(&self) -> usize {
+        match self {
+            CipherSuite::Aes256Gcm => 12,
+            CipherSuite::XChaCha20Poly1305 => 24,
+        }
+    }
+
+    /// Size of the nonce prefix used by streams, which leaves room for the counter and last chunk flag
+    fn stream_nonce_size(&self) -> usize {
+        self.nonce_size() - 5
+    }
+
+    fn id(&self) -> u8 {
+        match self {
+            CipherSuite::Aes256Gcm => 0,
+            CipherSuite::XChaCha20Poly1305 => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Result<Self> {
+        match id {
+            0 => Ok(CipherSuite::Aes256Gcm),
+            1 => Ok(CipherSuite::XChaCha20Poly1305),
+            _ => Err(Error::UnsupportedCipherSuite),
+        }
+    }
+
+    /// Cipher suite using nonces of the given size in bytes
+    pub fn from_nonce_size(size: usize) -> Option<Self> {
+        [CipherSuite::Aes256Gcm, CipherSuite::XChaCha20Poly1305]
+            .into_iter()
+            .find(|suite| suite.nonce_size() == size)
+    }
+
+    fn generate_nonce(&self) -> Vec<u8> {
+        match self {
+            CipherSuite::Aes256Gcm => crate::crypto::generate::<12>().to_vec(),
+            CipherSuite::XChaCha20Poly1305 => crate::crypto::generate::<24>().to_vec(),
+        }
+    }
+}
+
 #[derive(Zeroize)]
 pub struct Cipher {
     private_key: zeroize::Zeroizing<Vec<u8>>,
+    #[zeroize(skip)]
+    suite: CipherSuite,
 }
 
 impl Drop for Cipher {
@@ -37,14 +123,20 @@ impl Drop for Cipher {
 impl Default for Cipher {
     fn default() -> Self {
         let private_key = zeroize::Zeroizing::new(crate::crypto::generate::<34>().into());
-        Cipher { private_key }
+        Cipher {
+            private_key,
+            suite: CipherSuite::default(),
+        }
     }
 }
 
 impl<U: AsRef<[u8]>> From<U> for Cipher {
     fn from(private_key: U) -> Cipher {
         let private_key = zeroize::Zeroizing::new(private_key.as_ref().to_vec());
-        Cipher { private_key }
+        Cipher {
+            private_key,
+            suite: CipherSuite::default(),
+        }
     }
 }
 
@@ -57,7 +149,21 @@ impl Cipher {
     /// Import key into Cipher
     pub fn from_bytes(private_key: &[u8]) -> Cipher {
         let private_key = zeroize::Zeroizing::new(private_key.to_vec());
-        Cipher { private_key }
+        Cipher {
+            private_key,
+            suite: CipherSuite::default(),
+        }
+    }
+
+    /// Use the cipher suite to encrypt and decrypt. Defaults to AES-256-GCM
+    pub fn with_suite(mut self, suite: CipherSuite) -> Cipher {
+        self.suite = suite;
+        self
+    }
+
+    /// Cipher suite used to encrypt and decrypt
+    pub fn suite(&self) -> CipherSuite {
+        self.suite
     }
 
     /// Returns the stored key
@@ -73,8 +179,10 @@ impl Cipher {
         Ok(data)
     }
 
+    /// Used to generate and encrypt data with a random key, using the cipher suite matching the size of the nonce
     pub fn self_encrypt_with_nonce(data: &[u8], nonce: &[u8]) -> Result<Vec<u8>> {
-        let cipher = Cipher::new();
+        let suite = CipherSuite::from_nonce_size(nonce.len()).ok_or(Error::InvalidConversion)?;
+        let cipher = Cipher::new().with_suite(suite);
         let mut data = cipher.encrypt(data, Some(nonce))?;
         data.extend(cipher.private_key());
         Ok(data)
@@ -94,8 +202,10 @@ impl Cipher {
         cipher.encrypt(data, None)
     }
 
+    /// Used to encrypt data directly with key, using the cipher suite matching the size of the nonce
     pub fn direct_encrypt_with_nonce(data: &[u8], key: &[u8], nonce: &[u8]) -> Result<Vec<u8>> {
-        let cipher = Cipher::from(key);
+        let suite = CipherSuite::from_nonce_size(nonce.len()).ok_or(Error::InvalidConversion)?;
+        let cipher = Cipher::from(key).with_suite(suite);
         cipher.encrypt(data, Some(nonce))
     }
 
@@ -105,41 +215,103 @@ impl Cipher {
         cipher.decrypt(data)
     }
 
-    /// Used to encrypt data
+    /// Used to encrypt data. A suite other than AES-256-GCM is recorded along with the data, so it can be
+    /// decrypted regardless of the suite of the cipher.
+    ///
+    /// Note: Data encrypted with XChaCha20-Poly1305 can not be read by releases prior to the header being
+    ///       introduced, while data encrypted with AES-256-GCM keeps the format those releases expect
     pub fn encrypt(&self, data: &[u8], nonce: Option<&[u8]>) -> Result<Vec<u8>> {
         let nonce = match nonce {
-            Some(nonce) => nonce.try_into().map_err(|_| Error::InvalidConversion)?,
-            None => crate::crypto::generate::<12>(),
+            Some(nonce) if nonce.len() == self.suite.nonce_size() => nonce.to_vec(),
+            Some(_) => return Err(Error::InvalidConversion),
+            None => self.suite.generate_nonce(),
         };
 
-        let key = match self.private_key.len() {
-            32 => self.private_key.clone(),
-            _ => zeroize::Zeroizing::new(sha256_hash(&self.private_key, Some(&nonce))),
+        let key = cipher_key(self.private_key.clone(), &nonce);
+
+        let header = match self.suite {
+            CipherSuite::Aes256Gcm => vec![],
+            suite => data_header(suite),
         };
 
-        let cipher = Aes256Gcm::new(key.as_slice().into());
-        let mut cipher_data = cipher
-            .encrypt(nonce.as_slice().into(), data)
-            .map_err(|_| Error::EncryptionError)?;
+        let payload = Payload {
+            msg: data,
+            aad: &header,
+        };
 
-        cipher_data.extend(nonce);
+        let cipher_data = match self.suite {
+            CipherSuite::Aes256Gcm => {
+                Aes256Gcm::new(key.as_slice().into()).encrypt(nonce.as_slice().into(), payload)
+            }
+            CipherSuite::XChaCha20Poly1305 => XChaCha20Poly1305::new(key.as_slice().into())
+                .encrypt(nonce.as_slice().into(), payload),
+        }
+        .map_err(|_| Error::EncryptionError)?;
 
-        Ok(cipher_data)
+        let mut data = header;
+        data.extend(cipher_data);
+        data.extend(nonce);
+
+        Ok(data)
     }
 
-    /// Used to decrypt data
+    /// Used to decrypt data. Data without a header is decrypted using the suite of the cipher,
+    /// falling back to AES-256-GCM
     pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
-        let (nonce, payload) = extract_data_slice::<12>(data);
+        match split_data_header(data)? {
+            Some((suite, header, data)) => self.decrypt_with_suite(suite, header, data),
+            None => self
+                .decrypt_with_suite(self.suite, &[], data)
+                .or_else(|e| match self.suite {
+                    CipherSuite::Aes256Gcm => Err(e),
+                    _ => self.decrypt_with_suite(CipherSuite::Aes256Gcm, &[], data),
+                }),
+        }
+    }
 
-        let key = match self.private_key.len() {
-            32 => self.private_key.clone(),
-            _ => zeroize::Zeroizing::new(sha256_hash(&self.private_key, Some(nonce))),
+    fn decrypt_with_suite(
+        &self,
+        suite: CipherSuite,
+        header: &[u8],
+        data: &[u8],
+    ) -> Result<Vec<u8>> {
+        if data.len() < suite.nonce_size() {
+            return Err(Error::DecryptionError);
+        }
+
+        let (payload, nonce) = data.split_at(data.len() - suite.nonce_size());
+
+        let key = cipher_key(self.private_key.clone(), nonce);
+
+        let payload = Payload {
+            msg: payload,
+            aad: header,
         };
 
-        let cipher = Aes256Gcm::new(key.as_slice().into());
-        cipher
-            .decrypt(nonce.into(), payload)
-            .map_err(|_| Error::DecryptionError)
+        match suite {
+            CipherSuite::Aes256Gcm => {
+                Aes256Gcm::new(key.as_slice().into()).decrypt(nonce.into(), payload)
+            }
+            CipherSuite::XChaCha20Poly1305 => {
+                XChaCha20Poly1305::new(key.as_slice().into()).decrypt(nonce.into(), payload)
+            }
+        }
+        .map_err(|_| Error::DecryptionError)
+    }
+
+    /// Returns the cipher suite and the nonce the data was encrypted with. Data without a header
+    /// is assumed to be encrypted with AES-256-GCM
+    pub fn extract_nonce(data: &[u8]) -> Result<(CipherSuite, &[u8])> {
+        let (suite, data) = match split_data_header(data)? {
+            Some((suite, _, data)) => (suite, data),
+            None => (CipherSuite::Aes256Gcm, data),
+        };
+
+        if data.len() < suite.nonce_size() {
+            return Err(Error::DecryptionError);
+        }
+
+        Ok((suite, &data[data.len() - suite.nonce_size()..]))
     }
 
     /// Encrypts and embeds private key into async stream
@@ -211,45 +383,7 @@ impl Cipher {
         &self,
         stream: impl Stream<Item = std::io::Result<Vec<u8>>> + Unpin + Send + 'a,
     ) -> impl Stream<Item = std::io::Result<Vec<u8>>> + Send + 'a {
-        let mut reader = stream.into_async_read();
-
-        let nonce = crate::crypto::generate::<7>();
-
-        let key = match self.private_key.len() {
-            32 => self.private_key.clone(),
-            _ => zeroize::Zeroizing::new(sha256_hash(&self.private_key, Some(&nonce))),
-        };
-
-        let stream = async_stream::stream! {
-            let mut buffer = [0u8; AES256_GCM_ENCRYPTION_BUF_SIZE];
-            let cipher = Aes256Gcm::new(key.as_slice().into());
-            let mut stream = EncryptorBE32::from_aead(cipher, (&nonce).into());
-
-            yield Ok(nonce.to_vec());
-
-            loop {
-                match reader.read(&mut buffer).await {
-                    Ok(AES256_GCM_ENCRYPTION_BUF_SIZE) => match stream.encrypt_next(buffer.as_slice()).map_err(|_| std::io::Error::other(Error::EncryptionStreamError)) {
-                        Ok(data) => yield Ok(data),
-                        Err(e) => {
-                            yield Err(e);
-                            break;
-                        }
-                    }
-                    Ok(read_count) => {
-                        yield stream.encrypt_last(&buffer[..read_count]).map_err(|_| std::io::Error::other(Error::EncryptionStreamError)) ;
-                        break;
-                    }
-                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                    Err(e) => {
-                        yield Err(e);
-                        break;
-                    },
-                }
-            }
-        };
-
-        stream
+        self.encrypt_async_read_to_stream(stream.into_async_read())
     }
 
     /// Decrypt data from async stream into another async stream
@@ -257,61 +391,7 @@ impl Cipher {
         &self,
         stream: impl Stream<Item = std::io::Result<Vec<u8>>> + Unpin + Send + 'a,
     ) -> impl Stream<Item = std::io::Result<Vec<u8>>> + Send + 'a {
-        let mut reader = stream.into_async_read();
-
-        let private_key = self.private_key.clone();
-
-        let stream = async_stream::stream! {
-            let mut nonce = [0u8; 7];
-
-            if let Err(e) = reader.read_exact(&mut nonce).await {
-                yield Err(e);
-                return;
-            }
-
-            let key = match private_key.len() {
-                32 => private_key,
-                _ => zeroize::Zeroizing::new(sha256_hash(&private_key, Some(&nonce))),
-            };
-
-            let mut buffer = [0u8; AES256_GCM_DECRYPTION_BUF_SIZE];
-            let cipher = Aes256Gcm::new(key.as_slice().into());
-            let mut stream = DecryptorBE32::from_aead(cipher, nonce.as_slice().into());
-
-            loop {
-                match reader.read(&mut buffer).await {
-                    Ok(AES256_GCM_DECRYPTION_BUF_SIZE) => {
-                        match stream.decrypt_next(buffer.as_slice()).map_err(|_| std::io::Error::other(Error::DecryptionStreamError)) {
-                            Ok(data) => yield Ok(data),
-                            Err(e) => {
-                                yield Err(e);
-                                break;
-                            }
-                        };
-                    }
-                    Ok(0) => break,
-                    Ok(read_count) => {
-                        match stream.decrypt_last(&buffer[..read_count]).map_err(|_| std::io::Error::other(Error::DecryptionStreamError))  {
-                            Ok(data) => {
-                                yield Ok(data);
-                                break;
-                            },
-                            Err(e) => {
-                                yield Err(e);
-                                break;
-                            }
-                        };
-                    }
-                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                    Err(e) => {
-                        yield Err::<_, std::io::Error>(e);
-                        break;
-                    },
-                };
-            }
-        };
-
-        stream
+        self.decrypt_async_read_to_stream(stream.into_async_read())
     }
 
     /// Encrypts data from async reader into async stream
@@ -319,42 +399,60 @@ impl Cipher {
         &self,
         mut reader: R,
     ) -> impl Stream<Item = std::io::Result<Vec<u8>>> + Send + 'a {
-        let nonce = crate::crypto::generate::<7>();
+        let suite = self.suite;
+        let nonce = suite.generate_nonce()[..suite.stream_nonce_size()].to_vec();
 
         let private_key = self.private_key.clone();
 
         let stream = async_stream::stream! {
+            let key = cipher_key(private_key, &nonce);
+            let header = stream_header(stream_version(suite), suite, &nonce);
 
-            let key = match private_key.len() {
-                32 => private_key,
-                _ => zeroize::Zeroizing::new(sha256_hash(&private_key, Some(&nonce))),
-            };
+            let mut stream = StreamEncryptor::new(suite, &key, &nonce);
+
+            yield Ok(header.clone());
 
-            let mut buffer = [0u8; AES256_GCM_ENCRYPTION_BUF_SIZE];
-            let cipher = Aes256Gcm::new(key.as_slice().into());
-            let mut stream = EncryptorBE32::from_aead(cipher, nonce.as_slice().into());
+            let mut buffer = vec![0u8; STREAM_CHUNK_SIZE];
+            let mut next_buffer = vec![0u8; STREAM_CHUNK_SIZE];
 
-            yield Ok(nonce.to_vec());
+            let mut read_count = match read_chunk_async(&mut reader, &mut buffer).await {
+                Ok(count) => count,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
 
             loop {
-                match reader.read(&mut buffer).await {
-                    Ok(AES256_GCM_ENCRYPTION_BUF_SIZE) => match stream.encrypt_next(buffer.as_slice()).map_err(|_| std::io::Error::other(Error::EncryptionStreamError)) {
-                        Ok(data) => yield Ok(data),
+                // Note: A chunk is only known to not be the last one once the following chunk has been read
+                let next_count = match read_count {
+                    STREAM_CHUNK_SIZE => match read_chunk_async(&mut reader, &mut next_buffer).await {
+                        Ok(count) => count,
                         Err(e) => {
                             yield Err(e);
                             break;
                         }
-                    }
-                    Ok(read_count) => {
-                        yield stream.encrypt_last(&buffer[..read_count]).map_err(|_| std::io::Error::other(Error::EncryptionStreamError));
-                        break;
-                    }
-                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    },
+                    _ => 0,
+                };
+
+                if next_count == 0 {
+                    let payload = Payload { msg: &buffer[..read_count], aad: &header };
+                    yield stream.encrypt_last(payload).map_err(std::io::Error::other);
+                    break;
+                }
+
+                let payload = Payload { msg: &buffer[..read_count], aad: &header };
+                match stream.encrypt_next(payload).map_err(std::io::Error::other) {
+                    Ok(data) => yield Ok(data),
                     Err(e) => {
                         yield Err(e);
                         break;
-                    },
+                    }
                 }
+
+                std::mem::swap(&mut buffer, &mut next_buffer);
+                read_count = next_count;
             }
         };
 
@@ -369,56 +467,109 @@ impl Cipher {
         let private_key = self.private_key.clone();
 
         let stream = async_stream::stream! {
-            let mut nonce = [0u8; 7];
+            let mut magic = [0u8; STREAM_MAGIC_SIZE];
 
-            if let Err(e) = reader.read_exact(&mut nonce).await {
+            if let Err(e) = reader.read_exact(&mut magic).await {
                 yield Err(e);
                 return;
             }
 
-            let key = match private_key.len() {
-                32 => private_key,
-                _ => zeroize::Zeroizing::new(sha256_hash(&private_key, Some(&nonce))),
-            };
+            if &magic != STREAM_MAGIC {
+                // Streams written before the header was introduced start directly with the nonce
+                let mut nonce = [0u8; LEGACY_STREAM_NONCE_SIZE];
+                nonce.copy_from_slice(&magic[..LEGACY_STREAM_NONCE_SIZE]);
+                let reader = AsyncReadExt::chain(futures::io::Cursor::new(magic[LEGACY_STREAM_NONCE_SIZE..].to_vec()), reader);
+                let st = decrypt_legacy_async(private_key, nonce, reader);
+                for await item in st {
+                    yield item;
+                }
+                return;
+            }
 
-            let mut buffer = [0u8; AES256_GCM_DECRYPTION_BUF_SIZE];
-            let cipher = Aes256Gcm::new(key.as_slice().into());
-            let mut stream = DecryptorBE32::from_aead(cipher, nonce.as_slice().into());
+            let mut version = [0u8; 1];
+            if let Err(e) = reader.read_exact(&mut version).await {
+                yield Err(e);
+                return;
+            }
 
-            loop {
-                match reader.read(&mut buffer).await {
-                    Ok(AES256_GCM_DECRYPTION_BUF_SIZE) => {
-                        match stream.decrypt_next(buffer.as_slice()).map_err(|_| std::io::Error::other(Error::DecryptionStreamError)){
-                            Ok(data) => yield Ok(data),
-                            Err(e) => {
-                                yield Err(e);
-                                break;
-                            }
-                        };
+            let suite = match version[0] {
+                STREAM_VERSION_V1 => CipherSuite::Aes256Gcm,
+                STREAM_VERSION => {
+                    let mut id = [0u8; 1];
+                    if let Err(e) = reader.read_exact(&mut id).await {
+                        yield Err(e);
+                        return;
                     }
-                    Ok(0) => break,
-                    Ok(read_count) => {
-                        match stream.decrypt_last(&buffer[..read_count]).map_err(|_| std::io::Error::other(Error::DecryptionStreamError)) {
-                            Ok(data) => {
-                                yield Ok(data);
-                                break;
-                            },
-                            Err(e) => {
-                                yield Err(e);
-                                break;
-                            }
-                        };
+                    match CipherSuite::from_id(id[0]) {
+                        Ok(suite) => suite,
+                        Err(e) => {
+                            yield Err(std::io::Error::other(e));
+                            return;
+                        }
                     }
-                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                }
+                _ => {
+                    yield Err(std::io::Error::other(Error::UnsupportedStreamVersion));
+                    return;
+                }
+            };
+
+            let mut nonce = vec![0u8; suite.stream_nonce_size()];
+            if let Err(e) = reader.read_exact(&mut nonce).await {
+                yield Err(e);
+                return;
+            }
+
+            let key = cipher_key(private_key, &nonce);
+            let header = stream_header(version[0], suite, &nonce);
+
+            let mut stream = StreamDecryptor::new(suite, &key, &nonce);
+
+            let mut buffer = vec![0u8; STREAM_ENCRYPTED_CHUNK_SIZE];
+            let mut next_buffer = vec![0u8; STREAM_ENCRYPTED_CHUNK_SIZE];
+
+            let mut read_count = match read_chunk_async(&mut reader, &mut buffer).await {
+                Ok(count) => count,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+
+            loop {
+                let next_count = match read_count {
+                    STREAM_ENCRYPTED_CHUNK_SIZE => match read_chunk_async(&mut reader, &mut next_buffer).await {
+                        Ok(count) => count,
+                        Err(e) => {
+                            yield Err(e);
+                            break;
+                        }
+                    },
+                    _ => 0,
+                };
+
+                // Note: Decrypting the last chunk fails if the stream was truncated, since the chunk would not be flagged as the last one
+                if next_count == 0 {
+                    let payload = Payload { msg: &buffer[..read_count], aad: &header };
+                    yield stream.decrypt_last(payload).map_err(std::io::Error::other);
+                    break;
+                }
+
+                let payload = Payload { msg: &buffer[..read_count], aad: &header };
+                match stream.decrypt_next(payload).map_err(std::io::Error::other) {
+                    Ok(data) => yield Ok(data),
                     Err(e) => {
                         yield Err(e);
                         break;
-                    },
-                };
+                    }
+                }
+
+                std::mem::swap(&mut buffer, &mut next_buffer);
+                read_count = next_count;
             }
         };
 
-        stream
+        stream.boxed()
     }
 }
 
@@ -443,39 +594,44 @@ impl Cipher {
 
     /// Encrypts data from std reader into std writer
     pub fn encrypt_stream(&self, reader: &mut impl Read, writer: &mut impl Write) -> Result<()> {
-        let nonce = crate::crypto::generate::<7>();
+        let nonce = self.suite.generate_nonce()[..self.suite.stream_nonce_size()].to_vec();
 
-        let key = match self.private_key.len() {
-            32 => self.private_key.clone(),
-            _ => zeroize::Zeroizing::new(sha256_hash(&self.private_key, Some(&nonce))),
-        };
+        let key = cipher_key(self.private_key.clone(), &nonce);
+        let header = stream_header(stream_version(self.suite), self.suite, &nonce);
 
-        let mut buffer = [0u8; AES256_GCM_ENCRYPTION_BUF_SIZE];
+        let mut stream = StreamEncryptor::new(self.suite, &key, &nonce);
 
-        let cipher = Aes256Gcm::new(key.as_slice().into());
+        writer.write_all(&header)?;
 
-        let mut stream = EncryptorBE32::from_aead(cipher, nonce.as_slice().into());
-        writer.write_all(&nonce)?;
+        let mut buffer = vec![0u8; STREAM_CHUNK_SIZE];
+        let mut next_buffer = vec![0u8; STREAM_CHUNK_SIZE];
+        let mut read_count = read_chunk(reader, &mut buffer)?;
 
         loop {
-            match reader.read(&mut buffer) {
-                Ok(AES256_GCM_ENCRYPTION_BUF_SIZE) => {
-                    let ciphertext = stream
-                        .encrypt_next(buffer.as_slice())
-                        .map_err(|_| Error::EncryptionStreamError)?;
-                    writer.write_all(&ciphertext)?;
-                }
-                Ok(read_count) => {
-                    let ciphertext = stream
-                        .encrypt_last(&buffer[..read_count])
-                        .map_err(|_| Error::EncryptionStreamError)?;
-                    writer.write_all(&ciphertext)?;
-                    break;
-                }
-                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => return Err(Error::from(e)),
+            // Note: A chunk is only known to not be the last one once the following chunk has been read
+            let next_count = match read_count {
+                STREAM_CHUNK_SIZE => read_chunk(reader, &mut next_buffer)?,
+                _ => 0,
+            };
+
+            let payload = Payload {
+                msg: &buffer[..read_count],
+                aad: &header,
+            };
+
+            if next_count == 0 {
+                let ciphertext = stream.encrypt_last(payload)?;
+                writer.write_all(&ciphertext)?;
+                break;
             }
+
+            let ciphertext = stream.encrypt_next(payload)?;
+            writer.write_all(&ciphertext)?;
+
+            std::mem::swap(&mut buffer, &mut next_buffer);
+            read_count = next_count;
         }
+
         writer.flush()?;
 
         Ok(())
@@ -483,39 +639,103 @@ impl Cipher {
 
     /// Decrypts data from std reader into std writer
     pub fn decrypt_stream(&self, reader: &mut impl Read, writer: &mut impl Write) -> Result<()> {
-        let mut nonce = [0u8; 7];
+        let mut magic = [0u8; STREAM_MAGIC_SIZE];
 
-        reader.read_exact(&mut nonce)?;
+        reader.read_exact(&mut magic)?;
+
+        if &magic != STREAM_MAGIC {
+            // Streams written before the header was introduced start directly with the nonce
+            let mut nonce = [0u8; LEGACY_STREAM_NONCE_SIZE];
+            nonce.copy_from_slice(&magic[..LEGACY_STREAM_NONCE_SIZE]);
+            let mut reader = Read::chain(&magic[LEGACY_STREAM_NONCE_SIZE..], reader);
+            return self.decrypt_legacy_stream(nonce, &mut reader, writer);
+        }
+
+        let mut version = [0u8; 1];
+        reader.read_exact(&mut version)?;
 
-        let key = match self.private_key.len() {
-            32 => self.private_key.clone(),
-            _ => zeroize::Zeroizing::new(sha256_hash(&self.private_key, Some(&nonce))),
+        let suite = match version[0] {
+            STREAM_VERSION_V1 => CipherSuite::Aes256Gcm,
+            STREAM_VERSION => {
+                let mut id = [0u8; 1];
+                reader.read_exact(&mut id)?;
+                CipherSuite::from_id(id[0])?
+            }
+            _ => return Err(Error::UnsupportedStreamVersion),
         };
 
+        let mut nonce = vec![0u8; suite.stream_nonce_size()];
+        reader.read_exact(&mut nonce)?;
+
+        let key = cipher_key(self.private_key.clone(), &nonce);
+        let header = stream_header(version[0], suite, &nonce);
+
+        let mut stream = StreamDecryptor::new(suite, &key, &nonce);
+
+        let mut buffer = vec![0u8; STREAM_ENCRYPTED_CHUNK_SIZE];
+        let mut next_buffer = vec![0u8; STREAM_ENCRYPTED_CHUNK_SIZE];
+        let mut read_count = read_chunk(reader, &mut buffer)?;
+
+        loop {
+            let next_count = match read_count {
+                STREAM_ENCRYPTED_CHUNK_SIZE => read_chunk(reader, &mut next_buffer)?,
+                _ => 0,
+            };
+
+            let payload = Payload {
+                msg: &buffer[..read_count],
+                aad: &header,
+            };
+
+            // Note: Decrypting the last chunk fails if the stream was truncated, since the chunk would not be flagged as the last one
+            if next_count == 0 {
+                let plaintext = stream.decrypt_last(payload)?;
+                writer.write_all(&plaintext)?;
+                break;
+            }
+
+            let plaintext = stream.decrypt_next(payload)?;
+            writer.write_all(&plaintext)?;
+
+            std::mem::swap(&mut buffer, &mut next_buffer);
+            read_count = next_count;
+        }
+
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Decrypts a stream written before the header was introduced
+    fn decrypt_legacy_stream(
+        &self,
+        nonce: [u8; LEGACY_STREAM_NONCE_SIZE],
+        reader: &mut impl Read,
+        writer: &mut impl Write,
+    ) -> Result<()> {
+        let key = cipher_key(self.private_key.clone(), &nonce);
+
         let mut buffer = [0u8; AES256_GCM_DECRYPTION_BUF_SIZE];
 
         let cipher = Aes256Gcm::new(key.as_slice().into());
         let mut stream = DecryptorBE32::from_aead(cipher, nonce.as_slice().into());
 
         loop {
-            match reader.read(&mut buffer) {
-                Ok(AES256_GCM_DECRYPTION_BUF_SIZE) => {
+            match read_chunk(reader, &mut buffer)? {
+                AES256_GCM_DECRYPTION_BUF_SIZE => {
                     let plaintext = stream
                         .decrypt_next(buffer.as_slice())
                         .map_err(|_| Error::DecryptionStreamError)?;
 
                     writer.write_all(&plaintext)?
                 }
-                Ok(0) => break,
-                Ok(read_count) => {
+                0 => break,
+                read_count => {
                     let plaintext = stream
                         .decrypt_last(&buffer[..read_count])
                         .map_err(|_| Error::DecryptionStreamError)?;
                     writer.write_all(&plaintext)?;
                     break;
                 }
-                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => return Err(Error::from(e)),
             };
         }
 
@@ -524,6 +744,197 @@ impl Cipher {
     }
 }
 
+/// Fills the buffer from the reader, only returning less than its length at the end of the reader
+#[cfg(not(target_arch = "wasm32"))]
+fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read_count) => filled += read_count,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Fills the buffer from the reader, only returning less than its length at the end of the reader
+async fn read_chunk_async<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut [u8],
+) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]).await {
+            Ok(0) => break,
+            Ok(read_count) => filled += read_count,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Decrypts an async stream written before the header was introduced
+fn decrypt_legacy_async<'a, R: AsyncRead + Unpin + Send + 'a>(
+    private_key: zeroize::Zeroizing<Vec<u8>>,
+    nonce: [u8; LEGACY_STREAM_NONCE_SIZE],
+    mut reader: R,
+) -> impl Stream<Item = std::io::Result<Vec<u8>>> + Send + 'a {
+    async_stream::stream! {
+        let key = cipher_key(private_key, &nonce);
+
+        let mut buffer = [0u8; AES256_GCM_DECRYPTION_BUF_SIZE];
+        let cipher = Aes256Gcm::new(key.as_slice().into());
+        let mut stream = DecryptorBE32::from_aead(cipher, nonce.as_slice().into());
+
+        loop {
+            match read_chunk_async(&mut reader, &mut buffer).await {
+                Ok(AES256_GCM_DECRYPTION_BUF_SIZE) => {
+                    match stream.decrypt_next(buffer.as_slice()).map_err(|_| std::io::Error::other(Error::DecryptionStreamError)) {
+                        Ok(data) => yield Ok(data),
+                        Err(e) => {
+                            yield Err(e);
+                            break;
+                        }
+                    };
+                }
+                Ok(0) => break,
+                Ok(read_count) => {
+                    yield stream.decrypt_last(&buffer[..read_count]).map_err(|_| std::io::Error::other(Error::DecryptionStreamError));
+                    break;
+                }
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                },
+            };
+        }
+    }
+}
+
+/// Key used for encryption. Keys that are not 256bit are hashed along with the nonce
+fn cipher_key(
+    private_key: zeroize::Zeroizing<Vec<u8>>,
+    nonce: &[u8],
+) -> zeroize::Zeroizing<Vec<u8>> {
+    match private_key.len() {
+        32 => private_key,
+        _ => zeroize::Zeroizing::new(sha256_hash(&private_key, Some(nonce))),
+    }
+}
+
+/// Header of data encrypted in one piece, which is also authenticated along with the data
+fn data_header(suite: CipherSuite) -> Vec<u8> {
+    let mut header = Vec::with_capacity(DATA_HEADER_SIZE);
+    header.extend_from_slice(DATA_MAGIC);
+    header.push(suite.id());
+    header
+}
+
+/// Split the header from the data, returning `None` if the data was written without one
+fn split_data_header(data: &[u8]) -> Result<Option<(CipherSuite, &[u8], &[u8])>> {
+    if data.len() < DATA_HEADER_SIZE || !data.starts_with(DATA_MAGIC) {
+        return Ok(None);
+    }
+
+    let (header, data) = data.split_at(DATA_HEADER_SIZE);
+    let suite = CipherSuite::from_id(header[DATA_MAGIC_SIZE])?;
+    Ok(Some((suite, header, data)))
+}
+
+/// Version of the stream header written for the suite. AES-256-GCM streams keep the first version so they can
+/// still be read by releases that do not know of the cipher suite
+fn stream_version(suite: CipherSuite) -> u8 {
+    match suite {
+        CipherSuite::Aes256Gcm => STREAM_VERSION_V1,
+        _ => STREAM_VERSION,
+    }
+}
+
+/// Header of a stream, which is also authenticated along with every chunk
+fn stream_header(version: u8, suite: CipherSuite, nonce: &[u8]) -> Vec<u8> {
+    let mut header = Vec::with_capacity(STREAM_MAGIC_SIZE + 2 + nonce.len());
+    header.extend_from_slice(STREAM_MAGIC);
+    header.push(version);
+    if version != STREAM_VERSION_V1 {
+        header.push(suite.id());
+    }
+    header.extend_from_slice(nonce);
+    header
+}
+
+enum StreamEncryptor {
+    Aes256Gcm(EncryptorBE32<Aes256Gcm>),
+    XChaCha20Poly1305(EncryptorBE32<XChaCha20Poly1305>),
+}
+
+impl StreamEncryptor {
+    fn new(suite: CipherSuite, key: &[u8], nonce: &[u8]) -> Self {
+        match suite {
+            CipherSuite::Aes256Gcm => StreamEncryptor::Aes256Gcm(EncryptorBE32::from_aead(
+                Aes256Gcm::new(key.into()),
+                nonce.into(),
+            )),
+            CipherSuite::XChaCha20Poly1305 => StreamEncryptor::XChaCha20Poly1305(
+                EncryptorBE32::from_aead(XChaCha20Poly1305::new(key.into()), nonce.into()),
+            ),
+        }
+    }
+
+    fn encrypt_next(&mut self, payload: Payload<'_, '_>) -> Result<Vec<u8>> {
+        match self {
+            StreamEncryptor::Aes256Gcm(stream) => stream.encrypt_next(payload),
+            StreamEncryptor::XChaCha20Poly1305(stream) => stream.encrypt_next(payload),
+        }
+        .map_err(|_| Error::EncryptionStreamError)
+    }
+
+    fn encrypt_last(self, payload: Payload<'_, '_>) -> Result<Vec<u8>> {
+        match self {
+            StreamEncryptor::Aes256Gcm(stream) => stream.encrypt_last(payload),
+            StreamEncryptor::XChaCha20Poly1305(stream) => stream.encrypt_last(payload),
+        }
+        .map_err(|_| Error::EncryptionStreamError)
+    }
+}
+
+enum StreamDecryptor {
+    Aes256Gcm(DecryptorBE32<Aes256Gcm>),
+    XChaCha20Poly1305(DecryptorBE32<XChaCha20Poly1305>),
+}
+
+impl StreamDecryptor {
+    fn new(suite: CipherSuite, key: &[u8], nonce: &[u8]) -> Self {
+        match suite {
+            CipherSuite::Aes256Gcm => StreamDecryptor::Aes256Gcm(DecryptorBE32::from_aead(
+                Aes256Gcm::new(key.into()),
+                nonce.into(),
+            )),
+            CipherSuite::XChaCha20Poly1305 => StreamDecryptor::XChaCha20Poly1305(
+                DecryptorBE32::from_aead(XChaCha20Poly1305::new(key.into()), nonce.into()),
+            ),
+        }
+    }
+
+    fn decrypt_next(&mut self, payload: Payload<'_, '_>) -> Result<Vec<u8>> {
+        match self {
+            StreamDecryptor::Aes256Gcm(stream) => stream.decrypt_next(payload),
+            StreamDecryptor::XChaCha20Poly1305(stream) => stream.decrypt_next(payload),
+        }
+        .map_err(|_| Error::DecryptionStreamError)
+    }
+
+    fn decrypt_last(self, payload: Payload<'_, '_>) -> Result<Vec<u8>> {
+        match self {
+            StreamDecryptor::Aes256Gcm(stream) => stream.decrypt_last(payload),
+            StreamDecryptor::XChaCha20Poly1305(stream) => stream.decrypt_last(payload),
+        }
+        .map_err(|_| Error::DecryptionStreamError)
+    }
+}
+
 fn extract_data_slice<const N: usize>(data: &[u8]) -> (&[u8], &[u8]) {
     let extracted = &data[data.len() - N..];
     let payload = &data[..data.len() - N];
@@ -534,6 +945,9 @@ fn extract_data_slice<const N: usize>(data: &[u8]) -> (&[u8], &[u8]) {
 mod test {
     use crate::crypto::cipher::*;
 
+    // Magic, version and the AES-256-GCM nonce prefix, since AES-256-GCM streams keep the first version
+    const STREAM_HEADER_SIZE: usize = STREAM_MAGIC_SIZE + 1 + 7;
+
     #[test]
     fn cipher_aes256gcm_encrypt_decrypt() -> anyhow::Result<()> {
         let cipher = Cipher::from(b"this is my secret cipher key!");
@@ -552,6 +966,113 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn cipher_xchacha20poly1305_encrypt_decrypt() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my secret cipher key!")
+            .with_suite(CipherSuite::XChaCha20Poly1305);
+        let message = b"Hello, World!";
+
+        let cipher_data = cipher.encrypt(message, None)?;
+        assert_eq!(
+            cipher_data.len(),
+            DATA_HEADER_SIZE + message.len() + 16 + 24
+        );
+
+        let plaintext = cipher.decrypt(&cipher_data)?;
+        assert_eq!(plaintext, message);
+
+        // The suite is read from the header
+        let aes = Cipher::from(b"this is my secret cipher key!");
+        assert_eq!(aes.decrypt(&cipher_data)?, message);
+
+        let (suite, nonce) = Cipher::extract_nonce(&cipher_data)?;
+        assert_eq!(suite, CipherSuite::XChaCha20Poly1305);
+        assert_eq!(nonce, &cipher_data[cipher_data.len() - 24..]);
+        Ok(())
+    }
+
+    #[test]
+    fn cipher_header_is_authenticated() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my secret cipher key!")
+            .with_suite(CipherSuite::XChaCha20Poly1305);
+        let message = b"Hello, World!";
+
+        let mut cipher_data = cipher.encrypt(message, None)?;
+        assert!(cipher_data.starts_with(DATA_MAGIC));
+
+        cipher_data[DATA_MAGIC_SIZE] = 2;
+        assert!(matches!(
+            cipher.decrypt(&cipher_data),
+            Err(Error::UnsupportedCipherSuite)
+        ));
+
+        // Removing the header leaves data that cannot be decrypted as the legacy format
+        cipher_data[DATA_MAGIC_SIZE] = CipherSuite::XChaCha20Poly1305.id();
+        assert!(cipher.decrypt(&cipher_data[DATA_HEADER_SIZE..]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn cipher_aes256gcm_keeps_legacy_format() -> anyhow::Result<()> {
+        let key = b"this is my secret cipher key!";
+        let message = b"Hello, World!";
+
+        let cipher_data = Cipher::from(key).encrypt(message, None)?;
+        assert!(!cipher_data.starts_with(DATA_MAGIC));
+        assert_eq!(cipher_data.len(), message.len() + 16 + 12);
+
+        // Decrypted the way it was done before the header was introduced
+        let (payload, nonce) = cipher_data.split_at(cipher_data.len() - 12);
+        let legacy_key = cipher_key(zeroize::Zeroizing::new(key.to_vec()), nonce);
+        let plaintext = Aes256Gcm::new(legacy_key.as_slice().into())
+            .decrypt(nonce.into(), payload)
+            .map_err(|_| anyhow::anyhow!("unable to decrypt"))?;
+        assert_eq!(plaintext, message);
+
+        // A cipher using another suite still reads it
+        let cipher = Cipher::from(key).with_suite(CipherSuite::XChaCha20Poly1305);
+        assert_eq!(cipher.decrypt(&cipher_data)?, message);
+        Ok(())
+    }
+
+    /// Encrypt data the way it was done before the header was introduced
+    fn legacy_encrypt_data(suite: CipherSuite, key: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let nonce = suite.generate_nonce();
+        let key = cipher_key(zeroize::Zeroizing::new(key.to_vec()), &nonce);
+        let mut cipher_data = match suite {
+            CipherSuite::Aes256Gcm => {
+                Aes256Gcm::new(key.as_slice().into()).encrypt(nonce.as_slice().into(), data)
+            }
+            CipherSuite::XChaCha20Poly1305 => {
+                XChaCha20Poly1305::new(key.as_slice().into()).encrypt(nonce.as_slice().into(), data)
+            }
+        }
+        .map_err(|_| anyhow::anyhow!("unable to encrypt"))?;
+        cipher_data.extend(nonce);
+        Ok(cipher_data)
+    }
+
+    #[test]
+    fn cipher_reads_legacy_format() -> anyhow::Result<()> {
+        let key = b"this is my secret cipher key!";
+        let message = b"Hello, World!";
+
+        let cipher_data = legacy_encrypt_data(CipherSuite::Aes256Gcm, key, message)?;
+        assert_eq!(Cipher::from(key).decrypt(&cipher_data)?, message);
+        assert_eq!(Cipher::direct_decrypt(&cipher_data, key)?, message);
+
+        let (suite, nonce) = Cipher::extract_nonce(&cipher_data)?;
+        assert_eq!(suite, CipherSuite::Aes256Gcm);
+        assert_eq!(nonce, &cipher_data[cipher_data.len() - 12..]);
+
+        // Legacy data carries no suite, so the suite of the cipher is used
+        let cipher_data = legacy_encrypt_data(CipherSuite::XChaCha20Poly1305, key, message)?;
+        assert!(Cipher::from(key).decrypt(&cipher_data).is_err());
+        let cipher = Cipher::from(key).with_suite(CipherSuite::XChaCha20Poly1305);
+        assert_eq!(cipher.decrypt(&cipher_data)?, message);
+        Ok(())
+    }
+
     #[test]
     fn cipher_aes256gcm_self_encrypt_decrypt() -> anyhow::Result<()> {
         let message = b"Hello, World!";
@@ -692,4 +1213,245 @@ mod test {
 
         Ok(())
     }
+
+    /// Encrypts in the format used before the stream header was introduced
+    fn legacy_encrypt(key: &[u8], data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let nonce = crate::crypto::generate::<7>();
+        let key = sha256_hash(key, Some(&nonce));
+        let cipher = Aes256Gcm::new(key.as_slice().into());
+        let mut stream = EncryptorBE32::from_aead(cipher, nonce.as_slice().into());
+
+        let mut output = nonce.to_vec();
+        let mut chunks = data.chunks(AES256_GCM_ENCRYPTION_BUF_SIZE).peekable();
+        while let Some(chunk) = chunks.next() {
+            if chunk.len() == AES256_GCM_ENCRYPTION_BUF_SIZE {
+                output.extend(
+                    stream
+                        .encrypt_next(chunk)
+                        .map_err(|_| Error::EncryptionStreamError)?,
+                );
+            }
+            if chunks.peek().is_none() {
+                let last = match chunk.len() {
+                    AES256_GCM_ENCRYPTION_BUF_SIZE => &[][..],
+                    _ => chunk,
+                };
+                output.extend(
+                    stream
+                        .encrypt_last(last)
+                        .map_err(|_| Error::EncryptionStreamError)?,
+                );
+                break;
+            }
+        }
+        Ok(output)
+    }
+
+    #[test]
+    fn cipher_stream_reads_legacy_format() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my key");
+        let message = vec![7u8; 2000];
+
+        let cipher_data = legacy_encrypt(b"this is my key", &message)?;
+
+        let mut plaintext = Vec::<u8>::new();
+        cipher.decrypt_stream(&mut cipher_data.as_slice(), &mut plaintext)?;
+        assert_eq!(plaintext, message);
+        Ok(())
+    }
+
+    #[test]
+    fn cipher_stream_detects_truncation_and_reordering() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my key");
+        let message = vec![7u8; STREAM_CHUNK_SIZE * 3];
+        let mut cipher_data = Vec::<u8>::new();
+
+        cipher.encrypt_stream(&mut message.as_slice(), &mut cipher_data)?;
+        assert_eq!(cipher_data[STREAM_MAGIC_SIZE], STREAM_VERSION_V1);
+        assert_eq!(
+            cipher_data.len(),
+            STREAM_HEADER_SIZE + STREAM_ENCRYPTED_CHUNK_SIZE * 3
+        );
+
+        let chunk = |index: usize| {
+            let start = STREAM_HEADER_SIZE + STREAM_ENCRYPTED_CHUNK_SIZE * index;
+            &cipher_data[start..start + STREAM_ENCRYPTED_CHUNK_SIZE]
+        };
+
+        let truncated = &cipher_data[..STREAM_HEADER_SIZE + STREAM_ENCRYPTED_CHUNK_SIZE * 2];
+        let mut plaintext = Vec::<u8>::new();
+        assert!(cipher
+            .decrypt_stream(&mut &truncated[..], &mut plaintext)
+            .is_err());
+
+        let mut reordered = cipher_data[..STREAM_HEADER_SIZE].to_vec();
+        reordered.extend(chunk(1));
+        reordered.extend(chunk(0));
+        reordered.extend(chunk(2));
+        let mut plaintext = Vec::<u8>::new();
+        assert!(cipher
+            .decrypt_stream(&mut reordered.as_slice(), &mut plaintext)
+            .is_err());
+
+        let mut unsupported = cipher_data.clone();
+        unsupported[STREAM_MAGIC_SIZE] = STREAM_VERSION + 1;
+        let mut plaintext = Vec::<u8>::new();
+        assert!(matches!(
+            cipher.decrypt_stream(&mut unsupported.as_slice(), &mut plaintext),
+            Err(Error::UnsupportedStreamVersion)
+        ));
+
+        let mut plaintext = Vec::<u8>::new();
+        cipher.decrypt_stream(&mut cipher_data.as_slice(), &mut plaintext)?;
+        assert_eq!(plaintext, message);
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn cipher_async_stream_with_short_reads() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my key");
+        let message = (0..STREAM_CHUNK_SIZE * 2 + 100)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+
+        let base = stream::iter(
+            message
+                .chunks(1000)
+                .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+                .collect::<Vec<_>>(),
+        );
+
+        let cipher_data = cipher
+            .encrypt_async_stream(base)
+            .try_collect::<Vec<_>>()
+            .await?;
+
+        // Split the ciphertext at boundaries that do not line up with the chunks
+        let cipher_data = cipher_data.concat();
+        let cipher_stream = stream::iter(
+            cipher_data
+                .chunks(333)
+                .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+                .collect::<Vec<_>>(),
+        );
+
+        let plaintext = cipher
+            .decrypt_async_stream(cipher_stream)
+            .try_collect::<Vec<_>>()
+            .await?
+            .concat();
+
+        assert_eq!(plaintext, message);
+
+        let legacy = stream::iter(Ok::<_, std::io::Error>(Ok(legacy_encrypt(
+            b"this is my key",
+            &message,
+        )?)));
+        let plaintext = cipher
+            .decrypt_async_stream(legacy)
+            .try_collect::<Vec<_>>()
+            .await?
+            .concat();
+
+        assert_eq!(plaintext, message);
+        Ok(())
+    }
+
+    #[test]
+    fn cipher_xchacha20poly1305_stream_encrypt_decrypt() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my key").with_suite(CipherSuite::XChaCha20Poly1305);
+        let message = vec![7u8; STREAM_CHUNK_SIZE * 2 + 100];
+        let mut cipher_data = Vec::<u8>::new();
+
+        cipher.encrypt_stream(&mut message.as_slice(), &mut cipher_data)?;
+        assert_eq!(cipher_data[STREAM_MAGIC_SIZE], STREAM_VERSION);
+        assert_eq!(
+            cipher_data[STREAM_MAGIC_SIZE + 1],
+            CipherSuite::XChaCha20Poly1305.id()
+        );
+
+        // The suite is read from the header, so any cipher with the same key can decrypt it
+        let mut plaintext = Vec::<u8>::new();
+        Cipher::from(b"this is my key")
+            .decrypt_stream(&mut cipher_data.as_slice(), &mut plaintext)?;
+        assert_eq!(plaintext, message);
+
+        let mut tampered = cipher_data.clone();
+        tampered[STREAM_MAGIC_SIZE + 1] = CipherSuite::Aes256Gcm.id();
+        let mut plaintext = Vec::<u8>::new();
+        assert!(cipher
+            .decrypt_stream(&mut tampered.as_slice(), &mut plaintext)
+            .is_err());
+
+        let mut unknown = cipher_data.clone();
+        unknown[STREAM_MAGIC_SIZE + 1] = u8::MAX;
+        let mut plaintext = Vec::<u8>::new();
+        assert!(matches!(
+            cipher.decrypt_stream(&mut unknown.as_slice(), &mut plaintext),
+            Err(Error::UnsupportedCipherSuite)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn cipher_stream_reads_v1_format() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my key");
+        let message = vec![7u8; STREAM_CHUNK_SIZE + 100];
+
+        // Version 1 headers have no cipher suite and are always AES-256-GCM
+        let nonce = crate::crypto::generate::<7>();
+        let key = cipher_key(cipher.private_key.clone(), &nonce);
+        let header = stream_header(STREAM_VERSION_V1, CipherSuite::Aes256Gcm, &nonce);
+        assert_eq!(header.len(), STREAM_MAGIC_SIZE + 1 + 7);
+
+        let mut stream = StreamEncryptor::new(CipherSuite::Aes256Gcm, &key, &nonce);
+        let mut cipher_data = header.clone();
+        let (first, last) = message.split_at(STREAM_CHUNK_SIZE);
+        cipher_data.extend(stream.encrypt_next(Payload {
+            msg: first,
+            aad: &header,
+        })?);
+        cipher_data.extend(stream.encrypt_last(Payload {
+            msg: last,
+            aad: &header,
+        })?);
+
+        let mut plaintext = Vec::<u8>::new();
+        cipher.decrypt_stream(&mut cipher_data.as_slice(), &mut plaintext)?;
+        assert_eq!(plaintext, message);
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[tokio::test]
+    async fn cipher_xchacha20poly1305_async_stream_encrypt_decrypt() -> anyhow::Result<()> {
+        let cipher = Cipher::from(b"this is my key").with_suite(CipherSuite::XChaCha20Poly1305);
+        let message = (0..STREAM_CHUNK_SIZE + 100)
+            .map(|i| i as u8)
+            .collect::<Vec<_>>();
+
+        let base = stream::iter(Ok::<_, std::io::Error>(Ok(message.clone())));
+        let cipher_data = cipher
+            .encrypt_async_stream(base)
+            .try_collect::<Vec<_>>()
+            .await?
+            .concat();
+
+        let cipher_stream = stream::iter(
+            cipher_data
+                .chunks(777)
+                .map(|chunk| Ok::<_, std::io::Error>(chunk.to_vec()))
+                .collect::<Vec<_>>(),
+        );
+
+        let plaintext = cipher
+            .decrypt_async_stream(cipher_stream)
+            .try_collect::<Vec<_>>()
+            .await?
+            .concat();
+
+        assert_eq!(plaintext, message);
+        Ok(())
+    }
 }
@@ -6,11 +6,13 @@ use did_key::KeyMaterial;
 use ed25519_dalek::{Keypair, PublicKey, SecretKey, KEYPAIR_LENGTH, SECRET_KEY_LENGTH};
 use hmac::{Hmac, Mac};
 use sha2::Sha512;
-use zeroize::Zeroizing;
+use std::{fmt, str::FromStr};
+use zeroize::{Zeroize, Zeroizing};
 
 use super::DID;
 
 const ED25519_BIP32_NAME: &str = "ed25519 seed";
+const HARDENED_OFFSET: u32 = 0x8000_0000;
 type HmacSha512 = Hmac<Sha512>;
 
 #[derive(Clone, Display, Copy)]
@@ -45,29 +47,250 @@ pub fn generate_keypair(
     Ok((mnemonic.into_phrase(), did))
 }
 
+/// Index of a child key. Only hardened derivation is possible with ed25519 keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ChildIndex(u32);
+
+impl ChildIndex {
+    /// Hardened index. `index` must be lower than 2^31
+    pub fn hardened(index: u32) -> Result<Self, Error> {
+        if index >= HARDENED_OFFSET {
+            return Err(Error::InvalidDerivationPath);
+        }
+        Ok(Self(index))
+    }
+
+    pub fn index(&self) -> u32 {
+        self.0
+    }
+
+    fn to_bits(self) -> u32 {
+        self.0 | HARDENED_OFFSET
+    }
+}
+
+impl fmt::Display for ChildIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}'", self.0)
+    }
+}
+
+/// Path of a key derived from the master key (SLIP-0010), e.g. `m/0'/1'`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct DerivationPath(Vec<ChildIndex>);
+
+impl DerivationPath {
+    const DEVICE: u32 = 0;
+    const CONVERSATION: u32 = 1;
+    const BACKUP: u32 = 2;
+
+    pub fn new(indexes: Vec<ChildIndex>) -> Self {
+        Self(indexes)
+    }
+
+    /// Path of the key for a linked device: `m/0'/{index}'`
+    pub fn device(index: u32) -> Result<Self, Error> {
+        Self::purpose(Self::DEVICE, index)
+    }
+
+    /// Path of the signing key for a conversation: `m/1'/{index}'`
+    pub fn conversation(index: u32) -> Result<Self, Error> {
+        Self::purpose(Self::CONVERSATION, index)
+    }
+
+    /// Path of the key used to encrypt backups: `m/2'/{index}'`
+    pub fn backup(index: u32) -> Result<Self, Error> {
+        Self::purpose(Self::BACKUP, index)
+    }
+
+    fn purpose(purpose: u32, index: u32) -> Result<Self, Error> {
+        Ok(Self(vec![
+            ChildIndex::hardened(purpose)?,
+            ChildIndex::hardened(index)?,
+        ]))
+    }
+
+    /// Extend the path with a child
+    pub fn child(mut self, index: ChildIndex) -> Self {
+        self.0.push(index);
+        self
+    }
+
+    pub fn indexes(&self) -> &[ChildIndex] {
+        &self.0
+    }
+}
+
+impl fmt::Display for DerivationPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "m")?;
+        for index in &self.0 {
+            write!(f, "/{index}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for DerivationPath {
+    type Err = Error;
+
+    fn from_str(path: &str) -> Result<Self, Self::Err> {
+        let mut components = path.split('/');
+
+        if components.next() != Some("m") {
+            return Err(Error::InvalidDerivationPath);
+        }
+
+        components
+            .map(|component| {
+                let index = component
+                    .strip_suffix('\'')
+                    .or_else(|| component.strip_suffix('h'))
+                    .ok_or(Error::InvalidDerivationPath)?;
+                let index = index.parse().map_err(|_| Error::InvalidDerivationPath)?;
+                ChildIndex::hardened(index)
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map(Self)
+    }
+}
+
+/// Secret key along with the chain code used to derive child keys
+#[derive(Clone)]
+pub struct ExtendedSecretKey {
+    depth: u8,
+    child_index: Option<ChildIndex>,
+    secret_key: Zeroizing<[u8; SECRET_KEY_LENGTH]>,
+    chain_code: Zeroizing<[u8; 32]>,
+}
+
+impl ExtendedSecretKey {
+    /// Master key of the seed
+    pub fn from_seed(seed: &[u8]) -> Result<Self, Error> {
+        let mut mac = HmacSha512::new_from_slice(ED25519_BIP32_NAME.as_ref())
+            .map_err(|_| Error::PrivateKeyInvalid)?;
+        mac.update(seed);
+        Ok(Self::from_hmac(mac, 0, None))
+    }
+
+    /// Master key of the mnemonic phrase
+    pub fn from_mnemonic(mnemonic: &str, passphrase: Option<&str>) -> Result<Self, Error> {
+        let mnemonic = Mnemonic::from_phrase(mnemonic, Language::English)?;
+        let seed = Seed::new(&mnemonic, passphrase.unwrap_or_default());
+        Self::from_seed(seed.as_bytes())
+    }
+
+    /// Master key from the keypair and chain code stored by [`mnemonic_into_tesseract`]
+    pub fn from_tesseract(tesseract: &Tesseract) -> Result<Self, Error> {
+        let keypair = Zeroizing::new(tesseract.retrieve("keypair")?);
+        let keypair = Zeroizing::new(bs58::decode(keypair.as_str()).into_vec()?);
+        let chain = Zeroizing::new(tesseract.retrieve("chain")?);
+        let chain = Zeroizing::new(bs58::decode(chain.as_str()).into_vec()?);
+
+        if keypair.len() != KEYPAIR_LENGTH {
+            return Err(Error::InvalidPrivateKeyLength);
+        }
+
+        if chain.len() != 32 {
+            return Err(Error::InvalidDerivationPath);
+        }
+
+        let mut secret_key = Zeroizing::new([0u8; SECRET_KEY_LENGTH]);
+        secret_key.copy_from_slice(&keypair[..SECRET_KEY_LENGTH]);
+        let mut chain_code = Zeroizing::new([0u8; 32]);
+        chain_code.copy_from_slice(&chain);
+
+        Ok(Self {
+            depth: 0,
+            child_index: None,
+            secret_key,
+            chain_code,
+        })
+    }
+
+    fn from_hmac(mac: HmacSha512, depth: u8, child_index: Option<ChildIndex>) -> Self {
+        let mut bytes = mac.finalize().into_bytes();
+        let mut secret_key = Zeroizing::new([0u8; SECRET_KEY_LENGTH]);
+        secret_key.copy_from_slice(&bytes[..32]);
+        let mut chain_code = Zeroizing::new([0u8; 32]);
+        chain_code.copy_from_slice(&bytes[32..]);
+        bytes.as_mut_slice().zeroize();
+        Self {
+            depth,
+            child_index,
+            secret_key,
+            chain_code,
+        }
+    }
+
+    /// Derive the child key at `index`
+    pub fn derive_child(&self, index: ChildIndex) -> Result<Self, Error> {
+        let depth = self.depth.checked_add(1).ok_or(Error::MaxDerivationDepth)?;
+        let mut mac = HmacSha512::new_from_slice(self.chain_code.as_slice())
+            .map_err(|_| Error::PrivateKeyInvalid)?;
+        mac.update(&[0]);
+        mac.update(self.secret_key.as_slice());
+        mac.update(&index.to_bits().to_be_bytes());
+        Ok(Self::from_hmac(mac, depth, Some(index)))
+    }
+
+    /// Derive the key at `path`, relative to this key
+    pub fn derive(&self, path: &DerivationPath) -> Result<Self, Error> {
+        path.indexes()
+            .iter()
+            .try_fold(self.clone(), |key, index| key.derive_child(*index))
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Index of this key within its parent. `None` for the master key
+    pub fn child_index(&self) -> Option<ChildIndex> {
+        self.child_index
+    }
+
+    pub fn chain_code(&self) -> &[u8; 32] {
+        &self.chain_code
+    }
+
+    pub fn secret_key(&self) -> Result<SecretKey, Error> {
+        SecretKey::from_bytes(self.secret_key.as_slice()).map_err(Error::from)
+    }
+
+    pub fn public_key(&self) -> Result<PublicKey, Error> {
+        Ok((&self.secret_key()?).into())
+    }
+
+    pub fn keypair(&self) -> Result<Keypair, Error> {
+        let secret = self.secret_key()?;
+        let public = (&secret).into();
+        Ok(Keypair { secret, public })
+    }
+
+    pub fn did(&self) -> Result<DID, Error> {
+        self.secret_key().map(DID::from)
+    }
+}
+
 /// Generate DID from mnemonic phrase, extending compatibility
 pub fn did_from_mnemonic_with_chain(
     mnemonic: &str,
     passphrase: Option<&str>,
 ) -> Result<(DID, [u8; 32]), Error> {
-    let mnemonic = Mnemonic::from_phrase(mnemonic, Language::English)?;
-    let seed = Seed::new(&mnemonic, passphrase.unwrap_or_default());
-    let mut mac = HmacSha512::new_from_slice(ED25519_BIP32_NAME.as_ref()).unwrap();
-    mac.update(seed.as_bytes());
-    let bytes = mac.finalize().into_bytes();
-    let secret = SecretKey::from_bytes(&bytes[..32])?;
-    // Note: This will allow extending to `ed25519-dalek-bip32` for path derivation, with this being the root of the `ExtendedSecretKey` in the following format
-    /*
-       ExtendedSecretKey {
-           depth: 0,
-           child_index: ChildIndex::Normal(0),
-           secret_key,
-           chain_code
-       }
-    */
-    let mut chain_code = [0; 32];
-    chain_code.copy_from_slice(&bytes[32..]);
-    Ok((secret.into(), chain_code))
+    let key = ExtendedSecretKey::from_mnemonic(mnemonic, passphrase)?;
+    Ok((key.did()?, *key.chain_code()))
+}
+
+/// Generate the DID of the key at `path` from mnemonic phrase
+pub fn did_from_mnemonic_with_path(
+    mnemonic: &str,
+    passphrase: Option<&str>,
+    path: &DerivationPath,
+) -> Result<DID, Error> {
+    ExtendedSecretKey::from_mnemonic(mnemonic, passphrase)?
+        .derive(path)?
+        .did()
 }
 
 /// Generate DID from mnemonic phrase
@@ -116,7 +339,10 @@ pub fn mnemonic_into_tesseract(
 
 #[cfg(test)]
 mod test {
-    use super::did_from_mnemonic;
+    use super::{
+        did_from_mnemonic, did_from_mnemonic_with_path, ChildIndex, DerivationPath,
+        ExtendedSecretKey,
+    };
 
     const PHRASE: &str =
         "morning caution dose lab six actress pond humble pause enact virtual train";
@@ -128,4 +354,67 @@ mod test {
         assert_eq!(did.to_string(), expected);
         Ok(())
     }
+
+    #[test]
+    fn derivation_path_parsing() -> anyhow::Result<()> {
+        let path: DerivationPath = "m/0'/1h".parse()?;
+        assert_eq!(
+            path,
+            DerivationPath::new(vec![ChildIndex::hardened(0)?, ChildIndex::hardened(1)?])
+        );
+        assert_eq!(path.to_string(), "m/0'/1'");
+        assert_eq!(path, DerivationPath::device(1)?);
+
+        assert_eq!("m".parse::<DerivationPath>()?, DerivationPath::default());
+        assert!("m/0".parse::<DerivationPath>().is_err());
+        assert!("0'/1'".parse::<DerivationPath>().is_err());
+        assert!("m/2147483648'".parse::<DerivationPath>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn slip10_test_vector() -> anyhow::Result<()> {
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f")?;
+        let master = ExtendedSecretKey::from_seed(&seed)?;
+        assert_eq!(
+            hex::encode(master.secret_key()?.as_bytes()),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            hex::encode(master.chain_code()),
+            "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb"
+        );
+
+        let child = master.derive(&"m/0'/1'".parse()?)?;
+        assert_eq!(child.depth(), 2);
+        assert_eq!(child.child_index(), Some(ChildIndex::hardened(1)?));
+        assert_eq!(
+            hex::encode(child.secret_key()?.as_bytes()),
+            "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2"
+        );
+        assert_eq!(
+            hex::encode(child.chain_code()),
+            "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn derive_keys_from_phrase() -> anyhow::Result<()> {
+        let root = did_from_mnemonic(PHRASE, None)?;
+        let device = did_from_mnemonic_with_path(PHRASE, None, &DerivationPath::device(0)?)?;
+        let backup = did_from_mnemonic_with_path(PHRASE, None, &DerivationPath::backup(0)?)?;
+
+        assert_ne!(root, device);
+        assert_ne!(device, backup);
+        assert_eq!(
+            device,
+            did_from_mnemonic_with_path(PHRASE, None, &DerivationPath::device(0)?)?
+        );
+        assert_eq!(
+            root,
+            did_from_mnemonic_with_path(PHRASE, None, &DerivationPath::default())?
+        );
+        Ok(())
+    }
 }
@@ -14,6 +14,7 @@ pub mod cipher;
 pub mod hash;
 pub mod keypair;
 pub mod multihash;
+pub mod shamir;
 
 use serde::{Deserialize, Deserializer, Serialize};
 
@@ -96,6 +96,12 @@ pub fn multihash_file<H: Hasher + Default, P: AsRef<std::path::Path>>(
     Ok(digest.to_bytes())
 }
 
+/// Wraps an existing sha2-256 digest into a multihash
+pub fn sha2_256_multihash_digest(digest: &[u8]) -> Result<Vec<u8>, crate::error::Error> {
+    let digest = Code::Sha2_256.wrap(digest).map_err(anyhow::Error::from)?;
+    Ok(digest.to_bytes())
+}
+
 create_hash_functions!(Sha1);
 create_hash_functions!(Sha2_256);
 create_hash_functions!(Sha2_512);
@@ -128,6 +134,18 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn sha2_256_multihash_digest_test() -> anyhow::Result<()> {
+        let digest = crate::crypto::hash::sha256_hash(b"Hello, World!", None);
+        let hash = sha2_256_multihash_digest(&digest)?;
+
+        assert_eq!(
+            bs58::encode(&hash).into_string(),
+            String::from("QmdR1iHsUocy7pmRHBhNa9znM8eh8Mwqq5g5vcw8MDMXTt")
+        );
+        Ok(())
+    }
+
     #[test]
     fn sha2_256_multihash_test() -> anyhow::Result<()> {
         let hash = sha2_256_multihash_slice(b"Hello, World!")?;
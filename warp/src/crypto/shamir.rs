@@ -0,0 +1,200 @@
+#![allow(clippy::result_large_err)]
+//! Shamir's secret sharing over GF(2^8)
+//!
+//! A secret is split into `count` shares where any `threshold` of them are able to reconstruct
+//! the secret, while anything less reveals nothing about it.
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Share {
+    index: u8,
+    threshold: u8,
+    data: Vec<u8>,
+}
+
+impl std::fmt::Debug for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Share")
+            .field("index", &self.index)
+            .field("threshold", &self.threshold)
+            .finish()
+    }
+}
+
+impl Drop for Share {
+    fn drop(&mut self) {
+        self.data.zeroize();
+    }
+}
+
+impl Share {
+    /// Position of the share, starting from 1
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Number of shares needed to recover the secret
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Split `secret` into `count` shares, of which `threshold` are needed to recover it
+pub fn split(secret: &[u8], threshold: u8, count: u8) -> Result<Vec<Share>> {
+    if threshold == 0 || threshold > count {
+        return Err(Error::InvalidShareThreshold);
+    }
+
+    let mut shares = (1..=count)
+        .map(|index| Share {
+            index,
+            threshold,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect::<Vec<_>>();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+
+    for byte in secret {
+        coefficients[0] = *byte;
+        for coefficient in coefficients.iter_mut().skip(1) {
+            *coefficient = crate::crypto::generate::<1>()[0];
+        }
+
+        for share in shares.iter_mut() {
+            // Horner's method, starting from the highest degree
+            let value = coefficients.iter().rev().fold(0u8, |acc, coefficient| {
+                gf_mul(acc, share.index) ^ coefficient
+            });
+            share.data.push(value);
+        }
+    }
+
+    coefficients.zeroize();
+
+    Ok(shares)
+}
+
+/// Recover the secret from the shares provided
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>> {
+    let first = shares.first().ok_or(Error::InsufficientShares)?;
+
+    let threshold = first.threshold as usize;
+
+    if shares.len() < threshold {
+        return Err(Error::InsufficientShares);
+    }
+
+    let shares = &shares[..threshold];
+
+    for (i, share) in shares.iter().enumerate() {
+        if share.index == 0
+            || share.threshold != first.threshold
+            || share.data.len() != first.data.len()
+            || shares[..i].iter().any(|other| other.index == share.index)
+        {
+            return Err(Error::InvalidShare);
+        }
+    }
+
+    let mut secret = vec![0u8; first.data.len()];
+
+    for (i, share) in shares.iter().enumerate() {
+        // Lagrange basis polynomial of this share evaluated at x = 0
+        let mut basis = 1u8;
+        for (j, other) in shares.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            basis = gf_mul(basis, gf_div(other.index, other.index ^ share.index));
+        }
+
+        for (byte, value) in secret.iter_mut().zip(share.data.iter()) {
+            *byte ^= gf_mul(*value, basis);
+        }
+    }
+
+    Ok(secret)
+}
+
+// Multiplication in GF(2^8) using the AES reduction polynomial (x^8 + x^4 + x^3 + x + 1)
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (carry & 0x1b);
+        b >>= 1;
+    }
+    product
+}
+
+// a^254 is the multiplicative inverse of a in GF(2^8)
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    gf_mul(a, gf_inv(b))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::crypto::shamir::*;
+
+    #[test]
+    fn split_and_combine() -> anyhow::Result<()> {
+        let secret = b"Hello, World!";
+
+        let shares = split(secret, 3, 5)?;
+        assert_eq!(shares.len(), 5);
+
+        let recovered = combine(&shares[1..4])?;
+        assert_eq!(recovered, secret);
+
+        let recovered = combine(&[shares[4].clone(), shares[0].clone(), shares[2].clone()])?;
+        assert_eq!(recovered, secret);
+        Ok(())
+    }
+
+    #[test]
+    fn combine_insufficient_shares() -> anyhow::Result<()> {
+        let shares = split(b"Hello, World!", 3, 5)?;
+
+        assert!(combine(&shares[..2]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn combine_duplicate_shares() -> anyhow::Result<()> {
+        let shares = split(b"Hello, World!", 2, 3)?;
+
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_threshold() {
+        assert!(split(b"Hello, World!", 0, 3).is_err());
+        assert!(split(b"Hello, World!", 4, 3).is_err());
+    }
+}
@@ -109,6 +109,8 @@ pub enum Error {
     CannotFindFriendRequest,
     #[error("Unable to close friend request")]
     CannotCloseFriendRequest,
+    #[error("Friend request has expired")]
+    FriendRequestExpired,
     #[error("User does not exist as a friend")]
     FriendDoesntExist,
     #[error("User already exist as a friend")]
@@ -117,6 +119,26 @@ pub enum Error {
     BlockedByUser,
     #[error("Invalid identifier condition provided. Must be either public key, username, or your own identity")]
     InvalidIdentifierCondition,
+    #[error("You cannot authorize your own identity as a device")]
+    CannotAuthorizeOwnIdentity,
+    #[error("You cannot revoke the device currently in use")]
+    CannotRevokeOwnDevice,
+    #[error("Device is not authorized")]
+    DeviceNotAuthorized,
+    #[error("Identity has been revoked")]
+    IdentityRevoked,
+    #[error("Social recovery has not been set up for this identity")]
+    RecoveryNotSetup,
+    #[error("Recovery request does not exist")]
+    RecoveryRequestDoesntExist,
+    #[error("Username has already been claimed by another identity")]
+    UsernameTaken,
+    #[error("Username registry is unavailable")]
+    UsernameRegistryUnavailable,
+    #[error("Export bundle format version is not supported")]
+    UnsupportedBundleVersion,
+    #[error("Export bundle belongs to a different identity")]
+    BundleIdentityMismatch,
 
     //RayGun Errors
     #[error("Unable to create conversation")]
@@ -201,6 +223,20 @@ pub enum Error {
     InvalidPrivateKeyLength,
     #[error("Signature is invalid")]
     InvalidSignature,
+    #[error("Threshold must be at least 1 and no greater than the number of shares")]
+    InvalidShareThreshold,
+    #[error("Not enough shares were provided to recover the secret")]
+    InsufficientShares,
+    #[error("Share is invalid")]
+    InvalidShare,
+    #[error("Stream format version is not supported")]
+    UnsupportedStreamVersion,
+    #[error("Cipher suite is not supported")]
+    UnsupportedCipherSuite,
+    #[error("Derivation path is invalid")]
+    InvalidDerivationPath,
+    #[error("Maximum derivation depth has been reached")]
+    MaxDerivationDepth,
 
     //Tesseract Errors
     #[error("Tesseract is unavailable")]
@@ -213,6 +249,12 @@ pub enum Error {
     CorruptedDataStore,
     #[error("Unable to save tesseract")]
     CannotSaveTesseract,
+    #[error("Tesseract format version is not supported")]
+    UnsupportedTesseractVersion,
+    #[error("Key derivation parameters are invalid")]
+    InvalidKdfParameters,
+    #[error("Tesseract backend is locked")]
+    TesseractBackendLocked,
 
     //Data Errors
     #[error("Invalid data type")]
@@ -27,6 +27,90 @@ pub enum IdentityStatus {
     Offline,
 }
 
+/// Status of an identity along with an optional custom status, which is cleared once `until` has passed
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Presence {
+    status: IdentityStatus,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    emoji: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+
+    /// Time after which the presence clears back to [`IdentityStatus::Online`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    until: Option<DateTime<Utc>>,
+}
+
+impl Presence {
+    pub fn new(status: IdentityStatus) -> Self {
+        Self {
+            status,
+            emoji: None,
+            text: None,
+            until: None,
+        }
+    }
+}
+
+impl Presence {
+    pub fn set_status(&mut self, status: IdentityStatus) {
+        self.status = status;
+    }
+
+    pub fn set_emoji(&mut self, emoji: Option<String>) {
+        self.emoji = emoji;
+    }
+
+    pub fn set_text(&mut self, text: Option<String>) {
+        self.text = text;
+    }
+
+    pub fn set_until(&mut self, until: Option<DateTime<Utc>>) {
+        self.until = until;
+    }
+}
+
+impl Presence {
+    pub fn status(&self) -> IdentityStatus {
+        self.status
+    }
+
+    pub fn emoji(&self) -> Option<&str> {
+        self.emoji.as_deref()
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    pub fn until(&self) -> Option<DateTime<Utc>> {
+        self.until
+    }
+
+    /// Check if `until` has passed
+    pub fn is_expired(&self) -> bool {
+        self.until.is_some_and(|until| until <= Utc::now())
+    }
+}
+
+/// Field of an identity that was changed in an update. No fields are given when the identity is first seen
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentityField {
+    Username,
+    StatusMessage,
+    Picture,
+    Banner,
+    Platform,
+    Status,
+    Presence,
+    Metadata,
+    Devices,
+    Verified,
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, Display)]
 #[serde(rename_all = "lowercase")]
 #[repr(C)]
@@ -113,6 +197,14 @@ pub struct Relationship {
 pub struct FriendRequest {
     identity: DID,
     date: DateTime<Utc>,
+
+    /// Intro message sent along with the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+
+    /// Time after which the request is closed if it has not been answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<DateTime<Utc>>,
 }
 
 impl FriendRequest {
@@ -120,8 +212,18 @@ impl FriendRequest {
         Self {
             identity,
             date: date.unwrap_or_else(Utc::now),
+            message: None,
+            expires: None,
         }
     }
+
+    pub fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+
+    pub fn set_expires(&mut self, expires: Option<DateTime<Utc>>) {
+        self.expires = expires;
+    }
 }
 
 impl FriendRequest {
@@ -132,6 +234,208 @@ impl FriendRequest {
     pub fn identity(&self) -> &DID {
         &self.identity
     }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    pub fn expires(&self) -> Option<DateTime<Utc>> {
+        self.expires
+    }
+}
+
+/// Device that has been authorized to act on behalf of an identity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Device {
+    did: DID,
+    name: Option<String>,
+    authorized: DateTime<Utc>,
+}
+
+impl Device {
+    pub fn new(did: DID, name: Option<String>, authorized: DateTime<Utc>) -> Self {
+        Self {
+            did,
+            name,
+            authorized,
+        }
+    }
+}
+
+impl Device {
+    /// Key of the device, which is separate from the key of the identity
+    pub fn did(&self) -> &DID {
+        &self.did
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn authorized(&self) -> DateTime<Utc> {
+        self.authorized
+    }
+}
+
+/// Statement declaring the key of an identity as compromised, signed by the key itself so
+/// it could be created ahead of time and published even after the key is lost
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RevocationCertificate {
+    did: DID,
+    created: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    signature: Vec<u8>,
+}
+
+impl RevocationCertificate {
+    pub fn new(did: DID, reason: Option<String>) -> Self {
+        Self {
+            did,
+            created: Utc::now(),
+            reason,
+            signature: Vec::new(),
+        }
+    }
+
+    pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
+        self.signature = signature;
+        self
+    }
+}
+
+impl RevocationCertificate {
+    /// Key that is being revoked
+    pub fn did(&self) -> &DID {
+        &self.did
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    pub fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+}
+
+/// Request from a device to receive the recovery share held for an identity
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryRequest {
+    did: DID,
+    device: DID,
+    date: DateTime<Utc>,
+}
+
+impl RecoveryRequest {
+    pub fn new(did: DID, device: DID, date: DateTime<Utc>) -> Self {
+        Self { did, device, date }
+    }
+}
+
+impl RecoveryRequest {
+    /// Identity being recovered
+    pub fn did(&self) -> &DID {
+        &self.did
+    }
+
+    /// Temporary key of the device requesting the share
+    pub fn device(&self) -> &DID {
+        &self.device
+    }
+
+    pub fn date(&self) -> DateTime<Utc> {
+        self.date
+    }
+}
+
+/// Private record kept about a friend
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Contact {
+    did: DID,
+
+    /// Name given to the friend, shown in place of their username
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nickname: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+
+    /// Named groups the friend belongs to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<String>,
+
+    modified: DateTime<Utc>,
+}
+
+impl Contact {
+    pub fn new(did: DID) -> Self {
+        Self {
+            did,
+            nickname: None,
+            notes: None,
+            groups: vec![],
+            modified: Utc::now(),
+        }
+    }
+}
+
+impl Contact {
+    pub fn set_nickname(&mut self, nickname: Option<String>) {
+        self.nickname = nickname;
+    }
+
+    pub fn set_notes(&mut self, notes: Option<String>) {
+        self.notes = notes;
+    }
+
+    pub fn set_groups(&mut self, groups: Vec<String>) {
+        self.groups = groups;
+    }
+
+    pub fn set_modified(&mut self, time: DateTime<Utc>) {
+        self.modified = time;
+    }
+}
+
+impl Contact {
+    pub fn did(&self) -> &DID {
+        &self.did
+    }
+
+    pub fn nickname(&self) -> Option<&str> {
+        self.nickname.as_deref()
+    }
+
+    pub fn notes(&self) -> Option<&str> {
+        self.notes.as_deref()
+    }
+
+    pub fn groups(&self) -> &[String] {
+        &self.groups
+    }
+
+    pub fn modified(&self) -> DateTime<Utc> {
+        self.modified
+    }
+
+    /// Check if the record holds any information
+    pub fn is_empty(&self) -> bool {
+        self.nickname.is_none() && self.notes.is_none() && self.groups.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContactUpdate {
+    Nickname(Option<String>),
+    Notes(Option<String>),
+    AddGroup(String),
+    RemoveGroup(String),
 }
 
 impl Relationship {
@@ -235,6 +539,10 @@ pub struct Identity {
 
     /// Metadata
     metadata: IndexMap<String, String>,
+
+    /// Username has been claimed in a username registry trusted by the node
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    verified: bool,
 }
 
 impl core::hash::Hash for Identity {
@@ -271,6 +579,10 @@ impl Identity {
     pub fn set_metadata(&mut self, map: IndexMap<String, String>) {
         self.metadata = map;
     }
+
+    pub fn set_verified(&mut self, verified: bool) {
+        self.verified = verified;
+    }
 }
 
 impl Identity {
@@ -301,6 +613,10 @@ impl Identity {
     pub fn metadata(&self) -> &IndexMap<String, String> {
         &self.metadata
     }
+
+    pub fn verified(&self) -> bool {
+        self.verified
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -17,7 +17,10 @@ use crate::multipass::identity::{FriendRequest, Identifier, IdentityUpdate};
 use crate::tesseract::Tesseract;
 use crate::{Extension, SingleHandle};
 
-use self::identity::{IdentityImage, IdentityProfile, IdentityStatus, Platform, Relationship};
+use self::identity::{
+    Contact, ContactUpdate, Device, IdentityField, IdentityImage, IdentityProfile, IdentityStatus,
+    Platform, Presence, RecoveryRequest, Relationship, RevocationCertificate,
+};
 
 pub mod generator;
 pub mod identity;
@@ -26,21 +29,78 @@ pub mod identity;
 #[serde(rename_all = "snake_case")]
 #[allow(clippy::large_enum_variant)]
 pub enum MultiPassEventKind {
-    FriendRequestReceived { from: DID, date: DateTime<Utc> },
-    FriendRequestSent { to: DID, date: DateTime<Utc> },
-    IncomingFriendRequestRejected { did: DID },
-    OutgoingFriendRequestRejected { did: DID },
-    IncomingFriendRequestClosed { did: DID },
-    OutgoingFriendRequestClosed { did: DID },
-    FriendAdded { did: DID },
-    FriendRemoved { did: DID },
-    IdentityOnline { did: DID },
-    IdentityOffline { did: DID },
-    IdentityUpdate { did: DID },
-    Blocked { did: DID },
-    BlockedBy { did: DID },
-    Unblocked { did: DID },
-    UnblockedBy { did: DID },
+    FriendRequestReceived {
+        from: DID,
+        date: DateTime<Utc>,
+        message: Option<String>,
+    },
+    FriendRequestSent {
+        to: DID,
+        date: DateTime<Utc>,
+    },
+    IncomingFriendRequestRejected {
+        did: DID,
+    },
+    OutgoingFriendRequestRejected {
+        did: DID,
+    },
+    IncomingFriendRequestClosed {
+        did: DID,
+    },
+    OutgoingFriendRequestClosed {
+        did: DID,
+    },
+    FriendAdded {
+        did: DID,
+    },
+    FriendRemoved {
+        did: DID,
+    },
+    IdentityOnline {
+        did: DID,
+    },
+    IdentityOffline {
+        did: DID,
+    },
+    IdentityUpdate {
+        did: DID,
+        fields: Vec<IdentityField>,
+    },
+    Blocked {
+        did: DID,
+    },
+    BlockedBy {
+        did: DID,
+    },
+    Unblocked {
+        did: DID,
+    },
+    UnblockedBy {
+        did: DID,
+    },
+    DeviceAuthorized {
+        did: DID,
+    },
+    DeviceRevoked {
+        did: DID,
+    },
+    IdentityKeyRotated {
+        old: DID,
+        new: DID,
+    },
+    RecoveryShareReceived {
+        did: DID,
+    },
+    RecoveryShareRequested {
+        did: DID,
+        device: DID,
+    },
+    RecoveryShareApproved {
+        did: DID,
+    },
+    ContactUpdated {
+        did: DID,
+    },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -64,6 +124,100 @@ pub enum IdentityImportOption<'a> {
         /// Passphrase of the identity
         passphrase: String,
     },
+    /// Recover the identity from the shares held by friends
+    Recover {
+        /// Identity to recover
+        did: DID,
+
+        /// Friends holding a share of the recovery secret
+        friends: Vec<DID>,
+    },
+}
+
+/// Optional section of an export bundle. The identity itself is always included
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportSection {
+    /// Friends, blocked identities, pending requests, contacts and recovery shares held for friends
+    Friends,
+    /// Keys of group conversations
+    ConversationKeys,
+    /// Index of the files stored in Constellation
+    FileIndex,
+    /// Conversations along with their message history
+    Messages,
+}
+
+impl ExportSection {
+    /// Every section that could be included in a bundle
+    pub fn all() -> Vec<ExportSection> {
+        vec![
+            ExportSection::Friends,
+            ExportSection::ConversationKeys,
+            ExportSection::FileIndex,
+            ExportSection::Messages,
+        ]
+    }
+}
+
+/// Describes the contents of an export bundle
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportManifest {
+    version: u8,
+    did: DID,
+    created: DateTime<Utc>,
+    sections: Vec<ExportSection>,
+}
+
+impl ExportManifest {
+    pub fn new(
+        version: u8,
+        did: DID,
+        created: DateTime<Utc>,
+        sections: Vec<ExportSection>,
+    ) -> Self {
+        Self {
+            version,
+            did,
+            created,
+            sections,
+        }
+    }
+}
+
+impl ExportManifest {
+    /// Version of the bundle format
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Identity the bundle was exported from
+    pub fn did(&self) -> &DID {
+        &self.did
+    }
+
+    pub fn created(&self) -> DateTime<Utc> {
+        self.created
+    }
+
+    /// Sections included in the bundle
+    pub fn sections(&self) -> &[ExportSection] {
+        &self.sections
+    }
+
+    pub fn contains(&self, section: ExportSection) -> bool {
+        self.sections.contains(&section)
+    }
+}
+
+/// How an export bundle is imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BundleImportMode {
+    /// Import the bundle as a new identity. Fails if an identity is already loaded
+    #[default]
+    Replace,
+    /// Merge the sections of the bundle into the loaded identity, keeping anything that already exists
+    Merge,
 }
 
 pub type MultiPassEventStream = BoxStream<'static, MultiPassEventKind>;
@@ -106,6 +260,52 @@ pub trait LocalIdentity: Sync + Send {
     async fn update_identity(&mut self, option: IdentityUpdate) -> Result<(), Error>;
 
     fn tesseract(&self) -> Tesseract;
+
+    /// Authorize a device, identified by its own key, to act on behalf of the identity
+    async fn authorize_device(&mut self, _: &DID, _: Option<&str>) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Revoke the authorization of a device
+    async fn revoke_device(&mut self, _: &DID) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List of devices that are authorized to act on behalf of the identity
+    async fn list_devices(&self) -> Result<Vec<Device>, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Replace the key of the identity with a key derived from a new phrase, publishing a statement signed by both
+    /// keys so friends could migrate to the new key. Returns the identity along with the new phrase, which replaces
+    /// the phrase of the previous key.
+    ///
+    /// Note: Recovery shares held by friends are dropped once they migrate, so social recovery has to be set up again
+    ///       and a verified username has to be claimed again, which the registry hands over to the new key
+    async fn rotate_identity_key(&mut self) -> Result<IdentityProfile, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Create a certificate that revokes the current key of the identity
+    async fn create_revocation_certificate(
+        &self,
+        _: Option<&str>,
+    ) -> Result<RevocationCertificate, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Publish a previously created revocation certificate
+    async fn publish_revocation_certificate(
+        &mut self,
+        _: &RevocationCertificate,
+    ) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Claim the current username in a username registry so it could be shown as verified
+    async fn claim_username(&mut self) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
 }
 
 #[async_trait::async_trait]
@@ -122,6 +322,34 @@ pub trait MultiPassImportExport: Sync + Send {
     async fn export_identity<'a>(&mut self, _: ImportLocation<'a>) -> Result<(), Error> {
         Err(Error::Unimplemented)
     }
+
+    /// Export a signed bundle of the identity, along with the selected sections, to a specific location
+    async fn export_bundle<'a>(
+        &mut self,
+        _: ImportLocation<'a>,
+        _: &[ExportSection],
+    ) -> Result<ExportManifest, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Decrypt and verify a bundle without importing it, returning its manifest
+    async fn verify_bundle<'a>(
+        &self,
+        _: ImportLocation<'a>,
+        _: &str,
+    ) -> Result<ExportManifest, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Import a bundle using the passphrase of the identity it was exported from
+    async fn import_bundle<'a>(
+        &mut self,
+        _: ImportLocation<'a>,
+        _: &str,
+        _: BundleImportMode,
+    ) -> Result<Identity, Error> {
+        Err(Error::Unimplemented)
+    }
 }
 
 #[async_trait::async_trait]
@@ -131,6 +359,17 @@ pub trait Friends: Sync + Send {
         Err(Error::Unimplemented)
     }
 
+    /// Send friend request to corresponding public key along with an intro message, which is closed
+    /// if it has not been answered by the time it expires
+    async fn send_request_with(
+        &mut self,
+        _: &DID,
+        _: Option<String>,
+        _: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
     /// Accept friend request from public key
     async fn accept_request(&mut self, _: &DID) -> Result<(), Error> {
         Err(Error::Unimplemented)
@@ -200,6 +439,52 @@ pub trait Friends: Sync + Send {
     async fn has_friend(&self, _: &DID) -> Result<bool, Error> {
         Err(Error::Unimplemented)
     }
+
+    /// Split a recovery secret between friends, of which `threshold` would be needed to recover the identity
+    async fn setup_social_recovery(&mut self, _: &[DID], _: u8) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List the identities in which a recovery share is held
+    async fn list_recovery_shares(&self) -> Result<Vec<DID>, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List the requests for recovery shares that are pending approval
+    async fn list_recovery_requests(&self) -> Result<Vec<RecoveryRequest>, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Send the recovery share held for the identity to the requesting device, which should be confirmed
+    /// out of band beforehand since any peer could request a share
+    async fn approve_recovery_request(&mut self, _: &DID, _: &DID) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Dismiss the request for the recovery share held for the identity made by the device
+    async fn deny_recovery_request(&mut self, _: &DID, _: &DID) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List the contact records kept for friends
+    async fn list_contacts(&self) -> Result<Vec<Contact>, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Contact record kept for a friend
+    async fn get_contact(&self, _: &DID) -> Result<Contact, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Update the contact record kept for a friend
+    async fn update_contact(&mut self, _: &DID, _: ContactUpdate) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// List the names of the contact groups in use
+    async fn list_contact_groups(&self) -> Result<Vec<String>, Error> {
+        Err(Error::Unimplemented)
+    }
 }
 
 #[async_trait::async_trait]
@@ -232,6 +517,16 @@ pub trait IdentityInformation: Send + Sync {
         Err(Error::Unimplemented)
     }
 
+    /// Presence of the identity, including its custom status if it has not expired
+    async fn identity_presence(&self, _: &DID) -> Result<Presence, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Set the presence of own identity, replacing the status and any previous custom status
+    async fn set_identity_presence(&mut self, _: Presence) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
     /// Find the relationship with an existing identity.
     async fn identity_relationship(&self, _: &DID) -> Result<Relationship, Error> {
         Err(Error::Unimplemented)
@@ -1425,6 +1425,19 @@ pub trait RayGunAttachment: Sync + Send {
     ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
         Err(Error::Unimplemented)
     }
+
+    /// Stream the variant of an attached image best suited to display it at `size` pixels along
+    /// its longest side, falling back to the original when there is no such variant
+    /// Note: Must use the filename associated when downloading
+    async fn download_variant_stream(
+        &self,
+        _: Uuid,
+        _: Uuid,
+        _: &str,
+        _: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        Err(Error::Unimplemented)
+    }
 }
 
 #[async_trait::async_trait]
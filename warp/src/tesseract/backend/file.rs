@@ -0,0 +1,266 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::{Result, TesseractBackend};
+
+/// Number of previous versions of the file that are kept by default
+const DEFAULT_GENERATIONS: usize = 2;
+
+/// Backend storing the keystore in a file.
+///
+/// Writes go to a temporary file that replaces the keystore once it is fully written,
+/// so a failed or interrupted write leaves the previous keystore intact. Previous versions
+/// are kept as backups next to the file (e.g. `tesseract.bin.1`, `tesseract.bin.2`).
+#[derive(Debug, Clone)]
+pub struct FileBackend {
+    path: PathBuf,
+    generations: usize,
+    #[cfg(test)]
+    fail_after: Option<usize>,
+}
+
+impl FileBackend {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self::with_generations(path, DEFAULT_GENERATIONS)
+    }
+
+    /// Keep `generations` previous versions of the keystore. Zero disables backups
+    pub fn with_generations<P: AsRef<Path>>(path: P, generations: usize) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            generations,
+            #[cfg(test)]
+            fail_after: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn generations(&self) -> usize {
+        self.generations
+    }
+
+    /// Path to the backup of the given generation, with 1 being the most recent
+    pub fn backup_path(&self, generation: usize) -> PathBuf {
+        self.path_with_extension(&generation.to_string())
+    }
+
+    /// Load the backup of the given generation
+    pub fn load_backup(&self, generation: usize) -> Result<Option<Vec<u8>>> {
+        read(&self.backup_path(generation))
+    }
+
+    fn temp_path(&self) -> PathBuf {
+        self.path_with_extension("tmp")
+    }
+
+    fn path_with_extension(&self, extension: &str) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".");
+        path.push(extension);
+        PathBuf::from(path)
+    }
+
+    fn write_temp(&self, temp: &Path, data: &[u8]) -> Result<()> {
+        let mut fs = std::fs::File::create(temp)?;
+
+        #[cfg(test)]
+        if let Some(limit) = self.fail_after {
+            fs.write_all(&data[..limit.min(data.len())])?;
+            return Err(std::io::Error::other("simulated failure").into());
+        }
+
+        fs.write_all(data)?;
+        fs.sync_all()?;
+        Ok(())
+    }
+
+    fn rotate_backups(&self) -> Result<()> {
+        if self.generations == 0 || !self.path.is_file() {
+            return Ok(());
+        }
+
+        for generation in (1..self.generations).rev() {
+            let backup = self.backup_path(generation);
+            if backup.is_file() {
+                std::fs::rename(&backup, self.backup_path(generation + 1))?;
+            }
+        }
+
+        std::fs::copy(&self.path, self.backup_path(1))?;
+        Ok(())
+    }
+}
+
+fn read(path: &Path) -> Result<Option<Vec<u8>>> {
+    match std::fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn remove(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Flush the rename of the file to disk
+fn sync_parent(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+impl TesseractBackend for FileBackend {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        read(&self.path)
+    }
+
+    // Note: Saving an unchanged keystore, such as when it is locked, is skipped so it does not push the
+    //       previous generations out of the backups
+    fn store(&self, data: &[u8]) -> Result<()> {
+        if read(&self.path)?.is_some_and(|current| current == data) {
+            return Ok(());
+        }
+
+        let temp = self.temp_path();
+
+        let result = self
+            .write_temp(&temp, data)
+            .and_then(|_| self.rotate_backups())
+            .and_then(|_| std::fs::rename(&temp, &self.path).map_err(Into::into));
+
+        if let Err(e) = result {
+            let _ = std::fs::remove_file(&temp);
+            return Err(e);
+        }
+
+        sync_parent(&self.path)
+    }
+
+    fn clear(&self) -> Result<()> {
+        remove(&self.path)
+    }
+
+    fn clear_backups(&self) -> Result<()> {
+        for generation in 1..=self.generations {
+            remove(&self.backup_path(generation))?;
+        }
+        sync_parent(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tesseract::backend::{FileBackend, TesseractBackend};
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("tesseract-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).expect("temp dir created");
+        dir
+    }
+
+    #[test]
+    fn keeps_backup_generations() -> anyhow::Result<()> {
+        let dir = temp_dir();
+        let backend = FileBackend::with_generations(dir.join("tesseract.bin"), 2);
+
+        backend.store(b"first")?;
+        assert!(backend.load_backup(1)?.is_none());
+
+        backend.store(b"second")?;
+        backend.store(b"third")?;
+
+        assert_eq!(backend.load()?.as_deref(), Some(&b"third"[..]));
+        assert_eq!(backend.load_backup(1)?.as_deref(), Some(&b"second"[..]));
+        assert_eq!(backend.load_backup(2)?.as_deref(), Some(&b"first"[..]));
+        assert!(!backend.backup_path(3).exists());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn unchanged_store_keeps_backups() -> anyhow::Result<()> {
+        let dir = temp_dir();
+        let backend = FileBackend::with_generations(dir.join("tesseract.bin"), 2);
+
+        backend.store(b"first")?;
+        backend.store(b"second")?;
+        backend.store(b"second")?;
+
+        assert_eq!(backend.load_backup(1)?.as_deref(), Some(&b"first"[..]));
+        assert!(backend.load_backup(2)?.is_none());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn clear_backups_keeps_file() -> anyhow::Result<()> {
+        let dir = temp_dir();
+        let backend = FileBackend::with_generations(dir.join("tesseract.bin"), 2);
+
+        backend.store(b"first")?;
+        backend.store(b"second")?;
+        backend.store(b"third")?;
+        backend.clear_backups()?;
+
+        assert_eq!(backend.load()?.as_deref(), Some(&b"third"[..]));
+        assert!(backend.load_backup(1)?.is_none());
+        assert!(backend.load_backup(2)?.is_none());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn failed_write_keeps_previous_file() -> anyhow::Result<()> {
+        let dir = temp_dir();
+        let mut backend = FileBackend::new(dir.join("tesseract.bin"));
+
+        backend.store(b"keystore")?;
+
+        backend.fail_after = Some(4);
+        assert!(backend.store(b"new keystore").is_err());
+
+        assert_eq!(backend.load()?.as_deref(), Some(&b"keystore"[..]));
+        assert!(!backend.temp_path().exists());
+        assert!(backend.load_backup(1)?.is_none());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn interrupted_write_is_ignored() -> anyhow::Result<()> {
+        let dir = temp_dir();
+        let backend = FileBackend::new(dir.join("tesseract.bin"));
+
+        backend.store(b"keystore")?;
+
+        // Temporary file left behind by a write that never completed
+        std::fs::write(backend.temp_path(), b"new key")?;
+        assert_eq!(backend.load()?.as_deref(), Some(&b"keystore"[..]));
+
+        backend.store(b"new keystore")?;
+        assert_eq!(backend.load()?.as_deref(), Some(&b"new keystore"[..]));
+        assert!(!backend.temp_path().exists());
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use futures::{
+    channel::{mpsc, oneshot},
+    StreamExt,
+};
+use js_sys::Uint8Array;
+use parking_lot::{Mutex, RwLock};
+use rexie::{ObjectStore, Rexie, TransactionMode};
+use wasm_bindgen::JsValue;
+
+use super::{Result, TesseractBackend};
+use crate::error::Error;
+
+const STORE: &str = "tesseract";
+const KEY: &str = "keystore";
+
+enum Command {
+    Write(Option<Vec<u8>>),
+    Flush(oneshot::Sender<Result<()>>),
+}
+
+/// Backend storing the keystore in IndexedDB.
+///
+/// IndexedDB can only be accessed asynchronously, so the keystore is read when the backend is
+/// opened and writes are applied in order in the background. A write that failed is returned by
+/// the next call to `store`, `clear` or [`IndexedDbBackend::flush`].
+pub struct IndexedDbBackend {
+    sender: mpsc::UnboundedSender<Command>,
+    cache: Arc<RwLock<Option<Vec<u8>>>>,
+    error: Arc<Mutex<Option<Error>>>,
+}
+
+impl IndexedDbBackend {
+    /// Open the database, creating it if it does not exist
+    pub async fn open(name: &str) -> Result<Self> {
+        let database = Rexie::builder(name)
+            .version(1)
+            .add_object_store(ObjectStore::new(STORE))
+            .build()
+            .await
+            .map_err(map_error)?;
+
+        let transaction = database
+            .transaction(&[STORE], TransactionMode::ReadOnly)
+            .map_err(map_error)?;
+        let store = transaction.store(STORE).map_err(map_error)?;
+        let data = store
+            .get(JsValue::from_str(KEY))
+            .await
+            .map_err(map_error)?
+            .map(|value| Uint8Array::new(&value).to_vec());
+        transaction.done().await.map_err(map_error)?;
+
+        let (sender, mut receiver) = mpsc::unbounded();
+        let error = Arc::new(Mutex::new(None));
+
+        wasm_bindgen_futures::spawn_local({
+            let error = error.clone();
+            async move {
+                while let Some(command) = receiver.next().await {
+                    match command {
+                        Command::Write(data) => {
+                            if let Err(e) = write(&database, data).await {
+                                tracing::warn!("Unable to write tesseract to indexeddb: {e}");
+                                error.lock().replace(e);
+                            }
+                        }
+                        Command::Flush(response) => {
+                            let result = error.lock().take().map_or(Ok(()), Err);
+                            _ = response.send(result);
+                        }
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            sender,
+            cache: Arc::new(RwLock::new(data)),
+            error,
+        })
+    }
+
+    /// Wait for the pending writes to be applied, returning the error of a write that failed
+    pub async fn flush(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.sender
+            .unbounded_send(Command::Flush(tx))
+            .map_err(|_| Error::Any(anyhow::anyhow!("indexeddb backend is closed")))?;
+        rx.await
+            .map_err(|_| Error::Any(anyhow::anyhow!("indexeddb backend is closed")))?
+    }
+
+    fn write(&self, data: Option<Vec<u8>>) -> Result<()> {
+        if let Some(e) = self.error.lock().take() {
+            return Err(e);
+        }
+
+        self.sender
+            .unbounded_send(Command::Write(data))
+            .map_err(|_| Error::Any(anyhow::anyhow!("indexeddb backend is closed")))
+    }
+}
+
+async fn write(database: &Rexie, data: Option<Vec<u8>>) -> Result<()> {
+    let transaction = database
+        .transaction(&[STORE], TransactionMode::ReadWrite)
+        .map_err(map_error)?;
+    let store = transaction.store(STORE).map_err(map_error)?;
+    let key = JsValue::from_str(KEY);
+    match data {
+        Some(data) => {
+            let value: JsValue = Uint8Array::from(data.as_slice()).into();
+            store.put(&value, Some(&key)).await.map_err(map_error)?;
+        }
+        None => store.delete(key).await.map_err(map_error)?,
+    }
+    transaction.done().await.map_err(map_error)?;
+    Ok(())
+}
+
+fn map_error(e: rexie::Error) -> Error {
+    Error::Any(anyhow::anyhow!("{e}"))
+}
+
+impl TesseractBackend for IndexedDbBackend {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        Ok(self.cache.read().clone())
+    }
+
+    // Note: The cache is only updated once the write was queued, so a failed write that is
+    //       returned here leaves the cache as it was
+    fn store(&self, data: &[u8]) -> Result<()> {
+        self.write(Some(data.to_vec()))?;
+        *self.cache.write() = Some(data.to_vec());
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.write(None)?;
+        self.cache.write().take();
+        Ok(())
+    }
+}
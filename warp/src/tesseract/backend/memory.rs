@@ -0,0 +1,71 @@
+use std::sync::Arc;
+
+use parking_lot::RwLock;
+use zeroize::Zeroizing;
+
+use super::{Result, TesseractBackend};
+use crate::crypto::{cipher::Cipher, generate};
+
+/// Backend holding the keystore in memory, sealed with a key that only lives as long as the backend.
+///
+/// Clones share the same storage.
+#[derive(Clone)]
+pub struct MemoryBackend {
+    key: Arc<Zeroizing<[u8; 32]>>,
+    sealed: Arc<RwLock<Option<Vec<u8>>>>,
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self {
+            key: Arc::new(Zeroizing::new(generate::<32>())),
+            sealed: Default::default(),
+        }
+    }
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TesseractBackend for MemoryBackend {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        self.sealed
+            .read()
+            .as_deref()
+            .map(|data| Cipher::direct_decrypt(data, self.key.as_slice()))
+            .transpose()
+    }
+
+    fn store(&self, data: &[u8]) -> Result<()> {
+        let sealed = Cipher::direct_encrypt(data, self.key.as_slice())?;
+        *self.sealed.write() = Some(sealed);
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        self.sealed.write().take();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::tesseract::backend::{MemoryBackend, TesseractBackend};
+
+    #[test]
+    fn sealed_roundtrip() -> anyhow::Result<()> {
+        let backend = MemoryBackend::new();
+        assert!(backend.load()?.is_none());
+
+        backend.store(b"keystore")?;
+        assert_ne!(backend.sealed.read().as_deref(), Some(&b"keystore"[..]));
+        assert_eq!(backend.load()?.as_deref(), Some(&b"keystore"[..]));
+
+        backend.clear()?;
+        assert!(backend.load()?.is_none());
+        Ok(())
+    }
+}
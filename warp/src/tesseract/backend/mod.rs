@@ -0,0 +1,40 @@
+//! Storage backends the keystore can be persisted to
+#[cfg(not(target_arch = "wasm32"))]
+mod file;
+#[cfg(target_arch = "wasm32")]
+mod indexeddb;
+mod memory;
+#[cfg(all(target_os = "linux", feature = "secret-service"))]
+mod secret_service;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use self::file::FileBackend;
+#[cfg(target_arch = "wasm32")]
+pub use self::indexeddb::IndexedDbBackend;
+pub use self::memory::MemoryBackend;
+#[cfg(all(target_os = "linux", feature = "secret-service"))]
+pub use self::secret_service::SecretServiceBackend;
+
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Storage that holds the serialized keystore.
+///
+/// Entries are encrypted before they are handed to the backend, so a backend only
+/// has to persist the data as is.
+pub trait TesseractBackend: Send + Sync {
+    /// Load the stored keystore, or `None` if nothing has been stored yet
+    fn load(&self) -> Result<Option<Vec<u8>>>;
+
+    /// Persist the keystore, replacing what was previously stored
+    fn store(&self, data: &[u8]) -> Result<()>;
+
+    /// Remove the stored keystore
+    fn clear(&self) -> Result<()>;
+
+    /// Remove previous versions of the keystore kept by the backend, if any
+    fn clear_backups(&self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+//! Backend storing the keystore through the freedesktop Secret Service API, as provided by
+//! GNOME Keyring, KWallet or KeePassXC. Requires the `secret-service` feature.
+use std::collections::HashMap;
+
+use zbus::blocking::{Connection, Proxy};
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+use super::{Result, TesseractBackend};
+use crate::error::Error;
+
+const SERVICE: &str = "org.freedesktop.secrets";
+const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+const SERVICE_INTERFACE: &str = "org.freedesktop.Secret.Service";
+const DEFAULT_COLLECTION: &str = "/org/freedesktop/secrets/aliases/default";
+const COLLECTION_INTERFACE: &str = "org.freedesktop.Secret.Collection";
+const ITEM_INTERFACE: &str = "org.freedesktop.Secret.Item";
+const CONTENT_TYPE: &str = "application/json";
+
+/// Path returned in place of a prompt when none is needed
+const NO_PROMPT: &str = "/";
+
+/// Secret as transferred by the service: session, parameters, value and content type
+type Secret = (OwnedObjectPath, Vec<u8>, Vec<u8>, String);
+
+/// Backend storing the keystore as an item in the default collection of the Secret Service
+pub struct SecretServiceBackend {
+    connection: Connection,
+    session: OwnedObjectPath,
+    application: String,
+}
+
+impl SecretServiceBackend {
+    /// Connect to the Secret Service on the session bus. `application` is used to tell apart
+    /// keystores of different applications
+    pub fn connect(application: &str) -> Result<Self> {
+        let connection = Connection::session().map_err(map_error)?;
+        Self::with_connection(connection, application)
+    }
+
+    /// Use an existing connection to the Secret Service
+    pub fn with_connection(connection: Connection, application: &str) -> Result<Self> {
+        let service =
+            Proxy::new(&connection, SERVICE, SERVICE_PATH, SERVICE_INTERFACE).map_err(map_error)?;
+
+        // Note: The secret is transferred without additional encryption, which is fine since
+        //       the entries of the keystore are already encrypted
+        let (_, session): (OwnedValue, OwnedObjectPath) = service
+            .call("OpenSession", &("plain", Value::from("")))
+            .map_err(map_error)?;
+
+        Ok(Self {
+            connection,
+            session,
+            application: application.to_string(),
+        })
+    }
+
+    fn attributes(&self) -> HashMap<&str, &str> {
+        HashMap::from([
+            ("application", self.application.as_str()),
+            ("type", "tesseract"),
+        ])
+    }
+
+    fn items(&self) -> Result<Vec<OwnedObjectPath>> {
+        let service = Proxy::new(&self.connection, SERVICE, SERVICE_PATH, SERVICE_INTERFACE)
+            .map_err(map_error)?;
+
+        let (mut unlocked, locked): (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) = service
+            .call("SearchItems", &(self.attributes(),))
+            .map_err(map_error)?;
+
+        if !locked.is_empty() {
+            let (items, prompt): (Vec<OwnedObjectPath>, OwnedObjectPath) =
+                service.call("Unlock", &(locked,)).map_err(map_error)?;
+
+            // Prompting the user is left to the keyring itself
+            if prompt.as_str() != NO_PROMPT {
+                return Err(Error::TesseractBackendLocked);
+            }
+
+            unlocked.extend(items);
+        }
+
+        Ok(unlocked)
+    }
+
+    fn item(&self, path: &OwnedObjectPath) -> Result<Proxy<'_>> {
+        Proxy::new(&self.connection, SERVICE, path.as_str(), ITEM_INTERFACE).map_err(map_error)
+    }
+}
+
+fn map_error(e: zbus::Error) -> Error {
+    Error::Boxed(Box::new(e))
+}
+
+impl TesseractBackend for SecretServiceBackend {
+    fn load(&self) -> Result<Option<Vec<u8>>> {
+        let Some(path) = self.items()?.into_iter().next() else {
+            return Ok(None);
+        };
+
+        let (_, _, value, _): Secret = self
+            .item(&path)?
+            .call("GetSecret", &(&self.session,))
+            .map_err(map_error)?;
+
+        Ok(Some(value))
+    }
+
+    fn store(&self, data: &[u8]) -> Result<()> {
+        let collection = Proxy::new(
+            &self.connection,
+            SERVICE,
+            DEFAULT_COLLECTION,
+            COLLECTION_INTERFACE,
+        )
+        .map_err(map_error)?;
+
+        let label = format!("Tesseract keystore for {}", self.application);
+
+        let properties = HashMap::from([
+            ("org.freedesktop.Secret.Item.Label", Value::from(label)),
+            (
+                "org.freedesktop.Secret.Item.Attributes",
+                Value::from(self.attributes()),
+            ),
+        ]);
+
+        let secret: Secret = (
+            self.session.clone(),
+            vec![],
+            data.to_vec(),
+            CONTENT_TYPE.to_string(),
+        );
+
+        let (_, prompt): (OwnedObjectPath, OwnedObjectPath) = collection
+            .call("CreateItem", &(properties, secret, true))
+            .map_err(map_error)?;
+
+        if prompt.as_str() != NO_PROMPT {
+            return Err(Error::TesseractBackendLocked);
+        }
+
+        Ok(())
+    }
+
+    fn clear(&self) -> Result<()> {
+        for path in self.items()? {
+            let _: OwnedObjectPath = self.item(&path)?.call("Delete", &()).map_err(map_error)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+    use std::sync::Arc;
+
+    use parking_lot::Mutex;
+    use zbus::blocking::{Connection, ConnectionBuilder};
+    use zbus::dbus_interface;
+    use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+
+    use super::{Secret, DEFAULT_COLLECTION, NO_PROMPT, SERVICE_PATH};
+    use crate::tesseract::backend::{SecretServiceBackend, TesseractBackend};
+    use crate::tesseract::Tesseract;
+
+    const MAX_ITEMS: usize = 4;
+
+    type Items = Arc<Mutex<HashMap<usize, (HashMap<String, String>, Vec<u8>)>>>;
+
+    fn item_path(index: usize) -> OwnedObjectPath {
+        OwnedObjectPath::try_from(format!(
+            "/org/freedesktop/secrets/collection/default/{index}"
+        ))
+        .expect("valid path")
+    }
+
+    fn no_prompt() -> OwnedObjectPath {
+        OwnedObjectPath::try_from(NO_PROMPT).expect("valid path")
+    }
+
+    struct MockService {
+        items: Items,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.Secret.Service")]
+    impl MockService {
+        fn open_session(
+            &self,
+            algorithm: &str,
+            _input: OwnedValue,
+        ) -> zbus::fdo::Result<(OwnedValue, OwnedObjectPath)> {
+            if algorithm != "plain" {
+                return Err(zbus::fdo::Error::NotSupported(algorithm.into()));
+            }
+            let session = OwnedObjectPath::try_from("/org/freedesktop/secrets/session/0")
+                .expect("valid path");
+            Ok((Value::from("").into(), session))
+        }
+
+        fn search_items(
+            &self,
+            attributes: HashMap<String, String>,
+        ) -> (Vec<OwnedObjectPath>, Vec<OwnedObjectPath>) {
+            let items = self.items.lock();
+            let mut found = items
+                .iter()
+                .filter(|(_, (item_attributes, _))| {
+                    attributes
+                        .iter()
+                        .all(|(key, value)| item_attributes.get(key) == Some(value))
+                })
+                .map(|(index, _)| *index)
+                .collect::<Vec<_>>();
+            found.sort();
+            (found.into_iter().map(item_path).collect(), vec![])
+        }
+    }
+
+    struct MockCollection {
+        items: Items,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.Secret.Collection")]
+    impl MockCollection {
+        fn create_item(
+            &self,
+            properties: HashMap<String, OwnedValue>,
+            secret: Secret,
+            replace: bool,
+        ) -> zbus::fdo::Result<(OwnedObjectPath, OwnedObjectPath)> {
+            let attributes = properties
+                .get("org.freedesktop.Secret.Item.Attributes")
+                .cloned()
+                .and_then(|value| HashMap::<String, String>::try_from(Value::from(value)).ok())
+                .ok_or_else(|| zbus::fdo::Error::InvalidArgs("missing attributes".into()))?;
+
+            let mut items = self.items.lock();
+
+            let existing = items
+                .iter()
+                .find(|(_, (item_attributes, _))| replace && *item_attributes == attributes)
+                .map(|(index, _)| *index);
+
+            let index = existing
+                .or_else(|| (0..MAX_ITEMS).find(|index| !items.contains_key(index)))
+                .ok_or_else(|| zbus::fdo::Error::LimitsExceeded("collection is full".into()))?;
+
+            items.insert(index, (attributes, secret.2));
+
+            Ok((item_path(index), no_prompt()))
+        }
+    }
+
+    struct MockItem {
+        index: usize,
+        items: Items,
+    }
+
+    #[dbus_interface(name = "org.freedesktop.Secret.Item")]
+    impl MockItem {
+        fn get_secret(&self, session: OwnedObjectPath) -> zbus::fdo::Result<Secret> {
+            let items = self.items.lock();
+            let (_, value) = items
+                .get(&self.index)
+                .ok_or_else(|| zbus::fdo::Error::UnknownObject("item was deleted".into()))?;
+            Ok((session, vec![], value.clone(), "application/json".into()))
+        }
+
+        fn delete(&self) -> OwnedObjectPath {
+            self.items.lock().remove(&self.index);
+            no_prompt()
+        }
+    }
+
+    /// Serve a mock of the Secret Service over a peer-to-peer connection
+    fn mock_service() -> anyhow::Result<(Connection, Connection, Items)> {
+        let (server, client) = UnixStream::pair()?;
+        let items = Items::default();
+
+        let state = items.clone();
+        let server = std::thread::spawn(move || -> zbus::Result<Connection> {
+            let guid = zbus::Guid::generate();
+            let mut builder = ConnectionBuilder::unix_stream(server)
+                .server(&guid)
+                .p2p()
+                .serve_at(
+                    SERVICE_PATH,
+                    MockService {
+                        items: state.clone(),
+                    },
+                )?
+                .serve_at(
+                    DEFAULT_COLLECTION,
+                    MockCollection {
+                        items: state.clone(),
+                    },
+                )?;
+            for index in 0..MAX_ITEMS {
+                builder = builder.serve_at(
+                    item_path(index),
+                    MockItem {
+                        index,
+                        items: state.clone(),
+                    },
+                )?;
+            }
+            builder.build()
+        });
+
+        let client = ConnectionBuilder::unix_stream(client).p2p().build()?;
+        let server = server.join().expect("server thread panicked")?;
+
+        Ok((client, server, items))
+    }
+
+    #[test]
+    fn store_and_load() -> anyhow::Result<()> {
+        let (connection, _server, items) = mock_service()?;
+        let backend = SecretServiceBackend::with_connection(connection, "warp-test")?;
+
+        assert!(backend.load()?.is_none());
+
+        backend.store(b"first")?;
+        backend.store(b"second")?;
+        assert_eq!(items.lock().len(), 1);
+        assert_eq!(backend.load()?.as_deref(), Some(&b"second"[..]));
+
+        backend.clear()?;
+        assert!(backend.load()?.is_none());
+        assert!(items.lock().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn applications_are_kept_apart() -> anyhow::Result<()> {
+        let (connection, _server, _) = mock_service()?;
+        let first = SecretServiceBackend::with_connection(connection.clone(), "first")?;
+        let second = SecretServiceBackend::with_connection(connection, "second")?;
+
+        first.store(b"first")?;
+        assert!(second.load()?.is_none());
+
+        second.store(b"second")?;
+        assert_eq!(first.load()?.as_deref(), Some(&b"first"[..]));
+        assert_eq!(second.load()?.as_deref(), Some(&b"second"[..]));
+        Ok(())
+    }
+
+    #[test]
+    fn tesseract_with_secret_service() -> anyhow::Result<()> {
+        let (connection, _server, _) = mock_service()?;
+
+        let backend = SecretServiceBackend::with_connection(connection.clone(), "warp-test")?;
+        let tesseract = Tesseract::from_backend(backend)?;
+        tesseract.unlock(b"this is a passphrase")?;
+        tesseract.set("API", "MYKEY")?;
+
+        let backend = SecretServiceBackend::with_connection(connection, "warp-test")?;
+        let tesseract = Tesseract::from_backend(backend)?;
+        tesseract.unlock(b"this is a passphrase")?;
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Key derivation used to turn the passphrase of the keystore into an encryption key
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+const KEY_SIZE: usize = 32;
+const SALT_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KdfAlgorithm {
+    Argon2id,
+}
+
+/// Cost parameters of the key derivation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory used in KiB
+    pub memory_cost: u32,
+
+    /// Number of iterations
+    pub time_cost: u32,
+
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        // Recommended minimum for Argon2id by OWASP
+        Self {
+            memory_cost: 19 * 1024,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    pub fn new(memory_cost: u32, time_cost: u32, parallelism: u32) -> Result<Self> {
+        let params = Self {
+            memory_cost,
+            time_cost,
+            parallelism,
+        };
+        params.argon2_params()?;
+        Ok(params)
+    }
+
+    fn argon2_params(&self) -> Result<Params> {
+        Params::new(
+            self.memory_cost,
+            self.time_cost,
+            self.parallelism,
+            Some(KEY_SIZE),
+        )
+        .map_err(|_| Error::InvalidKdfParameters)
+    }
+}
+
+/// Header stored alongside the keystore recording how its key was derived
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Kdf {
+    pub algorithm: KdfAlgorithm,
+    pub salt: Vec<u8>,
+    #[serde(flatten)]
+    pub params: KdfParams,
+}
+
+impl Kdf {
+    /// Create a header with a random salt
+    pub fn new(params: KdfParams) -> Result<Self> {
+        params.argon2_params()?;
+        Ok(Self {
+            algorithm: KdfAlgorithm::Argon2id,
+            salt: crate::crypto::generate::<SALT_SIZE>().to_vec(),
+            params,
+        })
+    }
+
+    /// Derive the encryption key from the passphrase
+    pub fn derive(&self, passphrase: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+        let params = self.params.argon2_params()?;
+        let argon2 = match self.algorithm {
+            KdfAlgorithm::Argon2id => Argon2::new(Algorithm::Argon2id, Version::V0x13, params),
+        };
+        let mut key = Zeroizing::new(vec![0u8; KEY_SIZE]);
+        argon2
+            .hash_password_into(passphrase, &self.salt, &mut key)
+            .map_err(|_| Error::InvalidKdfParameters)?;
+        Ok(key)
+    }
+}
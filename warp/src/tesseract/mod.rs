@@ -6,16 +6,55 @@ use std::io::prelude::*;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+};
 
 use futures::{stream::BoxStream, StreamExt};
 use parking_lot::RwLock;
-use zeroize::Zeroize;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, Zeroizing};
 
-use crate::{crypto::cipher::Cipher, error::Error};
+use crate::{
+    crypto::cipher::{Cipher, CipherSuite},
+    error::Error,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use self::backend::FileBackend;
+pub use self::backend::TesseractBackend;
+use self::kdf::Kdf;
+pub use self::kdf::{KdfAlgorithm, KdfParams};
+
+pub mod backend;
+mod kdf;
 
 type Result<T> = std::result::Result<T, Error>;
 
+/// Version of the format the keystore is stored in
+const TESSERACT_VERSION: u8 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct TesseractDocument {
+    version: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<Kdf>,
+    /// Stores written before the suite was recorded are AES-256-GCM
+    #[serde(default)]
+    suite: CipherSuite,
+    /// Sorted so that saving an unchanged keystore writes the same bytes
+    entries: BTreeMap<String, Vec<u8>>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredTesseract {
+    Versioned(TesseractDocument),
+    /// Entries encrypted with the passphrase directly, prior to the key derivation
+    Legacy(HashMap<String, Vec<u8>>),
+}
+
 /// The key store that holds encrypted strings that can be used for later use.
 #[derive(Clone, Debug)]
 pub struct Tesseract {
@@ -35,8 +74,13 @@ impl Default for Tesseract {
         Tesseract {
             inner: Arc::new(RwLock::new(TesseractInner {
                 internal: Default::default(),
+                kdf: Default::default(),
+                kdf_params: Default::default(),
+                suite: Default::default(),
+                cipher_suite: Default::default(),
                 enc_pass: Default::default(),
                 file: Default::default(),
+                backend: Default::default(),
                 autosave: Default::default(),
                 check: Default::default(),
                 unlock: Default::default(),
@@ -57,6 +101,8 @@ impl Debug for TesseractInner {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("TesseractInner")
             .field("internal", &self.internal)
+            .field("kdf", &self.kdf)
+            .field("suite", &self.suite)
             .field("file", &self.file)
             .field("autosave", &self.autosave)
             .field("unlock", &self.unlock)
@@ -69,6 +115,8 @@ impl PartialEq for TesseractInner {
         self.autosave == other.autosave
             && self.unlock == other.unlock
             && self.internal == other.internal
+            && self.kdf == other.kdf
+            && self.suite == other.suite
             && self.enc_pass == other.enc_pass
     }
 }
@@ -111,7 +159,8 @@ impl Tesseract {
         }
     }
 
-    /// Loads the keystore from a file
+    /// Loads the keystore from a file. If the file is corrupted, the most recent
+    /// backup that can be read is used instead.
     ///
     /// # Example
     ///
@@ -130,11 +179,24 @@ impl Tesseract {
             let inner = &mut *store.inner.write();
             inner.check = true;
             let fs = std::fs::File::open(file)?;
-            let data = serde_json::from_reader(fs)?;
+            let data = match serde_json::from_reader(fs) {
+                Ok(data) => data,
+                Err(e) => {
+                    let backend = FileBackend::new(file);
+                    let data = (1..=backend.generations())
+                        .find_map(|generation| {
+                            let data = backend.load_backup(generation).ok()??;
+                            serde_json::from_slice(&data).ok()
+                        })
+                        .ok_or(e)?;
+                    tracing::warn!("Tesseract is corrupted. Restored from backup");
+                    data
+                }
+            };
+            inner.load(data)?;
             let file = std::fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
             inner.set_file(file);
             inner.set_autosave();
-            inner.internal = data;
         }
         Ok(store)
     }
@@ -156,7 +218,7 @@ impl Tesseract {
             let inner = &mut *store.inner.write();
             inner.check = true;
             let data = serde_json::from_reader(reader)?;
-            inner.internal = data;
+            inner.load(data)?;
         }
         Ok(store)
     }
@@ -348,12 +410,7 @@ impl Tesseract {
         inner.unlock(passphrase)
     }
 
-    /// To save to file using internal file path.
-    /// Note: Because we do not want to interrupt the functions due to it failing to
-    ///       save for whatever reason, this function will return `Result::Ok`
-    ///       regardless of success or error but would eventually log the error.
-    ///
-    /// TODO: Handle error without subjecting function to `Result::Err`
+    /// Save the keystore to its backend or file, returning the error if it could not be saved
     pub fn save(&self) -> Result<()> {
         let inner = &mut *self.inner.write();
         inner.save()
@@ -372,6 +429,90 @@ impl Tesseract {
         Tesseract::default()
     }
 
+    /// Set the cost parameters used when deriving a new key from the passphrase.
+    /// These are applied when the keystore is first unlocked, upgraded or its passphrase is changed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use warp::tesseract::{KdfParams, Tesseract};
+    /// let tesseract = Tesseract::default();
+    /// let params = KdfParams::new(8 * 1024, 1, 1).unwrap();
+    /// tesseract.set_kdf_params(params);
+    /// assert_eq!(tesseract.kdf_params(), params);
+    /// ```
+    pub fn set_kdf_params(&self, params: KdfParams) {
+        let inner = &mut *self.inner.write();
+        inner.kdf_params = params;
+    }
+
+    /// Cost parameters used when deriving a new key from the passphrase
+    pub fn kdf_params(&self) -> KdfParams {
+        let inner = &*self.inner.read();
+        inner.kdf_params
+    }
+
+    /// Set the cipher suite used to encrypt the entries. Defaults to AES-256-GCM.
+    /// Like the key derivation parameters, this is applied when the keystore is first unlocked,
+    /// upgraded or its passphrase is changed
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use warp::crypto::cipher::CipherSuite;
+    /// use warp::tesseract::Tesseract;
+    /// let tesseract = Tesseract::default();
+    /// tesseract.set_cipher_suite(CipherSuite::XChaCha20Poly1305);
+    /// assert_eq!(tesseract.cipher_suite(), CipherSuite::XChaCha20Poly1305);
+    /// ```
+    pub fn set_cipher_suite(&self, suite: CipherSuite) {
+        let inner = &mut *self.inner.write();
+        inner.cipher_suite = suite;
+    }
+
+    /// Cipher suite used when the entries are next re-encrypted
+    pub fn cipher_suite(&self) -> CipherSuite {
+        let inner = &*self.inner.read();
+        inner.cipher_suite
+    }
+
+    /// Loads the keystore from a backend, saving to it from then on.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use warp::tesseract::{backend::MemoryBackend, Tesseract};
+    ///
+    /// let backend = MemoryBackend::new();
+    /// let tesseract = Tesseract::from_backend(backend.clone()).unwrap();
+    /// tesseract.unlock(b"passphrase").unwrap();
+    /// tesseract.set("API", "MYKEY").unwrap();
+    ///
+    /// let tesseract = Tesseract::from_backend(backend).unwrap();
+    /// tesseract.unlock(b"passphrase").unwrap();
+    /// assert_eq!(tesseract.retrieve("API").unwrap(), "MYKEY");
+    /// ```
+    pub fn from_backend<B: TesseractBackend + 'static>(backend: B) -> Result<Self> {
+        let store = Tesseract::default();
+        {
+            let inner = &mut *store.inner.write();
+            inner.check = true;
+            if let Some(data) = backend.load()? {
+                inner.load(serde_json::from_slice(&data)?)?;
+            }
+            inner.set_backend(backend);
+            inner.set_autosave();
+        }
+        Ok(store)
+    }
+
+    /// Set the backend the keystore is saved to when using `Tesseract::save`.
+    /// Contents of the backend are not loaded.
+    pub fn set_backend<B: TesseractBackend + 'static>(&self, backend: B) {
+        let inner = &mut *self.inner.write();
+        inner.set_backend(backend);
+    }
+
     /// Enable the ability to autosave
     ///
     /// # Example
@@ -535,8 +676,16 @@ impl Tesseract {
 
 struct TesseractInner {
     internal: HashMap<String, Vec<u8>>,
+    /// Header of the key derivation used for the stored entries. `None` for legacy stores
+    kdf: Option<Kdf>,
+    kdf_params: KdfParams,
+    /// Cipher suite the stored entries are encrypted with
+    suite: CipherSuite,
+    cipher_suite: CipherSuite,
+    /// Derived key, encrypted while held in memory
     enc_pass: Vec<u8>,
     file: Option<PathBuf>,
+    backend: Option<Arc<dyn TesseractBackend>>,
     autosave: bool,
     check: bool,
     unlock: bool,
@@ -550,22 +699,60 @@ impl Drop for TesseractInner {
     }
 }
 
+impl TesseractInner {
+    fn load(&mut self, data: StoredTesseract) -> Result<()> {
+        match data {
+            StoredTesseract::Versioned(document) => {
+                if document.version > TESSERACT_VERSION {
+                    return Err(Error::UnsupportedTesseractVersion);
+                }
+                self.kdf = document.kdf;
+                self.suite = document.suite;
+                self.internal = document.entries.into_iter().collect();
+            }
+            StoredTesseract::Legacy(entries) => {
+                self.kdf = None;
+                self.suite = CipherSuite::Aes256Gcm;
+                self.internal = entries;
+            }
+        }
+        Ok(())
+    }
+
+    fn document(&self) -> TesseractDocument {
+        TesseractDocument {
+            version: TESSERACT_VERSION,
+            kdf: self.kdf.clone(),
+            suite: self.suite,
+            entries: self.internal.clone().into_iter().collect(),
+        }
+    }
+
+    fn set_backend<B: TesseractBackend + 'static>(&mut self, backend: B) {
+        self.file = None;
+        self.backend = Some(Arc::new(backend));
+    }
+
+    fn save_to_backend(&self, backend: &dyn TesseractBackend) -> Result<()> {
+        let data = serde_json::to_vec(&self.document())?;
+        backend.store(&data)
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 impl TesseractInner {
     fn to_file<S: AsRef<Path>>(&self, path: S) -> Result<()> {
-        let mut fs = std::fs::File::create(path)?;
-        self.to_writer(&mut fs)?;
-        fs.sync_all()?;
-        Ok(())
+        self.save_to_backend(&FileBackend::with_generations(path, 0))
     }
 
     fn to_writer<W: Write>(&self, writer: &mut W) -> Result<()> {
-        serde_json::to_writer(writer, &self.internal)?;
+        serde_json::to_writer(writer, &self.document())?;
         Ok(())
     }
 
     fn set_file<P: AsRef<Path>>(&mut self, file: P) {
         self.file = Some(file.as_ref().to_path_buf());
+        self.backend = Some(Arc::new(FileBackend::new(&file)));
         if !file.as_ref().is_file() {
             if let Err(_e) = self.to_file(file) {}
         }
@@ -576,14 +763,19 @@ impl TesseractInner {
     }
 
     fn save(&self) -> Result<()> {
-        if self.autosave_enabled() {
-            if let Some(path) = &self.file() {
-                if let Err(_e) = self.to_file(path) {
-                    //TODO: Logging
-                }
-            }
+        self.try_save()
+            .inspect_err(|e| tracing::warn!("Unable to save tesseract: {e}"))
+    }
+
+    fn try_save(&self) -> Result<()> {
+        if !self.autosave_enabled() {
+            return Ok(());
+        }
+
+        match &self.backend {
+            Some(backend) => self.save_to_backend(backend.as_ref()),
+            None => Ok(()),
         }
-        Ok(())
     }
 }
 
@@ -633,9 +825,18 @@ impl TesseractInner {
             return Err(Error::TesseractLocked);
         }
         let pkey = Cipher::self_decrypt(&self.enc_pass)?;
-        let data = Cipher::direct_encrypt(value.as_bytes(), &pkey)?;
-        self.internal.insert(key.to_string(), data);
-        self.save()
+        let data = self.entry_cipher(&pkey).encrypt(value.as_bytes(), None)?;
+        let previous = self.internal.insert(key.to_string(), data);
+
+        if let Err(e) = self.save() {
+            match previous {
+                Some(previous) => self.internal.insert(key.to_string(), previous),
+                None => self.internal.remove(key),
+            };
+            return Err(e);
+        }
+
+        Ok(())
     }
 
     fn exist(&self, key: &str) -> bool {
@@ -657,36 +858,104 @@ impl TesseractInner {
             .get(key)
             .cloned()
             .ok_or(Error::ObjectNotFound)?;
-        let slice = Cipher::direct_decrypt(&data, &pkey)?;
+        let slice = self.entry_cipher(&pkey).decrypt(&data)?;
         let plain_text = String::from_utf8_lossy(&slice[..]).to_string();
         Ok(plain_text)
     }
 
     fn update_unlock(&mut self, old_passphrase: &[u8], new_passphrase: &[u8]) -> Result<()> {
-        // TODO: Maybe copy current state and rollback in the event of an error?
         if !self.is_unlock() {
             return Err(Error::TesseractLocked);
         }
 
-        let pkey = Cipher::self_decrypt(&self.enc_pass)?;
+        let pkey = Zeroizing::new(Cipher::self_decrypt(&self.enc_pass)?);
+
+        let old_key = match &self.kdf {
+            Some(kdf) => kdf.derive(old_passphrase)?,
+            None => Zeroizing::new(old_passphrase.to_vec()),
+        };
 
-        if old_passphrase != pkey || old_passphrase == new_passphrase || pkey == new_passphrase {
+        if old_key != pkey || old_passphrase == new_passphrase {
             return Err(Error::InvalidPassphrase); //TODO: Mismatch?
         }
 
         let exported = self.export()?;
 
+        // A new salt is used for every passphrase
+        let kdf = Kdf::new(self.kdf_params)?;
+        let new_key = kdf.derive(new_passphrase)?;
+
         let mut encrypted = HashMap::new();
 
+        let cipher = Cipher::from(new_key.as_slice()).with_suite(self.cipher_suite);
         for (key, val) in exported {
-            let data = Cipher::direct_encrypt(val.as_bytes(), new_passphrase)?;
+            let data = cipher.encrypt(val.as_bytes(), None)?;
             encrypted.insert(key, data);
         }
 
-        self.lock();
-        self.internal = encrypted;
-        self.unlock(new_passphrase)?;
-        self.save()
+        let enc_pass = Cipher::self_encrypt(&new_key)?;
+
+        // Note: The previous state is kept so it can be restored if the keystore cannot be saved
+        let previous_internal = std::mem::replace(&mut self.internal, encrypted);
+        let previous_kdf = self.kdf.replace(kdf);
+        let previous_suite = std::mem::replace(&mut self.suite, self.cipher_suite);
+        let mut previous_enc_pass = std::mem::replace(&mut self.enc_pass, enc_pass);
+
+        if let Err(e) = self.try_save() {
+            self.enc_pass.zeroize();
+            self.internal = previous_internal;
+            self.kdf = previous_kdf;
+            self.suite = previous_suite;
+            self.enc_pass = previous_enc_pass;
+            return Err(e);
+        }
+
+        previous_enc_pass.zeroize();
+
+        // Backups are still encrypted with the previous passphrase, which is usually changed because it leaked
+        if self.autosave_enabled() {
+            if let Some(backend) = &self.backend {
+                if let Err(e) = backend.clear_backups() {
+                    tracing::warn!("Unable to remove tesseract backups: {e}");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Re-encrypt entries of a legacy store with a key derived from the passphrase
+    fn upgrade(&mut self, passphrase: &[u8]) -> Result<()> {
+        let kdf = Kdf::new(self.kdf_params)?;
+        let key = kdf.derive(passphrase)?;
+
+        let mut encrypted = HashMap::new();
+
+        // Note: Nothing is changed unless every entry could be decrypted with the passphrase
+        let cipher = Cipher::from(key.as_slice()).with_suite(self.cipher_suite);
+        for (name, data) in &self.internal {
+            let value = Zeroizing::new(Cipher::direct_decrypt(data, passphrase)?);
+            encrypted.insert(name.clone(), cipher.encrypt(&value, None)?);
+        }
+
+        let enc_pass = Cipher::self_encrypt(&key)?;
+
+        let previous_internal = std::mem::replace(&mut self.internal, encrypted);
+        let previous_kdf = self.kdf.replace(kdf);
+        let previous_suite = std::mem::replace(&mut self.suite, self.cipher_suite);
+        let mut previous_enc_pass = std::mem::replace(&mut self.enc_pass, enc_pass);
+
+        if let Err(e) = self.save() {
+            self.enc_pass.zeroize();
+            self.internal = previous_internal;
+            self.kdf = previous_kdf;
+            self.suite = previous_suite;
+            self.enc_pass = previous_enc_pass;
+            return Err(e);
+        }
+
+        previous_enc_pass.zeroize();
+        Ok(())
     }
 
     fn dry_retrieve(&self, key: &str) -> Result<()> {
@@ -696,13 +965,18 @@ impl TesseractInner {
 
         let pkey = Cipher::self_decrypt(&self.enc_pass)?;
         let data = self.internal.get(key).ok_or(Error::ObjectNotFound)?;
-        Cipher::direct_decrypt(data, &pkey)?;
+        self.entry_cipher(&pkey).decrypt(data)?;
         Ok(())
     }
     fn delete(&mut self, key: &str) -> Result<()> {
-        self.internal.remove(key).ok_or(Error::ObjectNotFound)?;
+        let previous = self.internal.remove(key).ok_or(Error::ObjectNotFound)?;
+
+        if let Err(e) = self.save() {
+            self.internal.insert(key.to_string(), previous);
+            return Err(e);
+        }
 
-        self.save()
+        Ok(())
     }
 
     fn clear(&mut self) {
@@ -716,7 +990,23 @@ impl TesseractInner {
     }
 
     fn unlock(&mut self, passphrase: &[u8]) -> Result<()> {
-        self.enc_pass = Cipher::self_encrypt(passphrase)?;
+        let legacy = self.kdf.is_none() && !self.internal.is_empty();
+
+        if self.kdf.is_none() && !legacy {
+            self.kdf = Some(Kdf::new(self.kdf_params)?);
+        }
+
+        if self.internal.is_empty() {
+            self.suite = self.cipher_suite;
+        }
+
+        let key = match &self.kdf {
+            Some(kdf) => kdf.derive(passphrase)?,
+            // Entries of a legacy store are encrypted with the passphrase until upgraded
+            None => Zeroizing::new(passphrase.to_vec()),
+        };
+
+        self.enc_pass = Cipher::self_encrypt(&key)?;
         if self.is_key_check_enabled() {
             let keys = self.internal_keys();
             for key in keys {
@@ -728,6 +1018,12 @@ impl TesseractInner {
         }
         self.unlock = true;
 
+        if legacy {
+            if let Err(e) = self.upgrade(passphrase) {
+                tracing::warn!("Unable to upgrade tesseract: {e}");
+            }
+        }
+
         let _ = self.event_tx.try_broadcast(TesseractEvent::Unlocked);
 
         Ok(())
@@ -756,6 +1052,10 @@ impl TesseractInner {
         stream.boxed()
     }
 
+    fn entry_cipher(&self, key: &[u8]) -> Cipher {
+        Cipher::from(key).with_suite(self.suite)
+    }
+
     fn internal_keys(&self) -> Vec<String> {
         self.internal.keys().cloned().collect::<Vec<_>>()
     }
@@ -764,32 +1064,52 @@ impl TesseractInner {
 #[cfg(target_arch = "wasm32")]
 impl TesseractInner {
     const NAMESPACE: &'static str = "warp.tesseract.";
+    // Kept outside of the namespace so it is not removed along with the entries
+    const KDF_KEY: &'static str = "warp.tesseract_kdf";
+    const SUITE_KEY: &'static str = "warp.tesseract_suite";
 
     fn save(&mut self) -> Result<()> {
+        self.try_save()
+            .inspect_err(|e| tracing::warn!("Unable to save tesseract: {e}"))
+    }
+
+    fn try_save(&mut self) -> Result<()> {
         use gloo::storage::{LocalStorage, Storage};
 
-        if self.autosave_enabled() {
-            // Note: Since we cant serialize the hashmap, we would clear out the localstorage, based on namespace, then we will save
-            //       so if we deleted any entries internally, it will reflect here when it saves.
-            {
-                let local_storage = LocalStorage::raw();
-                let length = LocalStorage::length();
-                for index in 0..length {
-                    if let Ok(key) = local_storage.key(index) {
-                        if let Some(key) = key {
-                            if !key.starts_with(Self::NAMESPACE) {
-                                continue;
-                            }
-                            LocalStorage::delete(&key);
+        if !self.autosave_enabled() {
+            return Ok(());
+        }
+
+        if let Some(backend) = &self.backend {
+            return self.save_to_backend(backend.as_ref());
+        }
+
+        // Note: Since we cant serialize the hashmap, we would clear out the localstorage, based on namespace, then we will save
+        //       so if we deleted any entries internally, it will reflect here when it saves.
+        {
+            let local_storage = LocalStorage::raw();
+            let length = LocalStorage::length();
+            for index in 0..length {
+                if let Ok(key) = local_storage.key(index) {
+                    if let Some(key) = key {
+                        if !key.starts_with(Self::NAMESPACE) {
+                            continue;
                         }
+                        LocalStorage::delete(&key);
                     }
                 }
             }
-            for (k, v) in &self.internal {
-                let k = Self::NAMESPACE.to_owned() + k;
-                LocalStorage::set(k, v).unwrap();
-            }
         }
+        let map_error = |e: gloo::storage::errors::StorageError| Error::Any(anyhow::anyhow!("{e}"));
+        for (k, v) in &self.internal {
+            let k = Self::NAMESPACE.to_owned() + k;
+            LocalStorage::set(k, v).map_err(map_error)?;
+        }
+        match &self.kdf {
+            Some(kdf) => LocalStorage::set(Self::KDF_KEY, kdf).map_err(map_error)?,
+            None => LocalStorage::delete(Self::KDF_KEY),
+        }
+        LocalStorage::set(Self::SUITE_KEY, self.suite).map_err(map_error)?;
 
         Ok(())
     }
@@ -810,6 +1130,9 @@ impl TesseractInner {
             self.internal.insert(key.to_owned(), value);
         }
 
+        self.kdf = LocalStorage::get(Self::KDF_KEY).ok();
+        self.suite = LocalStorage::get(Self::SUITE_KEY).unwrap_or_default();
+
         Ok(())
     }
 }
@@ -818,8 +1141,15 @@ impl TesseractInner {
 mod test {
     use futures::{FutureExt, StreamExt};
 
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use crate::crypto::cipher::{Cipher, CipherSuite};
     use crate::crypto::generate;
-    use crate::tesseract::{Tesseract, TesseractEvent};
+    use crate::error::Error;
+    use crate::tesseract::backend::MemoryBackend;
+    use crate::tesseract::{KdfParams, Tesseract, TesseractBackend, TesseractEvent};
 
     #[test]
     pub fn test_default() -> anyhow::Result<()> {
@@ -934,6 +1264,255 @@ mod test {
         Ok(())
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    pub fn tesseract_versioned_roundtrip() -> anyhow::Result<()> {
+        let tesseract = Tesseract::default();
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.unlock(b"this is a passphrase")?;
+        tesseract.set("API", "MYKEY")?;
+
+        let mut buffer = vec![];
+        tesseract.to_writer(&mut buffer)?;
+
+        let document: serde_json::Value = serde_json::from_slice(&buffer)?;
+        assert_eq!(document["version"], 1);
+        assert_eq!(document["kdf"]["algorithm"], "argon2id");
+
+        let tesseract = Tesseract::from_reader(&mut buffer.as_slice())?;
+        assert!(tesseract.unlock(b"not the passphrase").is_err());
+        tesseract.unlock(b"this is a passphrase")?;
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    pub fn tesseract_cipher_suite_roundtrip() -> anyhow::Result<()> {
+        let tesseract = Tesseract::default();
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.set_cipher_suite(CipherSuite::XChaCha20Poly1305);
+        tesseract.unlock(b"this is a passphrase")?;
+        tesseract.set("API", "MYKEY")?;
+
+        let mut buffer = vec![];
+        tesseract.to_writer(&mut buffer)?;
+        let document: serde_json::Value = serde_json::from_slice(&buffer)?;
+        assert_eq!(document["suite"], "xchacha20-poly1305");
+
+        // The suite of the stored entries is used regardless of the configured one
+        let tesseract = Tesseract::from_reader(&mut buffer.as_slice())?;
+        tesseract.unlock(b"this is a passphrase")?;
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+
+        // Entries are re-encrypted with the configured suite when the passphrase changes
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.update_unlock(b"this is a passphrase", b"another passphrase")?;
+        let mut buffer = vec![];
+        tesseract.to_writer(&mut buffer)?;
+        let document: serde_json::Value = serde_json::from_slice(&buffer)?;
+        assert_eq!(document["suite"], "aes256-gcm");
+
+        let tesseract = Tesseract::from_reader(&mut buffer.as_slice())?;
+        tesseract.unlock(b"another passphrase")?;
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    pub fn tesseract_legacy_upgrade() -> anyhow::Result<()> {
+        let passphrase = b"this is a passphrase";
+        let mut legacy = HashMap::new();
+        legacy.insert(
+            String::from("API"),
+            Cipher::direct_encrypt(b"MYKEY", passphrase)?,
+        );
+        let buffer = serde_json::to_vec(&legacy)?;
+
+        let tesseract = Tesseract::from_reader(&mut buffer.as_slice())?;
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.unlock(passphrase)?;
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+
+        let mut buffer = vec![];
+        tesseract.to_writer(&mut buffer)?;
+        let document: serde_json::Value = serde_json::from_slice(&buffer)?;
+        assert_eq!(document["version"], 1);
+        assert!(document["kdf"].is_object());
+
+        let tesseract = Tesseract::from_reader(&mut buffer.as_slice())?;
+        tesseract.unlock(passphrase)?;
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    pub fn tesseract_unsupported_version() -> anyhow::Result<()> {
+        let buffer = br#"{"version":2,"entries":{}}"#;
+        assert!(matches!(
+            Tesseract::from_reader(&mut buffer.as_slice()),
+            Err(Error::UnsupportedTesseractVersion)
+        ));
+        Ok(())
+    }
+
+    /// Backend that fails to store once `fail` is set
+    #[derive(Clone, Default)]
+    struct FailingBackend {
+        inner: MemoryBackend,
+        fail: Arc<AtomicBool>,
+    }
+
+    impl TesseractBackend for FailingBackend {
+        fn load(&self) -> Result<Option<Vec<u8>>, Error> {
+            self.inner.load()
+        }
+
+        fn store(&self, data: &[u8]) -> Result<(), Error> {
+            if self.fail.load(Ordering::SeqCst) {
+                return Err(Error::CannotSaveTesseract);
+            }
+            self.inner.store(data)
+        }
+
+        fn clear(&self) -> Result<(), Error> {
+            self.inner.clear()
+        }
+    }
+
+    #[test]
+    pub fn update_unlock_rollback() -> anyhow::Result<()> {
+        let backend = FailingBackend::default();
+        let tesseract = Tesseract::from_backend(backend.clone())?;
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.unlock(b"old passphrase")?;
+        tesseract.set("API", "MYKEY")?;
+
+        backend.fail.store(true, Ordering::SeqCst);
+        assert!(tesseract
+            .update_unlock(b"old passphrase", b"new passphrase")
+            .is_err());
+
+        assert!(tesseract.is_unlock());
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+
+        backend.fail.store(false, Ordering::SeqCst);
+        let restored = Tesseract::from_backend(backend.clone())?;
+        assert!(restored.unlock(b"new passphrase").is_err());
+        restored.unlock(b"old passphrase")?;
+        assert_eq!(restored.retrieve("API")?, "MYKEY");
+
+        tesseract.update_unlock(b"old passphrase", b"new passphrase")?;
+        let updated = Tesseract::from_backend(backend)?;
+        updated.unlock(b"new passphrase")?;
+        assert_eq!(updated.retrieve("API")?, "MYKEY");
+        Ok(())
+    }
+
+    #[test]
+    pub fn set_and_delete_rollback() -> anyhow::Result<()> {
+        let backend = FailingBackend::default();
+        let tesseract = Tesseract::from_backend(backend.clone())?;
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.unlock(b"passphrase")?;
+        tesseract.set("API", "MYKEY")?;
+
+        backend.fail.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            tesseract.set("API", "OTHERKEY"),
+            Err(Error::CannotSaveTesseract)
+        ));
+        assert!(tesseract.set("NEW", "VALUE").is_err());
+        assert!(tesseract.delete("API").is_err());
+
+        assert_eq!(tesseract.retrieve("API")?, "MYKEY");
+        assert!(!tesseract.exist("NEW"));
+
+        backend.fail.store(false, Ordering::SeqCst);
+        tesseract.delete("API")?;
+        assert!(!tesseract.exist("API"));
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    pub fn restore_from_backup() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("tesseract-{}", uuid::Uuid::new_v4()));
+        let tesseract = Tesseract::open_or_create(&dir, "tesseract.bin")?;
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.unlock(b"this is a passphrase")?;
+        tesseract.set("FIRST", "VALUE")?;
+        tesseract.set("SECOND", "VALUE")?;
+
+        // Locking saves the keystore again, which should not push it into the backups
+        tesseract.lock();
+        tesseract.unlock(b"this is a passphrase")?;
+        drop(tesseract);
+
+        // The most recent backup holds the keystore as it was before `SECOND` was set
+        let path = dir.join("tesseract.bin");
+        let backup = Tesseract::from_file(super::FileBackend::new(&path).backup_path(1))?;
+        backup.unlock(b"this is a passphrase")?;
+        assert_eq!(backup.retrieve("FIRST")?, "VALUE");
+        assert!(!backup.exist("SECOND"));
+        drop(backup);
+
+        // Simulate a file that was only partially written
+        let data = std::fs::read(&path)?;
+        std::fs::write(&path, &data[..data.len() / 2])?;
+
+        let tesseract = Tesseract::from_file(&path)?;
+        tesseract.unlock(b"this is a passphrase")?;
+        assert_eq!(tesseract.retrieve("FIRST")?, "VALUE");
+        assert!(!tesseract.exist("SECOND"));
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    #[test]
+    pub fn update_unlock_removes_backups() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("tesseract-{}", uuid::Uuid::new_v4()));
+        let tesseract = Tesseract::open_or_create(&dir, "tesseract.bin")?;
+        tesseract.set_kdf_params(KdfParams::new(1024, 1, 1)?);
+        tesseract.unlock(b"old passphrase")?;
+        tesseract.set("FIRST", "VALUE")?;
+        tesseract.set("SECOND", "VALUE")?;
+        tesseract.set("THIRD", "VALUE")?;
+
+        let path = dir.join("tesseract.bin");
+        let backend = super::FileBackend::new(&path);
+        assert!(backend.backup_path(1).is_file());
+
+        tesseract.update_unlock(b"old passphrase", b"new passphrase")?;
+        tesseract.set("FOURTH", "VALUE")?;
+        drop(tesseract);
+
+        for generation in 1..=backend.generations() {
+            let backup = backend.backup_path(generation);
+            if !backup.is_file() {
+                continue;
+            }
+            let tesseract = Tesseract::from_file(backup)?;
+            assert!(tesseract.unlock(b"old passphrase").is_err());
+        }
+
+        // A corrupted keystore is not restored to a version using the previous passphrase
+        let data = std::fs::read(&path)?;
+        std::fs::write(&path, &data[..data.len() / 2])?;
+
+        let tesseract = Tesseract::from_file(&path)?;
+        assert!(tesseract.unlock(b"old passphrase").is_err());
+        tesseract.unlock(b"new passphrase")?;
+        assert_eq!(tesseract.retrieve("FIRST")?, "VALUE");
+
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
     #[cfg(not(target_arch = "wasm32"))]
     #[tokio::test]
     async fn tesseract_event() -> anyhow::Result<()> {
@@ -1,6 +1,9 @@
 pub mod dummy;
 
 use crate::constellation::directory::Directory;
+use crate::constellation::item::Item;
+use crate::constellation::search::{ConstellationSearchStream, SearchOptions};
+use crate::constellation::usage::UsageReport;
 use crate::constellation::{
     Constellation, ConstellationEvent, ConstellationEventStream, ConstellationProgressStream,
 };
@@ -8,12 +11,14 @@ use crate::crypto::DID;
 use crate::error::Error;
 use crate::module::Module;
 use crate::multipass::identity::{
-    FriendRequest, Identifier, Identity, IdentityImage, IdentityProfile, IdentityStatus,
-    IdentityUpdate, Platform, Relationship,
+    Contact, ContactUpdate, Device, FriendRequest, Identifier, Identity, IdentityImage,
+    IdentityProfile, IdentityStatus, IdentityUpdate, Platform, Presence, RecoveryRequest,
+    Relationship, RevocationCertificate,
 };
 use crate::multipass::{
-    Friends, GetIdentity, IdentityImportOption, IdentityInformation, ImportLocation, LocalIdentity,
-    MultiPass, MultiPassEvent, MultiPassEventStream, MultiPassImportExport,
+    BundleImportMode, ExportManifest, ExportSection, Friends, GetIdentity, IdentityImportOption,
+    IdentityInformation, ImportLocation, LocalIdentity, MultiPass, MultiPassEvent,
+    MultiPassEventStream, MultiPassImportExport,
 };
 use crate::raygun::{
     AttachmentEventStream, Conversation, ConversationImage, EmbedState, GroupPermissionOpt,
@@ -247,6 +252,16 @@ where
         self.multipass.set_identity_status(status).await
     }
 
+    /// Presence of the identity, including its custom status if it has not expired
+    async fn identity_presence(&self, identity: &DID) -> Result<Presence, Error> {
+        self.multipass.identity_presence(identity).await
+    }
+
+    /// Set the presence of own identity, replacing the status and any previous custom status
+    async fn set_identity_presence(&mut self, presence: Presence) -> Result<(), Error> {
+        self.multipass.set_identity_presence(presence).await
+    }
+
     /// Find the relationship with an existing identity.
     async fn identity_relationship(&self, identity: &DID) -> Result<Relationship, Error> {
         self.multipass.identity_relationship(identity).await
@@ -277,6 +292,33 @@ where
     async fn export_identity<'a>(&mut self, location: ImportLocation<'a>) -> Result<(), Error> {
         self.multipass.export_identity(location).await
     }
+
+    async fn export_bundle<'a>(
+        &mut self,
+        location: ImportLocation<'a>,
+        sections: &[ExportSection],
+    ) -> Result<ExportManifest, Error> {
+        self.multipass.export_bundle(location, sections).await
+    }
+
+    async fn verify_bundle<'a>(
+        &self,
+        location: ImportLocation<'a>,
+        passphrase: &str,
+    ) -> Result<ExportManifest, Error> {
+        self.multipass.verify_bundle(location, passphrase).await
+    }
+
+    async fn import_bundle<'a>(
+        &mut self,
+        location: ImportLocation<'a>,
+        passphrase: &str,
+        mode: BundleImportMode,
+    ) -> Result<Identity, Error> {
+        self.multipass
+            .import_bundle(location, passphrase, mode)
+            .await
+    }
 }
 
 #[async_trait::async_trait]
@@ -290,6 +332,17 @@ where
         self.multipass.send_request(identity).await
     }
 
+    async fn send_request_with(
+        &mut self,
+        identity: &DID,
+        message: Option<String>,
+        expires: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        self.multipass
+            .send_request_with(identity, message, expires)
+            .await
+    }
+
     /// Accept friend request from public key
     async fn accept_request(&mut self, identity: &DID) -> Result<(), Error> {
         self.multipass.accept_request(identity).await
@@ -359,6 +412,50 @@ where
     async fn has_friend(&self, identity: &DID) -> Result<bool, Error> {
         self.multipass.has_friend(identity).await
     }
+
+    async fn setup_social_recovery(&mut self, friends: &[DID], threshold: u8) -> Result<(), Error> {
+        self.multipass
+            .setup_social_recovery(friends, threshold)
+            .await
+    }
+
+    async fn list_recovery_shares(&self) -> Result<Vec<DID>, Error> {
+        self.multipass.list_recovery_shares().await
+    }
+
+    async fn list_recovery_requests(&self) -> Result<Vec<RecoveryRequest>, Error> {
+        self.multipass.list_recovery_requests().await
+    }
+
+    async fn approve_recovery_request(
+        &mut self,
+        identity: &DID,
+        device: &DID,
+    ) -> Result<(), Error> {
+        self.multipass
+            .approve_recovery_request(identity, device)
+            .await
+    }
+
+    async fn deny_recovery_request(&mut self, identity: &DID, device: &DID) -> Result<(), Error> {
+        self.multipass.deny_recovery_request(identity, device).await
+    }
+
+    async fn list_contacts(&self) -> Result<Vec<Contact>, Error> {
+        self.multipass.list_contacts().await
+    }
+
+    async fn get_contact(&self, identity: &DID) -> Result<Contact, Error> {
+        self.multipass.get_contact(identity).await
+    }
+
+    async fn update_contact(&mut self, identity: &DID, option: ContactUpdate) -> Result<(), Error> {
+        self.multipass.update_contact(identity, option).await
+    }
+
+    async fn list_contact_groups(&self) -> Result<Vec<String>, Error> {
+        self.multipass.list_contact_groups().await
+    }
 }
 
 #[async_trait::async_trait]
@@ -387,6 +484,42 @@ where
     fn tesseract(&self) -> Tesseract {
         self.multipass.tesseract()
     }
+
+    async fn authorize_device(&mut self, did: &DID, name: Option<&str>) -> Result<(), Error> {
+        self.multipass.authorize_device(did, name).await
+    }
+
+    async fn revoke_device(&mut self, did: &DID) -> Result<(), Error> {
+        self.multipass.revoke_device(did).await
+    }
+
+    async fn list_devices(&self) -> Result<Vec<Device>, Error> {
+        self.multipass.list_devices().await
+    }
+
+    async fn rotate_identity_key(&mut self) -> Result<IdentityProfile, Error> {
+        self.multipass.rotate_identity_key().await
+    }
+
+    async fn create_revocation_certificate(
+        &self,
+        reason: Option<&str>,
+    ) -> Result<RevocationCertificate, Error> {
+        self.multipass.create_revocation_certificate(reason).await
+    }
+
+    async fn publish_revocation_certificate(
+        &mut self,
+        certificate: &RevocationCertificate,
+    ) -> Result<(), Error> {
+        self.multipass
+            .publish_revocation_certificate(certificate)
+            .await
+    }
+
+    async fn claim_username(&mut self) -> Result<(), Error> {
+        self.multipass.claim_username().await
+    }
 }
 
 #[async_trait::async_trait]
@@ -504,6 +637,14 @@ where
         self.constellation.get_stream(name).await
     }
 
+    async fn get_variant_stream(
+        &self,
+        name: &str,
+        size: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        self.constellation.get_variant_stream(name, size).await
+    }
+
     async fn rename(&mut self, current: &str, new: &str) -> Result<(), Error> {
         self.constellation.rename(current, new).await
     }
@@ -523,6 +664,39 @@ where
     async fn sync_ref(&mut self, name: &str) -> Result<(), Error> {
         self.constellation.sync_ref(name).await
     }
+
+    async fn search(&self, options: SearchOptions) -> Result<ConstellationSearchStream, Error> {
+        self.constellation.search(options).await
+    }
+
+    async fn set_item_metadata(
+        &mut self,
+        path: &str,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<(), Error> {
+        self.constellation.set_item_metadata(path, key, value).await
+    }
+
+    async fn add_tag(&mut self, path: &str, tag: &str) -> Result<(), Error> {
+        self.constellation.add_tag(path, tag).await
+    }
+
+    async fn remove_tag(&mut self, path: &str, tag: &str) -> Result<(), Error> {
+        self.constellation.remove_tag(path, tag).await
+    }
+
+    fn list_by_tag(&self, tag: &str) -> Result<Vec<Item>, Error> {
+        self.constellation.list_by_tag(tag)
+    }
+
+    async fn set_quota(&mut self, path: &str, quota: Option<usize>) -> Result<(), Error> {
+        self.constellation.set_quota(path, quota).await
+    }
+
+    fn usage_report(&self) -> Result<UsageReport, Error> {
+        self.constellation.usage_report()
+    }
 }
 
 #[async_trait::async_trait]
@@ -535,6 +709,15 @@ where
     async fn constellation_subscribe(&mut self) -> Result<ConstellationEventStream, Error> {
         self.constellation.constellation_subscribe().await
     }
+
+    async fn constellation_subscribe_path(
+        &mut self,
+        prefix: &str,
+    ) -> Result<ConstellationEventStream, Error> {
+        self.constellation
+            .constellation_subscribe_path(prefix)
+            .await
+    }
 }
 
 #[async_trait::async_trait]
@@ -629,6 +812,18 @@ where
             .download_stream(conversation_id, message_id, name)
             .await
     }
+
+    async fn download_variant_stream(
+        &self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        name: &str,
+        size: u32,
+    ) -> Result<BoxStream<'static, Result<Bytes, std::io::Error>>, Error> {
+        self.raygun
+            .download_variant_stream(conversation_id, message_id, name, size)
+            .await
+    }
 }
 
 #[async_trait::async_trait]